@@ -0,0 +1,191 @@
+// Embedding Pidgin in a Rust program (run with `cargo run --example embed`)
+use pidgin::ast::Stmt;
+use pidgin::builder::{ident, number};
+use pidgin::{Interpreter, PidginError, ProgramBuilder, StatementHook, Value};
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+// A writer that labels each line a script prints, to tell it apart from the host's own output
+struct Labeled {
+    label: &'static str,
+    line_start: bool,
+}
+
+impl Labeled {
+    fn new(label: &'static str) -> Self {
+        Self {
+            label,
+            line_start: true,
+        }
+    }
+}
+
+impl Write for Labeled {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for line in String::from_utf8_lossy(buf).split_inclusive('\n') {
+            if self.line_start {
+                print!("[{}] ", self.label);
+            }
+            print!("{line}");
+            self.line_start = line.ends_with('\n');
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stdout().flush()
+    }
+}
+
+// What the profiler below found out
+#[derive(Default)]
+struct Profile {
+    statements: usize,
+    calls: BTreeMap<String, usize>,
+    error: Option<String>,
+}
+
+// A hook that fills in a Profile as the program runs
+struct Profiler(Arc<Mutex<Profile>>);
+
+impl StatementHook for Profiler {
+    fn before_statement(&mut self, _stmt: &Stmt, _: &mut Interpreter) -> Result<(), String> {
+        self.0.lock().unwrap().statements += 1;
+        Ok(())
+    }
+
+    fn function_enter(
+        &mut self,
+        name: &str,
+        _: &[Value],
+        _: &mut Interpreter,
+    ) -> Result<(), String> {
+        *self
+            .0
+            .lock()
+            .unwrap()
+            .calls
+            .entry(name.to_string())
+            .or_default() += 1;
+        Ok(())
+    }
+
+    fn on_error(&mut self, _: &Stmt, error: &str, _: &mut Interpreter) {
+        // The message already ends with the position of what failed
+        self.0.lock().unwrap().error = Some(error.to_string());
+    }
+}
+
+fn main() -> Result<(), PidginError> {
+    // Run a whole program and read a variable it defined
+    let interpreter = pidgin::run_source("let answer = 6 * 7;")?;
+    println!("answer = {}", interpreter.variables()["answer"]);
+
+    // Keep one interpreter around to run several snippets that share their variables
+    let mut session = Interpreter::new();
+    pidgin::run_with_interpreter("function double(n) { return n * 2; }", &mut session)?;
+    pidgin::run_with_interpreter("let result = double(21);", &mut session)?;
+    println!("result = {}", session.variables()["result"]);
+
+    // Build a program in Rust instead of writing its source
+    let program = ProgramBuilder::new()
+        .let_("base")
+        .number(6.0)
+        .let_("built")
+        .value(ident("base").multiply(number(7.0)))
+        .build();
+    let mut built = Interpreter::new();
+    built.interpret(program).map_err(PidginError::runtime)?;
+    println!("built = {}", built.variables()["built"]);
+
+    // Give scripts a native function; errors returned from it stop the script like any other
+    let mut host = Interpreter::new();
+    host.register_builtin("hypot", |args| match args {
+        [Value::Number(a), Value::Number(b)] => Ok(Value::Number(a.hypot(*b))),
+        _ => Err("hypot expects two numbers".to_string()),
+    });
+    pidgin::run_with_interpreter("let h = hypot(3, 4);", &mut host)?;
+    println!("h = {}", host.variables()["h"]);
+
+    // And a native method, called as value.name(...) on values of one type
+    host.register_method("string", "repeat", 1, |text, args| match args {
+        [Value::Number(n)] if *n >= 0.0 => Ok(Value::String(text.to_string().repeat(*n as usize))),
+        _ => Err("repeat() requires a count that is not negative".to_string()),
+    })
+    .map_err(PidginError::runtime)?;
+    pidgin::run_with_interpreter(r#"let line = "-".repeat(5);"#, &mut host)?;
+    println!("line = {}", host.variables()["line"]);
+
+    // And an iterator, whose items a for-in loop takes one at a time
+    host.register_builtin("countdown", |args| match args {
+        [Value::Number(n)] => Ok(Value::iterator(
+            (1..=*n as u32).rev().map(|i| Value::Number(i as f64)),
+        )),
+        _ => Err("countdown expects a number".to_string()),
+    });
+    pidgin::run_with_interpreter(
+        r#"let launch = ""; for (i in countdown(3)) { launch = launch + i + " "; }"#,
+        &mut host,
+    )?;
+    println!("launch = {}", host.variables()["launch"]);
+
+    // Pass structured data in and out as JSON
+    let mut data = Interpreter::new();
+    let order = serde_json::json!({ "items": [3, 4], "customer": "Ada" });
+    data.set_variable("order", order.into())
+        .map_err(PidginError::runtime)?;
+    let script = r#"let items = order.get("items");
+let summary = Object("customer" => order.get("customer"), "total" => items[0] + items[1]);"#;
+    pidgin::run_with_interpreter(script, &mut data)?;
+    let summary = serde_json::Value::try_from(&data.variables()["summary"]).unwrap();
+    println!("summary = {summary}");
+
+    // Send what scripts print somewhere other than stdout and stderr
+    let mut labeled = Interpreter::new();
+    labeled.set_output(Labeled::new("out"), Labeled::new("err"));
+    pidgin::run_with_interpreter("printLn(\"hello\"); printErr(\"oops\");", &mut labeled)?;
+
+    // Watch a script run: count statements and calls to each function, and see its error
+    let profile = Arc::new(Mutex::new(Profile::default()));
+    let mut observed = Interpreter::new();
+    observed.set_statement_hook(Box::new(Profiler(Arc::clone(&profile))));
+    let script = "function square(n) { return n * n; }
+let total = square(2) + square(3);
+print missing;";
+    let _ = pidgin::run_with_interpreter(script, &mut observed);
+    let profile = profile.lock().unwrap();
+    println!(
+        "{} statements, calls: {:?}",
+        profile.statements, profile.calls
+    );
+    println!("error: {}", profile.error.as_deref().unwrap_or("none"));
+
+    // Set up once, then run scripts on several threads, each in its own interpreter
+    let mut setup = Interpreter::new();
+    pidgin::run_with_interpreter(
+        "let rate = 1.5; function price(n) { return n * rate; }",
+        &mut setup,
+    )?;
+    let snapshot = setup.snapshot();
+    let workers: Vec<_> = (1..=3)
+        .map(|n| {
+            let snapshot = snapshot.clone();
+            std::thread::spawn(move || {
+                let mut interpreter = snapshot.interpreter();
+                let source = format!("let cost = price({n});");
+                pidgin::run_with_interpreter(&source, &mut interpreter)
+                    .map(|()| interpreter.variables()["cost"].clone())
+            })
+        })
+        .collect();
+    for worker in workers {
+        println!("cost = {}", worker.join().unwrap()?);
+    }
+
+    // Errors carry their class (syntax, runtime, ...) as well as a message
+    if let Err(e) = pidgin::run_source("print missing;") {
+        println!("{:?} error: {e}", e.kind);
+    }
+    Ok(())
+}
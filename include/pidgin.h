@@ -0,0 +1,45 @@
+/* C API for embedding the Pidgin interpreter (see docs/FFI.md).
+ *
+ * Build the shared library with:
+ *     cargo rustc --lib --release --features ffi --crate-type cdylib
+ *
+ * All strings are NUL-terminated UTF-8. Strings returned by pidgin_get_var_as_string belong
+ * to the caller and must be freed with pidgin_free_string. An interpreter must only be used
+ * from one thread at a time.
+ */
+#ifndef PIDGIN_H
+#define PIDGIN_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* An interpreter, keeping its variables and functions between calls */
+typedef struct PidginInterpreter PidginInterpreter;
+
+/* Create an interpreter; free it with pidgin_free */
+PidginInterpreter *pidgin_new_interpreter(void);
+
+/* Run source code; returns 0 on success, or 1 (runtime error), 2 (NULL or invalid
+ * arguments), 3 (syntax error) or 4 (internal error) */
+int pidgin_eval(PidginInterpreter *interpreter, const char *source);
+
+/* Message of the last failed pidgin_eval, or NULL after a success; owned by the
+ * interpreter and valid until the next pidgin_eval or pidgin_free */
+const char *pidgin_last_error(const PidginInterpreter *interpreter);
+
+/* A global variable as text (as print shows it), or NULL if it is not defined;
+ * free it with pidgin_free_string */
+char *pidgin_get_var_as_string(const PidginInterpreter *interpreter, const char *name);
+
+/* Free a string returned by pidgin_get_var_as_string (NULL is ignored) */
+void pidgin_free_string(char *string);
+
+/* Free an interpreter (NULL is ignored) */
+void pidgin_free(PidginInterpreter *interpreter);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* PIDGIN_H */
@@ -1,49 +1,140 @@
+// Import serde, used to save parsed programs to .pgc files and to read programs built by
+// other frontends (Interpreter::interpret_ast_json)
+use serde::{Deserialize, Serialize};
+
+use crate::pattern::{Compiled, Decision};
+use crate::symbols::Slot;
+
+// Define the Span struct, the source position where an AST node starts
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct Span {
+    pub line: usize,   // Line number of the node's first token
+    pub column: usize, // Column number of the node's first token
+}
+
+// Implement methods for Span
+impl Span {
+    // Create a new Span at the given line and column
+    pub fn new(line: usize, column: usize) -> Self {
+        Self { line, column }
+    }
+}
+
 // Define the Expr enum, representing all possible expression types in the AST
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Expr {
-    Number(f64),             // Numeric literal expression
-    String(String),          // String literal expression
-    Boolean(bool),           // Boolean literal expression
-    Identifier(String),      // Identifier expression (variable name)
-    FixedArray(Vec<Expr>),   // For [a, b, c]
-    DynamicArray(Vec<Expr>), // For {a, b, c}
+    Number(f64),                                   // Numeric literal expression
+    String(String),                                // String literal expression
+    Boolean(bool),                                 // Boolean literal expression
+    Identifier(String, Span, #[serde(skip)] Slot), // Identifier expression (variable name), its position, and its symbol
+    FixedArray(Vec<Expr>),                         // For [a, b, c]
+    DynamicArray(Vec<Expr>),                       // For {a, b, c}
     Index {
         array: Box<Expr>, // The array being indexed
         index: Box<Expr>, // The index expression
+        span: Span,       // Position of the '['
     }, // Array indexing: arr[0]
-    Nil,                     // Nil literal expression
+    Nil,                                           // Nil literal expression
     Binary {
         left: Box<Expr>,    // Left operand of the binary expression
         operator: BinaryOp, // Operator of the binary expression
         right: Box<Expr>,   // Right operand of the binary expression
-        line: usize,        // Line number of the operator
-        column: usize,      // Column number of the operator
+        span: Span,         // Position of the operator
     },
     Unary {
         operator: UnaryOp,  // Operator of the unary expression
         operand: Box<Expr>, // Operand of the unary expression
+        span: Span,         // Position of the operator
     },
     Assignment {
         name: String,     // Name of the variable being assigned
         value: Box<Expr>, // Value being assigned to the variable
+        span: Span,       // Position of the variable name
+        #[serde(skip)]
+        slot: Slot, // Symbol of the variable (see src/symbols.rs)
     },
     MethodCall {
         object: Box<Expr>,
         method: String,
-        argument: Box<Expr>,
+        arguments: Vec<Expr>, // Arguments in parentheses, or a single Transform
+        span: Span,           // Position of the method name
     },
     Transform {
         from: String,
         to: String,
+        span: Span, // Position of the opening '`'
     },
     FunctionCall {
         name: String,         // Function name
         arguments: Vec<Expr>, // Arguments passed to the function
+        span: Span,           // Position of the function name
+        #[serde(skip)]
+        slot: Slot, // Symbol of the function's variable, for functions the program defines
     },
+    Match {
+        subject: Box<Expr>,    // The value the cases are tried against
+        cases: Vec<MatchCase>, // Cases in the order they are tried
+        span: Span,            // Position of the 'match' keyword
+    }, // match (value) { case [x, y] if x > y: x, case _: 0 }
+}
+
+// Implement methods for Expr
+impl Expr {
+    // Get the source position of this expression, for the kinds that can fail at runtime
+    // (literals and array literals have none; their failures come from their elements)
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Expr::Identifier(_, span, _)
+            | Expr::Index { span, .. }
+            | Expr::Binary { span, .. }
+            | Expr::Unary { span, .. }
+            | Expr::Assignment { span, .. }
+            | Expr::MethodCall { span, .. }
+            | Expr::Transform { span, .. }
+            | Expr::FunctionCall { span, .. }
+            | Expr::Match { span, .. } => Some(*span),
+            Expr::Number(_)
+            | Expr::String(_)
+            | Expr::Boolean(_)
+            | Expr::FixedArray(_)
+            | Expr::DynamicArray(_)
+            | Expr::Nil => None,
+        }
+    }
+}
+
+// Define the MatchCase struct, one 'case' of a match expression
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MatchCase {
+    pub pattern: Pattern,    // Shape the value must have
+    pub guard: Option<Expr>, // Condition after 'if', checked once the pattern matched
+    pub body: Expr,          // Value of the match when this case is chosen
+    #[serde(skip)]
+    pub compiled: Compiled, // Decision the pattern compiles to, made the first time it is needed
+}
+
+// Implement methods for MatchCase
+impl MatchCase {
+    // Get the tests the case's pattern makes and the names it binds, compiling it only once
+    pub fn decision(&self) -> &Decision {
+        self.compiled.decision(&self.pattern)
+    }
+}
+
+// Define the Pattern enum, the shapes a case can require of a value
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Pattern {
+    Wildcard,                       // _: matches anything
+    Binding(String, Span),          // A name: matches anything and is assigned the value
+    Number(f64),                    // Number literal: matches an equal number
+    String(String),                 // String literal: matches an equal string
+    Boolean(bool),                  // true or false
+    Array(Vec<Pattern>),            // [a, b]: an array (fixed or dynamic) of exactly that length
+    Object(Vec<(String, Pattern)>), // Object("k" => p): an object with those keys (and maybe more)
 }
 
 // Define the BinaryOp enum, representing all possible binary operators
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum BinaryOp {
     Add,          // Addition operator
     Subtract,     // Subtraction operator
@@ -57,56 +148,138 @@ pub enum BinaryOp {
     GreaterEqual, // Greater-than-or-equal operator
 }
 
+// Implement methods for BinaryOp
+impl BinaryOp {
+    // Get the operator as it is written in source code
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Subtract => "-",
+            BinaryOp::Multiply => "*",
+            BinaryOp::Divide => "/",
+            BinaryOp::Equal => "==",
+            BinaryOp::NotEqual => "!=",
+            BinaryOp::Less => "<",
+            BinaryOp::Greater => ">",
+            BinaryOp::LessEqual => "<=",
+            BinaryOp::GreaterEqual => ">=",
+        }
+    }
+}
+
 // Define the UnaryOp enum, representing all possible unary operators
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum UnaryOp {
     Minus, // Unary minus operator (negation)
 }
 
+// Implement methods for UnaryOp
+impl UnaryOp {
+    // Get the operator as it is written in source code
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            UnaryOp::Minus => "-",
+        }
+    }
+}
+
 // Define the Stmt enum, representing all possible statement types in the AST
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Stmt {
-    Expression(Expr), // Expression statement
-    Return(Expr),     // Return statement
+    Expression(Expr, Span), // Expression statement
+    Return(Expr, Span),     // Return statement
     Print {
         format: Expr,         // The format string expression
         arguments: Vec<Expr>, // The arguments to be formatted
+        span: Span,           // Position of the 'print' keyword
     }, // Print statement
     PrintLn {
         format: Expr,         // The format string expression
         arguments: Vec<Expr>, // The arguments to be formatted
+        span: Span,           // Position of the 'printLn' keyword
     }, // PrintLn statement
     PrintErr {
         format: Expr,         // The format string expression
         arguments: Vec<Expr>, // The arguments to be formatted
+        span: Span,           // Position of the 'printErr' keyword
     }, // PrintErr statement
     Import {
         names: Vec<String>, // Names to import (can be single or multiple)
         module: String,     // Module file path
+        span: Span,         // Position of the 'GET' keyword
     }, // Import statement: GET Alpha from math.pg;
     VarDeclaration {
         name: String,              // Name of the variable being declared
         initializer: Option<Expr>, // Optional initializer expression
+        span: Span,                // Position of the 'let' keyword
+        #[serde(skip)]
+        slot: Slot, // Symbol of the variable
     },
+    Unpack {
+        names: Vec<String>, // Variables each element is assigned to, in order
+        value: Expr,        // Array with exactly one element per name
+        declares: bool,     // Written with 'let' (otherwise it only assigns: a, b = b, a;)
+        span: Span,         // Position of the 'let' keyword, or of the first name
+    }, // let q, r = divmod(7, 2);
     FunctionDeclaration {
         name: String,            // Name of the function
         parameters: Vec<String>, // Parameter names
+        #[serde(default)]
+        parameter_spans: Vec<Span>, // Position of each parameter name (empty if unknown)
         body: Box<Stmt>,         // Function body
+        doc: Option<String>,     // Text of the /// comments just above it, if any
+        span: Span,              // Position of the 'function' keyword
     },
-    Block(Vec<Stmt>), // Block statement (a sequence of statements)
+    Block(Vec<Stmt>, Span), // Block statement (a sequence of statements)
     If {
         condition: Expr,                // Condition expression for the if statement
         then_branch: Box<Stmt>,         // Statement to execute if condition is true
         else_branch: Option<Box<Stmt>>, // Optional statement to execute if condition is false
+        span: Span,                     // Position of the 'if' keyword
     },
     While {
         condition: Expr, // Condition expression for the while loop
         body: Box<Stmt>, // Body of the while loop
+        span: Span,      // Position of the 'while' keyword
+    },
+    ForIn {
+        variable: String, // Variable each item is assigned to
+        iterable: Expr,   // What is traversed (array, string, object, or iterator)
+        body: Box<Stmt>,  // Body of the loop, run once per item
+        span: Span,       // Position of the 'for' keyword
+        #[serde(skip)]
+        slot: Slot, // Symbol of the variable
     },
 }
 
+// Implement methods for Stmt
+impl Stmt {
+    // Get the source position where this statement starts
+    pub fn span(&self) -> Span {
+        match self {
+            Stmt::Expression(_, span)
+            | Stmt::Return(_, span)
+            | Stmt::Block(_, span)
+            | Stmt::Print { span, .. }
+            | Stmt::PrintLn { span, .. }
+            | Stmt::PrintErr { span, .. }
+            | Stmt::Import { span, .. }
+            | Stmt::VarDeclaration { span, .. }
+            | Stmt::Unpack { span, .. }
+            | Stmt::FunctionDeclaration { span, .. }
+            | Stmt::If { span, .. }
+            | Stmt::While { span, .. }
+            | Stmt::ForIn { span, .. } => *span,
+        }
+    }
+}
+
+// Version of the AST's serialized shape, bumped whenever it changes (.pgc files and the parse
+// cache of imported modules record it, so neither is read by a pidgin that expects another)
+pub const FORMAT_VERSION: u32 = 9;
+
 // Define the Program struct, representing the root of the AST (a list of statements)
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Program {
     pub statements: Vec<Stmt>, // The list of statements in the program
 }
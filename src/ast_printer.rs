@@ -0,0 +1,294 @@
+// Human-readable tree rendering of the AST (default `--ast` output)
+//
+// Each node is printed on its own line, indented under its parent with box-drawing
+// guides, followed by its source position (`@line:column`) where the node has one:
+//
+//   Program
+//   └─ VarDeclaration x @1:1
+//      └─ Binary + @1:11
+//         ├─ Number 1
+//         └─ Number 2
+use crate::ast::{Expr, Pattern, Program, Span, Stmt};
+
+// Define the Node struct, one line of the tree together with its children
+struct Node {
+    label: String,       // Text shown for this node
+    children: Vec<Node>, // Child nodes, printed indented below
+}
+
+impl Node {
+    // Create a node without children
+    fn leaf(label: String) -> Self {
+        Self {
+            label,
+            children: Vec::new(),
+        }
+    }
+
+    // Create a node with children
+    fn branch(label: String, children: Vec<Node>) -> Self {
+        Self { label, children }
+    }
+
+    // Prefix the node's label with the role it plays in its parent (e.g. "condition: ")
+    fn role(mut self, role: &str) -> Self {
+        self.label = format!("{role}: {}", self.label);
+        self
+    }
+}
+
+// Render a program as an indented tree, one node per line
+pub fn print_tree(program: &Program) -> String {
+    let root = Node::branch(
+        "Program".to_string(),
+        program.statements.iter().map(stmt_node).collect(),
+    );
+
+    let mut out = String::new();
+    out.push_str(&root.label);
+    out.push('\n');
+    render_children(&root, "", &mut out);
+    out
+}
+
+// Render the children of a node, drawing guides under the given prefix
+fn render_children(node: &Node, prefix: &str, out: &mut String) {
+    for (index, child) in node.children.iter().enumerate() {
+        let last = index + 1 == node.children.len();
+        out.push_str(prefix);
+        out.push_str(if last { "└─ " } else { "├─ " });
+        out.push_str(&child.label);
+        out.push('\n');
+
+        let child_prefix = format!("{prefix}{}", if last { "   " } else { "│  " });
+        render_children(child, &child_prefix, out);
+    }
+}
+
+// Format a source position for display
+fn at(span: Span) -> String {
+    format!("@{}:{}", span.line, span.column)
+}
+
+// Build the tree node for a statement
+fn stmt_node(stmt: &Stmt) -> Node {
+    let span = at(stmt.span());
+    match stmt {
+        Stmt::Expression(expr, _) => {
+            Node::branch(format!("Expression {span}"), vec![expr_node(expr)])
+        }
+        Stmt::Return(expr, _) => Node::branch(format!("Return {span}"), vec![expr_node(expr)]),
+        Stmt::Print {
+            format, arguments, ..
+        } => print_node("Print", &span, format, arguments),
+        Stmt::PrintLn {
+            format, arguments, ..
+        } => print_node("PrintLn", &span, format, arguments),
+        Stmt::PrintErr {
+            format, arguments, ..
+        } => print_node("PrintErr", &span, format, arguments),
+        Stmt::Import { names, module, .. } => Node::leaf(format!(
+            "Import {{{}}} from \"{module}\" {span}",
+            names.join(", ")
+        )),
+        Stmt::VarDeclaration {
+            name, initializer, ..
+        } => Node::branch(
+            format!("VarDeclaration {name} {span}"),
+            initializer.iter().map(expr_node).collect(),
+        ),
+        Stmt::Unpack {
+            names,
+            value,
+            declares,
+            ..
+        } => Node::branch(
+            format!(
+                "Unpack {}{} {span}",
+                if *declares { "let " } else { "" },
+                names.join(", ")
+            ),
+            vec![expr_node(value)],
+        ),
+        Stmt::FunctionDeclaration {
+            name,
+            parameters,
+            body,
+            ..
+        } => Node::branch(
+            format!(
+                "FunctionDeclaration {name}({}) {span}",
+                parameters.join(", ")
+            ),
+            vec![stmt_node(body)],
+        ),
+        Stmt::Block(statements, _) => Node::branch(
+            format!("Block {span}"),
+            statements.iter().map(stmt_node).collect(),
+        ),
+        Stmt::If {
+            condition,
+            then_branch,
+            else_branch,
+            ..
+        } => {
+            let mut children = vec![
+                expr_node(condition).role("condition"),
+                stmt_node(then_branch).role("then"),
+            ];
+            if let Some(else_branch) = else_branch {
+                children.push(stmt_node(else_branch).role("else"));
+            }
+            Node::branch(format!("If {span}"), children)
+        }
+        Stmt::While {
+            condition, body, ..
+        } => Node::branch(
+            format!("While {span}"),
+            vec![
+                expr_node(condition).role("condition"),
+                stmt_node(body).role("body"),
+            ],
+        ),
+        Stmt::ForIn {
+            variable,
+            iterable,
+            body,
+            ..
+        } => Node::branch(
+            format!("ForIn {variable} {span}"),
+            vec![
+                expr_node(iterable).role("iterable"),
+                stmt_node(body).role("body"),
+            ],
+        ),
+    }
+}
+
+// Build the tree node for one of the print statements
+fn print_node(kind: &str, span: &str, format: &Expr, arguments: &[Expr]) -> Node {
+    let mut children = vec![expr_node(format).role("format")];
+    children.extend(
+        arguments
+            .iter()
+            .map(|argument| expr_node(argument).role("argument")),
+    );
+    Node::branch(format!("{kind} {span}"), children)
+}
+
+// Build the tree node for an expression
+fn expr_node(expr: &Expr) -> Node {
+    match expr {
+        Expr::Number(n) => Node::leaf(format!("Number {n}")),
+        Expr::String(s) => Node::leaf(format!("String {s:?}")),
+        Expr::Boolean(b) => Node::leaf(format!("Boolean {b}")),
+        Expr::Identifier(name, span, _) => Node::leaf(format!("Identifier {name} {}", at(*span))),
+        Expr::Nil => Node::leaf("Nil".to_string()),
+        Expr::FixedArray(elements) => Node::branch(
+            "FixedArray".to_string(),
+            elements.iter().map(expr_node).collect(),
+        ),
+        Expr::DynamicArray(elements) => Node::branch(
+            "DynamicArray".to_string(),
+            elements.iter().map(expr_node).collect(),
+        ),
+        Expr::Index { array, index, span } => Node::branch(
+            format!("Index {}", at(*span)),
+            vec![
+                expr_node(array).role("array"),
+                expr_node(index).role("index"),
+            ],
+        ),
+        Expr::Binary {
+            left,
+            operator,
+            right,
+            span,
+        } => Node::branch(
+            format!("Binary {} {}", operator.symbol(), at(*span)),
+            vec![expr_node(left), expr_node(right)],
+        ),
+        Expr::Unary {
+            operator,
+            operand,
+            span,
+        } => Node::branch(
+            format!("Unary {} {}", operator.symbol(), at(*span)),
+            vec![expr_node(operand)],
+        ),
+        Expr::Assignment {
+            name, value, span, ..
+        } => Node::branch(
+            format!("Assignment {name} {}", at(*span)),
+            vec![expr_node(value)],
+        ),
+        Expr::MethodCall {
+            object,
+            method,
+            arguments,
+            span,
+        } => Node::branch(
+            format!("MethodCall .{method} {}", at(*span)),
+            std::iter::once(expr_node(object).role("object"))
+                .chain(
+                    arguments
+                        .iter()
+                        .map(|argument| expr_node(argument).role("argument")),
+                )
+                .collect(),
+        ),
+        Expr::Transform { from, to, span } => {
+            Node::leaf(format!("Transform `{from} -> {to}` {}", at(*span)))
+        }
+        Expr::FunctionCall {
+            name,
+            arguments,
+            span,
+            ..
+        } => Node::branch(
+            format!("FunctionCall {name} {}", at(*span)),
+            arguments.iter().map(expr_node).collect(),
+        ),
+        Expr::Match {
+            subject,
+            cases,
+            span,
+        } => Node::branch(
+            format!("Match {}", at(*span)),
+            std::iter::once(expr_node(subject).role("subject"))
+                .chain(cases.iter().map(|case| {
+                    let guard = case
+                        .guard
+                        .iter()
+                        .map(|guard| expr_node(guard).role("guard"));
+                    Node::branch(
+                        format!("Case {}", pattern_text(&case.pattern)),
+                        guard.chain([expr_node(&case.body).role("body")]).collect(),
+                    )
+                }))
+                .collect(),
+        ),
+    }
+}
+
+// Write a match pattern the way it appears in source code
+fn pattern_text(pattern: &Pattern) -> String {
+    match pattern {
+        Pattern::Wildcard => "_".to_string(),
+        Pattern::Binding(name, _) => name.clone(),
+        Pattern::Number(n) => n.to_string(),
+        Pattern::String(s) => format!("{s:?}"),
+        Pattern::Boolean(b) => b.to_string(),
+        Pattern::Array(elements) => {
+            let elements: Vec<String> = elements.iter().map(pattern_text).collect();
+            format!("[{}]", elements.join(", "))
+        }
+        Pattern::Object(fields) => {
+            let fields: Vec<String> = fields
+                .iter()
+                .map(|(key, field)| format!("{key:?} => {}", pattern_text(field)))
+                .collect();
+            format!("Object({})", fields.join(", "))
+        }
+    }
+}
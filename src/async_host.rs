@@ -0,0 +1,80 @@
+// Async host functions: letting scripts call into async Rust code (Interpreter::interpret_async)
+//
+// The interpreter walks the syntax tree with ordinary recursive calls, so it cannot pause in
+// the middle of a program to await a future. Instead, interpret_async runs the program on a
+// thread of its own. When the program calls an async builtin, that thread sends the call over
+// an AsyncBridge and waits; the task that awaits interpret_async receives it, awaits the
+// builtin's future (on the caller's executor, e.g. inside a tokio runtime) and sends the
+// result back. The caller's executor is never blocked.
+use crate::error::PidginError;
+use crate::interpreter::{Interpreter, Value};
+use std::collections::VecDeque;
+use std::future::{poll_fn, Future};
+use std::pin::Pin;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::task::{Poll, Waker};
+
+// The future an async builtin returns
+pub type HostFuture = Pin<Box<dyn Future<Output = Result<Value, String>> + Send>>;
+
+// A function provided by the embedding program that returns a future (see
+// Interpreter::register_async_builtin)
+pub type AsyncHostFunction = Arc<dyn Fn(Vec<Value>) -> HostFuture + Send + Sync>;
+
+// Define the Event enum, what the program's thread tells the awaiting task
+pub enum Event {
+    Call(String, Vec<Value>, mpsc::Sender<Result<Value, String>>), // Await this builtin
+    Done(Box<Interpreter>, Result<(), PidginError>),               // The program finished
+}
+
+// Define the Shared struct, the queue of events and who to wake when one arrives
+#[derive(Default)]
+struct Shared {
+    events: VecDeque<Event>,
+    waker: Option<Waker>,
+}
+
+// Define the AsyncBridge struct, the connection between the program's thread and the awaiting
+// task (cheap to clone)
+#[derive(Clone, Default)]
+pub struct AsyncBridge(Arc<Mutex<Shared>>);
+
+// Implement methods for AsyncBridge
+impl AsyncBridge {
+    // Queue an event for the awaiting task and wake it
+    pub fn send(&self, event: Event) {
+        let waker = {
+            let mut shared = self.0.lock().unwrap();
+            shared.events.push_back(event);
+            shared.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    // Call an async builtin from the program's thread, waiting until the task has awaited it
+    pub fn call(&self, name: &str, arguments: Vec<Value>) -> Result<Value, String> {
+        let (reply, result) = mpsc::channel();
+        self.send(Event::Call(name.to_string(), arguments, reply));
+        result
+            .recv()
+            .unwrap_or_else(|_| Err(format!("Call to '{name}' was cancelled")))
+    }
+
+    // Wait for the next event from the program's thread
+    pub async fn next(&self) -> Event {
+        poll_fn(|cx| {
+            let mut shared = self.0.lock().unwrap();
+            match shared.events.pop_front() {
+                Some(event) => Poll::Ready(event),
+                None => {
+                    shared.waker = Some(cx.waker().clone());
+                    Poll::Pending
+                }
+            }
+        })
+        .await
+    }
+}
@@ -0,0 +1,76 @@
+// Micro-benchmarking for Pidgin programs (`pidgin bench`)
+//
+// The source is lexed and parsed once; every run then executes it in a fresh interpreter so
+// runs do not see each other's globals. With a function name, the program is run once per
+// iteration (untimed) to define its globals and only the call to that function is timed.
+use crate::ast::Program;
+use crate::interpreter::{Interpreter, Limits};
+use std::time::{Duration, Instant};
+
+// Define the BenchOptions struct, what to benchmark and how often
+#[derive(Debug, Clone)]
+pub struct BenchOptions {
+    pub runs: usize,              // Number of timed runs
+    pub warmup: usize,            // Untimed runs before measuring
+    pub function: Option<String>, // Time only this zero-argument function
+    pub limits: Limits,           // Resource limits applied to every run
+}
+
+// Define the BenchStats struct, the timings collected over all runs
+#[derive(Debug, Clone)]
+pub struct BenchStats {
+    pub runs: usize,    // Number of timed runs
+    pub min: Duration,  // Fastest run
+    pub mean: Duration, // Average run
+    pub max: Duration,  // Slowest run
+}
+
+// Run a parsed program repeatedly and collect its timings
+pub fn bench_program(program: &Program, options: &BenchOptions) -> Result<BenchStats, String> {
+    if options.runs == 0 {
+        return Err("Number of runs must be at least 1".to_string());
+    }
+
+    for _ in 0..options.warmup {
+        run_once(program, options)?;
+    }
+
+    let mut timings = Vec::with_capacity(options.runs);
+    for _ in 0..options.runs {
+        timings.push(run_once(program, options)?);
+    }
+
+    let total: Duration = timings.iter().sum();
+    Ok(BenchStats {
+        runs: timings.len(),
+        min: timings.iter().min().copied().unwrap_or_default(),
+        mean: total / timings.len() as u32,
+        max: timings.iter().max().copied().unwrap_or_default(),
+    })
+}
+
+// Execute one run in a fresh interpreter, returning the time spent in the measured part
+fn run_once(program: &Program, options: &BenchOptions) -> Result<Duration, String> {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_limits(options.limits);
+    let program = program.clone(); // Copied outside the timer
+
+    match &options.function {
+        Some(name) => {
+            interpreter.interpret(program)?;
+            let start = Instant::now();
+            interpreter.call(name)?;
+            Ok(start.elapsed())
+        }
+        None => {
+            let start = Instant::now();
+            interpreter.interpret(program)?;
+            Ok(start.elapsed())
+        }
+    }
+}
+
+// Format a duration in milliseconds for the report
+pub fn format_duration(duration: Duration) -> String {
+    format!("{:.3} ms", duration.as_secs_f64() * 1000.0)
+}
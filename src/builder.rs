@@ -0,0 +1,476 @@
+// Building programs from Rust code instead of parsing source (ProgramBuilder)
+//
+// Statements are added one after another with ProgramBuilder's methods; expressions are made
+// with the functions in this module and combined with the operator methods on Expr:
+//
+//     use pidgin::builder::{ident, number, ProgramBuilder};
+//
+//     let program = ProgramBuilder::new()
+//         .let_("x").number(1.0)
+//         .let_("y").value(ident("x").add(number(2.0)))
+//         .print_ln("{}", vec![ident("y")])
+//         .build();
+//
+// There is no source text, so each statement is given the next line number, as if the program
+// were written one statement per line; runtime errors report those lines.
+use crate::ast::{BinaryOp, Expr, Program, Span, Stmt, UnaryOp};
+use crate::symbols::Slot;
+
+// Define the ProgramBuilder struct, a program being built one statement at a time
+#[derive(Debug, Clone)]
+pub struct ProgramBuilder {
+    statements: Vec<Stmt>, // The statements added so far
+    line: usize,           // Line number given to the next statement
+}
+
+// Implement Default for ProgramBuilder (an empty program)
+impl Default for ProgramBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Implement methods for ProgramBuilder
+impl ProgramBuilder {
+    // Create a builder for an empty program
+    pub fn new() -> Self {
+        Self {
+            statements: Vec::new(),
+            line: 1,
+        }
+    }
+
+    // Declare a variable; the returned builder takes its initial value
+    pub fn let_(self, name: &str) -> LetBuilder {
+        LetBuilder {
+            program: self,
+            name: name.to_string(),
+        }
+    }
+
+    // Assign a new value to an existing variable (x = value;)
+    pub fn assign(self, name: &str, value: Expr) -> Self {
+        let assignment = Expr::Assignment {
+            name: name.to_string(),
+            value: Box::new(value),
+            span: Span::default(),
+            slot: Slot::default(),
+        };
+        self.expr(assignment)
+    }
+
+    // Evaluate an expression for its effect (an expression statement)
+    pub fn expr(mut self, expr: Expr) -> Self {
+        let span = self.next_span();
+        self.push(Stmt::Expression(expr, span))
+    }
+
+    // Call a function for its effect (name(arguments);)
+    pub fn call(self, name: &str, arguments: Vec<Expr>) -> Self {
+        self.expr(call(name, arguments))
+    }
+
+    // Print a format string with its arguments, without a newline
+    pub fn print(mut self, format: &str, arguments: Vec<Expr>) -> Self {
+        let span = self.next_span();
+        self.push(Stmt::Print {
+            format: string(format),
+            arguments,
+            span,
+        })
+    }
+
+    // Print a format string with its arguments, followed by a newline
+    pub fn print_ln(mut self, format: &str, arguments: Vec<Expr>) -> Self {
+        let span = self.next_span();
+        self.push(Stmt::PrintLn {
+            format: string(format),
+            arguments,
+            span,
+        })
+    }
+
+    // Print a format string with its arguments to stderr, followed by a newline
+    pub fn print_err(mut self, format: &str, arguments: Vec<Expr>) -> Self {
+        let span = self.next_span();
+        self.push(Stmt::PrintErr {
+            format: string(format),
+            arguments,
+            span,
+        })
+    }
+
+    // Import names from a module file (GET names from module;)
+    pub fn import(mut self, names: &[&str], module: &str) -> Self {
+        let span = self.next_span();
+        self.push(Stmt::Import {
+            names: names.iter().map(|name| name.to_string()).collect(),
+            module: module.to_string(),
+            span,
+        })
+    }
+
+    // Define a function; `body` adds the statements of its body to the builder it is given
+    pub fn function(
+        mut self,
+        name: &str,
+        parameters: &[&str],
+        body: impl FnOnce(ProgramBuilder) -> ProgramBuilder,
+    ) -> Self {
+        let span = self.next_span();
+        let body = self.block(body);
+        self.push(Stmt::FunctionDeclaration {
+            name: name.to_string(),
+            parameters: parameters.iter().map(|name| name.to_string()).collect(),
+            parameter_spans: vec![span; parameters.len()],
+            body,
+            doc: None,
+            span,
+        })
+    }
+
+    // Return a value from the function being built
+    pub fn return_(mut self, value: Expr) -> Self {
+        let span = self.next_span();
+        self.push(Stmt::Return(value, span))
+    }
+
+    // Run the statements `then` adds only if the condition is true
+    pub fn if_(
+        mut self,
+        condition: Expr,
+        then: impl FnOnce(ProgramBuilder) -> ProgramBuilder,
+    ) -> Self {
+        let span = self.next_span();
+        let then_branch = self.block(then);
+        self.push(Stmt::If {
+            condition,
+            then_branch,
+            else_branch: None,
+            span,
+        })
+    }
+
+    // Run the statements `then` adds if the condition is true, and those `otherwise` adds if not
+    pub fn if_else(
+        mut self,
+        condition: Expr,
+        then: impl FnOnce(ProgramBuilder) -> ProgramBuilder,
+        otherwise: impl FnOnce(ProgramBuilder) -> ProgramBuilder,
+    ) -> Self {
+        let span = self.next_span();
+        let then_branch = self.block(then);
+        let else_branch = self.block(otherwise);
+        self.push(Stmt::If {
+            condition,
+            then_branch,
+            else_branch: Some(else_branch),
+            span,
+        })
+    }
+
+    // Run the statements `body` adds for as long as the condition is true
+    pub fn while_(
+        mut self,
+        condition: Expr,
+        body: impl FnOnce(ProgramBuilder) -> ProgramBuilder,
+    ) -> Self {
+        let span = self.next_span();
+        let body = self.block(body);
+        self.push(Stmt::While {
+            condition,
+            body,
+            span,
+        })
+    }
+
+    // Finish the program
+    pub fn build(self) -> Program {
+        Program {
+            statements: self.statements,
+        }
+    }
+
+    // Get the span for the next statement, moving on to the following line
+    fn next_span(&mut self) -> Span {
+        let span = Span::new(self.line, 1);
+        self.line += 1;
+        span
+    }
+
+    // Build a block from the statements a closure adds, continuing the line numbering
+    fn block(&mut self, body: impl FnOnce(ProgramBuilder) -> ProgramBuilder) -> Box<Stmt> {
+        let span = Span::new(self.line, 1);
+        let inner = body(ProgramBuilder {
+            statements: Vec::new(),
+            line: self.line,
+        });
+        self.line = inner.line;
+        Box::new(Stmt::Block(inner.statements, span))
+    }
+
+    // Add a statement, giving the expressions in it the statement's line
+    fn push(mut self, mut stmt: Stmt) -> Self {
+        let line = stmt.span().line;
+        match &mut stmt {
+            Stmt::Expression(expr, _) | Stmt::Return(expr, _) => place(expr, line),
+            Stmt::Print {
+                format, arguments, ..
+            }
+            | Stmt::PrintLn {
+                format, arguments, ..
+            }
+            | Stmt::PrintErr {
+                format, arguments, ..
+            } => {
+                place(format, line);
+                arguments
+                    .iter_mut()
+                    .for_each(|argument| place(argument, line));
+            }
+            Stmt::VarDeclaration {
+                initializer: Some(expr),
+                ..
+            } => place(expr, line),
+            Stmt::If { condition, .. } | Stmt::While { condition, .. } => place(condition, line),
+            _ => {}
+        }
+        self.statements.push(stmt);
+        self
+    }
+}
+
+// Define the LetBuilder struct, a variable declaration waiting for its initial value
+#[derive(Debug, Clone)]
+pub struct LetBuilder {
+    program: ProgramBuilder, // The program the declaration is added to
+    name: String,            // Name of the variable
+}
+
+// Implement methods for LetBuilder
+impl LetBuilder {
+    // Start the variable at a number
+    pub fn number(self, value: f64) -> ProgramBuilder {
+        self.value(number(value))
+    }
+
+    // Start the variable at a string
+    pub fn string(self, value: &str) -> ProgramBuilder {
+        self.value(string(value))
+    }
+
+    // Start the variable at a boolean
+    pub fn boolean(self, value: bool) -> ProgramBuilder {
+        self.value(boolean(value))
+    }
+
+    // Start the variable at nil
+    pub fn nil(self) -> ProgramBuilder {
+        self.value(nil())
+    }
+
+    // Start the variable at the value of any expression
+    pub fn value(self, value: Expr) -> ProgramBuilder {
+        self.declare(Some(value))
+    }
+
+    // Declare the variable without a value (let x;)
+    pub fn uninitialized(self) -> ProgramBuilder {
+        self.declare(None)
+    }
+
+    // Add the declaration to the program
+    fn declare(self, initializer: Option<Expr>) -> ProgramBuilder {
+        let mut program = self.program;
+        let span = program.next_span();
+        program.push(Stmt::VarDeclaration {
+            name: self.name,
+            initializer,
+            span,
+            slot: Slot::default(),
+        })
+    }
+}
+
+// Make a number literal
+pub fn number(value: f64) -> Expr {
+    Expr::Number(value)
+}
+
+// Make a string literal
+pub fn string(value: &str) -> Expr {
+    Expr::String(value.to_string())
+}
+
+// Make a boolean literal
+pub fn boolean(value: bool) -> Expr {
+    Expr::Boolean(value)
+}
+
+// Make the nil literal
+pub fn nil() -> Expr {
+    Expr::Nil
+}
+
+// Refer to a variable by name
+pub fn ident(name: &str) -> Expr {
+    Expr::Identifier(name.to_string(), Span::default(), Slot::default())
+}
+
+// Call a function with arguments
+pub fn call(name: &str, arguments: Vec<Expr>) -> Expr {
+    Expr::FunctionCall {
+        name: name.to_string(),
+        arguments,
+        span: Span::default(),
+        slot: Slot::default(),
+    }
+}
+
+// Make a fixed array, like [a, b, c]
+pub fn array(items: Vec<Expr>) -> Expr {
+    Expr::FixedArray(items)
+}
+
+// Make a dynamic array, like {a, b, c}
+pub fn dynamic_array(items: Vec<Expr>) -> Expr {
+    Expr::DynamicArray(items)
+}
+
+// Implement the operators for Expr, so expressions can be combined: ident("x").add(number(1.0))
+impl Expr {
+    // Make a binary expression with this expression on the left (like every expression made
+    // here, its line is set when the statement holding it is added to a ProgramBuilder)
+    fn binary(self, operator: BinaryOp, right: Expr) -> Expr {
+        Expr::Binary {
+            left: Box::new(self),
+            operator,
+            right: Box::new(right),
+            span: Span::default(),
+        }
+    }
+
+    // Add (or concatenate) another expression: self + right (named after BinaryOp::Add like
+    // its neighbors, rather than implementing std::ops::Add for only some of the operators)
+    #[allow(clippy::should_implement_trait)]
+    pub fn add(self, right: Expr) -> Expr {
+        self.binary(BinaryOp::Add, right)
+    }
+
+    // Subtract another expression: self - right
+    pub fn subtract(self, right: Expr) -> Expr {
+        self.binary(BinaryOp::Subtract, right)
+    }
+
+    // Multiply by another expression: self * right
+    pub fn multiply(self, right: Expr) -> Expr {
+        self.binary(BinaryOp::Multiply, right)
+    }
+
+    // Divide by another expression: self / right
+    pub fn divide(self, right: Expr) -> Expr {
+        self.binary(BinaryOp::Divide, right)
+    }
+
+    // Compare for equality: self == right
+    pub fn equal(self, right: Expr) -> Expr {
+        self.binary(BinaryOp::Equal, right)
+    }
+
+    // Compare for inequality: self != right
+    pub fn not_equal(self, right: Expr) -> Expr {
+        self.binary(BinaryOp::NotEqual, right)
+    }
+
+    // Compare: self < right
+    pub fn less(self, right: Expr) -> Expr {
+        self.binary(BinaryOp::Less, right)
+    }
+
+    // Compare: self > right
+    pub fn greater(self, right: Expr) -> Expr {
+        self.binary(BinaryOp::Greater, right)
+    }
+
+    // Compare: self <= right
+    pub fn less_equal(self, right: Expr) -> Expr {
+        self.binary(BinaryOp::LessEqual, right)
+    }
+
+    // Compare: self >= right
+    pub fn greater_equal(self, right: Expr) -> Expr {
+        self.binary(BinaryOp::GreaterEqual, right)
+    }
+
+    // Negate: -self
+    pub fn negate(self) -> Expr {
+        Expr::Unary {
+            operator: UnaryOp::Minus,
+            operand: Box::new(self),
+            span: Span::default(),
+        }
+    }
+
+    // Index into an array: self[index]
+    pub fn index(self, index: Expr) -> Expr {
+        Expr::Index {
+            array: Box::new(self),
+            index: Box::new(index),
+            span: Span::default(),
+        }
+    }
+
+    // Call a method: self.method(arguments...)
+    pub fn method(self, method: &str, arguments: Vec<Expr>) -> Expr {
+        Expr::MethodCall {
+            object: Box::new(self),
+            method: method.to_string(),
+            arguments,
+            span: Span::default(),
+        }
+    }
+}
+
+// Give the expressions that have no line yet (line 0) the given line, in column 1
+fn place(expr: &mut Expr, line: usize) {
+    match expr {
+        Expr::Identifier(_, span, _)
+        | Expr::Transform { span, .. }
+        | Expr::Binary { span, .. }
+        | Expr::Unary { span, .. }
+        | Expr::Assignment { span, .. }
+        | Expr::Index { span, .. }
+        | Expr::MethodCall { span, .. }
+        | Expr::FunctionCall { span, .. }
+        | Expr::Match { span, .. }
+            if span.line == 0 =>
+        {
+            *span = Span::new(line, 1);
+        }
+        _ => {}
+    }
+    match expr {
+        Expr::Binary { left, right, .. } => {
+            place(left, line);
+            place(right, line);
+        }
+        Expr::Unary { operand, .. } => place(operand, line),
+        Expr::Assignment { value, .. } => place(value, line),
+        Expr::Index { array, index, .. } => {
+            place(array, line);
+            place(index, line);
+        }
+        Expr::MethodCall {
+            object, arguments, ..
+        } => {
+            place(object, line);
+            arguments.iter_mut().for_each(|item| place(item, line));
+        }
+        Expr::FunctionCall {
+            arguments: items, ..
+        }
+        | Expr::FixedArray(items)
+        | Expr::DynamicArray(items) => items.iter_mut().for_each(|item| place(item, line)),
+        _ => {}
+    }
+}
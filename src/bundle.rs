@@ -0,0 +1,126 @@
+// Standalone executables: a copy of the pidgin binary with a program appended (pidgin bundle)
+//
+// Layout of a bundled executable:
+//   [pidgin binary][payload][payload length: u64 little-endian][MAGIC]
+// The payload is a list of files, each stored as
+//   [path length: u64][path][source length: u64][source]
+// with the main program first, followed by every module it imports. On startup, pidgin
+// checks its own executable for the trailer and, if present, runs the embedded program.
+use crate::watch::program_files;
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
+
+const MAGIC: &[u8; 16] = b"PIDGIN-BUNDLE-v1"; // Marks the end of a bundled executable
+const TRAILER_LEN: u64 = 8 + MAGIC.len() as u64; // Payload length plus magic
+
+// Define the Bundle struct, the program embedded in an executable
+#[derive(Debug, Clone)]
+pub struct Bundle {
+    pub main: (String, String),         // Path and source of the main program
+    pub modules: Vec<(String, String)>, // Path and source of each imported module
+}
+
+// Create a standalone executable at `output` that runs the program in `path`
+pub fn bundle_file(path: &str, output: &str) -> Result<(), String> {
+    let exe = std::env::current_exe()
+        .map_err(|e| format!("Failed to locate the pidgin executable: {e}"))?;
+    let mut binary =
+        fs::read(&exe).map_err(|e| format!("Failed to read '{}': {e}", exe.display()))?;
+    binary.truncate(interpreter_len(&binary)); // Never nest bundles
+
+    // The program and its modules, read now so the executable works without them
+    let mut payload = Vec::new();
+    for file in program_files(path) {
+        let source =
+            fs::read_to_string(&file).map_err(|e| format!("Failed to read file '{file}': {e}"))?;
+        write_entry(&mut payload, &file);
+        write_entry(&mut payload, &source);
+    }
+
+    binary.extend_from_slice(&payload);
+    binary.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    binary.extend_from_slice(MAGIC);
+    fs::write(output, binary).map_err(|e| format!("Failed to write file '{output}': {e}"))?;
+    make_executable(output)
+}
+
+// Read the program embedded in the running executable, if there is one
+pub fn embedded_bundle() -> Option<Bundle> {
+    let exe = std::env::current_exe().ok()?;
+    let mut file = File::open(exe).ok()?;
+
+    // Only the trailer is read unless this really is a bundle
+    let size = file.metadata().ok()?.len();
+    if size < TRAILER_LEN {
+        return None;
+    }
+    let mut trailer = [0u8; TRAILER_LEN as usize];
+    file.seek(SeekFrom::Start(size - TRAILER_LEN)).ok()?;
+    file.read_exact(&mut trailer).ok()?;
+    if &trailer[8..] != MAGIC {
+        return None;
+    }
+
+    let payload_len = u64::from_le_bytes(trailer[..8].try_into().ok()?);
+    let start = (size - TRAILER_LEN).checked_sub(payload_len)?;
+    let mut payload = vec![0u8; payload_len as usize];
+    file.seek(SeekFrom::Start(start)).ok()?;
+    file.read_exact(&mut payload).ok()?;
+    parse_payload(&payload)
+}
+
+// Get the length of the interpreter part of an executable (all of it if not a bundle)
+fn interpreter_len(binary: &[u8]) -> usize {
+    let len = binary.len();
+    if len < TRAILER_LEN as usize || &binary[len - MAGIC.len()..] != MAGIC {
+        return len;
+    }
+    let trailer_start = len - TRAILER_LEN as usize;
+    let mut payload_len = [0u8; 8];
+    payload_len.copy_from_slice(&binary[trailer_start..trailer_start + 8]);
+    trailer_start.saturating_sub(u64::from_le_bytes(payload_len) as usize)
+}
+
+// Append a length-prefixed string to the payload
+fn write_entry(payload: &mut Vec<u8>, text: &str) {
+    payload.extend_from_slice(&(text.len() as u64).to_le_bytes());
+    payload.extend_from_slice(text.as_bytes());
+}
+
+// Split a payload back into its files
+fn parse_payload(payload: &[u8]) -> Option<Bundle> {
+    let mut files = Vec::new();
+    let mut rest = payload;
+    while !rest.is_empty() {
+        let (path, after_path) = read_entry(rest)?;
+        let (source, after_source) = read_entry(after_path)?;
+        files.push((path, source));
+        rest = after_source;
+    }
+
+    let mut files = files.into_iter();
+    Some(Bundle {
+        main: files.next()?,
+        modules: files.collect(),
+    })
+}
+
+// Read one length-prefixed string, returning it and the remaining bytes
+fn read_entry(bytes: &[u8]) -> Option<(String, &[u8])> {
+    let len = u64::from_le_bytes(bytes.get(..8)?.try_into().ok()?) as usize;
+    let text = bytes.get(8..8usize.checked_add(len)?)?;
+    Some((String::from_utf8(text.to_vec()).ok()?, &bytes[8 + len..]))
+}
+
+// Mark the output file as executable (Unix only; Windows decides by extension)
+#[cfg(unix)]
+fn make_executable(path: &str) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))
+        .map_err(|e| format!("Failed to make '{path}' executable: {e}"))
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &str) -> Result<(), String> {
+    Ok(())
+}
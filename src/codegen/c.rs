@@ -0,0 +1,622 @@
+// C backend: translate a Pidgin program into a standalone C99 source file
+//
+// The generated code relies on the runtime in runtime.c (embedded in every output file) for
+// values, operators, methods, and builtins, so the program behaves the same as under the
+// interpreter. Every variable name gets a fixed slot in the globals, so lookups are array
+// accesses. Imported modules are resolved at build time and compiled into the same file.
+use crate::ast::{Expr, MatchCase, Program, Stmt};
+use crate::interpreter::{is_builtin, parse_module};
+use crate::pattern::{Step, Test};
+use std::collections::HashMap;
+
+const RUNTIME: &str = include_str!("runtime.c"); // The runtime (values, operators, builtins)
+const INDENT: &str = "  "; // Indentation used in the generated code
+
+// Translate a program into C; `source_path` is only used in the header comment
+pub fn transpile(program: &Program, source_path: &str) -> Result<String, String> {
+    let mut generator = CGenerator::default();
+    let main = generator.body(&program.statements, false)?;
+
+    // Generate each imported module (which may import further modules) exactly once
+    let mut modules = Vec::new();
+    let mut index = 0;
+    while index < generator.modules.len() {
+        let (_, program) = generator.modules[index].clone();
+        modules.push(generator.body(&program.statements, true)?);
+        index += 1;
+    }
+
+    let mut output = format!(
+        "/* Generated by pidgin build from {}. Do not edit. */\n",
+        source_path.replace("*/", "* /")
+    );
+
+    // Variable names, indexed by slot (C does not allow empty arrays, so there is always one)
+    let names: Vec<String> = if generator.names.is_empty() {
+        vec![c_string("")]
+    } else {
+        generator.names.iter().map(|name| c_string(name)).collect()
+    };
+    output.push_str(&format!("#define PG_NAME_COUNT {}\n", names.len()));
+    output.push_str(&format!(
+        "static const char *const pg_names[PG_NAME_COUNT] = {{{}}};\n\n",
+        names.join(", ")
+    ));
+    output.push_str(RUNTIME);
+    output.push('\n');
+
+    // Declarations first, so functions and modules can refer to each other in any order
+    for (id, _) in generator.functions.iter().enumerate() {
+        output.push_str(&format!("static PgValue pg_fn_{id}(void);\n"));
+    }
+    for (id, _) in generator.modules.iter().enumerate() {
+        output.push_str(&format!("static void pg_module_{id}(void);\n"));
+    }
+    for (id, function) in generator.functions.iter().enumerate() {
+        let params = if function.params.is_empty() {
+            "NULL".to_string()
+        } else {
+            let slots: Vec<String> = function.params.iter().map(usize::to_string).collect();
+            output.push_str(&format!(
+                "static const int pg_params_{id}[] = {{{}}};\n",
+                slots.join(", ")
+            ));
+            format!("pg_params_{id}")
+        };
+        output.push_str(&format!(
+            "static const PgFunction pg_function_{id} = {{{}, {params}, pg_fn_{id}}}; /* {} */\n",
+            function.params.len(),
+            function.name
+        ));
+    }
+    for (id, slots) in generator.imports.iter().enumerate() {
+        let slots: Vec<String> = slots.iter().map(usize::to_string).collect();
+        output.push_str(&format!(
+            "static const int pg_import_{id}[] = {{{}}};\n",
+            slots.join(", ")
+        ));
+    }
+    output.push('\n');
+
+    for (id, function) in generator.functions.iter().enumerate() {
+        output.push_str(&format!(
+            "/* function {} */\nstatic PgValue pg_fn_{id}(void) {{\n{}{INDENT}return pg_nil();\n}}\n\n",
+            function.name, function.body
+        ));
+    }
+    for (id, body) in modules.iter().enumerate() {
+        output.push_str(&format!(
+            "/* module {} */\nstatic void pg_module_{id}(void) {{\n{body}}}\n\n",
+            generator.modules[id].0.replace("*/", "* /")
+        ));
+    }
+
+    output.push_str(&format!(
+        "int main(int argc, char **argv) {{\n{INDENT}pg_start(argc, argv);\n{main}{INDENT}pg_run_timers();\n{INDENT}return 0;\n}}\n"
+    ));
+    Ok(output)
+}
+
+// Define the CFunction struct, a compiled user-defined function
+struct CFunction {
+    name: String,       // Name in the source (for comments)
+    params: Vec<usize>, // Slots of the parameters
+    body: String,       // Generated statements of the body
+}
+
+// Define the CGenerator struct, which holds the state used while generating code
+#[derive(Default)]
+struct CGenerator {
+    output: String,                  // Code generated so far for the current body
+    indent: usize,                   // Current indentation level
+    function_depth: usize,           // Number of enclosing function declarations
+    in_module: bool,                 // Whether the current body is an imported module
+    names: Vec<String>,              // Variable names, indexed by slot
+    slots: HashMap<String, usize>,   // Slot of each variable name
+    functions: Vec<CFunction>,       // Every function declaration in the program
+    modules: Vec<(String, Program)>, // Imported modules found so far (file, AST)
+    imports: Vec<Vec<usize>>,        // Slots of the names imported by each import statement
+}
+
+impl CGenerator {
+    // Generate a body of top-level statements (indented one level)
+    fn body(&mut self, statements: &[Stmt], in_module: bool) -> Result<String, String> {
+        self.output.clear();
+        self.indent = 1;
+        self.in_module = in_module;
+        for stmt in statements {
+            self.stmt(stmt)?;
+        }
+        Ok(std::mem::take(&mut self.output))
+    }
+
+    // Get the slot for a variable name, assigning a new one the first time it is seen
+    fn slot(&mut self, name: &str) -> usize {
+        if let Some(slot) = self.slots.get(name) {
+            return *slot;
+        }
+        self.names.push(name.to_string());
+        self.slots.insert(name.to_string(), self.names.len() - 1);
+        self.names.len() - 1
+    }
+
+    // Write one line of code at the current indentation
+    fn line(&mut self, code: &str) {
+        for _ in 0..self.indent {
+            self.output.push_str(INDENT);
+        }
+        self.output.push_str(code);
+        self.output.push('\n');
+    }
+
+    // Generate code for a statement
+    fn stmt(&mut self, stmt: &Stmt) -> Result<(), String> {
+        match stmt {
+            Stmt::Expression(expr, _) => {
+                let code = self.expr(expr)?;
+                self.line(&format!("(void){code};"));
+            }
+            Stmt::Return(expr, _) => {
+                let code = self.expr(expr)?;
+                if self.function_depth > 0 {
+                    self.line(&format!("return {code};"));
+                } else {
+                    // The value is still computed before the error, as in the interpreter
+                    let message = if self.in_module {
+                        "Return statement not allowed at module level"
+                    } else {
+                        "Return statement not allowed outside function"
+                    };
+                    self.line(&format!("(void){code};"));
+                    self.line(&format!("pg_fail({});", c_string(message)));
+                }
+            }
+            Stmt::Print {
+                format, arguments, ..
+            } => self.print(format, arguments, false, false)?,
+            Stmt::PrintLn {
+                format, arguments, ..
+            } => self.print(format, arguments, false, true)?,
+            Stmt::PrintErr {
+                format, arguments, ..
+            } => self.print(format, arguments, true, true)?,
+            Stmt::Import { names, module, .. } => {
+                let (file, program) = parse_module(module)?;
+                let module_id = match self.modules.iter().position(|(known, _)| *known == file) {
+                    Some(id) => id,
+                    None => {
+                        self.modules.push((file.clone(), program));
+                        self.modules.len() - 1
+                    }
+                };
+                let slots = names.iter().map(|name| self.slot(name)).collect();
+                self.imports.push(slots);
+                self.line(&format!(
+                    "pg_import(pg_module_{module_id}, {}, pg_import_{}, {});",
+                    c_string(&file),
+                    self.imports.len() - 1,
+                    names.len()
+                ));
+            }
+            Stmt::VarDeclaration {
+                name, initializer, ..
+            } => {
+                let value = match initializer {
+                    Some(init) => self.expr(init)?,
+                    None => "pg_nil()".to_string(),
+                };
+                let slot = self.variable(name);
+                self.line(&format!("pg_set({slot}, {value});"));
+            }
+            Stmt::Unpack { names, value, .. } => {
+                // pg_unpack checks the array; its elements are then assigned in order
+                let value = self.expr(value)?;
+                self.line("{");
+                self.indent += 1;
+                self.line(&format!(
+                    "const PgValue *pg_items = pg_unpack({value}, {});",
+                    names.len()
+                ));
+                for (index, name) in names.iter().enumerate() {
+                    let slot = self.variable(name);
+                    self.line(&format!("pg_set({slot}, pg_items[{index}]);"));
+                }
+                self.indent -= 1;
+                self.line("}");
+            }
+            Stmt::FunctionDeclaration {
+                name,
+                parameters,
+                body,
+                ..
+            } => {
+                let id = self.function(name, parameters, body)?;
+                let slot = self.variable(name);
+                self.line(&format!("pg_set({slot}, pg_function(&pg_function_{id}));"));
+            }
+            Stmt::Block(..) => {
+                self.line("{");
+                self.block_contents(stmt)?;
+                self.line("}");
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                let condition = self.expr(condition)?;
+                self.line(&format!("if (pg_truthy({condition})) {{"));
+                self.block_contents(then_branch)?;
+                if let Some(else_branch) = else_branch {
+                    self.line("} else {");
+                    self.block_contents(else_branch)?;
+                }
+                self.line("}");
+            }
+            Stmt::While {
+                condition, body, ..
+            } => {
+                let condition = self.expr(condition)?;
+                self.line(&format!("while (pg_truthy({condition})) {{"));
+                self.block_contents(body)?;
+                self.line("}");
+            }
+            Stmt::ForIn {
+                variable,
+                iterable,
+                body,
+                ..
+            } => {
+                // A block of its own, so nested loops each have their own pg_iter
+                let iterable = self.expr(iterable)?;
+                let slot = self.variable(variable);
+                self.line("{");
+                self.indent += 1;
+                self.line(&format!("PgIter pg_iter = pg_iterate({iterable});"));
+                self.line("PgValue pg_item;");
+                self.line("while (pg_iter_next(&pg_iter, &pg_item)) {");
+                self.indent += 1;
+                self.line(&format!("pg_set({slot}, pg_item);"));
+                self.indent -= 1;
+                self.block_contents(body)?;
+                self.line("}");
+                self.indent -= 1;
+                self.line("}");
+            }
+        }
+        Ok(())
+    }
+
+    // Generate a function declaration as a separate C function, returning its id
+    fn function(
+        &mut self,
+        name: &str,
+        parameters: &[String],
+        body: &Stmt,
+    ) -> Result<usize, String> {
+        let params = parameters.iter().map(|p| self.slot(p)).collect();
+        let id = self.functions.len();
+        self.functions.push(CFunction {
+            name: name.to_string(),
+            params,
+            body: String::new(),
+        });
+
+        // Generate the body on its own, then return to the enclosing code
+        let outer = std::mem::take(&mut self.output);
+        let outer_indent = self.indent;
+        self.indent = 0;
+        self.function_depth += 1;
+        let result = self.block_contents(body);
+        self.function_depth -= 1;
+        self.indent = outer_indent;
+        self.functions[id].body = std::mem::replace(&mut self.output, outer);
+        result?;
+        Ok(id)
+    }
+
+    // Generate the statements inside braces (a block's statements, or a single statement)
+    fn block_contents(&mut self, stmt: &Stmt) -> Result<(), String> {
+        self.indent += 1;
+        let result = match stmt {
+            Stmt::Block(statements, _) => statements.iter().try_for_each(|s| self.stmt(s)),
+            other => self.stmt(other),
+        };
+        self.indent -= 1;
+        result
+    }
+
+    // Generate a print, printLn, or printErr statement
+    fn print(
+        &mut self,
+        format: &Expr,
+        arguments: &[Expr],
+        to_stderr: bool,
+        newline: bool,
+    ) -> Result<(), String> {
+        // Like the interpreter, a string literal is always a format string
+        let formatted = !arguments.is_empty() || matches!(format, Expr::String(_));
+        let format = self.expr(format)?;
+        self.line(&format!("pg_push({format});"));
+        if formatted {
+            self.line(&format!("pg_print_begin({});", arguments.len()));
+        }
+        for argument in arguments {
+            let code = self.expr(argument)?;
+            self.line(&format!("pg_push({code});"));
+        }
+        self.line(&format!(
+            "pg_print({}, {}, {}, {});",
+            arguments.len(),
+            u8::from(formatted),
+            u8::from(to_stderr),
+            u8::from(newline)
+        ));
+        Ok(())
+    }
+
+    // Get a variable's slot, with its name as a comment for readers of the generated code
+    fn variable(&mut self, name: &str) -> String {
+        format!("{} /* {name} */", self.slot(name))
+    }
+
+    // Generate code for an expression
+    fn expr(&mut self, expr: &Expr) -> Result<String, String> {
+        Ok(match expr {
+            Expr::Number(n) => format!("pg_number({n:?})"),
+            Expr::String(s) => format!("pg_string({})", c_string(s)),
+            Expr::Boolean(b) => format!("pg_boolean({})", u8::from(*b)),
+            Expr::Nil => "pg_nil()".to_string(),
+            Expr::Identifier(name, _, _) => format!("pg_get({})", self.variable(name)),
+            Expr::FixedArray(elements) => self.array(elements, false)?,
+            Expr::DynamicArray(elements) => self.array(elements, true)?,
+            Expr::Index { array, index, .. } => format!(
+                "(pg_push({}), pg_push({}), pg_index())",
+                self.expr(array)?,
+                self.expr(index)?
+            ),
+            Expr::Binary {
+                left,
+                operator,
+                right,
+                span,
+            } => format!(
+                "(pg_push({}), pg_push({}), pg_binary(\"{}\", {}, {}))",
+                self.expr(left)?,
+                self.expr(right)?,
+                operator.symbol(),
+                span.line,
+                span.column
+            ),
+            Expr::Unary { operand, .. } => format!("pg_negate({})", self.expr(operand)?),
+            Expr::Assignment { name, value, .. } => {
+                let value = self.expr(value)?;
+                format!("pg_set({}, {value})", self.variable(name))
+            }
+            Expr::MethodCall {
+                object,
+                method,
+                arguments,
+                ..
+            } => self.method_call(object, method, arguments)?,
+            Expr::Transform { .. } => {
+                "(pg_fail(\"Transform should not be evaluated directly\"), pg_nil())".to_string()
+            }
+            Expr::FunctionCall {
+                name, arguments, ..
+            } => self.call(name, arguments)?,
+            Expr::Match { subject, cases, .. } => self.match_expr(subject, cases)?,
+        })
+    }
+
+    // Generate a match expression. The value stays pushed while the cases are tried, each with
+    // a condition that runs the tests its pattern compiles to, then assigns the names it binds,
+    // then checks the guard; the chosen case pops it before its result is evaluated. The names
+    // get back what they held when the guard fails or the result is ready, as in the
+    // interpreter.
+    fn match_expr(&mut self, subject: &Expr, cases: &[MatchCase]) -> Result<String, String> {
+        let mut code = format!("(pg_push({}), ", self.expr(subject)?);
+        for case in cases {
+            let decision = case.decision();
+            let mut conditions = Vec::new();
+            for test in &decision.tests {
+                match test {
+                    Test::Array(path, length) => {
+                        conditions.push(format!("pg_match_array({}, {length})", c_part(path)));
+                    }
+                    Test::Object(path, keys) => {
+                        conditions.push(format!("pg_match_object({})", c_part(path)));
+                        for key in keys {
+                            conditions.push(format!(
+                                "pg_match_has({}, {})",
+                                c_part(path),
+                                c_string(key)
+                            ));
+                        }
+                    }
+                    Test::Number(path, n) => {
+                        conditions.push(format!("pg_equal({}, pg_number({n:?}))", c_part(path)));
+                    }
+                    Test::String(path, s) => conditions.push(format!(
+                        "pg_equal({}, pg_string({}))",
+                        c_part(path),
+                        c_string(s)
+                    )),
+                    Test::Boolean(path, b) => conditions.push(format!(
+                        "pg_equal({}, pg_boolean({}))",
+                        c_part(path),
+                        u8::from(*b)
+                    )),
+                }
+            }
+            let count = decision.bindings.len();
+            for (name, path) in &decision.bindings {
+                let slot = self.variable(name);
+                conditions.push(format!("pg_match_bind({slot}, {})", c_part(path)));
+            }
+            if let Some(guard) = &case.guard {
+                let guard = format!("pg_truthy({})", self.expr(guard)?);
+                conditions.push(if count == 0 {
+                    guard
+                } else {
+                    format!("({guard} || pg_match_unbind({count}))")
+                });
+            }
+            if conditions.is_empty() {
+                conditions.push("1".to_string());
+            }
+            let body = self.expr(&case.body)?;
+            let result = if count == 0 {
+                body
+            } else {
+                format!("pg_match_result({body}, {count})")
+            };
+            code.push_str(&format!(
+                "({}) ? (pg_match_end(), {result}) : ",
+                conditions.join(" && ")
+            ));
+        }
+        code.push_str("pg_match_fail())");
+        Ok(code)
+    }
+
+    // Generate an array literal from the pushed elements
+    fn array(&mut self, elements: &[Expr], dynamic: bool) -> Result<String, String> {
+        let mut parts = Vec::new();
+        for element in elements {
+            parts.push(format!("pg_push({})", self.expr(element)?));
+        }
+        parts.push(format!(
+            "pg_array({}, {})",
+            elements.len(),
+            u8::from(dynamic)
+        ));
+        Ok(format!("({})", parts.join(", ")))
+    }
+
+    // Generate a call to a builtin or user-defined function; a function the program defines
+    // takes precedence over a builtin of the same name, which is checked as the call runs
+    fn call(&mut self, name: &str, arguments: &[Expr]) -> Result<String, String> {
+        let defined = self.defined_call(name, arguments)?;
+        if !is_builtin(name) {
+            return Ok(defined);
+        }
+        let mut parts = Vec::new();
+        // readLine only looks at (and evaluates) its first argument
+        let evaluated = if name == "readLine" {
+            &arguments[..arguments.len().min(1)]
+        } else {
+            arguments
+        };
+        for argument in evaluated {
+            parts.push(format!("pg_push({})", self.expr(argument)?));
+        }
+        let builtin = match name {
+            "readLine" => "pg_read_line",
+            "confirm" => "pg_builtin_confirm",
+            "prompt" => "pg_builtin_prompt",
+            "Date" => "pg_builtin_date",
+            "Duration" => "pg_builtin_duration",
+            "Object" => "pg_builtin_object",
+            "deepCopy" => "pg_builtin_deep_copy",
+            "freeze" => "pg_builtin_freeze",
+            "parallelMap" => "pg_builtin_parallel_map",
+            "spawn" => "pg_builtin_spawn",
+            "wait" => "pg_builtin_wait",
+            "setTimeout" => "pg_builtin_set_timeout",
+            "setInterval" => "pg_builtin_set_interval",
+            "clearTimer" => "pg_builtin_clear_timer",
+            _ => "pg_builtin_args",
+        };
+        // args() reports how many arguments were written, even though none are allowed
+        let count = if name == "args" {
+            arguments.len()
+        } else {
+            evaluated.len()
+        };
+        parts.push(format!("{builtin}({count})"));
+        Ok(format!(
+            "(pg_is_function({}) ? {defined} : ({}))",
+            self.variable(name),
+            parts.join(", ")
+        ))
+    }
+
+    // Generate a call to a function the program defines
+    fn defined_call(&mut self, name: &str, arguments: &[Expr]) -> Result<String, String> {
+        // Each argument is bound before the next one is evaluated
+        let mut parts = vec![format!(
+            "pg_call_begin({}, {})",
+            self.variable(name),
+            arguments.len()
+        )];
+        for (index, argument) in arguments.iter().enumerate() {
+            parts.push(format!("pg_bind({index}, {})", self.expr(argument)?));
+        }
+        parts.push("pg_call_end()".to_string());
+        Ok(format!("({})", parts.join(", ")))
+    }
+
+    // Generate a method call. pg_method_begin checks the method and its number of arguments
+    // before they are evaluated, like the interpreter; replaceChar takes a transform instead.
+    fn method_call(
+        &mut self,
+        object: &Expr,
+        method: &str,
+        arguments: &[Expr],
+    ) -> Result<String, String> {
+        let object = self.expr(object)?;
+        if method == "replaceChar" {
+            let call = match arguments {
+                [Expr::Transform { from, to, .. }] => {
+                    let (from, to) = (self.variable(from), self.variable(to));
+                    format!("pg_replace_char({from}, {to})")
+                }
+                _ => "(pg_fail(\"ReplaceChar method requires a transform argument\"), pg_nil())"
+                    .to_string(),
+            };
+            return Ok(format!(
+                "(pg_push({object}), pg_method_begin(\"replaceChar\", 0), {call})"
+            ));
+        }
+
+        let method_name = c_string(method);
+        let mut parts = vec![
+            format!("pg_push({object})"),
+            format!("pg_method_begin({method_name}, {})", arguments.len()),
+        ];
+        for argument in arguments {
+            parts.push(format!("pg_push({})", self.expr(argument)?));
+        }
+        parts.push(format!("pg_method({method_name}, {})", arguments.len()));
+        Ok(format!("({})", parts.join(", ")))
+    }
+}
+
+// Get the part of the value being matched (the one last pushed) at a path
+fn c_part(path: &[Step]) -> String {
+    path.iter()
+        .fold("pg_match_value()".to_string(), |value, step| match step {
+            Step::Index(index) => format!("pg_match_item({value}, {index})"),
+            Step::Field(key) => format!("pg_match_field({value}, {})", c_string(key)),
+        })
+}
+
+// Quote a string as a C string literal (non-ASCII bytes as octal escapes)
+fn c_string(s: &str) -> String {
+    let mut literal = String::from("\"");
+    for byte in s.bytes() {
+        match byte {
+            b'"' => literal.push_str("\\\""),
+            b'\\' => literal.push_str("\\\\"),
+            b'?' => literal.push_str("\\?"), // Avoid trigraphs such as ??=
+            b'\n' => literal.push_str("\\n"),
+            b'\t' => literal.push_str("\\t"),
+            b' '..=b'~' => literal.push(byte as char),
+            _ => literal.push_str(&format!("\\{byte:03o}")),
+        }
+    }
+    literal.push('"');
+    literal
+}
@@ -0,0 +1,400 @@
+// JavaScript backend: translate a Pidgin program into a standalone JavaScript file
+//
+// The generated code relies on the runtime shim in runtime.js (embedded at the top of every
+// output file) for variables, operators, methods, and builtins, so the program behaves the
+// same as under the interpreter. Imported modules are resolved at build time and bundled
+// into the output, which therefore has no dependencies and runs under Node.js or in a browser.
+use crate::ast::{Expr, MatchCase, Program, Stmt};
+use crate::interpreter::parse_module;
+use crate::pattern::{Step, Test};
+
+const RUNTIME: &str = include_str!("runtime.js"); // The runtime shim (defines `$pg`)
+const INDENT: &str = "  "; // Indentation used in the generated code
+
+// Translate a program into JavaScript; `source_path` is only used in the header comment
+pub fn transpile(program: &Program, source_path: &str) -> Result<String, String> {
+    let mut generator = JsGenerator::default();
+
+    // The main program goes last, but is generated first so that its imports are known
+    let main = generator.program_body(&program.statements, false)?;
+
+    let mut output = String::new();
+    output.push_str(&format!(
+        "// Generated by pidgin build from {source_path}. Do not edit.\n"
+    ));
+    output.push_str("\"use strict\";\n\n");
+    output.push_str(RUNTIME);
+    output.push('\n');
+
+    // Generate each imported module (which may import further modules) exactly once
+    let mut index = 0;
+    while index < generator.modules.len() {
+        let (file, program) = generator.modules[index].clone();
+        let body = generator.program_body(&program.statements, true)?;
+        output.push_str(&format!(
+            "$pg.modules.set({}, function () {{\n{body}}});\n\n",
+            string_literal(&file)
+        ));
+        index += 1;
+    }
+
+    output.push_str(&format!("$pg.run(function () {{\n{main}}});\n"));
+    Ok(output)
+}
+
+// Define the JsGenerator struct, which holds the state used while generating code
+#[derive(Default)]
+struct JsGenerator {
+    output: String,                  // Code generated so far for the current body
+    indent: usize,                   // Current indentation level
+    function_depth: usize,           // Number of enclosing function declarations
+    in_module: bool,                 // Whether the current body is an imported module
+    modules: Vec<(String, Program)>, // Imported modules found so far (file, AST)
+}
+
+impl JsGenerator {
+    // Generate the statements of a program or module body (indented one level)
+    fn program_body(&mut self, statements: &[Stmt], in_module: bool) -> Result<String, String> {
+        self.output.clear();
+        self.indent = 1;
+        self.in_module = in_module;
+        for stmt in statements {
+            self.stmt(stmt)?;
+        }
+        Ok(std::mem::take(&mut self.output))
+    }
+
+    // Write one line of code at the current indentation
+    fn line(&mut self, code: &str) {
+        for _ in 0..self.indent {
+            self.output.push_str(INDENT);
+        }
+        self.output.push_str(code);
+        self.output.push('\n');
+    }
+
+    // Generate code for a statement
+    fn stmt(&mut self, stmt: &Stmt) -> Result<(), String> {
+        match stmt {
+            Stmt::Expression(expr, _) => {
+                let code = self.expr(expr)?;
+                self.line(&format!("{code};"));
+            }
+            Stmt::Return(expr, _) => {
+                let code = self.expr(expr)?;
+                if self.function_depth > 0 {
+                    self.line(&format!("return {code};"));
+                } else {
+                    // The value is still computed before the error, as in the interpreter
+                    let message = if self.in_module {
+                        "Return statement not allowed at module level"
+                    } else {
+                        "Return statement not allowed outside function"
+                    };
+                    self.line(&format!("{code};"));
+                    self.line(&format!("throw $pg.fail({});", string_literal(message)));
+                }
+            }
+            Stmt::Print {
+                format, arguments, ..
+            } => self.print(format, arguments, "out", false)?,
+            Stmt::PrintLn {
+                format, arguments, ..
+            } => self.print(format, arguments, "out", true)?,
+            Stmt::PrintErr {
+                format, arguments, ..
+            } => self.print(format, arguments, "err", true)?,
+            Stmt::Import { names, module, .. } => {
+                let (file, program) = parse_module(module)?;
+                if !self.modules.iter().any(|(known, _)| *known == file) {
+                    self.modules.push((file.clone(), program));
+                }
+                let names: Vec<String> = names.iter().map(|name| string_literal(name)).collect();
+                self.line(&format!(
+                    "$pg.importNames({}, [{}]);",
+                    string_literal(&file),
+                    names.join(", ")
+                ));
+            }
+            Stmt::VarDeclaration {
+                name, initializer, ..
+            } => {
+                let value = match initializer {
+                    Some(init) => self.expr(init)?,
+                    None => "null".to_string(),
+                };
+                self.line(&format!("$pg.set({}, {value});", string_literal(name)));
+            }
+            Stmt::Unpack { names, value, .. } => {
+                let value = self.expr(value)?;
+                let names: Vec<String> = names.iter().map(|name| string_literal(name)).collect();
+                self.line(&format!("$pg.unpack({value}, [{}]);", names.join(", ")));
+            }
+            Stmt::FunctionDeclaration {
+                name,
+                parameters,
+                body,
+                ..
+            } => {
+                let params: Vec<String> = parameters.iter().map(|p| string_literal(p)).collect();
+                self.line(&format!(
+                    "$pg.set({}, new $pg.PgFunction([{}], function () {{",
+                    string_literal(name),
+                    params.join(", ")
+                ));
+                self.function_depth += 1;
+                let result = self.block_contents(body);
+                self.function_depth -= 1;
+                result?;
+                self.line("}));");
+            }
+            Stmt::Block(..) => {
+                self.line("{");
+                self.block_contents(stmt)?;
+                self.line("}");
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                let condition = self.expr(condition)?;
+                self.line(&format!("if ($pg.truthy({condition})) {{"));
+                self.block_contents(then_branch)?;
+                if let Some(else_branch) = else_branch {
+                    self.line("} else {");
+                    self.block_contents(else_branch)?;
+                }
+                self.line("}");
+            }
+            Stmt::While {
+                condition, body, ..
+            } => {
+                let condition = self.expr(condition)?;
+                self.line(&format!("while ($pg.truthy({condition})) {{"));
+                self.block_contents(body)?;
+                self.line("}");
+            }
+            Stmt::ForIn {
+                variable,
+                iterable,
+                body,
+                ..
+            } => {
+                let iterable = self.expr(iterable)?;
+                self.line(&format!("for (const $item of $pg.iterate({iterable})) {{"));
+                self.indent += 1;
+                self.line(&format!("$pg.set({}, $item);", string_literal(variable)));
+                self.indent -= 1;
+                self.block_contents(body)?;
+                self.line("}");
+            }
+        }
+        Ok(())
+    }
+
+    // Generate the statements inside braces (a block's statements, or a single statement)
+    fn block_contents(&mut self, stmt: &Stmt) -> Result<(), String> {
+        self.indent += 1;
+        let result = match stmt {
+            Stmt::Block(statements, _) => statements.iter().try_for_each(|s| self.stmt(s)),
+            other => self.stmt(other),
+        };
+        self.indent -= 1;
+        result
+    }
+
+    // Generate a print, printLn, or printErr statement
+    fn print(
+        &mut self,
+        format: &Expr,
+        arguments: &[Expr],
+        stream: &str,
+        newline: bool,
+    ) -> Result<(), String> {
+        // Like the interpreter, a string literal is always a format string
+        let formatted = !arguments.is_empty() || matches!(format, Expr::String(_));
+        let format = self.expr(format)?;
+        let arguments = self.thunks(arguments)?;
+        self.line(&format!(
+            "$pg.print({format}, {arguments}, \"{stream}\", {newline}, {formatted});"
+        ));
+        Ok(())
+    }
+
+    // Generate an array of functions that evaluate each expression when called
+    fn thunks(&mut self, exprs: &[Expr]) -> Result<String, String> {
+        let thunks = exprs
+            .iter()
+            .map(|expr| self.expr(expr).map(|code| format!("() => {code}")))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(format!("[{}]", thunks.join(", ")))
+    }
+
+    // Generate code for an expression
+    fn expr(&mut self, expr: &Expr) -> Result<String, String> {
+        Ok(match expr {
+            Expr::Number(n) => format!("{n}"),
+            Expr::String(s) => string_literal(s),
+            Expr::Boolean(b) => b.to_string(),
+            Expr::Nil => "null".to_string(),
+            Expr::Identifier(name, _, _) => format!("$pg.get({})", string_literal(name)),
+            Expr::FixedArray(elements) => {
+                format!("new $pg.PgArray({}, false)", self.list(elements)?)
+            }
+            Expr::DynamicArray(elements) => {
+                format!("new $pg.PgArray({}, true)", self.list(elements)?)
+            }
+            Expr::Index { array, index, .. } => {
+                format!("$pg.index({}, {})", self.expr(array)?, self.expr(index)?)
+            }
+            Expr::Binary {
+                left,
+                operator,
+                right,
+                span,
+            } => format!(
+                "$pg.binary(\"{}\", {}, {}, {}, {})",
+                operator.symbol(),
+                self.expr(left)?,
+                self.expr(right)?,
+                span.line,
+                span.column
+            ),
+            Expr::Unary { operand, .. } => format!("$pg.negate({})", self.expr(operand)?),
+            Expr::Assignment { name, value, .. } => {
+                format!("$pg.set({}, {})", string_literal(name), self.expr(value)?)
+            }
+            Expr::MethodCall {
+                object,
+                method,
+                arguments,
+                ..
+            } => self.method_call(object, method, arguments)?,
+            Expr::Transform { .. } => {
+                "(() => { throw $pg.fail(\"Transform should not be evaluated directly\"); })()"
+                    .to_string()
+            }
+            Expr::FunctionCall {
+                name, arguments, ..
+            } => {
+                format!(
+                    "$pg.call({}, {})",
+                    string_literal(name),
+                    self.thunks(arguments)?
+                )
+            }
+            Expr::Match { subject, cases, .. } => self.match_expr(subject, cases)?,
+        })
+    }
+
+    // Generate a match expression: a function of the value that tries each case in turn. A
+    // case's condition runs the tests its pattern compiles to, so && stops at the first that
+    // fails. A case that binds names then has the runtime assign them while its guard and
+    // result run and give them back their values afterwards, as in the interpreter; its result
+    // is kept in $r, unless the guard failed.
+    fn match_expr(&mut self, subject: &Expr, cases: &[MatchCase]) -> Result<String, String> {
+        let subject = self.expr(subject)?;
+        let mut code = String::from("(($m, $r) => ");
+        for case in cases {
+            let decision = case.decision();
+            let mut conditions: Vec<String> = decision
+                .tests
+                .iter()
+                .map(|test| match test {
+                    Test::Array(path, length) => {
+                        format!("$pg.isArrayOf({}, {length})", js_part(path))
+                    }
+                    Test::Object(path, keys) => {
+                        let keys: Vec<String> =
+                            keys.iter().map(|key| string_literal(key)).collect();
+                        format!("$pg.hasFields({}, [{}])", js_part(path), keys.join(", "))
+                    }
+                    Test::Number(path, n) => format!("{} === {n}", js_part(path)),
+                    Test::String(path, s) => format!("{} === {}", js_part(path), string_literal(s)),
+                    Test::Boolean(path, b) => format!("{} === {b}", js_part(path)),
+                })
+                .collect();
+            let guard = match &case.guard {
+                Some(guard) => Some(format!("$pg.truthy({})", self.expr(guard)?)),
+                None => None,
+            };
+            let body = self.expr(&case.body)?;
+            let result = if decision.bindings.is_empty() {
+                conditions.extend(guard);
+                body
+            } else {
+                let bindings: Vec<String> = decision
+                    .bindings
+                    .iter()
+                    .map(|(name, path)| format!("[{}, {}]", string_literal(name), js_part(path)))
+                    .collect();
+                conditions.push(format!(
+                    "($r = $pg.matchCase([{}], () => {}, () => {body})) !== $pg.noCase",
+                    bindings.join(", "),
+                    guard.as_deref().unwrap_or("true")
+                ));
+                "$r".to_string()
+            };
+            if conditions.is_empty() {
+                conditions.push("true".to_string());
+            }
+            code.push_str(&format!("{} ? {result} : ", conditions.join(" && ")));
+        }
+        code.push_str(&format!("$pg.noMatch($m))({subject})"));
+        Ok(code)
+    }
+
+    // Generate a JavaScript array literal from a list of expressions
+    fn list(&mut self, exprs: &[Expr]) -> Result<String, String> {
+        let items = exprs
+            .iter()
+            .map(|expr| self.expr(expr))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(format!("[{}]", items.join(", ")))
+    }
+
+    // Generate a method call. The runtime checks the method and its number of arguments, like
+    // the interpreter; only replaceChar with a transform is translated directly.
+    fn method_call(
+        &mut self,
+        object: &Expr,
+        method: &str,
+        arguments: &[Expr],
+    ) -> Result<String, String> {
+        let object = self.expr(object)?;
+        if let ("replaceChar", [Expr::Transform { from, to, .. }]) = (method, arguments) {
+            return Ok(format!(
+                "$pg.replaceChar({object}, {}, {})",
+                string_literal(from),
+                string_literal(to)
+            ));
+        }
+        let arguments = self.thunks(arguments)?;
+        Ok(format!(
+            "$pg.method({object}, {}, {arguments})",
+            string_literal(method)
+        ))
+    }
+}
+
+// Get the part of the matched value ($m) at a path
+fn js_part(path: &[Step]) -> String {
+    if path.is_empty() {
+        return "$m".to_string();
+    }
+    let steps: Vec<String> = path
+        .iter()
+        .map(|step| match step {
+            Step::Index(index) => index.to_string(),
+            Step::Field(key) => string_literal(key),
+        })
+        .collect();
+    format!("$pg.part($m, [{}])", steps.join(", "))
+}
+
+// Quote a string as a JavaScript string literal
+fn string_literal(s: &str) -> String {
+    serde_json::Value::String(s.to_string()).to_string()
+}
@@ -0,0 +1,20 @@
+// Code generation backends for Pidgin programs (pidgin build --target <target>)
+pub mod c;
+pub mod js;
+
+use crate::ast::Program;
+
+// A backend: translates a program (and the path it was read from) into source code
+pub type Transpiler = fn(&Program, &str) -> Result<String, String>;
+
+// Available targets: name, backend, and the extension of the generated file
+pub const TARGETS: &[(&str, Transpiler, &str)] =
+    &[("js", js::transpile, "js"), ("c", c::transpile, "c")];
+
+// Find a target by name
+pub fn find_target(name: &str) -> Option<(Transpiler, &'static str)> {
+    TARGETS
+        .iter()
+        .find(|(target, _, _)| *target == name)
+        .map(|(_, transpile, extension)| (*transpile, *extension))
+}
@@ -0,0 +1,1924 @@
+/* Pidgin C runtime, embedded in every program built with `pidgin build --target c`.
+ *
+ * Generated code never uses C variables for Pidgin variables: every read, write, call and
+ * operator goes through these functions so that programs behave exactly as they do under
+ * the Rust interpreter (one flat set of globals, saved and restored around function calls,
+ * arrays with value semantics, the same error messages).
+ *
+ * Operands are evaluated left to right by pushing them onto a value stack with the comma
+ * operator, since C leaves the evaluation order of function arguments unspecified. Values
+ * are never freed: the runtime is kept small and is meant for short-lived programs.
+ *
+ * The generated code defines PG_NAME_COUNT and pg_names (every variable name in the
+ * program, indexed by slot) before this runtime. Needs a C99 compiler and the C library. */
+#include <ctype.h>
+#include <math.h>
+#include <stdarg.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <time.h>
+
+/* Not every program uses every runtime function; keep compilers from warning about that */
+#if defined(__GNUC__)
+#define PG_RUNTIME static __attribute__((unused))
+#else
+#define PG_RUNTIME static
+#endif
+
+typedef enum {
+  PG_UNDEFINED, /* An unset variable slot (never a program value) */
+  PG_NIL,
+  PG_NUMBER,
+  PG_STRING,
+  PG_BOOLEAN,
+  PG_FIXED_ARRAY,
+  PG_DYNAMIC_ARRAY,
+  PG_OBJECT,
+  PG_DATE,
+  PG_DURATION,
+  PG_FUNCTION,
+  PG_TASK
+} PgType;
+
+typedef struct PgValue PgValue;
+typedef struct PgArray PgArray;
+typedef struct PgObject PgObject;
+
+/* A user-defined function: parameter slots plus the compiled body */
+typedef struct {
+  size_t param_count;
+  const int *params;
+  PgValue (*body)(void);
+} PgFunction;
+
+struct PgValue {
+  PgType type;
+  union {
+    double number;
+    const char *string;
+    int boolean;
+    PgArray *array;
+    PgObject *object;
+    time_t date;
+    double seconds; /* The length of a duration */
+    const PgFunction *function;
+    PgValue *task; /* The return value of a spawned call (it runs straight away) */
+  } as;
+};
+
+struct PgArray {
+  size_t length;
+  PgValue *items;
+  int frozen; /* Set by freeze(): methods that update it are refused */
+};
+
+/* Objects keep their keys in insertion order */
+struct PgObject {
+  size_t length;
+  const char **keys;
+  PgValue *values;
+  int frozen;
+};
+
+static PgValue *pg_env;        /* The program's globals, one slot per name */
+static int pg_argc;            /* Command-line arguments for args() */
+static char **pg_argv;
+
+/* ---- Errors and memory ---- */
+
+/* Report a runtime error like the interpreter does and stop the program */
+PG_RUNTIME void pg_fail(const char *format, ...) {
+  va_list args;
+  fflush(stdout);
+  fputs("Error: ", stderr);
+  va_start(args, format);
+  vfprintf(stderr, format, args);
+  va_end(args);
+  fputc('\n', stderr);
+  exit(1);
+}
+
+PG_RUNTIME void *pg_alloc(size_t size) {
+  void *memory = malloc(size ? size : 1);
+  if (memory == NULL) pg_fail("Out of memory");
+  return memory;
+}
+
+PG_RUNTIME char *pg_strdup(const char *s) {
+  char *copy = pg_alloc(strlen(s) + 1);
+  strcpy(copy, s);
+  return copy;
+}
+
+/* ---- Growable strings ---- */
+
+typedef struct {
+  char *data;
+  size_t length;
+  size_t capacity;
+} PgBuffer;
+
+PG_RUNTIME void pg_buffer_append(PgBuffer *buffer, const char *text) {
+  size_t extra = strlen(text);
+  if (buffer->length + extra + 1 > buffer->capacity) {
+    size_t capacity = buffer->capacity ? buffer->capacity : 32;
+    char *data;
+    while (buffer->length + extra + 1 > capacity) capacity *= 2;
+    data = pg_alloc(capacity);
+    if (buffer->data != NULL) memcpy(data, buffer->data, buffer->length);
+    free(buffer->data);
+    buffer->data = data;
+    buffer->capacity = capacity;
+  }
+  memcpy(buffer->data + buffer->length, text, extra);
+  buffer->length += extra;
+  buffer->data[buffer->length] = '\0';
+}
+
+PG_RUNTIME const char *pg_buffer_finish(PgBuffer *buffer) {
+  if (buffer->data == NULL) return "";
+  return buffer->data;
+}
+
+/* ---- Values ---- */
+
+PG_RUNTIME PgValue pg_nil(void) {
+  PgValue value;
+  value.type = PG_NIL;
+  return value;
+}
+
+PG_RUNTIME PgValue pg_number(double n) {
+  PgValue value;
+  value.type = PG_NUMBER;
+  value.as.number = n;
+  return value;
+}
+
+PG_RUNTIME PgValue pg_string(const char *s) {
+  PgValue value;
+  value.type = PG_STRING;
+  value.as.string = s;
+  return value;
+}
+
+PG_RUNTIME PgValue pg_boolean(int b) {
+  PgValue value;
+  value.type = PG_BOOLEAN;
+  value.as.boolean = b != 0;
+  return value;
+}
+
+PG_RUNTIME PgValue pg_array_value(PgType type, size_t length) {
+  PgValue value;
+  value.type = type;
+  value.as.array = pg_alloc(sizeof(PgArray));
+  value.as.array->length = length;
+  value.as.array->items = pg_alloc(length * sizeof(PgValue));
+  value.as.array->frozen = 0;
+  return value;
+}
+
+PG_RUNTIME PgValue pg_object_value(size_t length) {
+  PgValue value;
+  value.type = PG_OBJECT;
+  value.as.object = pg_alloc(sizeof(PgObject));
+  value.as.object->length = length;
+  value.as.object->keys = pg_alloc(length * sizeof(const char *));
+  value.as.object->values = pg_alloc(length * sizeof(PgValue));
+  value.as.object->frozen = 0;
+  return value;
+}
+
+/* Get the name of a value's type, as the interpreter shows it */
+PG_RUNTIME const char *pg_type_name(PgValue value) {
+  static const char *const names[] = {"nil",    "nil",      "number",   "string",
+                                      "boolean", "fixed array", "dynamic array",
+                                      "object", "date",     "duration", "function", "task"};
+  return names[value.type];
+}
+
+/* Check whether a value is a frozen array or object */
+PG_RUNTIME int pg_is_frozen(PgValue value) {
+  if (value.type == PG_FIXED_ARRAY || value.type == PG_DYNAMIC_ARRAY) return value.as.array->frozen;
+  return value.type == PG_OBJECT && value.as.object->frozen;
+}
+
+PG_RUNTIME PgValue pg_date(time_t t) {
+  PgValue value;
+  value.type = PG_DATE;
+  value.as.date = t;
+  return value;
+}
+
+PG_RUNTIME PgValue pg_duration(double seconds) {
+  PgValue value;
+  value.type = PG_DURATION;
+  value.as.seconds = seconds;
+  return value;
+}
+
+PG_RUNTIME PgValue pg_function(const PgFunction *function) {
+  PgValue value;
+  value.type = PG_FUNCTION;
+  value.as.function = function;
+  return value;
+}
+
+PG_RUNTIME int pg_is_array(PgValue value) {
+  return value.type == PG_FIXED_ARRAY || value.type == PG_DYNAMIC_ARRAY;
+}
+
+/* Format a number like Rust's f64 Display: shortest round-trip digits, never an exponent */
+PG_RUNTIME const char *pg_format_number(double n) {
+  char digits[40], text[400];
+  char *mantissa, *out = text;
+  int precision, exponent, count = 0, i;
+
+  if (isnan(n)) return "NaN";
+  if (isinf(n)) return n > 0 ? "inf" : "-inf";
+  if (n == 0) return signbit(n) ? "-0" : "0";
+
+  for (precision = 1; precision <= 17; precision++) {
+    snprintf(digits, sizeof digits, "%.*e", precision - 1, n);
+    if (strtod(digits, NULL) == n) break;
+  }
+
+  /* Split "-d.ddde+XX" into its significant digits and exponent */
+  if (n < 0) *out++ = '-';
+  mantissa = digits + (n < 0 ? 1 : 0);
+  exponent = atoi(strchr(mantissa, 'e') + 1);
+  for (i = 0; mantissa[i] != 'e'; i++) {
+    if (mantissa[i] != '.') mantissa[count++] = mantissa[i];
+  }
+  while (count > 1 && mantissa[count - 1] == '0') count--;
+
+  if (exponent < 0) {
+    *out++ = '0';
+    *out++ = '.';
+    for (i = 0; i < -exponent - 1; i++) *out++ = '0';
+    for (i = 0; i < count; i++) *out++ = mantissa[i];
+  } else {
+    for (i = 0; i <= exponent; i++) *out++ = i < count ? mantissa[i] : '0';
+    if (count > exponent + 1) {
+      *out++ = '.';
+      for (i = exponent + 1; i < count; i++) *out++ = mantissa[i];
+    }
+  }
+  *out = '\0';
+  return pg_strdup(text);
+}
+
+PG_RUNTIME const char *pg_format_date(time_t t, const char *pattern) {
+  struct tm *parts = localtime(&t);
+  size_t size = 64 + strlen(pattern) * 16;
+  char *text = pg_alloc(size);
+  if (strftime(text, size, pattern, parts) == 0) text[0] = '\0';
+  return text;
+}
+
+/* Write a duration as hours, minutes, and seconds: 1:30:00, -0:00:05, 0:00:02.5 */
+PG_RUNTIME const char *pg_format_duration(double seconds) {
+  PgBuffer buffer = {NULL, 0, 0};
+  double total = fabs(seconds), hours, minutes, rest;
+  hours = floor(total / 3600);
+  minutes = floor((total - hours * 3600) / 60);
+  rest = total - hours * 3600 - minutes * 60;
+  if (seconds < 0) pg_buffer_append(&buffer, "-");
+  pg_buffer_append(&buffer, pg_format_number(hours));
+  pg_buffer_append(&buffer, minutes < 10 ? ":0" : ":");
+  pg_buffer_append(&buffer, pg_format_number(minutes));
+  pg_buffer_append(&buffer, rest < 10 ? ":0" : ":");
+  pg_buffer_append(&buffer, pg_format_number(rest));
+  return pg_buffer_finish(&buffer);
+}
+
+PG_RUNTIME const char *pg_show(PgValue value);
+
+/* Join values as text: "a, b, c" */
+PG_RUNTIME void pg_append_list(PgBuffer *buffer, const PgValue *items, size_t length) {
+  size_t i;
+  for (i = 0; i < length; i++) {
+    if (i > 0) pg_buffer_append(buffer, ", ");
+    pg_buffer_append(buffer, pg_show(items[i]));
+  }
+}
+
+/* Convert a value to the text print shows */
+PG_RUNTIME const char *pg_show(PgValue value) {
+  PgBuffer buffer = {NULL, 0, 0};
+  size_t i;
+  switch (value.type) {
+  case PG_UNDEFINED:
+  case PG_NIL:
+    return "nil";
+  case PG_NUMBER:
+    return pg_format_number(value.as.number);
+  case PG_STRING:
+    return value.as.string;
+  case PG_BOOLEAN:
+    return value.as.boolean ? "true" : "false";
+  case PG_FIXED_ARRAY:
+    pg_buffer_append(&buffer, "[");
+    pg_append_list(&buffer, value.as.array->items, value.as.array->length);
+    pg_buffer_append(&buffer, "]");
+    break;
+  case PG_DYNAMIC_ARRAY:
+    pg_buffer_append(&buffer, "{");
+    pg_append_list(&buffer, value.as.array->items, value.as.array->length);
+    pg_buffer_append(&buffer, "}");
+    break;
+  case PG_OBJECT:
+    pg_buffer_append(&buffer, "{ ");
+    for (i = 0; i < value.as.object->length; i++) {
+      if (i > 0) pg_buffer_append(&buffer, ", ");
+      pg_buffer_append(&buffer, value.as.object->keys[i]);
+      pg_buffer_append(&buffer, ": ");
+      pg_buffer_append(&buffer, pg_show(value.as.object->values[i]));
+    }
+    pg_buffer_append(&buffer, " }");
+    break;
+  case PG_DATE:
+    return pg_format_date(value.as.date, "%Y-%m-%d %H:%M:%S");
+  case PG_DURATION:
+    return pg_format_duration(value.as.seconds);
+  case PG_FUNCTION:
+    pg_buffer_append(&buffer, "function(");
+    for (i = 0; i < value.as.function->param_count; i++) {
+      if (i > 0) pg_buffer_append(&buffer, ", ");
+      pg_buffer_append(&buffer, pg_names[value.as.function->params[i]]);
+    }
+    pg_buffer_append(&buffer, ") { ... }");
+    break;
+  case PG_TASK:
+    return "<task>";
+  }
+  return pg_buffer_finish(&buffer);
+}
+
+/* Write a number as Rust's f64 Debug output does (1.0, 2.5) */
+PG_RUNTIME void pg_append_debug_number(PgBuffer *buffer, double n) {
+  pg_buffer_append(buffer, pg_format_number(n));
+  if (n == floor(n) && fabs(n) < 1e16) pg_buffer_append(buffer, ".0");
+}
+
+/* Describe a value in error messages (like the interpreter's debug output) */
+PG_RUNTIME const char *pg_describe(PgValue value) {
+  PgBuffer buffer = {NULL, 0, 0};
+  size_t i;
+  switch (value.type) {
+  case PG_UNDEFINED:
+  case PG_NIL:
+    return "Nil";
+  case PG_NUMBER:
+    pg_buffer_append(&buffer, "Number(");
+    pg_append_debug_number(&buffer, value.as.number);
+    pg_buffer_append(&buffer, ")");
+    break;
+  case PG_STRING:
+    pg_buffer_append(&buffer, "String(\"");
+    pg_buffer_append(&buffer, value.as.string);
+    pg_buffer_append(&buffer, "\")");
+    break;
+  case PG_BOOLEAN:
+    return value.as.boolean ? "Boolean(true)" : "Boolean(false)";
+  case PG_FIXED_ARRAY:
+  case PG_DYNAMIC_ARRAY:
+    pg_buffer_append(&buffer, value.type == PG_FIXED_ARRAY ? "FixedArray([" : "DynamicArray([");
+    for (i = 0; i < value.as.array->length; i++) {
+      if (i > 0) pg_buffer_append(&buffer, ", ");
+      pg_buffer_append(&buffer, pg_describe(value.as.array->items[i]));
+    }
+    pg_buffer_append(&buffer, "])");
+    break;
+  case PG_OBJECT:
+    return "Object";
+  case PG_DATE:
+    pg_buffer_append(&buffer, "Date(");
+    pg_buffer_append(&buffer, pg_show(value));
+    pg_buffer_append(&buffer, ")");
+    break;
+  case PG_DURATION:
+    pg_buffer_append(&buffer, "Duration(");
+    pg_append_debug_number(&buffer, value.as.seconds);
+    pg_buffer_append(&buffer, ")");
+    break;
+  case PG_FUNCTION:
+    return "Function";
+  case PG_TASK:
+    return "Task(TaskHandle)";
+  }
+  return pg_buffer_finish(&buffer);
+}
+
+PG_RUNTIME int pg_truthy(PgValue value) {
+  if (value.type == PG_BOOLEAN) return value.as.boolean;
+  return value.type != PG_NIL;
+}
+
+PG_RUNTIME int pg_equal(PgValue a, PgValue b) {
+  size_t i;
+  if (a.type != b.type) return 0;
+  switch (a.type) {
+  case PG_NIL:
+    return 1;
+  case PG_NUMBER:
+    return a.as.number == b.as.number;
+  case PG_STRING:
+    return strcmp(a.as.string, b.as.string) == 0;
+  case PG_BOOLEAN:
+    return a.as.boolean == b.as.boolean;
+  case PG_DURATION:
+    return a.as.seconds == b.as.seconds;
+  case PG_FIXED_ARRAY:
+  case PG_DYNAMIC_ARRAY:
+    if (a.as.array->length != b.as.array->length) return 0;
+    for (i = 0; i < a.as.array->length; i++) {
+      if (!pg_equal(a.as.array->items[i], b.as.array->items[i])) return 0;
+    }
+    return 1;
+  case PG_TASK:
+    return a.as.task == b.as.task;
+  default:
+    return 0;
+  }
+}
+
+/* ---- The value stack (keeps operands in evaluation order) ---- */
+
+static PgValue *pg_stack;
+static size_t pg_stack_length, pg_stack_capacity;
+
+/* Push an evaluated operand; returns 0 so it can be used inside comma expressions */
+PG_RUNTIME int pg_push(PgValue value) {
+  if (pg_stack_length == pg_stack_capacity) {
+    PgValue *stack;
+    pg_stack_capacity = pg_stack_capacity ? pg_stack_capacity * 2 : 64;
+    stack = pg_alloc(pg_stack_capacity * sizeof(PgValue));
+    if (pg_stack_length > 0) memcpy(stack, pg_stack, pg_stack_length * sizeof(PgValue));
+    free(pg_stack);
+    pg_stack = stack;
+  }
+  pg_stack[pg_stack_length++] = value;
+  return 0;
+}
+
+/* Take the last `count` pushed operands (valid until the next push) */
+PG_RUNTIME PgValue *pg_pop(size_t count) {
+  pg_stack_length -= count;
+  return pg_stack + pg_stack_length;
+}
+
+/* ---- Variables ---- */
+
+PG_RUNTIME PgValue pg_get(int slot) {
+  if (pg_env[slot].type == PG_UNDEFINED) pg_fail("Undefined variable '%s'", pg_names[slot]);
+  return pg_env[slot];
+}
+
+PG_RUNTIME PgValue pg_set(int slot, PgValue value) {
+  pg_env[slot] = value;
+  return value;
+}
+
+/* let a, b = value; and a, b = value;: check that the value is an array with one element per
+ * name, and get its elements to assign */
+PG_RUNTIME const PgValue *pg_unpack(PgValue value, size_t count) {
+  if (!pg_is_array(value)) {
+    pg_fail("Cannot unpack a %s into %lu variables", pg_type_name(value), (unsigned long)count);
+  }
+  if (value.as.array->length != count) {
+    pg_fail("Cannot unpack %lu values into %lu variables", (unsigned long)value.as.array->length,
+            (unsigned long)count);
+  }
+  return value.as.array->items;
+}
+
+PG_RUNTIME PgValue *pg_new_env(void) {
+  PgValue *env = pg_alloc(PG_NAME_COUNT * sizeof(PgValue));
+  int i;
+  for (i = 0; i < PG_NAME_COUNT; i++) env[i].type = PG_UNDEFINED;
+  return env;
+}
+
+/* ---- Operators ---- */
+
+PG_RUNTIME PgValue pg_concat(const char *a, const char *b) {
+  char *text = pg_alloc(strlen(a) + strlen(b) + 1);
+  strcpy(text, a);
+  strcat(text, b);
+  return pg_string(text);
+}
+
+/* Move a date by a number of seconds (date + duration); dates here keep whole seconds */
+PG_RUNTIME PgValue pg_shift_date(time_t date, double seconds) {
+  return pg_date(date + (time_t)seconds);
+}
+
+/* Apply a binary operator to the last two pushed operands, with the interpreter's rules */
+PG_RUNTIME PgValue pg_binary(const char *op, int line, int column) {
+  PgValue *operands = pg_pop(2);
+  PgValue a = operands[0], b = operands[1];
+  int numbers = a.type == PG_NUMBER && b.type == PG_NUMBER;
+  int durations = a.type == PG_DURATION && b.type == PG_DURATION;
+  const char *kind = "comparison";
+
+  if (strcmp(op, "==") == 0) return pg_boolean(pg_equal(a, b));
+  if (strcmp(op, "!=") == 0) return pg_boolean(!pg_equal(a, b));
+  if (durations && strchr("<>", op[0]) != NULL) {
+    /* Durations compare by their length */
+    a = pg_number(a.as.seconds);
+    b = pg_number(b.as.seconds);
+    numbers = 1;
+  }
+  if (strcmp(op, "+") == 0) {
+    kind = "addition";
+    if (numbers) return pg_number(a.as.number + b.as.number);
+    if (durations) return pg_duration(a.as.seconds + b.as.seconds);
+    if (a.type == PG_DATE && b.type == PG_DURATION) return pg_shift_date(a.as.date, b.as.seconds);
+    if (a.type == PG_DURATION && b.type == PG_DATE) return pg_shift_date(b.as.date, a.as.seconds);
+    if ((a.type == PG_STRING &&
+         (b.type == PG_STRING || b.type == PG_NUMBER || b.type == PG_BOOLEAN)) ||
+        (b.type == PG_STRING && (a.type == PG_NUMBER || a.type == PG_BOOLEAN))) {
+      return pg_concat(pg_show(a), pg_show(b));
+    }
+  } else if (strcmp(op, "-") == 0) {
+    kind = "subtraction";
+    if (numbers) return pg_number(a.as.number - b.as.number);
+    if (durations) return pg_duration(a.as.seconds - b.as.seconds);
+    if (a.type == PG_DATE && b.type == PG_DURATION) return pg_shift_date(a.as.date, -b.as.seconds);
+    if (a.type == PG_DATE && b.type == PG_DATE) return pg_duration(difftime(a.as.date, b.as.date));
+  } else if (strcmp(op, "*") == 0) {
+    kind = "multiplication";
+    if (numbers) return pg_number(a.as.number * b.as.number);
+    if (a.type == PG_DURATION && b.type == PG_NUMBER) return pg_duration(a.as.seconds * b.as.number);
+    if (a.type == PG_NUMBER && b.type == PG_DURATION) return pg_duration(a.as.number * b.as.seconds);
+  } else if (strcmp(op, "/") == 0) {
+    kind = "division";
+    if (numbers) {
+      if (b.as.number == 0) pg_fail("Division by zero");
+      return pg_number(a.as.number / b.as.number);
+    }
+    if (a.type == PG_DURATION && b.type == PG_NUMBER) {
+      if (b.as.number == 0) pg_fail("Division by zero");
+      return pg_duration(a.as.seconds / b.as.number);
+    }
+    if (durations) {
+      if (b.as.seconds == 0) pg_fail("Division by zero");
+      return pg_number(a.as.seconds / b.as.seconds);
+    }
+  } else if (numbers) {
+    if (strcmp(op, "<") == 0) return pg_boolean(a.as.number < b.as.number);
+    if (strcmp(op, ">") == 0) return pg_boolean(a.as.number > b.as.number);
+    if (strcmp(op, "<=") == 0) return pg_boolean(a.as.number <= b.as.number);
+    return pg_boolean(a.as.number >= b.as.number);
+  }
+  pg_fail("Invalid operands for %s: %s %s %s at line %d column %d", kind, pg_describe(a), op,
+          pg_describe(b), line, column);
+  return pg_nil();
+}
+
+PG_RUNTIME PgValue pg_negate(PgValue value) {
+  if (value.type == PG_DURATION) return pg_duration(-value.as.seconds);
+  if (value.type != PG_NUMBER) pg_fail("Invalid operand for unary minus");
+  return pg_number(-value.as.number);
+}
+
+/* Convert a number to an array index the way Rust's `as usize` does */
+PG_RUNTIME size_t pg_to_index(double n) {
+  if (isnan(n) || n <= 0) return 0;
+  if (n >= 18446744073709551615.0) return (size_t)-1;
+  return (size_t)n;
+}
+
+/* Index the array pushed before the index */
+PG_RUNTIME PgValue pg_index(void) {
+  PgValue *operands = pg_pop(2);
+  size_t index;
+  if (operands[1].type != PG_NUMBER) pg_fail("Array index must be a number");
+  if (!pg_is_array(operands[0])) pg_fail("Can only index arrays");
+  index = pg_to_index(operands[1].as.number);
+  if (index >= operands[0].as.array->length) {
+    pg_fail("Array index %zu out of bounds (array length: %zu)", index,
+            operands[0].as.array->length);
+  }
+  return operands[0].as.array->items[index];
+}
+
+/* Build an array from the last `length` pushed values */
+PG_RUNTIME PgValue pg_array(size_t length, int dynamic) {
+  PgValue array = pg_array_value(dynamic ? PG_DYNAMIC_ARRAY : PG_FIXED_ARRAY, length);
+  if (length > 0) memcpy(array.as.array->items, pg_pop(length), length * sizeof(PgValue));
+  return array;
+}
+
+/* ---- Methods ---- */
+
+#define PG_ON(type) (1u << (type))
+#define PG_ON_ARRAYS (PG_ON(PG_FIXED_ARRAY) | PG_ON(PG_DYNAMIC_ARRAY))
+
+/* The built-in methods, like src/methods.rs: the receiver types each is called on and its
+ * number of arguments (replaceChar takes a transform, handled apart) */
+static const struct {
+  const char *name;
+  size_t arity;
+  unsigned on;        /* PG_ON bits of the receiver types */
+  const char *on_text; /* The same types, for messages */
+  int updates;         /* Returns an updated copy of the receiver, which frozen values refuse */
+} pg_methods[] = {
+    {"push", 1, PG_ON(PG_DYNAMIC_ARRAY), "dynamic arrays", 1},
+    {"pop", 0, PG_ON(PG_DYNAMIC_ARRAY), "dynamic arrays", 0},
+    {"clear", 0, PG_ON(PG_DYNAMIC_ARRAY), "dynamic arrays", 1},
+    {"insert", 2, PG_ON(PG_DYNAMIC_ARRAY), "dynamic arrays", 1},
+    {"remove", 1, PG_ON(PG_DYNAMIC_ARRAY), "dynamic arrays", 0},
+    {"length", 0, PG_ON_ARRAYS, "arrays", 0},
+    {"reverse", 0, PG_ON_ARRAYS, "arrays", 0},
+    {"toUpper", 0, PG_ON(PG_STRING), "strings", 0},
+    {"toLower", 0, PG_ON(PG_STRING), "strings", 0},
+    {"trim", 0, PG_ON(PG_STRING), "strings", 0},
+    {"format", 1, PG_ON(PG_DATE), "dates", 0},
+    {"getYear", 0, PG_ON(PG_DATE), "dates", 0},
+    {"getMonth", 0, PG_ON(PG_DATE), "dates", 0},
+    {"getDay", 0, PG_ON(PG_DATE), "dates", 0},
+    {"toHours", 0, PG_ON(PG_DURATION), "durations", 0},
+    {"toMinutes", 0, PG_ON(PG_DURATION), "durations", 0},
+    {"toSeconds", 0, PG_ON(PG_DURATION), "durations", 0},
+    {"keys", 0, PG_ON(PG_OBJECT), "objects", 0},
+    {"set", 2, PG_ON(PG_OBJECT), "objects", 1},
+    {"get", 1, PG_ON(PG_OBJECT), "objects", 0},
+    {"has", 1, PG_ON(PG_OBJECT), "objects", 0},
+};
+
+PG_RUNTIME int pg_call_begin(int slot, size_t count);
+PG_RUNTIME int pg_bind(size_t index, PgValue value);
+PG_RUNTIME PgValue pg_call_end(void);
+
+/* Find the slot of a method the program defined on the receiver's type
+ * (function String.shout(self) { ... }), or -1 if there is none */
+PG_RUNTIME int pg_extension(PgValue object, const char *method) {
+  static const char *const types[] = {NULL,    NULL,    "Number", "String",   "Boolean", "Array",
+                                      "Array", "Object", "Date",  "Duration", NULL, NULL};
+  const char *type = types[object.type];
+  size_t length;
+  int slot;
+  if (type == NULL) return -1;
+  length = strlen(type);
+  for (slot = 0; slot < PG_NAME_COUNT; slot++) {
+    const char *name = pg_names[slot];
+    if (strncmp(name, type, length) == 0 && name[length] == '.' &&
+        strcmp(name + length + 1, method) == 0 && pg_env[slot].type == PG_FUNCTION) {
+      return slot;
+    }
+  }
+  return -1;
+}
+
+/* Check the method, its receiver, and its number of arguments before the arguments are
+ * evaluated. Methods the program defined come before the built-in ones. */
+PG_RUNTIME int pg_method_begin(const char *method, size_t count) {
+  PgValue object = pg_stack[pg_stack_length - 1];
+  size_t i;
+  int slot = pg_extension(object, method);
+
+  if (slot >= 0) {
+    size_t arity = pg_env[slot].as.function->param_count - 1; /* Less the receiver */
+    if (count != arity) {
+      pg_fail("Method '%s' expects %lu arguments, got %lu", method, (unsigned long)arity,
+              (unsigned long)count);
+    }
+    return 0;
+  }
+  if (strcmp(method, "replaceChar") == 0) {
+    if (object.type != PG_STRING) pg_fail("ReplaceChar method can only be called on strings");
+    return 0;
+  }
+  for (i = 0; i < sizeof pg_methods / sizeof pg_methods[0]; i++) {
+    if (strcmp(method, pg_methods[i].name) != 0) continue;
+    if (object.type == PG_UNDEFINED || !(pg_methods[i].on & PG_ON(object.type))) {
+      pg_fail("%s method can only be called on %s", method, pg_methods[i].on_text);
+    }
+    if (pg_methods[i].updates && pg_is_frozen(object)) {
+      pg_fail("Cannot call %s() on a frozen %s: it cannot be changed", method, pg_type_name(object));
+    }
+    if (count != pg_methods[i].arity) {
+      pg_fail("Method '%s' expects %lu arguments, got %lu", method,
+              (unsigned long)pg_methods[i].arity, (unsigned long)count);
+    }
+    return 0;
+  }
+  pg_fail("Unsupported method: %s", method);
+  return 0;
+}
+
+/* Call a method on the receiver pushed before its `count` arguments, once pg_method_begin has
+ * checked them */
+PG_RUNTIME PgValue pg_method(const char *method, size_t count) {
+  PgValue *args = pg_pop(count + 1), object = args[0], result;
+  int is_array = pg_is_array(object), slot = pg_extension(object, method);
+  size_t i, length = is_array ? object.as.array->length : 0;
+
+  /* A method defined in Pidgin is called like a function, with the receiver first */
+  if (slot >= 0) {
+    pg_call_begin(slot, count + 1);
+    for (i = 0; i <= count; i++) pg_bind(i, args[i]);
+    return pg_call_end();
+  }
+  args++;
+
+  if (strcmp(method, "push") == 0) {
+    result = pg_array_value(PG_DYNAMIC_ARRAY, length + 1);
+    memcpy(result.as.array->items, object.as.array->items, length * sizeof(PgValue));
+    result.as.array->items[length] = args[0];
+    return result;
+  }
+  if (strcmp(method, "pop") == 0) {
+    if (length == 0) pg_fail("Cannot pop from empty array");
+    return object.as.array->items[length - 1];
+  }
+  if (strcmp(method, "length") == 0) return pg_number((double)length);
+  if (strcmp(method, "clear") == 0) return pg_array_value(PG_DYNAMIC_ARRAY, 0);
+  if (strcmp(method, "reverse") == 0) {
+    result = pg_array_value(object.type, length);
+    for (i = 0; i < length; i++) result.as.array->items[i] = object.as.array->items[length - 1 - i];
+    return result;
+  }
+  if (strcmp(method, "insert") == 0) {
+    size_t at;
+    if (args[0].type != PG_NUMBER) pg_fail("insert() requires (index, value) arguments");
+    at = pg_to_index(args[0].as.number);
+    if (at > length) pg_fail("Insert index %zu out of bounds (array length: %zu)", at, length);
+    result = pg_array_value(PG_DYNAMIC_ARRAY, length + 1);
+    memcpy(result.as.array->items, object.as.array->items, at * sizeof(PgValue));
+    result.as.array->items[at] = args[1];
+    memcpy(result.as.array->items + at + 1, object.as.array->items + at,
+           (length - at) * sizeof(PgValue));
+    return result;
+  }
+  if (strcmp(method, "remove") == 0) {
+    size_t at;
+    if (args[0].type != PG_NUMBER) pg_fail("remove() requires a numeric index argument");
+    at = pg_to_index(args[0].as.number);
+    if (at >= length) pg_fail("Remove index %zu out of bounds (array length: %zu)", at, length);
+    return object.as.array->items[at];
+  }
+  if (strcmp(method, "toUpper") == 0 || strcmp(method, "toLower") == 0) {
+    char *text = pg_strdup(object.as.string);
+    for (i = 0; text[i] != '\0'; i++) {
+      unsigned char c = (unsigned char)text[i];
+      text[i] = (char)(method[2] == 'U' ? toupper(c) : tolower(c));
+    }
+    return pg_string(text);
+  }
+  if (strcmp(method, "trim") == 0) {
+    const char *start = object.as.string;
+    size_t end;
+    char *text;
+    while (isspace((unsigned char)*start)) start++;
+    end = strlen(start);
+    while (end > 0 && isspace((unsigned char)start[end - 1])) end--;
+    text = pg_alloc(end + 1);
+    memcpy(text, start, end);
+    text[end] = '\0';
+    return pg_string(text);
+  }
+  if (strcmp(method, "format") == 0) {
+    if (args[0].type != PG_STRING) pg_fail("Date.format() requires a string argument");
+    return pg_string(pg_format_date(object.as.date, args[0].as.string));
+  }
+  if (strcmp(method, "getYear") == 0 || strcmp(method, "getMonth") == 0 ||
+      strcmp(method, "getDay") == 0) {
+    struct tm *parts = localtime(&object.as.date);
+    if (method[3] == 'Y') return pg_number(parts->tm_year + 1900);
+    if (method[3] == 'M') return pg_number(parts->tm_mon + 1);
+    return pg_number(parts->tm_mday);
+  }
+  if (strcmp(method, "toHours") == 0) return pg_number(object.as.seconds / 3600);
+  if (strcmp(method, "toMinutes") == 0) return pg_number(object.as.seconds / 60);
+  if (strcmp(method, "toSeconds") == 0) return pg_number(object.as.seconds);
+  if (strcmp(method, "keys") == 0) {
+    result = pg_array_value(PG_DYNAMIC_ARRAY, object.as.object->length);
+    for (i = 0; i < object.as.object->length; i++) {
+      result.as.array->items[i] = pg_string(object.as.object->keys[i]);
+    }
+    return result;
+  }
+  if (strcmp(method, "set") == 0) {
+    size_t n = object.as.object->length;
+    if (args[0].type != PG_STRING) {
+      pg_fail("set() requires (key, value) arguments where key is a string");
+    }
+    for (i = 0; i < n; i++) {
+      if (strcmp(object.as.object->keys[i], args[0].as.string) == 0) break;
+    }
+    result = pg_object_value(i < n ? n : n + 1);
+    memcpy(result.as.object->keys, object.as.object->keys, n * sizeof(const char *));
+    memcpy(result.as.object->values, object.as.object->values, n * sizeof(PgValue));
+    result.as.object->keys[i] = args[0].as.string;
+    result.as.object->values[i] = args[1];
+    return result;
+  }
+  if (strcmp(method, "get") == 0 || strcmp(method, "has") == 0) {
+    if (args[0].type != PG_STRING) pg_fail("%s() requires a string key argument", method);
+    for (i = 0; i < object.as.object->length; i++) {
+      if (strcmp(object.as.object->keys[i], args[0].as.string) == 0) {
+        return method[0] == 'h' ? pg_boolean(1) : object.as.object->values[i];
+      }
+    }
+    return method[0] == 'h' ? pg_boolean(0) : pg_nil();
+  }
+  pg_fail("Unsupported method: %s", method);
+  return pg_nil();
+}
+
+/* Resolve a replaceChar pattern: a variable's value, or the name itself as a literal */
+PG_RUNTIME const char *pg_transform_value(int slot) {
+  PgValue value = pg_env[slot];
+  if (value.type == PG_UNDEFINED) return pg_names[slot];
+  if (value.type == PG_STRING || value.type == PG_NUMBER || value.type == PG_BOOLEAN) {
+    return pg_show(value);
+  }
+  pg_fail("Variable '%s' is not a valid replacement value", pg_names[slot]);
+  return "";
+}
+
+/* string.replaceChar(from -> to) on the pushed receiver */
+PG_RUNTIME PgValue pg_replace_char(int from_slot, int to_slot) {
+  const char *text = pg_pop(1)[0].as.string, *from, *to, *match;
+  PgBuffer buffer = {NULL, 0, 0};
+  from = pg_transform_value(from_slot);
+  to = pg_transform_value(to_slot);
+  if (from[0] == '\0') return pg_string(text);
+  while ((match = strstr(text, from)) != NULL) {
+    char *before = pg_alloc((size_t)(match - text) + 1);
+    memcpy(before, text, (size_t)(match - text));
+    before[match - text] = '\0';
+    pg_buffer_append(&buffer, before);
+    pg_buffer_append(&buffer, to);
+    free(before);
+    text = match + strlen(from);
+  }
+  pg_buffer_append(&buffer, text);
+  return pg_string(pg_buffer_finish(&buffer));
+}
+
+/* ---- Functions ---- */
+
+typedef struct {
+  const PgFunction *function;
+  PgValue *saved; /* The caller's globals, restored after the call */
+} PgFrame;
+
+static PgFrame *pg_frames;
+static size_t pg_frame_count, pg_frame_capacity;
+
+/* Start a call of a function value, saving the globals; pg_bind binds its arguments */
+PG_RUNTIME void pg_frame_push(const PgFunction *function) {
+  PgFrame *frame;
+  if (pg_frame_count == pg_frame_capacity) {
+    PgFrame *frames;
+    pg_frame_capacity = pg_frame_capacity ? pg_frame_capacity * 2 : 16;
+    frames = pg_alloc(pg_frame_capacity * sizeof(PgFrame));
+    if (pg_frame_count > 0) memcpy(frames, pg_frames, pg_frame_count * sizeof(PgFrame));
+    free(pg_frames);
+    pg_frames = frames;
+  }
+  frame = &pg_frames[pg_frame_count++];
+  frame->function = function;
+  frame->saved = pg_alloc(PG_NAME_COUNT * sizeof(PgValue));
+  memcpy(frame->saved, pg_env, PG_NAME_COUNT * sizeof(PgValue));
+}
+
+/* Start calling a user-defined function: check it, then save the globals. The arguments are
+ * then bound one at a time with pg_bind (each evaluated after the previous is bound). */
+PG_RUNTIME int pg_call_begin(int slot, size_t count) {
+  PgValue value = pg_env[slot];
+  if (value.type != PG_FUNCTION) pg_fail("Undefined function '%s'", pg_names[slot]);
+  if (count != value.as.function->param_count) {
+    pg_fail("Function '%s' expects %zu arguments, got %zu", pg_names[slot],
+            value.as.function->param_count, count);
+  }
+  pg_frame_push(value.as.function);
+  return 0;
+}
+
+/* Bind the next argument of the innermost call being set up */
+PG_RUNTIME int pg_bind(size_t index, PgValue value) {
+  PgFrame *frame = &pg_frames[pg_frame_count - 1];
+  pg_env[frame->function->params[index]] = value;
+  return 0;
+}
+
+/* Run the body of the innermost call and restore the caller's globals */
+PG_RUNTIME PgValue pg_call_end(void) {
+  const PgFunction *function = pg_frames[pg_frame_count - 1].function;
+  PgValue result = function->body();
+  PgFrame *frame = &pg_frames[--pg_frame_count];
+  memcpy(pg_env, frame->saved, PG_NAME_COUNT * sizeof(PgValue));
+  free(frame->saved);
+  return result;
+}
+
+/* ---- Builtins ---- */
+
+/* Show a prompt and read one line of input without its line ending, or return NULL at the end
+ * of input */
+PG_RUNTIME const char *pg_read_input(const char *prompt) {
+  PgBuffer buffer = {NULL, 0, 0};
+  char chunk[256];
+  int read = 0;
+  fputs(prompt, stdout);
+  fflush(stdout);
+  while (fgets(chunk, sizeof chunk, stdin) != NULL) {
+    read = 1;
+    pg_buffer_append(&buffer, chunk);
+    if (buffer.length > 0 && buffer.data[buffer.length - 1] == '\n') break;
+  }
+  if (!read) return NULL;
+  if (buffer.length > 0 && buffer.data[buffer.length - 1] == '\n') {
+    buffer.data[--buffer.length] = '\0';
+    if (buffer.length > 0 && buffer.data[buffer.length - 1] == '\r') {
+      buffer.data[--buffer.length] = '\0';
+    }
+  }
+  return pg_buffer_finish(&buffer);
+}
+
+/* readLine(prompt?): the prompt (if any) has been pushed */
+PG_RUNTIME PgValue pg_read_line(size_t count) {
+  PgValue *args = pg_pop(count);
+  const char *line = pg_read_input(count > 0 ? pg_show(args[0]) : "");
+  return pg_string(line == NULL ? "" : line);
+}
+
+/* confirm(message): ask a yes/no question until it is answered; the end of input is "no" */
+PG_RUNTIME PgValue pg_builtin_confirm(size_t count) {
+  PgValue *args = pg_pop(count);
+  PgBuffer question = {NULL, 0, 0};
+  const char *answer;
+  if (count != 1) pg_fail("confirm() expects 1 argument, got %zu", count);
+  pg_buffer_append(&question, pg_show(args[0]));
+  pg_buffer_append(&question, " (y/n) ");
+  while ((answer = pg_read_input(pg_buffer_finish(&question))) != NULL) {
+    size_t start = 0, end = strlen(answer), i;
+    char word[4];
+    while (start < end && isspace((unsigned char)answer[start])) start++;
+    while (end > start && isspace((unsigned char)answer[end - 1])) end--;
+    if (end - start > 3) continue;
+    for (i = 0; start + i < end; i++) word[i] = (char)tolower((unsigned char)answer[start + i]);
+    word[i] = '\0';
+    if (strcmp(word, "y") == 0 || strcmp(word, "yes") == 0) return pg_boolean(1);
+    if (strcmp(word, "n") == 0 || strcmp(word, "no") == 0) return pg_boolean(0);
+  }
+  return pg_boolean(0);
+}
+
+/* prompt(message, default?): read a line, or give the default (or "") when it is empty */
+PG_RUNTIME PgValue pg_builtin_prompt(size_t count) {
+  PgValue *args = pg_pop(count);
+  const char *answer;
+  if (count < 1 || count > 2) pg_fail("prompt() expects 1 or 2 arguments, got %zu", count);
+  answer = pg_read_input(pg_show(args[0]));
+  if (answer == NULL || answer[0] == '\0') return pg_string(count > 1 ? pg_show(args[1]) : "");
+  return pg_string(answer);
+}
+
+/* Make a local time from its parts, or return 0 if the parts do not form a valid date */
+PG_RUNTIME int pg_make_time(int y, int mo, int d, int h, int mi, int s, time_t *result) {
+  struct tm parts;
+  memset(&parts, 0, sizeof parts);
+  parts.tm_year = y - 1900;
+  parts.tm_mon = mo - 1;
+  parts.tm_mday = d;
+  parts.tm_hour = h;
+  parts.tm_min = mi;
+  parts.tm_sec = s;
+  parts.tm_isdst = -1;
+  *result = mktime(&parts);
+  return parts.tm_year == y - 1900 && parts.tm_mon == mo - 1 && parts.tm_mday == d &&
+         parts.tm_hour == h && parts.tm_min == mi && parts.tm_sec == s;
+}
+
+/* Match the start of text against a word ignoring case, returning its length or 0 */
+PG_RUNTIME size_t pg_match_word(const char *text, const char *word, size_t length) {
+  size_t i;
+  for (i = 0; i < length; i++) {
+    if (tolower((unsigned char)text[i]) != word[i]) return 0;
+  }
+  return length;
+}
+
+/* Read the text at *pos against a format of strftime specifiers (the common ones chrono
+ * accepts), storing each field in parts and marking it in seen, both indexed by specifier.
+ * Whitespace in the format matches any amount of whitespace. Returns 0 if the text does not
+ * match. */
+PG_RUNTIME int pg_read_date(const char *text, size_t *pos, const char *format, int *parts,
+                            char *seen) {
+  static const char *const months[] = {"january", "february", "march", "april",
+                                       "may", "june", "july", "august",
+                                       "september", "october", "november", "december"};
+  const char *f;
+  for (f = format; *f != '\0'; f++) {
+    char spec;
+    int value = 0, digits = 0, max = 2, negative = 0, low = 0, high = 99;
+    if (isspace((unsigned char)*f)) {
+      while (isspace((unsigned char)text[*pos])) (*pos)++;
+      continue;
+    }
+    if (*f != '%') {
+      if (text[*pos] != *f) return 0;
+      (*pos)++;
+      continue;
+    }
+    spec = *++f;
+    switch (spec) {
+    case 'F':
+      if (!pg_read_date(text, pos, "%Y-%m-%d", parts, seen)) return 0;
+      continue;
+    case 'T':
+      if (!pg_read_date(text, pos, "%H:%M:%S", parts, seen)) return 0;
+      continue;
+    case 'R':
+      if (!pg_read_date(text, pos, "%H:%M", parts, seen)) return 0;
+      continue;
+    case '%':
+      if (text[*pos] != '%') return 0;
+      (*pos)++;
+      continue;
+    case 'p':
+      if (pg_match_word(text + *pos, "am", 2)) {
+        parts['p'] = 0;
+      } else if (pg_match_word(text + *pos, "pm", 2)) {
+        parts['p'] = 1;
+      } else {
+        return 0;
+      }
+      seen['p'] = 1;
+      *pos += 2;
+      continue;
+    case 'b':
+    case 'h':
+    case 'B':
+      for (value = 0; value < 12; value++) {
+        if (pg_match_word(text + *pos, months[value], 3)) break;
+      }
+      if (value == 12) return 0;
+      *pos += 3;
+      if (spec == 'B') *pos += pg_match_word(text + *pos, months[value] + 3, strlen(months[value] + 3));
+      parts['m'] = value + 1;
+      seen['m'] = 1;
+      continue;
+    case 'Y':
+      max = 4;
+      low = -9999;
+      high = 9999;
+      break;
+    case 'm':
+      low = 1;
+      high = 12;
+      break;
+    case 'd':
+    case 'e':
+      low = 1;
+      high = 31;
+      break;
+    case 'H':
+      high = 23;
+      break;
+    case 'I':
+      low = 1;
+      high = 12;
+      break;
+    case 'M':
+    case 'S':
+      high = 59;
+      break;
+    case 'y':
+      break;
+    default:
+      return 0;
+    }
+    /* A number, after any whitespace; only a year may have a sign */
+    while (isspace((unsigned char)text[*pos])) (*pos)++;
+    if (spec == 'Y' && (text[*pos] == '+' || text[*pos] == '-')) negative = text[(*pos)++] == '-';
+    while (digits < max && isdigit((unsigned char)text[*pos])) {
+      value = value * 10 + (text[(*pos)++] - '0');
+      digits++;
+    }
+    if (digits == 0) return 0;
+    if (negative) value = -value;
+    if (value < low || value > high) return 0;
+    if (spec == 'e') spec = 'd';
+    parts[(unsigned char)spec] = value;
+    seen[(unsigned char)spec] = 1;
+  }
+  return 1;
+}
+
+/* Parse a date written in a format, or return 0. A time of day is only used when both its
+ * hour and minute are given; otherwise the date is at midnight. */
+PG_RUNTIME int pg_parse_date(const char *text, const char *format, time_t *result) {
+  int parts[128];
+  char seen[128];
+  size_t pos = 0;
+  int year, hour = -1, h = 0, mi = 0, s = 0;
+  memset(seen, 0, sizeof seen);
+  if (!pg_read_date(text, &pos, format, parts, seen) || text[pos] != '\0') return 0;
+  if (seen['Y']) {
+    year = parts['Y'];
+  } else if (seen['y']) {
+    year = parts['y'] + (parts['y'] < 70 ? 2000 : 1900);
+  } else {
+    return 0;
+  }
+  if (!seen['m'] || !seen['d']) return 0;
+  if (seen['H']) {
+    hour = parts['H'];
+  } else if (seen['I'] && seen['p']) {
+    hour = parts['I'] % 12 + (parts['p'] ? 12 : 0);
+  }
+  if (hour >= 0 && seen['M']) {
+    h = hour;
+    mi = parts['M'];
+    s = seen['S'] ? parts['S'] : 0;
+  }
+  return pg_make_time(year, parts['m'], parts['d'], h, mi, s, result);
+}
+
+/* Date(), Date("YYYY-MM-DD[ HH:MM:SS]"), Date(text, format) or Date(year, month, day) */
+PG_RUNTIME PgValue pg_builtin_date(size_t count) {
+  PgValue *args = pg_pop(count);
+  time_t t;
+  if (count == 0) return pg_date(time(NULL));
+  if (count == 1) {
+    int y, mo, d, h = 0, mi = 0, s = 0, used = 0;
+    const char *text;
+    if (args[0].type != PG_STRING) pg_fail("Date() argument must be a string");
+    text = args[0].as.string;
+    if (!((sscanf(text, "%4d-%2d-%2d %2d:%2d:%2d%n", &y, &mo, &d, &h, &mi, &s, &used) == 6 &&
+           text[used] == '\0') ||
+          (h = mi = s = 0, used = 0,
+           sscanf(text, "%4d-%2d-%2d%n", &y, &mo, &d, &used) == 3 && text[used] == '\0')) ||
+        !pg_make_time(y, mo, d, h, mi, s, &t)) {
+      pg_fail("Unable to parse date: '%s' (expected YYYY-MM-DD or YYYY-MM-DD HH:MM:SS; give a "
+              "format for others, e.g. Date(text, \"%%d/%%m/%%Y\"))",
+              text);
+    }
+    return pg_date(t);
+  }
+  if (count == 2) {
+    if (args[0].type != PG_STRING || args[1].type != PG_STRING) {
+      pg_fail("Date() text and format must be strings");
+    }
+    if (!pg_parse_date(args[0].as.string, args[1].as.string, &t)) {
+      pg_fail("Unable to parse date: '%s' with format '%s'", args[0].as.string,
+              args[1].as.string);
+    }
+    return pg_date(t);
+  }
+  if (count == 3) {
+    if (args[0].type != PG_NUMBER || args[1].type != PG_NUMBER || args[2].type != PG_NUMBER) {
+      pg_fail("Date() year, month, and day must be numbers");
+    }
+    if (!pg_make_time((int)args[0].as.number, (int)args[1].as.number, (int)args[2].as.number,
+                      0, 0, 0, &t)) {
+      pg_fail("Invalid date values");
+    }
+    return pg_date(t);
+  }
+  pg_fail("Date() takes 0, 1, 2, or 3 arguments");
+  return pg_nil();
+}
+
+/* parallelMap(array, function): the calls run one after another here (the interpreter spreads
+ * them over threads; the results and output are the same) */
+PG_RUNTIME PgValue pg_builtin_parallel_map(size_t count) {
+  PgValue *args = pg_pop(count), items, function, result;
+  size_t i;
+  if (count != 2) pg_fail("parallelMap() expects 2 arguments, got %zu", count);
+  items = args[0]; /* Copied: the calls below reuse the stack the arguments are on */
+  function = args[1];
+  if (items.type != PG_FIXED_ARRAY && items.type != PG_DYNAMIC_ARRAY) {
+    pg_fail("parallelMap() needs an array as its first argument, not a %s", pg_type_name(items));
+  }
+  if (function.type != PG_FUNCTION) {
+    pg_fail("parallelMap() needs a function as its second argument, not a %s",
+            pg_type_name(function));
+  }
+  if (function.as.function->param_count != 1) {
+    pg_fail("parallelMap() function must take 1 argument (an item), not %zu",
+            function.as.function->param_count);
+  }
+  result = pg_array_value(items.type, items.as.array->length);
+  for (i = 0; i < items.as.array->length; i++) {
+    pg_frame_push(function.as.function);
+    pg_bind(0, items.as.array->items[i]);
+    result.as.array->items[i] = pg_call_end();
+  }
+  return result;
+}
+
+/* spawn(function, arguments...): C programs have one thread, so the call runs straight away
+ * and the task holds its return value for wait() */
+PG_RUNTIME PgValue pg_builtin_spawn(size_t count) {
+  PgValue *args = pg_pop(count), function, task;
+  size_t i;
+  if (count == 0) pg_fail("spawn() expects a function and its arguments");
+  function = args[0];
+  if (function.type != PG_FUNCTION) {
+    pg_fail("spawn() needs a function as its first argument, not a %s", pg_type_name(function));
+  }
+  if (count - 1 != function.as.function->param_count) {
+    pg_fail("spawn() function expects %zu arguments, got %zu", function.as.function->param_count,
+            count - 1);
+  }
+  pg_frame_push(function.as.function);
+  for (i = 1; i < count; i++) pg_bind(i - 1, args[i]);
+  task.type = PG_TASK;
+  task.as.task = pg_alloc(sizeof(PgValue));
+  *task.as.task = pg_call_end();
+  return task;
+}
+
+/* wait(task): the spawned call's return value */
+PG_RUNTIME PgValue pg_builtin_wait(size_t count) {
+  PgValue *args = pg_pop(count);
+  if (count != 1) pg_fail("wait() expects 1 argument, got %zu", count);
+  if (args[0].type != PG_TASK) {
+    pg_fail("wait() needs a task (from spawn), not a %s", pg_type_name(args[0]));
+  }
+  return *args[0].as.task;
+}
+
+/* ---- Timers (setTimeout, setInterval) ---- */
+
+/* A call scheduled for later. The C library cannot sleep, so timers run on a clock of their
+ * own, which jumps to each timer's time when it runs: the order is kept, the waiting is not. */
+typedef struct {
+  int id;        /* What setTimeout/setInterval returned, for clearTimer */
+  double due;    /* When it next runs, in milliseconds on the timer clock */
+  double every;  /* How often an interval repeats (negative for a timeout) */
+  const PgFunction *function;
+  size_t runs;   /* How many times it has run */
+} PgTimer;
+
+static PgTimer *pg_timers;
+static size_t pg_timer_count, pg_timer_capacity;
+static int pg_next_timer = 1;
+static double pg_timer_clock;
+
+/* setTimeout(function, delay) and setInterval(function, delay): the delay is in milliseconds
+ * or a duration */
+PG_RUNTIME PgValue pg_set_timer(const char *builtin, size_t count, int repeat) {
+  PgValue *args = pg_pop(count);
+  PgTimer *timer;
+  double delay;
+  if (count != 2) pg_fail("%s() expects 2 arguments, got %zu", builtin, count);
+  if (args[0].type != PG_FUNCTION) {
+    pg_fail("%s() needs a function as its first argument, not a %s", builtin,
+            pg_type_name(args[0]));
+  }
+  if (args[0].as.function->param_count > 1) {
+    pg_fail("%s() function must take no arguments or 1 (how many times it has run), not %zu",
+            builtin, args[0].as.function->param_count);
+  }
+  if (args[1].type == PG_NUMBER) {
+    delay = args[1].as.number;
+  } else if (args[1].type == PG_DURATION) {
+    delay = args[1].as.seconds * 1000;
+  } else {
+    pg_fail("%s() delay must be a number of milliseconds or a duration, not a %s", builtin,
+            pg_type_name(args[1]));
+  }
+  if (!(delay > 0)) delay = 0; /* Also NaN */
+  if (pg_timer_count == pg_timer_capacity) {
+    PgTimer *timers;
+    pg_timer_capacity = pg_timer_capacity ? pg_timer_capacity * 2 : 8;
+    timers = pg_alloc(pg_timer_capacity * sizeof(PgTimer));
+    if (pg_timer_count > 0) memcpy(timers, pg_timers, pg_timer_count * sizeof(PgTimer));
+    free(pg_timers);
+    pg_timers = timers;
+  }
+  timer = &pg_timers[pg_timer_count++];
+  timer->id = pg_next_timer++;
+  timer->due = pg_timer_clock + delay;
+  timer->every = repeat ? delay : -1;
+  timer->function = args[0].as.function;
+  timer->runs = 0;
+  return pg_number(timer->id);
+}
+
+PG_RUNTIME PgValue pg_builtin_set_timeout(size_t count) {
+  return pg_set_timer("setTimeout", count, 0);
+}
+
+PG_RUNTIME PgValue pg_builtin_set_interval(size_t count) {
+  return pg_set_timer("setInterval", count, 1);
+}
+
+/* clearTimer(id): cancel a timer (an unknown id is ignored) */
+PG_RUNTIME PgValue pg_builtin_clear_timer(size_t count) {
+  PgValue *args = pg_pop(count);
+  size_t i;
+  if (count != 1) pg_fail("clearTimer() expects 1 argument, got %zu", count);
+  if (args[0].type != PG_NUMBER) {
+    pg_fail("clearTimer() needs a timer id (from setTimeout or setInterval), not a %s",
+            pg_type_name(args[0]));
+  }
+  for (i = 0; i < pg_timer_count; i++) {
+    if (pg_timers[i].id == args[0].as.number) {
+      pg_timers[i] = pg_timers[--pg_timer_count];
+      break;
+    }
+  }
+  return pg_nil();
+}
+
+/* Run the timers once the program has finished, earliest first, until none are left */
+PG_RUNTIME void pg_run_timers(void) {
+  while (pg_timer_count > 0) {
+    size_t i, next = 0;
+    PgTimer *timer;
+    const PgFunction *function;
+    size_t runs;
+    for (i = 1; i < pg_timer_count; i++) {
+      if (pg_timers[i].due < pg_timers[next].due ||
+          (pg_timers[i].due == pg_timers[next].due && pg_timers[i].id < pg_timers[next].id)) {
+        next = i;
+      }
+    }
+    /* Reschedule (or drop) the timer first, so its callback can clear it */
+    timer = &pg_timers[next];
+    pg_timer_clock = timer->due;
+    function = timer->function;
+    runs = ++timer->runs;
+    if (timer->every >= 0) {
+      timer->due += timer->every;
+    } else {
+      pg_timers[next] = pg_timers[--pg_timer_count];
+    }
+    pg_frame_push(function);
+    if (function->param_count == 1) pg_bind(0, pg_number((double)runs));
+    pg_call_end();
+  }
+}
+
+/* Duration(hours, minutes, seconds) */
+PG_RUNTIME PgValue pg_builtin_duration(size_t count) {
+  PgValue *args = pg_pop(count);
+  if (count != 3) pg_fail("Duration() takes 3 arguments (hours, minutes, seconds)");
+  if (args[0].type != PG_NUMBER || args[1].type != PG_NUMBER || args[2].type != PG_NUMBER) {
+    pg_fail("Duration() hours, minutes, and seconds must be numbers");
+  }
+  return pg_duration(args[0].as.number * 3600 + args[1].as.number * 60 + args[2].as.number);
+}
+
+/* Object(key, value, ...) from the pushed arguments */
+PG_RUNTIME PgValue pg_builtin_object(size_t count) {
+  PgValue *args = pg_pop(count), result;
+  size_t i, j, length = 0;
+  if (count % 2 != 0) pg_fail("Object() requires an even number of arguments (key-value pairs)");
+  result = pg_object_value(count / 2);
+  for (i = 0; i < count; i += 2) {
+    PgValue key = args[i];
+    const char *name;
+    if (key.type != PG_STRING && key.type != PG_NUMBER && key.type != PG_BOOLEAN) {
+      pg_fail("Object() keys must be strings, numbers, or booleans");
+    }
+    name = pg_show(key);
+    for (j = 0; j < length && strcmp(result.as.object->keys[j], name) != 0; j++) {
+    }
+    result.as.object->keys[j] = name;
+    result.as.object->values[j] = args[i + 1];
+    if (j == length) length++;
+  }
+  result.as.object->length = length;
+  return result;
+}
+
+/* args(): the program's command-line arguments */
+PG_RUNTIME PgValue pg_builtin_args(size_t count) {
+  PgValue result;
+  int i;
+  pg_pop(count);
+  if (count != 0) pg_fail("args() expects 0 arguments, got %zu", count);
+  result = pg_array_value(PG_FIXED_ARRAY, (size_t)(pg_argc > 1 ? pg_argc - 1 : 0));
+  for (i = 1; i < pg_argc; i++) result.as.array->items[i - 1] = pg_string(pg_argv[i]);
+  return result;
+}
+
+/* Copy a value and everything nested inside it */
+PG_RUNTIME PgValue pg_deep_copy(PgValue value) {
+  PgValue copy = value;
+  size_t i;
+  if (pg_is_array(value)) {
+    copy = pg_array_value(value.type, value.as.array->length);
+    for (i = 0; i < value.as.array->length; i++) {
+      copy.as.array->items[i] = pg_deep_copy(value.as.array->items[i]);
+    }
+  } else if (value.type == PG_OBJECT) {
+    copy = pg_object_value(value.as.object->length);
+    for (i = 0; i < value.as.object->length; i++) {
+      copy.as.object->keys[i] = value.as.object->keys[i];
+      copy.as.object->values[i] = pg_deep_copy(value.as.object->values[i]);
+    }
+  }
+  return copy;
+}
+
+/* Make a frozen copy of an array or object, with everything inside it frozen too */
+PG_RUNTIME PgValue pg_freeze(PgValue value) {
+  PgValue copy = value;
+  size_t i;
+  if (pg_is_array(value)) {
+    copy = pg_array_value(value.type, value.as.array->length);
+    for (i = 0; i < value.as.array->length; i++) {
+      copy.as.array->items[i] = pg_freeze(value.as.array->items[i]);
+    }
+    copy.as.array->frozen = 1;
+  } else if (value.type == PG_OBJECT) {
+    copy = pg_object_value(value.as.object->length);
+    for (i = 0; i < value.as.object->length; i++) {
+      copy.as.object->keys[i] = value.as.object->keys[i];
+      copy.as.object->values[i] = pg_freeze(value.as.object->values[i]);
+    }
+    copy.as.object->frozen = 1;
+  }
+  return copy;
+}
+
+/* freeze(value): the value has been pushed */
+PG_RUNTIME PgValue pg_builtin_freeze(size_t count) {
+  PgValue *args = pg_pop(count);
+  if (count != 1) pg_fail("freeze() expects 1 argument, got %zu", count);
+  return pg_freeze(args[0]);
+}
+
+/* deepCopy(value): the value has been pushed */
+PG_RUNTIME PgValue pg_builtin_deep_copy(size_t count) {
+  PgValue *args = pg_pop(count);
+  if (count != 1) pg_fail("deepCopy() expects 1 argument, got %zu", count);
+  return pg_deep_copy(args[0]);
+}
+
+/* Check whether a variable holds a function the program defined, which a call by that name
+ * prefers to a builtin of the same name */
+PG_RUNTIME int pg_is_function(size_t slot) {
+  return pg_env[slot].type == PG_FUNCTION;
+}
+
+/* ---- Printing ---- */
+
+/* Check the pushed format value before the print arguments are evaluated */
+PG_RUNTIME int pg_print_begin(size_t count) {
+  if (count > 0 && pg_stack[pg_stack_length - 1].type != PG_STRING) {
+    pg_fail("Format string must be a string");
+  }
+  return 0;
+}
+
+/* Take an argument for a placeholder, noting that it was used */
+PG_RUNTIME PgValue pg_format_argument(const PgValue *args, int *used, size_t count, size_t index,
+                                      const char *placeholder) {
+  if (index >= count) {
+    pg_fail("Placeholder %s has no argument: it takes argument %lu, but only %lu argument%s given",
+            placeholder, (unsigned long)index + 1, (unsigned long)count,
+            count == 1 ? " was" : "s were");
+  }
+  used[index] = 1;
+  return args[index];
+}
+
+/* A format specifier, the part of a placeholder after ':' */
+typedef struct {
+  char fill[5]; /* UTF-8 character the padding is made of */
+  char align;   /* '<', '>', '^', or 0: numbers go right, everything else left */
+  int zero;     /* Pad a number with zeros after its sign (the 0 flag) */
+  size_t width; /* Minimum number of characters */
+  long precision; /* Decimals of a number, or the most characters of anything else; -1 for none */
+} PgSpec;
+
+/* Count the characters (not bytes) of UTF-8 text */
+PG_RUNTIME size_t pg_utf8_count(const char *text) {
+  size_t count = 0;
+  for (; *text != '\0'; text++) {
+    if (((unsigned char)*text & 0xC0) != 0x80) count++;
+  }
+  return count;
+}
+
+/* Read a format specifier: [[fill]align][0][width][.precision]. Returns 0 if it is invalid. */
+PG_RUNTIME int pg_parse_spec(const char *text, size_t length, PgSpec *out) {
+  PgSpec spec = {" ", 0, 0, 0, -1};
+  const char *p = text, *end = text + length;
+  size_t first = 1; /* Bytes in the first character */
+  while (first < length && ((unsigned char)text[first] & 0xC0) == 0x80) first++;
+  if (length > first && strchr("<>^", text[first]) != NULL && first < sizeof spec.fill) {
+    memcpy(spec.fill, text, first);
+    spec.fill[first] = '\0';
+    spec.align = text[first];
+    p = text + first + 1;
+  } else if (length > 0 && strchr("<>^", text[0]) != NULL) {
+    spec.align = text[0];
+    p = text + 1;
+  }
+  if (p < end && *p == '0') {
+    spec.zero = 1;
+    p++;
+  }
+  for (; p < end && isdigit((unsigned char)*p); p++) {
+    spec.width = spec.width * 10 + (size_t)(*p - '0');
+  }
+  if (p < end && *p == '.' && p + 1 < end && isdigit((unsigned char)p[1])) {
+    spec.precision = 0;
+    for (p++; p < end && isdigit((unsigned char)*p); p++) {
+      spec.precision = spec.precision * 10 + (*p - '0');
+    }
+  }
+  *out = spec;
+  return p == end;
+}
+
+/* Check whether a format specifier can show a value: arrays and objects cannot be cut */
+PG_RUNTIME int pg_spec_fits(PgValue value, const PgSpec *spec) {
+  return spec->precision < 0 || (value.type != PG_FIXED_ARRAY &&
+                                 value.type != PG_DYNAMIC_ARRAY && value.type != PG_OBJECT);
+}
+
+/* Show a value the way a format specifier says */
+PG_RUNTIME void pg_append_spec(PgBuffer *buffer, PgValue value, const PgSpec *spec) {
+  const char *text = pg_show(value);
+  size_t length, padding, before, i;
+  char align = spec->align ? spec->align : value.type == PG_NUMBER ? '>' : '<';
+  if (spec->precision >= 0 && value.type == PG_NUMBER) {
+    if (isfinite(value.as.number)) {
+      int size = snprintf(NULL, 0, "%.*f", (int)spec->precision, value.as.number);
+      char *rounded = pg_alloc((size_t)size + 1);
+      snprintf(rounded, (size_t)size + 1, "%.*f", (int)spec->precision, value.as.number);
+      text = rounded;
+    }
+  } else if (!pg_spec_fits(value, spec)) {
+    pg_fail("Cannot use a precision ('.%ld') with a value of type %s", spec->precision,
+            pg_type_name(value));
+  } else if (spec->precision >= 0) {
+    /* Cut to the first `precision` characters */
+    size_t end = 0, kept = 0;
+    char *cut;
+    for (; text[end] != '\0'; end++) {
+      int continuation = ((unsigned char)text[end] & 0xC0) == 0x80;
+      if (!continuation && kept == (size_t)spec->precision) break;
+      if (!continuation) kept++;
+    }
+    cut = pg_alloc(end + 1);
+    memcpy(cut, text, end);
+    cut[end] = '\0';
+    text = cut;
+  }
+  length = pg_utf8_count(text);
+  padding = spec->width > length ? spec->width - length : 0;
+  if (spec->zero && value.type == PG_NUMBER) {
+    /* The zeros go between the sign and the digits */
+    if (*text == '-') pg_buffer_append(buffer, "-");
+    for (i = 0; i < padding; i++) pg_buffer_append(buffer, "0");
+    pg_buffer_append(buffer, *text == '-' ? text + 1 : text);
+    return;
+  }
+  before = align == '<' ? 0 : align == '>' ? padding : padding / 2;
+  for (i = 0; i < before; i++) pg_buffer_append(buffer, spec->fill);
+  pg_buffer_append(buffer, text);
+  for (i = before; i < padding; i++) pg_buffer_append(buffer, spec->fill);
+}
+
+/* Fill in the placeholders of a format string, with the same rules as src/format.rs: without
+ * arguments, braces that are not a placeholder with a value are printed as text */
+PG_RUNTIME const char *pg_format(const char *format, const PgValue *args, size_t count) {
+  PgBuffer buffer = {NULL, 0, 0};
+  int *used = pg_alloc(count * sizeof(int) + 1);
+  int lenient = count == 0;
+  size_t next = 0, i;
+  char text[2] = {0, 0}, placeholder[64];
+  memset(used, 0, count * sizeof(int));
+  while (*format != '\0') {
+    if (format[0] == '{' && format[1] == '{') {
+      pg_buffer_append(&buffer, "{");
+      format += 2;
+    } else if (format[0] == '{') {
+      const char *start = format + 1, *end = strchr(start, '}'), *colon;
+      PgSpec spec = {" ", 0, 0, 0, -1};
+      size_t length;
+      if (end == NULL) {
+        if (!lenient) pg_fail("Unclosed '{' in format string (write '{{' for a literal brace)");
+        pg_buffer_append(&buffer, "{");
+        format++;
+        continue;
+      }
+      colon = memchr(start, ':', (size_t)(end - start));
+      if (colon != NULL) {
+        if (!pg_parse_spec(colon + 1, (size_t)(end - colon - 1), &spec)) {
+          if (!lenient) {
+            pg_fail("Invalid format specifier ':%.*s' in format string", (int)(end - colon - 1),
+                    colon + 1);
+          }
+          pg_buffer_append(&buffer, "{");
+          format++;
+          continue;
+        }
+        end = colon;
+      }
+      while (start < end && isspace((unsigned char)*start)) start++;
+      while (end > start && isspace((unsigned char)end[-1])) end--;
+      length = (size_t)(end - start);
+      snprintf(placeholder, sizeof placeholder, "{%.*s}", (int)length, start);
+      if (lenient && (length == 0 || strspn(start, "0123456789") >= length)) {
+        /* {} and {0} have no argument to show, so they are text */
+        pg_buffer_append(&buffer, "{");
+        format++;
+        continue;
+      } else if (length == 0) {
+        pg_append_spec(&buffer, pg_format_argument(args, used, count, next++, "{}"), &spec);
+      } else if (strspn(start, "0123456789") >= length) {
+        size_t index = (size_t)strtoul(start, NULL, 10);
+        pg_append_spec(&buffer, pg_format_argument(args, used, count, index, placeholder), &spec);
+      } else {
+        int valid =
+            isalpha((unsigned char)*start) || *start == '_' || (unsigned char)*start >= 0x80;
+        for (i = 0; i < length; i++) {
+          unsigned char c = (unsigned char)start[i];
+          if (!isalnum(c) && c != '_' && c < 0x80) valid = 0;
+        }
+        if (valid) {
+          for (i = 0; i < PG_NAME_COUNT; i++) {
+            if (strlen(pg_names[i]) == length && strncmp(pg_names[i], start, length) == 0) break;
+          }
+        }
+        if (lenient && (!valid || i == PG_NAME_COUNT || pg_env[i].type == PG_UNDEFINED ||
+                        !pg_spec_fits(pg_env[i], &spec))) {
+          pg_buffer_append(&buffer, "{");
+          format++;
+          continue;
+        } else if (!valid) {
+          pg_fail("Invalid placeholder '%s' in format string (write '{{' for a literal brace)",
+                  placeholder);
+        } else if (i == PG_NAME_COUNT || pg_env[i].type == PG_UNDEFINED) {
+          pg_fail("Undefined variable '%.*s' in format string", (int)length, start);
+        }
+        pg_append_spec(&buffer, pg_env[i], &spec);
+      }
+      format = strchr(format, '}') + 1;
+    } else if (format[0] == '}') {
+      /* A lone '}' has nothing to close, so it is kept as text */
+      pg_buffer_append(&buffer, "}");
+      format += format[1] == '}' ? 2 : 1;
+    } else {
+      text[0] = *format++;
+      pg_buffer_append(&buffer, text);
+    }
+  }
+  for (i = 0; i < count; i++) {
+    if (!used[i]) {
+      pg_fail("Argument %lu is not used in the format string (%lu argument%s given)",
+              (unsigned long)i + 1, (unsigned long)count, count == 1 ? " was" : "s were");
+    }
+  }
+  free(used);
+  return pg_buffer_finish(&buffer);
+}
+
+/* print/printLn/printErr: a single value, or a format string when it is a string literal or
+ * arguments follow it. Like the interpreter, printErr ends its line on standard error. */
+PG_RUNTIME void pg_print(size_t count, int formatted, int to_stderr, int newline) {
+  PgValue *args = pg_pop(count + 1);
+  FILE *stream = to_stderr ? stderr : stdout;
+  if (!formatted) {
+    fputs(pg_show(args[0]), stream);
+  } else {
+    fputs(pg_format(args[0].as.string, args + 1, count), stream);
+  }
+  if (newline) {
+    fputc('\n', stream);
+  } else {
+    fflush(stream); /* Show an unfinished line (a prompt or progress update) right away */
+  }
+}
+
+/* ---- for-in loops ---- */
+
+/* Where a for-in loop takes its next item from: the items of an array (strings and objects are
+ * turned into one first), or an iterator object's next function and its state */
+typedef struct {
+  PgArray *items;
+  size_t index;
+  const PgFunction *next; /* NULL unless traversing an iterator object */
+  PgValue state;
+} PgIter;
+
+/* Find a field of an object, or NULL if it has none with that key */
+PG_RUNTIME PgValue *pg_field(PgObject *object, const char *key) {
+  size_t i;
+  for (i = 0; i < object->length; i++) {
+    if (strcmp(object->keys[i], key) == 0) return &object->values[i];
+  }
+  return NULL;
+}
+
+PG_RUNTIME int pg_compare_keys(const void *a, const void *b) {
+  return strcmp(*(const char *const *)a, *(const char *const *)b);
+}
+
+/* Start traversing a value: elements, characters, [key, value] entries sorted by key, or the
+ * items an iterator object (one with a next function) produces */
+PG_RUNTIME PgIter pg_iterate(PgValue value) {
+  PgIter iter;
+  PgValue items;
+  size_t i, length;
+  iter.index = 0;
+  iter.next = NULL;
+  iter.state = pg_nil();
+
+  if (pg_is_array(value)) {
+    iter.items = value.as.array;
+  } else if (value.type == PG_STRING) {
+    const char *text = value.as.string;
+    items = pg_array_value(PG_FIXED_ARRAY, pg_utf8_count(text));
+    for (i = 0; *text != '\0'; i++) {
+      char *character;
+      length = 1;
+      while (((unsigned char)text[length] & 0xC0) == 0x80) length++;
+      character = pg_alloc(length + 1);
+      memcpy(character, text, length);
+      character[length] = '\0';
+      items.as.array->items[i] = pg_string(character);
+      text += length;
+    }
+    iter.items = items.as.array;
+  } else if (value.type == PG_OBJECT) {
+    PgObject *object = value.as.object;
+    PgValue *next = pg_field(object, "next"), *state = pg_field(object, "state");
+    const char **keys;
+    if (next != NULL && next->type == PG_FUNCTION) {
+      if (next->as.function->param_count != 1) {
+        pg_fail("An iterator's next function must take 1 argument (its state), not %zu",
+                next->as.function->param_count);
+      }
+      iter.next = next->as.function;
+      if (state != NULL) iter.state = *state;
+      return iter;
+    }
+    keys = pg_alloc(object->length * sizeof(const char *));
+    memcpy(keys, object->keys, object->length * sizeof(const char *));
+    qsort(keys, object->length, sizeof(const char *), pg_compare_keys);
+    items = pg_array_value(PG_FIXED_ARRAY, object->length);
+    for (i = 0; i < object->length; i++) {
+      PgValue entry = pg_array_value(PG_FIXED_ARRAY, 2);
+      entry.as.array->items[0] = pg_string(keys[i]);
+      entry.as.array->items[1] = *pg_field(object, keys[i]);
+      items.as.array->items[i] = entry;
+    }
+    iter.items = items.as.array;
+  } else {
+    pg_fail("Cannot iterate over a %s", pg_type_name(value));
+  }
+  return iter;
+}
+
+/* Take the next item of a for-in loop; returns 0 when there are no more */
+PG_RUNTIME int pg_iter_next(PgIter *iter, PgValue *item) {
+  PgValue step, *done, *field;
+  if (iter->next == NULL) {
+    if (iter->index >= iter->items->length) return 0;
+    *item = iter->items->items[iter->index++];
+    return 1;
+  }
+
+  /* An iterator object: next(state) returns {done, value, state} */
+  pg_frame_push(iter->next);
+  pg_bind(0, iter->state);
+  step = pg_call_end();
+  if (step.type != PG_OBJECT) {
+    pg_fail("An iterator's next function must return an object, not a %s", pg_type_name(step));
+  }
+  done = pg_field(step.as.object, "done");
+  if (done == NULL) pg_fail("An iterator's next function must return an object with a 'done' field");
+  if (pg_truthy(*done)) return 0;
+  field = pg_field(step.as.object, "state");
+  iter->state = field != NULL ? *field : pg_nil();
+  field = pg_field(step.as.object, "value");
+  *item = field != NULL ? *field : pg_nil();
+  return 1;
+}
+
+/* ---- Match expressions ---- */
+
+/* The value being matched, pushed while its cases are tried */
+PG_RUNTIME PgValue pg_match_value(void) {
+  return pg_stack[pg_stack_length - 1];
+}
+
+/* Parts of the matched value; the tests before them have checked they exist */
+PG_RUNTIME PgValue pg_match_item(PgValue array, size_t index) {
+  return array.as.array->items[index];
+}
+
+PG_RUNTIME PgValue pg_match_field(PgValue object, const char *key) {
+  return *pg_field(object.as.object, key);
+}
+
+/* The tests src/pattern.rs compiles patterns to (literals are compared with pg_equal) */
+PG_RUNTIME int pg_match_array(PgValue value, size_t length) {
+  return pg_is_array(value) && value.as.array->length == length;
+}
+
+PG_RUNTIME int pg_match_object(PgValue value) {
+  return value.type == PG_OBJECT;
+}
+
+PG_RUNTIME int pg_match_has(PgValue object, const char *key) {
+  return pg_field(object.as.object, key) != NULL;
+}
+
+/* The values a case's names shadowed, given back once its guard fails or its result is ready */
+typedef struct {
+  int slot;
+  PgValue value;
+} PgShadowed;
+
+static PgShadowed *pg_shadowed;
+static size_t pg_shadowed_length, pg_shadowed_capacity;
+
+/* Assign a name of a case its part of the matched value, keeping what it held */
+PG_RUNTIME int pg_match_bind(int slot, PgValue value) {
+  if (pg_shadowed_length == pg_shadowed_capacity) {
+    PgShadowed *shadowed;
+    pg_shadowed_capacity = pg_shadowed_capacity ? pg_shadowed_capacity * 2 : 16;
+    shadowed = pg_alloc(pg_shadowed_capacity * sizeof(PgShadowed));
+    if (pg_shadowed_length > 0) {
+      memcpy(shadowed, pg_shadowed, pg_shadowed_length * sizeof(PgShadowed));
+    }
+    free(pg_shadowed);
+    pg_shadowed = shadowed;
+  }
+  pg_shadowed[pg_shadowed_length].slot = slot;
+  pg_shadowed[pg_shadowed_length++].value = pg_env[slot];
+  pg_env[slot] = value;
+  return 1;
+}
+
+/* Give the last `count` names bound back what they held; 0, so a failed guard ends the case */
+PG_RUNTIME int pg_match_unbind(size_t count) {
+  while (count-- > 0) {
+    pg_shadowed_length--;
+    pg_env[pg_shadowed[pg_shadowed_length].slot] = pg_shadowed[pg_shadowed_length].value;
+  }
+  return 0;
+}
+
+/* The result of a chosen case, once its `count` names are given back */
+PG_RUNTIME PgValue pg_match_result(PgValue result, size_t count) {
+  pg_match_unbind(count);
+  return result;
+}
+
+/* A case was chosen: the matched value is no longer needed */
+PG_RUNTIME int pg_match_end(void) {
+  pg_pop(1);
+  return 0;
+}
+
+PG_RUNTIME PgValue pg_match_fail(void) {
+  pg_fail("No case matched the value %s", pg_show(pg_match_value()));
+  return pg_nil();
+}
+
+/* ---- Modules ---- */
+
+/* Run a module in a fresh set of globals and copy the requested names into ours */
+PG_RUNTIME void pg_import(void (*module)(void), const char *file, const int *slots, size_t count) {
+  PgValue *saved = pg_env, *exported;
+  size_t i;
+  pg_env = pg_new_env();
+  module();
+  exported = pg_env;
+  pg_env = saved;
+  for (i = 0; i < count; i++) {
+    const char *name = pg_names[slots[i]];
+    if (exported[slots[i]].type == PG_UNDEFINED) {
+      pg_fail("Name '%s' not found in module '%s'", name, file);
+    }
+    if (!isupper((unsigned char)name[0])) {
+      pg_fail("Cannot import '%s' - only names starting with uppercase letters can be imported",
+              name);
+    }
+    pg_env[slots[i]] = exported[slots[i]];
+  }
+  /* Methods the module defined on built-in types come along with any import from it */
+  for (i = 0; i < PG_NAME_COUNT; i++) {
+    if (strchr(pg_names[i], '.') && exported[i].type == PG_FUNCTION) pg_env[i] = exported[i];
+  }
+}
+
+/* Set up the runtime; called first by the generated main() */
+PG_RUNTIME void pg_start(int argc, char **argv) {
+  pg_argc = argc;
+  pg_argv = argv;
+  pg_env = pg_new_env();
+}
@@ -0,0 +1,1113 @@
+// Pidgin JavaScript runtime, embedded in every program built with `pidgin build --target js`.
+//
+// Generated code never touches JavaScript variables directly: every read, write, call and
+// operator goes through this shim so that programs behave exactly as they do under the
+// Rust interpreter (one flat set of globals, saved and restored around function calls,
+// arrays with value semantics, the same error messages).
+const $pg = (() => {
+  // A runtime error raised by the program (reported as "Error: <message>")
+  class PgError extends Error {}
+
+  // Fixed ([a, b]) and dynamic ({a, b}) arrays; never mutated once created
+  class PgArray {
+    constructor(items, dynamic) {
+      this.items = items;
+      this.dynamic = dynamic;
+      this.frozen = false; // Set by freeze(); objects (Maps) get the same property
+    }
+  }
+
+  // A user-defined function: parameter names plus the compiled body
+  class PgFunction {
+    constructor(params, body) {
+      this.params = params;
+      this.body = body;
+    }
+  }
+
+  // A function call started by spawn(). JavaScript has one thread, so the call runs straight
+  // away; its return value (or error) waits here for wait().
+  class PgTask {
+    constructor(value, error) {
+      this.value = value;
+      this.error = error;
+    }
+  }
+
+  // A date/time in local time
+  class PgDate {
+    constructor(date) {
+      this.date = date;
+    }
+  }
+
+  // A length of time, in seconds (Duration(hours, minutes, seconds))
+  class PgDuration {
+    constructor(seconds) {
+      this.seconds = seconds;
+    }
+  }
+
+  const isNode = typeof process !== "undefined" && process.stdout && process.stdout.write;
+  let env = new Map(); // The program's globals
+  const modules = new Map(); // Compiled module bodies by file name
+  const pending = { out: "", err: "" }; // Partial lines waiting for a newline (browsers)
+
+  // Write program output; `globalThis.pidginWrite(text, stream)` overrides the default
+  function write(text, stream) {
+    if (typeof globalThis.pidginWrite === "function") {
+      globalThis.pidginWrite(text, stream);
+    } else if (isNode) {
+      (stream === "err" ? process.stderr : process.stdout).write(text);
+    } else {
+      // Browsers can only log whole lines
+      const lines = (pending[stream] + text).split("\n");
+      pending[stream] = lines.pop();
+      for (const line of lines) {
+        (stream === "err" ? console.error : console.log)(line);
+      }
+    }
+  }
+
+  // Flush any partial browser output line
+  function flush() {
+    for (const stream of ["out", "err"]) {
+      if (pending[stream] !== "") {
+        (stream === "err" ? console.error : console.log)(pending[stream]);
+        pending[stream] = "";
+      }
+    }
+  }
+
+  function fail(message) {
+    return new PgError(message);
+  }
+
+  // Format a number like Rust's f64 Display (no exponents, "inf", "-0")
+  function formatNumber(n) {
+    if (Number.isNaN(n)) return "NaN";
+    if (n === Infinity) return "inf";
+    if (n === -Infinity) return "-inf";
+    if (Object.is(n, -0)) return "-0";
+    if (Math.abs(n) >= 1e21 || (n !== 0 && Math.abs(n) < 1e-6)) {
+      return n.toLocaleString("en-US", { useGrouping: false, maximumFractionDigits: 20 });
+    }
+    return String(n);
+  }
+
+  function pad(n, width) {
+    return String(n).padStart(width, "0");
+  }
+
+  const MONTHS = ["January", "February", "March", "April", "May", "June", "July", "August",
+    "September", "October", "November", "December"];
+  const DAYS = ["Sunday", "Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday"];
+
+  // Format a date with the common strftime specifiers supported by chrono
+  function formatDate(d, pattern) {
+    return pattern.replace(/%([a-zA-Z%])/g, (match, spec) => {
+      switch (spec) {
+        case "Y": return String(d.getFullYear());
+        case "y": return pad(d.getFullYear() % 100, 2);
+        case "m": return pad(d.getMonth() + 1, 2);
+        case "d": return pad(d.getDate(), 2);
+        case "e": return String(d.getDate()).padStart(2, " ");
+        case "H": return pad(d.getHours(), 2);
+        case "I": return pad(d.getHours() % 12 || 12, 2);
+        case "M": return pad(d.getMinutes(), 2);
+        case "S": return pad(d.getSeconds(), 2);
+        case "p": return d.getHours() < 12 ? "AM" : "PM";
+        case "B": return MONTHS[d.getMonth()];
+        case "b": return MONTHS[d.getMonth()].slice(0, 3);
+        case "A": return DAYS[d.getDay()];
+        case "a": return DAYS[d.getDay()].slice(0, 3);
+        case "F": return formatDate(d, "%Y-%m-%d");
+        case "T": return formatDate(d, "%H:%M:%S");
+        case "%": return "%";
+        default: return match;
+      }
+    });
+  }
+
+  // Read a date written in a format of strftime specifiers (the common ones chrono accepts), or
+  // return null if the text does not match it. Whitespace in the format matches any amount of
+  // whitespace, and a time of day is only used when both its hour and minute are given.
+  function parseDate(text, pattern) {
+    const parts = {};
+    let pos = 0;
+    const number = (max, signed) => {
+      while (/\s/.test(text[pos] || "")) pos++;
+      const match = new RegExp(`^${signed ? "[+-]?" : ""}\\d{1,${max}}`).exec(text.slice(pos));
+      if (!match) return null;
+      pos += match[0].length;
+      return Number(match[0]);
+    };
+    const month = (long) => {
+      const rest = text.slice(pos).toLowerCase();
+      const index = MONTHS.findIndex((name) => rest.startsWith(name.slice(0, 3).toLowerCase()));
+      if (index < 0) return null;
+      pos += 3;
+      const tail = MONTHS[index].slice(3).toLowerCase();
+      if (long && tail && text.slice(pos).toLowerCase().startsWith(tail)) pos += tail.length;
+      return index + 1;
+    };
+    const RANGES = { m: [1, 12], d: [1, 31], e: [1, 31], H: [0, 23], I: [1, 12], M: [0, 59], S: [0, 59], y: [0, 99] };
+    const read = (format) => {
+      for (let i = 0; i < format.length; i++) {
+        const c = format[i];
+        if (/\s/.test(c)) {
+          while (/\s/.test(text[pos] || "")) pos++;
+          continue;
+        }
+        if (c !== "%") {
+          if (text[pos] !== c) return false;
+          pos++;
+          continue;
+        }
+        const spec = format[++i];
+        let value;
+        switch (spec) {
+          case "Y": value = number(4, true); break;
+          case "y": case "m": case "d": case "e": case "H": case "I": case "M": case "S":
+            value = number(2, false); break;
+          case "b": case "h": value = month(false); break;
+          case "B": value = month(true); break;
+          case "p": {
+            const half = text.slice(pos, pos + 2).toLowerCase();
+            if (half !== "am" && half !== "pm") return false;
+            pos += 2;
+            parts.pm = half === "pm";
+            continue;
+          }
+          case "F": if (!read("%Y-%m-%d")) return false; continue;
+          case "T": if (!read("%H:%M:%S")) return false; continue;
+          case "R": if (!read("%H:%M")) return false; continue;
+          case "%": if (text[pos] !== "%") return false; pos++; continue;
+          default: return false;
+        }
+        if (value === null) return false;
+        const range = RANGES[spec];
+        if (range && (value < range[0] || value > range[1])) return false;
+        parts[{ b: "m", h: "m", B: "m", e: "d" }[spec] || spec] = value;
+      }
+      return true;
+    };
+    if (!read(pattern) || pos !== text.length) return null;
+
+    const year = parts.Y !== undefined ? parts.Y : parts.y !== undefined ? parts.y + (parts.y < 70 ? 2000 : 1900) : undefined;
+    if (year === undefined || parts.m === undefined || parts.d === undefined) return null;
+    let hour = parts.H;
+    if (hour === undefined && parts.I !== undefined && parts.pm !== undefined) hour = (parts.I % 12) + (parts.pm ? 12 : 0);
+    const timed = hour !== undefined && parts.M !== undefined;
+    const [h, mi, s] = timed ? [hour, parts.M, parts.S || 0] : [0, 0, 0];
+    const date = new Date(year, parts.m - 1, parts.d, h, mi, s);
+    date.setFullYear(year); // Years 0-99 are not moved into the 1900s
+    if (date.getMonth() !== parts.m - 1 || date.getDate() !== parts.d) return null;
+    return date;
+  }
+
+  // Write a duration as hours, minutes, and seconds: 1:30:00, -0:00:05, 0:00:02.5
+  function formatDuration(seconds) {
+    const sign = seconds < 0 ? "-" : "";
+    const total = Math.abs(seconds);
+    const hours = Math.floor(total / 3600);
+    const minutes = Math.floor((total - hours * 3600) / 60);
+    const rest = total - hours * 3600 - minutes * 60;
+    const twoDigits = (n) => (n < 10 ? "0" : "") + formatNumber(n);
+    return `${sign}${formatNumber(hours)}:${twoDigits(minutes)}:${twoDigits(rest)}`;
+  }
+
+  // Convert a value to the text print shows
+  function show(value) {
+    if (value === null || value === undefined) return "nil";
+    if (typeof value === "number") return formatNumber(value);
+    if (typeof value === "string") return value;
+    if (typeof value === "boolean") return String(value);
+    if (value instanceof PgArray) {
+      const items = value.items.map(show).join(", ");
+      return value.dynamic ? `{${items}}` : `[${items}]`;
+    }
+    if (value instanceof Map) {
+      const pairs = [...value].map(([key, item]) => `${key}: ${show(item)}`);
+      return `{ ${pairs.join(", ")} }`;
+    }
+    if (value instanceof PgDate) return formatDate(value.date, "%Y-%m-%d %H:%M:%S");
+    if (value instanceof PgDuration) return formatDuration(value.seconds);
+    if (value instanceof PgFunction) return `function(${value.params.join(", ")}) { ... }`;
+    if (value instanceof PgTask) return "<task>";
+    return String(value);
+  }
+
+  // Write a number as Rust's f64 Debug output does (1.0, 2.5)
+  function debugNumber(n) {
+    return Number.isInteger(n) ? formatNumber(n) + ".0" : formatNumber(n);
+  }
+
+  // Describe a value in error messages (like the interpreter's debug output)
+  function describe(value) {
+    if (value === null || value === undefined) return "Nil";
+    if (typeof value === "number") return `Number(${debugNumber(value)})`;
+    if (typeof value === "string") return `String(${JSON.stringify(value)})`;
+    if (typeof value === "boolean") return `Boolean(${value})`;
+    if (value instanceof PgArray) {
+      const items = value.items.map(describe).join(", ");
+      return `${value.dynamic ? "DynamicArray" : "FixedArray"}([${items}])`;
+    }
+    if (value instanceof Map) return "Object";
+    if (value instanceof PgDate) return `Date(${show(value)})`;
+    if (value instanceof PgDuration) return `Duration(${debugNumber(value.seconds)})`;
+    if (value instanceof PgTask) return "Task(TaskHandle)";
+    return "Function";
+  }
+
+  function truthy(value) {
+    if (typeof value === "boolean") return value;
+    return value !== null && value !== undefined;
+  }
+
+  function equal(a, b) {
+    if (a === null || a === undefined) return b === null || b === undefined;
+    if (typeof a !== "object") return a === b;
+    if (a instanceof PgArray && b instanceof PgArray && a.dynamic === b.dynamic) {
+      return a.items.length === b.items.length && a.items.every((item, i) => equal(item, b.items[i]));
+    }
+    if (a instanceof PgDuration && b instanceof PgDuration) return a.seconds === b.seconds;
+    if (a instanceof PgTask) return a === b; // The same task
+    return false;
+  }
+
+  const OPERATION_NAMES = { "+": "addition", "-": "subtraction", "*": "multiplication", "/": "division" };
+
+  // Move a date by a number of seconds (date + duration), to the millisecond
+  function shiftDate(date, seconds) {
+    const shifted = new Date(date.date.getTime() + Math.trunc(seconds * 1000));
+    if (Number.isNaN(shifted.getTime())) throw fail("Date out of range");
+    return new PgDate(shifted);
+  }
+
+  // Apply an operator that involves a date or duration, or return undefined if it does not
+  // apply to these operands
+  function timeBinary(op, a, b) {
+    const isDuration = (value) => value instanceof PgDuration;
+    switch (op) {
+      case "+":
+        if (isDuration(a) && isDuration(b)) return new PgDuration(a.seconds + b.seconds);
+        if (a instanceof PgDate && isDuration(b)) return shiftDate(a, b.seconds);
+        if (isDuration(a) && b instanceof PgDate) return shiftDate(b, a.seconds);
+        return undefined;
+      case "-":
+        if (isDuration(a) && isDuration(b)) return new PgDuration(a.seconds - b.seconds);
+        if (a instanceof PgDate && isDuration(b)) return shiftDate(a, -b.seconds);
+        if (a instanceof PgDate && b instanceof PgDate) return new PgDuration((a.date - b.date) / 1000);
+        return undefined;
+      case "*":
+        if (isDuration(a) && typeof b === "number") return new PgDuration(a.seconds * b);
+        if (typeof a === "number" && isDuration(b)) return new PgDuration(a * b.seconds);
+        return undefined;
+      case "/":
+        if (!isDuration(a) || !(typeof b === "number" || isDuration(b))) return undefined;
+        if ((isDuration(b) ? b.seconds : b) === 0) throw fail("Division by zero");
+        return isDuration(b) ? a.seconds / b.seconds : new PgDuration(a.seconds / b);
+      default:
+        return isDuration(a) && isDuration(b) ? binary(op, a.seconds, b.seconds) : undefined;
+    }
+  }
+
+  // Apply a binary operator with the interpreter's type rules
+  function binary(op, a, b, line, column) {
+    const numbers = typeof a === "number" && typeof b === "number";
+    const invalid = () => {
+      const kind = OPERATION_NAMES[op] || "comparison";
+      return fail(`Invalid operands for ${kind}: ${describe(a)} ${op} ${describe(b)} at line ${line} column ${column}`);
+    };
+    if (!numbers && op !== "==" && op !== "!=") {
+      const result = timeBinary(op, a, b);
+      if (result !== undefined) return result;
+    }
+    switch (op) {
+      case "+":
+        if (numbers) return a + b;
+        if ((typeof a === "string" && ["string", "number", "boolean"].includes(typeof b)) ||
+            (typeof b === "string" && ["number", "boolean"].includes(typeof a))) {
+          return show(a) + show(b);
+        }
+        throw invalid();
+      case "-": if (numbers) return a - b; throw invalid();
+      case "*": if (numbers) return a * b; throw invalid();
+      case "/":
+        if (!numbers) throw invalid();
+        if (b === 0) throw fail("Division by zero");
+        return a / b;
+      case "==": return equal(a, b);
+      case "!=": return !equal(a, b);
+      case "<": if (numbers) return a < b; throw invalid();
+      case ">": if (numbers) return a > b; throw invalid();
+      case "<=": if (numbers) return a <= b; throw invalid();
+      case ">=": if (numbers) return a >= b; throw invalid();
+    }
+    throw fail(`Unknown operator ${op}`);
+  }
+
+  function negate(value) {
+    if (typeof value === "number") return -value;
+    if (value instanceof PgDuration) return new PgDuration(-value.seconds);
+    throw fail("Invalid operand for unary minus");
+  }
+
+  // Convert a number to an array index the way Rust's `as usize` does
+  function toIndex(n) {
+    return Number.isNaN(n) ? 0 : Math.max(0, Math.trunc(n));
+  }
+
+  function index(array, i) {
+    if (typeof i !== "number") throw fail("Array index must be a number");
+    if (!(array instanceof PgArray)) throw fail("Can only index arrays");
+    const n = toIndex(i);
+    if (n >= array.items.length) {
+      throw fail(`Array index ${n} out of bounds (array length: ${array.items.length})`);
+    }
+    return array.items[n];
+  }
+
+  function get(name) {
+    if (!env.has(name)) throw fail(`Undefined variable '${name}'`);
+    return env.get(name);
+  }
+
+  function set(name, value) {
+    env.set(name, value);
+    return value;
+  }
+
+  // let a, b = value;: assign the elements of an array with one element per name
+  function unpack(value, names) {
+    if (!(value instanceof PgArray)) {
+      throw fail(`Cannot unpack a ${typeName(value)} into ${names.length} variables`);
+    }
+    if (value.items.length !== names.length) {
+      throw fail(`Cannot unpack ${value.items.length} values into ${names.length} variables`);
+    }
+    names.forEach((name, i) => env.set(name, value.items[i]));
+  }
+
+  // Resolve a replaceChar pattern: a variable's value, or the name itself as a literal
+  function transformValue(name) {
+    if (!env.has(name)) return name;
+    const value = env.get(name);
+    if (["string", "number", "boolean"].includes(typeof value)) return show(value);
+    throw fail(`Variable '${name}' is not a valid replacement value`);
+  }
+
+  // Get the name of a value's type, as the interpreter shows it (e.g. "dynamic array")
+  function typeName(value) {
+    if (value === null || value === undefined) return "nil";
+    if (value instanceof PgArray) return value.dynamic ? "dynamic array" : "fixed array";
+    if (value instanceof PgDate) return "date";
+    if (value instanceof PgDuration) return "duration";
+    if (value instanceof PgFunction) return "function";
+    if (value instanceof PgTask) return "task";
+    if (value instanceof Map) return "object";
+    return typeof value;
+  }
+
+  // The built-in methods, like src/methods.rs: the receiver types each is called on, its
+  // number of arguments, and what it does (replaceChar takes a transform, handled apart)
+  const DYNAMIC = ["dynamic array"];
+  const ARRAYS = ["fixed array", "dynamic array"];
+  const UPDATING = ["push", "clear", "insert", "set"]; // Return an updated copy: not when frozen
+  const METHODS = {
+    push: { on: DYNAMIC, arity: 1, call: (o, [value]) => new PgArray([...o.items, value], true) },
+    pop: {
+      on: DYNAMIC,
+      arity: 0,
+      call: (o) => {
+        if (o.items.length === 0) throw fail("Cannot pop from empty array");
+        return o.items[o.items.length - 1];
+      },
+    },
+    clear: { on: DYNAMIC, arity: 0, call: () => new PgArray([], true) },
+    insert: {
+      on: DYNAMIC,
+      arity: 2,
+      call: (o, [at, value]) => {
+        if (typeof at !== "number") throw fail("insert() requires (index, value) arguments");
+        const position = toIndex(at);
+        if (position > o.items.length) {
+          throw fail(`Insert index ${position} out of bounds (array length: ${o.items.length})`);
+        }
+        const items = [...o.items];
+        items.splice(position, 0, value);
+        return new PgArray(items, true);
+      },
+    },
+    remove: {
+      on: DYNAMIC,
+      arity: 1,
+      call: (o, [at]) => {
+        if (typeof at !== "number") throw fail("remove() requires a numeric index argument");
+        const position = toIndex(at);
+        if (position >= o.items.length) {
+          throw fail(`Remove index ${position} out of bounds (array length: ${o.items.length})`);
+        }
+        return o.items[position];
+      },
+    },
+    length: { on: ARRAYS, arity: 0, call: (o) => o.items.length },
+    reverse: { on: ARRAYS, arity: 0, call: (o) => new PgArray([...o.items].reverse(), o.dynamic) },
+    toUpper: { on: ["string"], arity: 0, call: (o) => o.toUpperCase() },
+    toLower: { on: ["string"], arity: 0, call: (o) => o.toLowerCase() },
+    trim: { on: ["string"], arity: 0, call: (o) => o.trim() },
+    format: {
+      on: ["date"],
+      arity: 1,
+      call: (o, [pattern]) => {
+        if (typeof pattern !== "string") throw fail("Date.format() requires a string argument");
+        return formatDate(o.date, pattern);
+      },
+    },
+    getYear: { on: ["date"], arity: 0, call: (o) => o.date.getFullYear() },
+    getMonth: { on: ["date"], arity: 0, call: (o) => o.date.getMonth() + 1 },
+    getDay: { on: ["date"], arity: 0, call: (o) => o.date.getDate() },
+    toHours: { on: ["duration"], arity: 0, call: (o) => o.seconds / 3600 },
+    toMinutes: { on: ["duration"], arity: 0, call: (o) => o.seconds / 60 },
+    toSeconds: { on: ["duration"], arity: 0, call: (o) => o.seconds },
+    keys: { on: ["object"], arity: 0, call: (o) => new PgArray([...o.keys()], true) },
+    set: {
+      on: ["object"],
+      arity: 2,
+      call: (o, [key, value]) => {
+        if (typeof key !== "string") {
+          throw fail("set() requires (key, value) arguments where key is a string");
+        }
+        const copy = new Map(o);
+        copy.set(key, value);
+        return copy;
+      },
+    },
+    get: {
+      on: ["object"],
+      arity: 1,
+      call: (o, [key]) => {
+        if (typeof key !== "string") throw fail("get() requires a string key argument");
+        return o.has(key) ? o.get(key) : null;
+      },
+    },
+    has: {
+      on: ["object"],
+      arity: 1,
+      call: (o, [key]) => {
+        if (typeof key !== "string") throw fail("has() requires a string key argument");
+        return o.has(key);
+      },
+    },
+  };
+
+  // Call a method on a value. `args` holds thunks for the arguments, evaluated once the method
+  // is known to exist for the receiver and to take that many.
+  function method(object, name, args) {
+    // Methods the program defined on this type come before the built-in ones
+    const extension = env.get(`${extensionType(object)}.${name}`);
+    if (extension instanceof PgFunction) return callExtension(object, name, extension, args);
+    if (name === "replaceChar") {
+      if (typeof object !== "string") throw fail("ReplaceChar method can only be called on strings");
+      throw fail("ReplaceChar method requires a transform argument");
+    }
+    if (!Object.hasOwn(METHODS, name)) throw fail(`Unsupported method: ${name}`);
+    const { on, arity, call } = METHODS[name];
+    if (!on.includes(typeName(object))) {
+      const types = on === ARRAYS ? ["array"] : on; // Both kinds of array: an array method
+      throw fail(`${name} method can only be called on ${types.map((t) => `${t}s`).join(" or ")}`);
+    }
+    if (UPDATING.includes(name) && object.frozen) {
+      throw fail(`Cannot call ${name}() on a frozen ${typeName(object)}: it cannot be changed`);
+    }
+    if (args.length !== arity) {
+      throw fail(`Method '${name}' expects ${arity} arguments, got ${args.length}`);
+    }
+    return call(object, args.map((arg) => arg()));
+  }
+
+  // The name a program defines methods on a value's type under (function String.shout(self))
+  function extensionType(value) {
+    if (value instanceof PgArray) return "Array";
+    if (value instanceof PgDate) return "Date";
+    if (value instanceof PgDuration) return "Duration";
+    if (value instanceof Map) return "Object";
+    switch (typeof value) {
+      case "string": return "String";
+      case "number": return "Number";
+      case "boolean": return "Boolean";
+      default: return null;
+    }
+  }
+
+  // Call a method defined in Pidgin: the receiver is its first parameter, and the arguments
+  // are evaluated before any is bound, like those of a built-in method
+  function callExtension(object, name, fn, args) {
+    if (args.length + 1 !== fn.params.length) {
+      throw fail(`Method '${name}' expects ${fn.params.length - 1} arguments, got ${args.length}`);
+    }
+    return callWith(fn, [object, ...args.map((arg) => arg())]);
+  }
+
+  // Call a function value with its arguments already evaluated
+  function callWith(fn, values) {
+    const saved = new Map(env); // Functions cannot change the caller's globals
+    try {
+      fn.params.forEach((param, i) => env.set(param, values[i]));
+      const result = fn.body();
+      return result === undefined ? null : result;
+    } finally {
+      env = saved;
+    }
+  }
+
+  // Traverse a value for a for-in loop: elements, characters, [key, value] entries sorted by
+  // key, or the items an iterator object (one with a next function) produces
+  function* iterate(value) {
+    if (value instanceof PgArray || typeof value === "string") {
+      yield* value instanceof PgArray ? value.items : value;
+      return;
+    }
+    if (!(value instanceof Map)) throw fail(`Cannot iterate over a ${typeName(value)}`);
+    const next = value.get("next");
+    if (!(next instanceof PgFunction)) {
+      for (const key of [...value.keys()].sort()) yield new PgArray([key, value.get(key)], false);
+      return;
+    }
+    if (next.params.length !== 1) {
+      throw fail(`An iterator's next function must take 1 argument (its state), not ${next.params.length}`);
+    }
+    let state = value.has("state") ? value.get("state") : null;
+    for (;;) {
+      const step = callWith(next, [state]);
+      if (!(step instanceof Map)) {
+        throw fail(`An iterator's next function must return an object, not a ${typeName(step)}`);
+      }
+      if (!step.has("done")) throw fail("An iterator's next function must return an object with a 'done' field");
+      if (truthy(step.get("done"))) return;
+      state = step.has("state") ? step.get("state") : null;
+      yield step.has("value") ? step.get("value") : null;
+    }
+  }
+
+  // Match expressions run the tests src/pattern.rs compiles their patterns to; a part of the
+  // matched value is reached by a path of element indexes and field names
+  function part(value, path) {
+    return path.reduce((value, step) => (typeof step === "number" ? value.items[step] : value.get(step)), value);
+  }
+
+  function isArrayOf(value, length) {
+    return value instanceof PgArray && value.items.length === length;
+  }
+
+  function hasFields(value, keys) {
+    return value instanceof Map && keys.every((key) => value.has(key));
+  }
+
+  // A case whose pattern matched: its names hold their parts of the value while its guard and
+  // result run, then get back what they held before. Gives the result, or noCase when the
+  // guard fails.
+  const noCase = Symbol("noCase");
+
+  function matchCase(bindings, guard, body) {
+    const shadowed = bindings.map(([name]) => [name, env.has(name), env.get(name)]);
+    bindings.forEach(([name, value]) => env.set(name, value));
+    try {
+      return guard() ? body() : noCase;
+    } finally {
+      shadowed.reverse().forEach(([name, had, value]) => (had ? env.set(name, value) : env.delete(name)));
+    }
+  }
+
+  function noMatch(value) {
+    throw fail(`No case matched the value ${show(value)}`);
+  }
+
+  // string.replaceChar(from -> to): each side is a variable's value or the name itself
+  function replaceChar(object, from, to) {
+    if (typeof object !== "string") throw fail("ReplaceChar method can only be called on strings");
+    return object.split(transformValue(from)).join(transformValue(to));
+  }
+
+  // Read one line from standard input (Node) or a prompt dialog (browsers), or return null at
+  // the end of input (or when the dialog is cancelled)
+  function readInput(promptText) {
+    if (!isNode) {
+      return typeof prompt === "function" ? prompt(promptText) : null;
+    }
+    write(promptText, "out");
+    const fs = require("fs");
+    const bytes = [];
+    const buffer = Buffer.alloc(1);
+    let read;
+    while ((read = fs.readSync(0, buffer, 0, 1, null)) === 1 && buffer[0] !== 10) {
+      bytes.push(buffer[0]);
+    }
+    if (read !== 1 && bytes.length === 0) return null;
+    return Buffer.from(bytes).toString("utf8").replace(/\r$/, "");
+  }
+
+  function readLine(promptText) {
+    const answer = readInput(promptText);
+    return answer === null ? "" : answer;
+  }
+
+  // Ask a yes/no question until it is answered; the end of input counts as "no"
+  function confirmAnswer(args) {
+    if (args.length !== 1) throw fail(`confirm() expects 1 argument, got ${args.length}`);
+    const question = `${show(args[0]())} (y/n) `;
+    for (;;) {
+      const answer = readInput(question);
+      if (answer === null) return false;
+      const word = answer.trim().toLowerCase();
+      if (word === "y" || word === "yes") return true;
+      if (word === "n" || word === "no") return false;
+    }
+  }
+
+  // parallelMap(array, function): JavaScript has one thread, so the calls run one after another
+  // (the interpreter spreads them over threads; the results and output are the same)
+  function parallelMap(args) {
+    if (args.length !== 2) throw fail(`parallelMap() expects 2 arguments, got ${args.length}`);
+    const items = args[0]();
+    if (!(items instanceof PgArray)) {
+      throw fail(`parallelMap() needs an array as its first argument, not a ${typeName(items)}`);
+    }
+    const fn = args[1]();
+    if (!(fn instanceof PgFunction)) {
+      throw fail(`parallelMap() needs a function as its second argument, not a ${typeName(fn)}`);
+    }
+    if (fn.params.length !== 1) {
+      throw fail(`parallelMap() function must take 1 argument (an item), not ${fn.params.length}`);
+    }
+    return new PgArray(items.items.map((item) => callWith(fn, [item])), items.dynamic);
+  }
+
+  const tasks = []; // Tasks spawned and not yet waited for
+
+  // spawn(function, arguments...): run the call now and keep its result for wait()
+  function spawn(args) {
+    if (args.length === 0) throw fail("spawn() expects a function and its arguments");
+    const fn = args[0]();
+    if (!(fn instanceof PgFunction)) {
+      throw fail(`spawn() needs a function as its first argument, not a ${typeName(fn)}`);
+    }
+    if (args.length - 1 !== fn.params.length) {
+      throw fail(`spawn() function expects ${fn.params.length} arguments, got ${args.length - 1}`);
+    }
+    const values = args.slice(1).map((arg) => arg());
+    let task;
+    try {
+      task = new PgTask(callWith(fn, values), null);
+    } catch (error) {
+      if (!(error instanceof PgError)) throw error;
+      task = new PgTask(null, error);
+    }
+    tasks.push(task);
+    return task;
+  }
+
+  // wait(task): the spawned call's return value; its error, if it failed, is raised here
+  function wait(task) {
+    if (!(task instanceof PgTask)) throw fail(`wait() needs a task (from spawn), not a ${typeName(task)}`);
+    const pending = tasks.indexOf(task);
+    if (pending >= 0) tasks.splice(pending, 1);
+    if (task.error !== null) throw task.error;
+    return task.value;
+  }
+
+  const timers = new Map(); // Pending timers: id -> the platform's timer handle
+  let nextTimer = 1;
+
+  // setTimeout(function, delay) and setInterval(function, delay), on the platform's own timers:
+  // callbacks run once the program has finished, and get the run count if they take an argument
+  function setTimer(builtin, args, repeat) {
+    if (args.length !== 2) throw fail(`${builtin}() expects 2 arguments, got ${args.length}`);
+    const fn = args[0]();
+    if (!(fn instanceof PgFunction)) {
+      throw fail(`${builtin}() needs a function as its first argument, not a ${typeName(fn)}`);
+    }
+    if (fn.params.length > 1) {
+      throw fail(`${builtin}() function must take no arguments or 1 (how many times it has run), not ${fn.params.length}`);
+    }
+    const delayValue = args[1]();
+    let delay;
+    if (typeof delayValue === "number") {
+      delay = delayValue;
+    } else if (delayValue instanceof PgDuration) {
+      delay = delayValue.seconds * 1000;
+    } else {
+      throw fail(`${builtin}() delay must be a number of milliseconds or a duration, not a ${typeName(delayValue)}`);
+    }
+    delay = delay > 0 ? delay : 0;
+
+    const id = nextTimer++;
+    let runs = 0;
+    const fire = () => {
+      runs += 1;
+      if (repeat) {
+        timers.set(id, globalThis.setTimeout(fire, delay));
+      } else {
+        timers.delete(id);
+      }
+      guard(() => {
+        callWith(fn, fn.params.map(() => runs));
+        waitForTasks();
+      });
+    };
+    timers.set(id, globalThis.setTimeout(fire, delay));
+    return id;
+  }
+
+  function clearTimer(args) {
+    if (args.length !== 1) throw fail(`clearTimer() expects 1 argument, got ${args.length}`);
+    const id = args[0]();
+    if (typeof id !== "number") {
+      throw fail(`clearTimer() needs a timer id (from setTimeout or setInterval), not a ${typeName(id)}`);
+    }
+    if (timers.has(id)) {
+      globalThis.clearTimeout(timers.get(id));
+      timers.delete(id);
+    }
+    return null;
+  }
+
+  // Read a line, or give the default (or "") when it is empty
+  function promptAnswer(args) {
+    if (args.length < 1 || args.length > 2) {
+      throw fail(`prompt() expects 1 or 2 arguments, got ${args.length}`);
+    }
+    const message = show(args[0]());
+    const fallback = args.length > 1 ? show(args[1]()) : "";
+    const answer = readInput(message);
+    return answer === null || answer === "" ? fallback : answer;
+  }
+
+  // Make a frozen copy of an array or object, with everything inside it frozen too
+  function freeze(value) {
+    let copy;
+    if (value instanceof PgArray) {
+      copy = new PgArray(value.items.map(freeze), value.dynamic);
+    } else if (value instanceof Map) {
+      copy = new Map([...value].map(([key, field]) => [key, freeze(field)]));
+    } else {
+      return value;
+    }
+    copy.frozen = true;
+    return copy;
+  }
+
+  // Copy a value and everything nested inside it
+  function deepCopy(value) {
+    if (value instanceof PgArray) return new PgArray(value.items.map(deepCopy), value.dynamic);
+    if (value instanceof Map) {
+      return new Map([...value].map(([key, field]) => [key, deepCopy(field)]));
+    }
+    if (value instanceof PgDate) return new PgDate(new Date(value.date.getTime()));
+    return value;
+  }
+
+  function makeDate(args) {
+    if (args.length === 0) return new PgDate(new Date());
+    if (args.length === 1) {
+      const text = args[0]();
+      if (typeof text !== "string") throw fail("Date() argument must be a string");
+      const match = /^(\d{4})-(\d{2})-(\d{2})(?: (\d{2}):(\d{2}):(\d{2}))?$/.exec(text);
+      const unparsed = `Unable to parse date: '${text}' (expected YYYY-MM-DD or YYYY-MM-DD HH:MM:SS; give a format for others, e.g. Date(text, "%d/%m/%Y"))`;
+      if (!match) throw fail(unparsed);
+      const [y, mo, d, h = 0, mi = 0, s = 0] = match.slice(1).filter((part) => part !== undefined).map(Number);
+      const date = new Date(y, mo - 1, d, h, mi, s);
+      if (date.getMonth() !== mo - 1 || date.getDate() !== d || date.getHours() !== h ||
+          date.getMinutes() !== mi || date.getSeconds() !== s) {
+        throw fail(unparsed);
+      }
+      return new PgDate(date);
+    }
+    if (args.length === 2) {
+      const [text, pattern] = args.map((arg) => arg());
+      if (typeof text !== "string" || typeof pattern !== "string") {
+        throw fail("Date() text and format must be strings");
+      }
+      const date = parseDate(text, pattern);
+      if (date === null) throw fail(`Unable to parse date: '${text}' with format '${pattern}'`);
+      return new PgDate(date);
+    }
+    if (args.length === 3) {
+      const [y, m, d] = args.map((arg) => arg());
+      if (![y, m, d].every((part) => typeof part === "number")) {
+        throw fail("Date() year, month, and day must be numbers");
+      }
+      const date = new Date(Math.trunc(y), Math.trunc(m) - 1, Math.trunc(d));
+      if (date.getFullYear() !== Math.trunc(y) || date.getMonth() !== Math.trunc(m) - 1 || date.getDate() !== Math.trunc(d)) {
+        throw fail("Invalid date values");
+      }
+      return new PgDate(date);
+    }
+    throw fail("Date() takes 0, 1, 2, or 3 arguments");
+  }
+
+  function makeDuration(args) {
+    if (args.length !== 3) throw fail("Duration() takes 3 arguments (hours, minutes, seconds)");
+    const [hours, minutes, seconds] = args.map((arg) => arg());
+    if (![hours, minutes, seconds].every((part) => typeof part === "number")) {
+      throw fail("Duration() hours, minutes, and seconds must be numbers");
+    }
+    return new PgDuration(hours * 3600 + minutes * 60 + seconds);
+  }
+
+  function makeObject(args) {
+    if (args.length % 2 !== 0) {
+      throw fail("Object() requires an even number of arguments (key-value pairs)");
+    }
+    const object = new Map();
+    for (let i = 0; i < args.length; i += 2) {
+      const key = args[i]();
+      if (!["string", "number", "boolean"].includes(typeof key)) {
+        throw fail("Object() keys must be strings, numbers, or booleans");
+      }
+      object.set(show(key), args[i + 1]());
+    }
+    return object;
+  }
+
+  // Call a builtin or user-defined function; `args` are thunks evaluated in order, each
+  // after the previous parameter has been bound (as the interpreter does)
+  function call(name, args) {
+    // A function the program defines takes precedence over a builtin of the same name
+    const fn = env.get(name);
+    if (!(fn instanceof PgFunction)) {
+      switch (name) {
+        case "readLine": return readLine(args.length > 0 ? show(args[0]()) : "");
+        case "confirm": return confirmAnswer(args);
+        case "prompt": return promptAnswer(args);
+        case "parallelMap": return parallelMap(args);
+        case "spawn": return spawn(args);
+        case "setTimeout": return setTimer("setTimeout", args, false);
+        case "setInterval": return setTimer("setInterval", args, true);
+        case "clearTimer": return clearTimer(args);
+        case "wait":
+          if (args.length !== 1) throw fail(`wait() expects 1 argument, got ${args.length}`);
+          return wait(args[0]());
+        case "Date": return makeDate(args);
+        case "Duration": return makeDuration(args);
+        case "Object": return makeObject(args);
+        case "deepCopy":
+          if (args.length !== 1) throw fail(`deepCopy() expects 1 argument, got ${args.length}`);
+          return deepCopy(args[0]());
+        case "freeze":
+          if (args.length !== 1) throw fail(`freeze() expects 1 argument, got ${args.length}`);
+          return freeze(args[0]());
+        case "args":
+          if (args.length !== 0) throw fail(`args() expects 0 arguments, got ${args.length}`);
+          return new PgArray(isNode ? process.argv.slice(2) : [], false);
+      }
+    }
+
+    if (!(fn instanceof PgFunction)) throw fail(`Undefined function '${name}'`);
+    if (args.length !== fn.params.length) {
+      throw fail(`Function '${name}' expects ${fn.params.length} arguments, got ${args.length}`);
+    }
+
+    const saved = new Map(env); // Functions cannot change the caller's globals
+    try {
+      fn.params.forEach((param, i) => env.set(param, args[i]()));
+      const result = fn.body();
+      return result === undefined ? null : result;
+    } finally {
+      env = saved;
+    }
+  }
+
+  // Read a format specifier (the part of a placeholder after ':'): [[fill]align][width][.precision]
+  function parseSpec(text) {
+    const match = /^(?:(.)?([<>^]))?(0?)([0-9]*)(?:\.([0-9]+))?$/su.exec(text);
+    if (match === null) throw fail(`Invalid format specifier ':${text}' in format string`);
+    return {
+      fill: match[1] === undefined ? " " : match[1],
+      align: match[2] === undefined ? null : match[2],
+      zero: match[3] === "0",
+      width: match[4] === "" ? 0 : Number(match[4]),
+      precision: match[5] === undefined ? null : Number(match[5]),
+    };
+  }
+
+  // Round to a number of decimals like Rust's {:.N}, where exact ties go to the even digit
+  function toFixed(n, decimals) {
+    const scaled = n * 10 ** decimals;
+    const whole = Math.trunc(scaled);
+    // An exact tie is a multiple of 2^-(decimals + 1); most decimal inputs are slightly off one
+    const tie = Math.abs(scaled - whole) === 0.5 && Number.isInteger(n * 2 ** (decimals + 1));
+    if (tie && whole % 2 === 0) {
+      return (whole / 10 ** decimals).toFixed(decimals);
+    }
+    return n.toFixed(decimals);
+  }
+
+  // Show a value the way a format specifier says (numbers align right, the rest left)
+  function applySpec(value, spec) {
+    if (spec === null) return show(value);
+    let text = show(value);
+    if (spec.precision !== null && typeof value === "number") {
+      if (Number.isFinite(value)) text = toFixed(value, Math.min(spec.precision, 100));
+    } else if (spec.precision !== null && (value instanceof PgArray || value instanceof Map)) {
+      throw fail(
+        `Cannot use a precision ('.${spec.precision}') with a value of type ${typeName(value)}`
+      );
+    } else if (spec.precision !== null) {
+      text = Array.from(text).slice(0, spec.precision).join("");
+    }
+    const padding = spec.width - Array.from(text).length;
+    if (padding <= 0) return text;
+    if (spec.zero && typeof value === "number") {
+      // The zeros go between the sign and the digits
+      const sign = text.startsWith("-") ? "-" : "";
+      return sign + "0".repeat(padding) + text.slice(sign.length);
+    }
+    const align = spec.align || (typeof value === "number" ? ">" : "<");
+    const before = align === "<" ? 0 : align === ">" ? padding : Math.floor(padding / 2);
+    return spec.fill.repeat(before) + text + spec.fill.repeat(padding - before);
+  }
+
+  // Fill in the placeholders of a format string, with the same rules as src/format.rs
+  function formatText(format, values) {
+    const used = values.map(() => false);
+    const lenient = values.length === 0; // Whether braces that are not placeholders are text
+    let text = "";
+    let next = 0;
+    let i = 0;
+    // How many arguments were given, for messages (which count arguments from 1)
+    const given = values.length === 1 ? "1 argument was given" : `${values.length} arguments were given`;
+    const argument = (index, placeholder) => {
+      if (index >= values.length) {
+        throw fail(`Placeholder ${placeholder} has no argument: it takes argument ${index + 1}, but only ${given}`);
+      }
+      used[index] = true;
+      return values[index];
+    };
+    // Show the value of the placeholder starting at i and ending at end
+    const fill = (end) => {
+      if (end < 0) throw fail("Unclosed '{' in format string (write '{{' for a literal brace)");
+      const body = format.slice(i + 1, end);
+      const colon = body.indexOf(":");
+      const inner = (colon < 0 ? body : body.slice(0, colon)).trim();
+      const spec = colon < 0 ? null : parseSpec(body.slice(colon + 1));
+      if (inner === "") {
+        return applySpec(argument(next++, "{}"), spec);
+      } else if (/^[0-9]+$/.test(inner)) {
+        return applySpec(argument(Number(inner), `{${inner}}`), spec);
+      } else if (/^[\p{L}_][\p{L}\p{N}_]*$/u.test(inner)) {
+        if (!env.has(inner)) throw fail(`Undefined variable '${inner}' in format string`);
+        return applySpec(env.get(inner), spec);
+      }
+      throw fail(
+        `Invalid placeholder '{${inner}}' in format string (write '{{' for a literal brace)`
+      );
+    };
+    while (i < format.length) {
+      const c = format[i];
+      if (c === "{" && format[i + 1] === "{") {
+        text += "{";
+        i += 2;
+      } else if (c === "{") {
+        const end = format.indexOf("}", i + 1);
+        try {
+          text += fill(end);
+          i = end + 1;
+        } catch (error) {
+          // Without arguments, a brace that does not start a placeholder is just text
+          if (!lenient || !(error instanceof PgError)) throw error;
+          text += "{";
+          i++;
+        }
+      } else if (c === "}") {
+        // A lone '}' has nothing to close, so it is kept as text
+        text += "}";
+        i += format[i + 1] === "}" ? 2 : 1;
+      } else {
+        text += c;
+        i++;
+      }
+    }
+    const unused = used.indexOf(false);
+    if (unused >= 0) {
+      throw fail(`Argument ${unused + 1} is not used in the format string (${given})`);
+    }
+    return text;
+  }
+
+  // print/printLn/printErr: a single value, or a format string when it is a string literal or
+  // arguments follow it. `args` are thunks so they are only evaluated once the format string is
+  // known to be valid. Like the interpreter, printErr ends its line on standard error.
+  function print(format, args, stream, newline, formatted) {
+    let text;
+    if (!formatted) {
+      text = show(format);
+    } else {
+      if (typeof format !== "string") throw fail("Format string must be a string");
+      text = formatText(format, args.map((arg) => arg()));
+    }
+    write(text, stream);
+    if (newline) write("\n", stream);
+  }
+
+  // Run a module in a fresh set of globals and copy the requested names into ours
+  function importNames(file, names) {
+    const saved = env;
+    env = new Map();
+    let exported;
+    try {
+      modules.get(file)();
+      exported = env;
+    } finally {
+      env = saved;
+    }
+    for (const name of names) {
+      if (!exported.has(name)) throw fail(`Name '${name}' not found in module '${file}'`);
+      if (!/^\p{Lu}/u.test(name)) {
+        throw fail(`Cannot import '${name}' - only names starting with uppercase letters can be imported`);
+      }
+      env.set(name, exported.get(name));
+    }
+    // Methods the module defined on built-in types come along with any import from it
+    for (const [name, value] of exported) {
+      if (name.includes(".")) env.set(name, value);
+    }
+  }
+
+  function waitForTasks() {
+    while (tasks.length > 0) wait(tasks[0]);
+  }
+
+  // Run part of the program (its main body or a timer's callback), reporting runtime errors like
+  // the interpreter does; an error also cancels every timer, since it ends the program
+  function guard(body) {
+    try {
+      body();
+    } catch (error) {
+      if (!(error instanceof PgError)) throw error;
+      for (const handle of timers.values()) globalThis.clearTimeout(handle);
+      timers.clear();
+      flush();
+      write(`Error: ${error.message}\n`, "err");
+      if (isNode) process.exitCode = 1;
+    }
+    flush();
+  }
+
+  // Run the compiled program; the timers it set run afterwards
+  function run(program) {
+    guard(() => {
+      program();
+      waitForTasks(); // The program is not done until its tasks are
+    });
+  }
+
+  return {
+    PgArray, PgFunction, fail, truthy, binary, negate, index, get, set, unpack, method, replaceChar,
+    call,
+    iterate, part, isArrayOf, hasFields, matchCase, noCase, noMatch,
+    print,
+    modules, importNames, run,
+  };
+})();
@@ -0,0 +1,128 @@
+// Statement coverage of a program run (pidgin --coverage <file.pg>)
+//
+// A statement hook counts how many times each line's statements run, in the program and in
+// every module it imports. Lines where statements start are the lines that can be covered;
+// after the run they are summarized per file, and can be written in the lcov format read by
+// coverage tools and CI services.
+use crate::ast::{Program, Stmt};
+use crate::interpreter::{Interpreter, StatementHook};
+use crate::visitor::{self, Visitor};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+type LineCounts = BTreeMap<usize, usize>; // Line number -> times its statements ran
+
+// Define the Coverage struct, the counts for every file of a run (cheap to clone and share)
+#[derive(Debug, Clone, Default)]
+pub struct Coverage {
+    files: Arc<Mutex<BTreeMap<String, LineCounts>>>, // File path -> its line counts
+}
+
+// Implement methods for Coverage
+impl Coverage {
+    // Create a hook that records the statements run in one file
+    pub fn hook(&self, file: &str, program: &Program) -> Box<dyn StatementHook> {
+        // Every line where a statement starts is coverable, even if it never runs
+        let mut lines = StatementLines::default();
+        visitor::walk_program(&mut lines, program);
+        let mut files = self.files.lock().unwrap();
+        let counts = files.entry(file.to_string()).or_default();
+        for line in lines.0 {
+            counts.entry(line).or_insert(0);
+        }
+
+        Box::new(CoverageHook {
+            coverage: self.clone(),
+            file: file.to_string(),
+        })
+    }
+
+    // Summarize the coverage of each file and in total
+    pub fn summary(&self) -> String {
+        let files = self.files.lock().unwrap();
+        let width = files.keys().map(String::len).max().unwrap_or(0).max(6);
+        let mut out = String::from("Coverage:\n");
+        let (mut total_hit, mut total_found) = (0, 0);
+        for (file, counts) in files.iter() {
+            let (hit, found) = hit_and_found(counts);
+            out.push_str(&format!("  {file:<width$}  {}\n", format_ratio(hit, found)));
+            total_hit += hit;
+            total_found += found;
+        }
+        out.push_str(&format!(
+            "  {:<width$}  {}\n",
+            "total:",
+            format_ratio(total_hit, total_found)
+        ));
+        out
+    }
+
+    // Write the coverage in the lcov tracefile format
+    pub fn lcov(&self) -> String {
+        let mut out = String::new();
+        for (file, counts) in self.files.lock().unwrap().iter() {
+            out.push_str(&format!("TN:\nSF:{file}\n"));
+            for (line, count) in counts {
+                out.push_str(&format!("DA:{line},{count}\n"));
+            }
+            let (hit, found) = hit_and_found(counts);
+            out.push_str(&format!("LF:{found}\nLH:{hit}\nend_of_record\n"));
+        }
+        out
+    }
+}
+
+// Count the lines of a file that ran, and the lines that could have
+fn hit_and_found(counts: &LineCounts) -> (usize, usize) {
+    let hit = counts.values().filter(|count| **count > 0).count();
+    (hit, counts.len())
+}
+
+// Format covered lines out of coverable lines, with the percentage
+fn format_ratio(hit: usize, found: usize) -> String {
+    let percent = if found == 0 {
+        100.0
+    } else {
+        hit as f64 * 100.0 / found as f64
+    };
+    format!("{hit:>4}/{found:<4} lines  {percent:5.1}%")
+}
+
+// Define the CoverageHook struct, the statement hook recording one file's coverage
+struct CoverageHook {
+    coverage: Coverage, // Where the counts go
+    file: String,       // The file whose statements this hook sees
+}
+
+// Implement StatementHook for CoverageHook, counting each statement's line
+impl StatementHook for CoverageHook {
+    fn before_statement(
+        &mut self,
+        stmt: &Stmt,
+        _interpreter: &mut Interpreter,
+    ) -> Result<(), String> {
+        let mut files = self.coverage.files.lock().unwrap();
+        if let Some(counts) = files.get_mut(&self.file) {
+            *counts.entry(stmt.span().line).or_insert(0) += 1;
+        }
+        Ok(())
+    }
+
+    fn module_hook(&mut self, file: &str, program: &Program) -> Option<Box<dyn StatementHook>> {
+        Some(self.coverage.hook(file, program))
+    }
+}
+
+// Define the StatementLines struct, a visitor collecting the lines where statements start
+#[derive(Default)]
+struct StatementLines(Vec<usize>);
+
+// Implement Visitor for StatementLines
+impl Visitor for StatementLines {
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        if !matches!(stmt, Stmt::Block(..)) {
+            self.0.push(stmt.span().line); // Blocks only group statements; they never run alone
+        }
+        visitor::walk_stmt(self, stmt);
+    }
+}
@@ -0,0 +1,533 @@
+// Debug Adapter Protocol server for editors (pidgin dap)
+//
+// Lets editors such as VS Code debug a program with their own UI. Messages are JSON, framed
+// like the language server's (see lsp.rs), on stdin/stdout. The program runs with a statement
+// hook, as in `pidgin debug`: between statements it checks for requests (such as 'pause'),
+// and while paused it answers requests for the call stack, variables, and expressions until
+// one resumes it. A separate thread reads stdin, so requests arrive while the program runs.
+//
+// The program's printed output is captured and sent to the editor as output events, since
+// stdout carries the protocol. For the same reason, programs cannot read input (readLine).
+use crate::ast::{Program, Span, Stmt};
+use crate::debugger::line_starts;
+use crate::interpreter::{Interpreter, StatementHook, Value};
+use crate::lexer::Lexer;
+use crate::lsp::{read_message, send};
+use crate::parser::Parser;
+use crate::trace::describe;
+use serde_json::{json, Value as Json};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+const DISCONNECTED: &str = "Program stopped by the debugger"; // Error used to end the run
+const THREAD_ID: u64 = 1; // Pidgin programs have a single thread
+const SCOPE_REFERENCE: usize = 1; // variablesReference of the one scope, all the variables
+
+// Define the Mode enum, when the program pauses next
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Continue,       // Only at breakpoints
+    Entry,          // Before the first statement (stopOnEntry)
+    Pause,          // Before the next statement, as the editor asked
+    StepIn,         // Before the next statement
+    Next(usize),    // Before the next statement at this call depth or shallower
+    StepOut(usize), // Before the next statement shallower than this call depth
+}
+
+// Define the Next enum, what to do after handling a request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Next {
+    Stay,       // Keep handling requests
+    Resume,     // Let the program run
+    Disconnect, // End the session
+}
+
+// Define the Launch struct, the program the editor asked to debug
+struct Launch {
+    path: String,            // Path of the program
+    parsed: Option<Program>, // Its AST, until it starts running
+    args: Vec<String>,       // Arguments for args()
+}
+
+// Define the Session struct, the state of a debugging session
+struct Session {
+    messages: Receiver<Json>,                      // Messages from the editor
+    seq: u64,                                      // Sequence number of the last message sent
+    launch: Option<Launch>,                        // The program, once 'launch' has arrived
+    configured: bool,                              // Whether 'configurationDone' has arrived
+    breakpoints: HashMap<String, BTreeSet<usize>>, // Breakpoint lines by canonical file path
+    line_starts: HashMap<usize, Span>,             // First statement on each line of the program
+    mode: Mode,                                    // When to pause next
+    frames: Vec<usize>,     // Current line in each running call, outermost first
+    references: Vec<Value>, // Values the editor can expand while paused
+}
+
+// Implement methods for Session
+impl Session {
+    // Send a message, numbering it
+    fn send(&mut self, mut message: Json) -> Result<(), String> {
+        self.seq += 1;
+        message["seq"] = json!(self.seq);
+        send(&message)
+    }
+
+    // Send an event
+    fn event(&mut self, event: &str, body: Json) -> Result<(), String> {
+        self.send(json!({ "type": "event", "event": event, "body": body }))
+    }
+
+    // Answer a request successfully
+    fn respond(&mut self, request: &Json, body: Json) -> Result<(), String> {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": true,
+            "body": body,
+        }))
+    }
+
+    // Answer a request with an error
+    fn fail(&mut self, request: &Json, message: &str) -> Result<(), String> {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": false,
+            "message": message,
+        }))
+    }
+
+    // Send what the program printed since the last call
+    fn flush_output(&mut self, interpreter: &mut Interpreter) -> Result<(), String> {
+        let output = interpreter.take_output();
+        if output.is_empty() {
+            return Ok(());
+        }
+        self.event("output", json!({ "category": "stdout", "output": output }))
+    }
+
+    // Handle one request; `paused` is the interpreter when the program is paused in it
+    fn handle(&mut self, request: &Json, paused: Option<&mut Interpreter>) -> Result<Next, String> {
+        let arguments = &request["arguments"];
+        let depth = self.frames.len().saturating_sub(1);
+
+        match request["command"].as_str().unwrap_or("") {
+            "initialize" => {
+                self.respond(
+                    request,
+                    json!({
+                        "supportsConfigurationDoneRequest": true,
+                        "supportsEvaluateForHovers": true,
+                        "supportsTerminateRequest": true,
+                    }),
+                )?;
+                self.event("initialized", json!({}))?;
+            }
+            "launch" => match load_program(arguments) {
+                Ok(launch) => {
+                    if let Some(program) = &launch.parsed {
+                        self.line_starts = line_starts(program);
+                    }
+                    if arguments["stopOnEntry"].as_bool() == Some(true) {
+                        self.mode = Mode::Entry;
+                    }
+                    self.launch = Some(launch);
+                    self.respond(request, json!({}))?;
+                }
+                Err(e) => self.fail(request, &e)?,
+            },
+            "setBreakpoints" => {
+                let path = arguments["source"]["path"].as_str().unwrap_or("");
+                let lines: BTreeSet<usize> = arguments["breakpoints"]
+                    .as_array()
+                    .map(|breakpoints| {
+                        breakpoints
+                            .iter()
+                            .filter_map(|breakpoint| breakpoint["line"].as_u64())
+                            .map(|line| line as usize)
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                let verified = self.verify(path, &lines);
+                self.breakpoints.insert(canonical(path), lines);
+                self.respond(request, json!({ "breakpoints": verified }))?;
+            }
+            "configurationDone" => {
+                self.configured = true;
+                self.respond(request, json!({}))?;
+            }
+            "threads" => {
+                self.respond(
+                    request,
+                    json!({ "threads": [{ "id": THREAD_ID, "name": "main" }] }),
+                )?;
+            }
+            "stackTrace" => {
+                let body = match paused.as_deref() {
+                    Some(interpreter) => self.stack_trace(interpreter),
+                    None => json!({ "stackFrames": [], "totalFrames": 0 }),
+                };
+                self.respond(request, body)?;
+            }
+            "scopes" => {
+                self.respond(
+                    request,
+                    json!({ "scopes": [{
+                        "name": "Variables",
+                        "variablesReference": SCOPE_REFERENCE,
+                        "expensive": false,
+                    }] }),
+                )?;
+            }
+            "variables" => {
+                let reference = arguments["variablesReference"].as_u64().unwrap_or(0) as usize;
+                let variables = match paused.as_deref() {
+                    Some(interpreter) => self.variables(interpreter, reference),
+                    None => Vec::new(),
+                };
+                self.respond(request, json!({ "variables": variables }))?;
+            }
+            "evaluate" => {
+                let expression = arguments["expression"].as_str().unwrap_or("");
+                match paused {
+                    Some(interpreter) => match interpreter.evaluate_source(expression) {
+                        Ok(value) => {
+                            let body = json!({
+                                "result": describe(&value),
+                                "variablesReference": self.reference(&value),
+                            });
+                            self.flush_output(interpreter)?; // In case it called print
+                            self.respond(request, body)?;
+                        }
+                        Err(e) => self.fail(request, &e)?,
+                    },
+                    None => self.fail(request, "The program is not paused")?,
+                }
+            }
+            "continue" => {
+                self.mode = Mode::Continue;
+                self.respond(request, json!({ "allThreadsContinued": true }))?;
+                return Ok(Next::Resume);
+            }
+            "next" => {
+                self.mode = Mode::Next(depth);
+                self.respond(request, json!({}))?;
+                return Ok(Next::Resume);
+            }
+            "stepIn" => {
+                self.mode = Mode::StepIn;
+                self.respond(request, json!({}))?;
+                return Ok(Next::Resume);
+            }
+            "stepOut" => {
+                self.mode = Mode::StepOut(depth);
+                self.respond(request, json!({}))?;
+                return Ok(Next::Resume);
+            }
+            "pause" => {
+                self.mode = Mode::Pause;
+                self.respond(request, json!({}))?;
+            }
+            "disconnect" | "terminate" => {
+                self.respond(request, json!({}))?;
+                return Ok(Next::Disconnect);
+            }
+            command => self.fail(request, &format!("Unsupported request '{command}'"))?,
+        }
+        Ok(Next::Stay)
+    }
+
+    // Check which breakpoint lines can pause: those where a statement starts in the program
+    fn verify(&self, path: &str, lines: &BTreeSet<usize>) -> Vec<Json> {
+        let is_program = self
+            .launch
+            .as_ref()
+            .is_some_and(|launch| canonical(&launch.path) == canonical(path));
+        lines
+            .iter()
+            .map(|line| {
+                if !is_program && self.launch.is_some() {
+                    json!({ "verified": false, "line": line, "message": "Breakpoints only work in the program being debugged, not in its modules" })
+                } else if is_program && !self.line_starts.contains_key(line) {
+                    json!({ "verified": false, "line": line, "message": "No statement starts on this line" })
+                } else {
+                    json!({ "verified": true, "line": line })
+                }
+            })
+            .collect()
+    }
+
+    // List the running calls, innermost first
+    fn stack_trace(&self, interpreter: &Interpreter) -> Json {
+        let path = self
+            .launch
+            .as_ref()
+            .map_or("", |launch| launch.path.as_str());
+        let source = json!({
+            "name": Path::new(path).file_name().map(|name| name.to_string_lossy()),
+            "path": canonical(path),
+        });
+        let frames: Vec<Json> = self
+            .frames
+            .iter()
+            .enumerate()
+            .rev()
+            .map(|(depth, line)| {
+                let name = match depth {
+                    0 => "<main>".to_string(),
+                    _ => interpreter
+                        .call_stack()
+                        .get(depth - 1)
+                        .map_or("?".to_string(), |name| format!("{name}()")),
+                };
+                json!({ "id": depth, "name": name, "line": line, "column": 1, "source": source })
+            })
+            .collect();
+        json!({ "stackFrames": frames, "totalFrames": self.frames.len() })
+    }
+
+    // List the variables of the scope, or the elements of an array or object
+    fn variables(&mut self, interpreter: &Interpreter, reference: usize) -> Vec<Json> {
+        let mut children: Vec<(String, Value)> = if reference == SCOPE_REFERENCE {
+            let mut variables: Vec<_> = interpreter
+                .variables()
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.clone()))
+                .collect();
+            variables.sort_by(|(a, _), (b, _)| a.cmp(b));
+            variables
+        } else {
+            match self
+                .references
+                .get(reference.wrapping_sub(SCOPE_REFERENCE + 1))
+                .map(Value::thawed)
+            {
+                Some(Value::FixedArray(items)) | Some(Value::DynamicArray(items)) => items
+                    .iter()
+                    .enumerate()
+                    .map(|(index, item)| (format!("[{index}]"), item.clone()))
+                    .collect(),
+                Some(Value::Object(fields)) => fields
+                    .iter()
+                    .map(|(key, value)| (key.clone(), value.clone()))
+                    .collect(),
+                _ => Vec::new(),
+            }
+        };
+        if reference != SCOPE_REFERENCE {
+            children.sort_by(|(a, _), (b, _)| a.cmp(b));
+        }
+
+        children
+            .into_iter()
+            .map(|(name, value)| {
+                json!({
+                    "name": name,
+                    "value": describe(&value),
+                    "variablesReference": self.reference(&value),
+                })
+            })
+            .collect()
+    }
+
+    // Give an array or object a variablesReference so the editor can expand it (0 otherwise)
+    fn reference(&mut self, value: &Value) -> usize {
+        match value.thawed() {
+            Value::FixedArray(items) | Value::DynamicArray(items) if !items.is_empty() => {}
+            Value::Object(fields) if !fields.is_empty() => {}
+            _ => return 0,
+        }
+        self.references.push(value.clone());
+        self.references.len() + SCOPE_REFERENCE
+    }
+
+    // Handle requests until one resumes the program
+    fn wait(&mut self, interpreter: &mut Interpreter) -> Result<(), String> {
+        loop {
+            let request = self.messages.recv().map_err(|_| DISCONNECTED.to_string())?;
+            match self.handle(&request, Some(interpreter))? {
+                Next::Stay => {}
+                Next::Resume => {
+                    self.references.clear(); // Values may change once the program runs
+                    return Ok(());
+                }
+                Next::Disconnect => return Err(DISCONNECTED.to_string()),
+            }
+        }
+    }
+}
+
+// Define the SessionHook struct, the statement hook that lets the session pause the program
+struct SessionHook(Arc<Mutex<Session>>);
+
+// Implement StatementHook for SessionHook
+impl StatementHook for SessionHook {
+    fn before_statement(
+        &mut self,
+        stmt: &Stmt,
+        interpreter: &mut Interpreter,
+    ) -> Result<(), String> {
+        let mut session = self.0.lock().unwrap();
+        session.flush_output(interpreter)?;
+
+        // Keep track of the line each running call is on, for the call stack
+        let span = stmt.span();
+        let depth = interpreter.call_stack().len();
+        session.frames.resize(depth + 1, span.line);
+        session.frames[depth] = span.line;
+
+        // Requests sent while the program runs, such as 'pause' or 'setBreakpoints'
+        loop {
+            match session.messages.try_recv() {
+                Ok(request) => {
+                    if session.handle(&request, None)? == Next::Disconnect {
+                        return Err(DISCONNECTED.to_string());
+                    }
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return Err(DISCONNECTED.to_string()),
+            }
+        }
+
+        // A breakpoint pauses once per visit to its line, at the line's first statement
+        let path = session
+            .launch
+            .as_ref()
+            .map_or(String::new(), |launch| canonical(&launch.path));
+        let at_breakpoint = session
+            .breakpoints
+            .get(&path)
+            .is_some_and(|lines| lines.contains(&span.line))
+            && session.line_starts.get(&span.line) == Some(&span);
+        let reason = match session.mode {
+            _ if at_breakpoint => "breakpoint",
+            Mode::Entry => "entry",
+            Mode::Pause => "pause",
+            Mode::StepIn => "step",
+            Mode::Next(next_depth) if depth <= next_depth => "step",
+            Mode::StepOut(out_depth) if depth < out_depth => "step",
+            _ => return Ok(()),
+        };
+
+        session.event(
+            "stopped",
+            json!({ "reason": reason, "threadId": THREAD_ID, "allThreadsStopped": true }),
+        )?;
+        session.wait(interpreter)
+    }
+}
+
+// Read and parse the program named in a 'launch' request
+fn load_program(arguments: &Json) -> Result<Launch, String> {
+    let path = arguments["program"]
+        .as_str()
+        .ok_or("The launch configuration needs a 'program' (a .pg file)")?;
+    let source =
+        fs::read_to_string(path).map_err(|e| format!("Failed to read file '{path}': {e}"))?;
+    let tokens = Lexer::new(&source).tokenize()?;
+    let program = Parser::new(&tokens).parse()?;
+    let args = arguments["args"]
+        .as_array()
+        .map(|args| {
+            args.iter()
+                .filter_map(|arg| arg.as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Ok(Launch {
+        path: path.to_string(),
+        parsed: Some(program),
+        args,
+    })
+}
+
+// Get the canonical form of a path, so the editor's paths and ours compare equal
+fn canonical(path: &str) -> String {
+    Path::new(path).canonicalize().map_or_else(
+        |_| path.to_string(),
+        |path| path.to_string_lossy().to_string(),
+    )
+}
+
+// Run the debug adapter until the editor disconnects
+pub fn run() -> Result<(), String> {
+    let (sender, messages) = mpsc::channel();
+    thread::spawn(move || {
+        let stdin = io::stdin();
+        let mut input = stdin.lock();
+        while let Ok(Some(message)) = read_message(&mut input) {
+            if sender.send(message).is_err() {
+                break;
+            }
+        }
+    });
+
+    let session = Arc::new(Mutex::new(Session {
+        messages,
+        seq: 0,
+        launch: None,
+        configured: false,
+        breakpoints: HashMap::new(),
+        line_starts: HashMap::new(),
+        mode: Mode::Continue,
+        frames: Vec::new(),
+        references: Vec::new(),
+    }));
+
+    // Set up: the editor sends 'launch' and its breakpoints, then 'configurationDone'
+    loop {
+        let mut session = session.lock().unwrap();
+        if session.configured && session.launch.is_some() {
+            break;
+        }
+        let Ok(request) = session.messages.recv() else {
+            return Ok(()); // The editor went away
+        };
+        if session.handle(&request, None)? == Next::Disconnect {
+            return Ok(());
+        }
+    }
+
+    // Run the program, pausing as the editor asks
+    let (args, program) = {
+        let mut session = session.lock().unwrap();
+        let launch = session.launch.as_mut().expect("launched");
+        (launch.args.clone(), launch.parsed.take().expect("parsed"))
+    };
+    let mut interpreter = Interpreter::new();
+    interpreter.capture_output();
+    interpreter.set_script_args(args);
+    interpreter.set_statement_hook(Box::new(SessionHook(Arc::clone(&session))));
+    let result = interpreter.interpret(program);
+
+    let mut session = session.lock().unwrap();
+    session.flush_output(&mut interpreter)?;
+    let exit_code = match result {
+        Ok(()) => 0,
+        Err(e) if e == DISCONNECTED => return Ok(()),
+        Err(e) => {
+            session.event(
+                "output",
+                json!({ "category": "stderr", "output": format!("Error: {e}\n") }),
+            )?;
+            1
+        }
+    };
+    session.event("exited", json!({ "exitCode": exit_code }))?;
+    session.event("terminated", json!({}))?;
+
+    // The editor may still ask questions before it disconnects
+    while let Ok(request) = session.messages.recv() {
+        if session.handle(&request, None)? == Next::Disconnect {
+            break;
+        }
+    }
+    Ok(())
+}
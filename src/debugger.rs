@@ -0,0 +1,228 @@
+// Interactive debugger (pidgin debug <file.pg>)
+//
+// Runs a program with a statement hook that pauses before a statement when asked to, either
+// by a breakpoint on its line or by stepping, and then reads commands from stdin. Only the
+// main program can be debugged; imported modules run without pausing.
+use crate::ast::{Program, Span, Stmt};
+use crate::interpreter::{Interpreter, StatementHook};
+use crate::visitor::{self, Visitor};
+use std::collections::{BTreeSet, HashMap};
+use std::io::{self, Write};
+
+pub const QUIT: &str = "Program stopped by the debugger"; // Error used to end the run on 'quit'
+
+const HELP: &str = "\
+Commands:
+  break <line>, b <line>   Pause whenever the program reaches this line
+  delete <line>            Remove the breakpoint on this line
+  breakpoints              List breakpoints
+  step, s                  Run one statement, going into function calls
+  next, n                  Run one statement, running function calls without pausing
+  continue, c              Run until the next breakpoint
+  print <expr>, p <expr>   Evaluate an expression in the paused program
+  vars                     List the variables and their values
+  backtrace, bt            List the functions currently running
+  list, l                  Show the source around the current line
+  quit, q                  Stop the program";
+
+// Define the Mode enum, when the debugger pauses next
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Step,        // Before the next statement
+    Next(usize), // Before the next statement at this call depth or shallower
+    Continue,    // Only at breakpoints
+}
+
+// Define the LineStarts struct, a visitor that finds the first statement on each line
+#[derive(Default)]
+struct LineStarts(HashMap<usize, Span>);
+
+// Implement Visitor for LineStarts, keeping the leftmost statement of each line
+impl Visitor for LineStarts {
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        if !matches!(stmt, Stmt::Block(..)) {
+            let span = stmt.span();
+            let first = self.0.entry(span.line).or_insert(span);
+            if span.column < first.column {
+                *first = span;
+            }
+        }
+        visitor::walk_stmt(self, stmt);
+    }
+}
+
+// Find the first statement on each line of a program, where a breakpoint on the line pauses
+pub fn line_starts(program: &Program) -> HashMap<usize, Span> {
+    let mut line_starts = LineStarts::default();
+    visitor::walk_program(&mut line_starts, program);
+    line_starts.0
+}
+
+// Define the Debugger struct, the state of a debugging session
+pub struct Debugger {
+    lines: Vec<String>,                // Source lines of the program, for 'list'
+    line_starts: HashMap<usize, Span>, // First statement on each line; breakpoints pause there
+    breakpoints: BTreeSet<usize>,      // Lines to pause on
+    mode: Mode,                        // When to pause next
+}
+
+// Implement methods for Debugger
+impl Debugger {
+    // Create a debugger for a program, paused before its first statement
+    pub fn new(source: &str, program: &Program) -> Self {
+        Self {
+            lines: source.lines().map(str::to_string).collect(),
+            line_starts: line_starts(program),
+            breakpoints: BTreeSet::new(),
+            mode: Mode::Step,
+        }
+    }
+
+    // Print the source around a line, marking it and any breakpoints
+    fn list(&self, line: usize) {
+        let first = line.saturating_sub(3).max(1);
+        let last = (line + 3).min(self.lines.len());
+        for number in first..=last {
+            let marker = if number == line { "->" } else { "  " };
+            let breakpoint = if self.breakpoints.contains(&number) {
+                "*"
+            } else {
+                " "
+            };
+            println!(
+                "{breakpoint}{marker} {number:>4} | {}",
+                self.lines[number - 1]
+            );
+        }
+    }
+
+    // Read and run commands until one resumes the program
+    fn prompt(&mut self, line: usize, interpreter: &mut Interpreter) -> Result<(), String> {
+        let stdin = io::stdin();
+        loop {
+            print!("(debug) ");
+            io::stdout().flush().unwrap();
+
+            let mut input = String::new();
+            if stdin.read_line(&mut input).unwrap_or(0) == 0 {
+                return Err(QUIT.to_string()); // End of input
+            }
+            let input = input.trim();
+            let (command, argument) = input.split_once(' ').unwrap_or((input, ""));
+            let argument = argument.trim();
+
+            match command {
+                "" => continue,
+                "break" | "b" => match self.parse_line(argument) {
+                    Ok(number) => {
+                        self.breakpoints.insert(number);
+                        println!("Breakpoint set on line {number}");
+                        if !self.line_starts.contains_key(&number) {
+                            println!("Note: no statement starts on line {number}, so it never pauses there");
+                        }
+                    }
+                    Err(e) => println!("{e}"),
+                },
+                "delete" => match self.parse_line(argument) {
+                    Ok(number) if self.breakpoints.remove(&number) => {
+                        println!("Breakpoint on line {number} removed")
+                    }
+                    Ok(number) => println!("No breakpoint on line {number}"),
+                    Err(e) => println!("{e}"),
+                },
+                "breakpoints" => {
+                    if self.breakpoints.is_empty() {
+                        println!("No breakpoints");
+                    }
+                    for number in &self.breakpoints {
+                        println!("Line {number}: {}", self.lines[number - 1].trim());
+                    }
+                }
+                "step" | "s" => {
+                    self.mode = Mode::Step;
+                    return Ok(());
+                }
+                "next" | "n" => {
+                    self.mode = Mode::Next(interpreter.call_stack().len());
+                    return Ok(());
+                }
+                "continue" | "c" => {
+                    self.mode = Mode::Continue;
+                    return Ok(());
+                }
+                "print" | "p" if argument.is_empty() => println!("Usage: print <expr>"),
+                "print" | "p" => match interpreter.evaluate_source(argument) {
+                    Ok(value) => println!("{value}"),
+                    Err(e) => println!("Error: {e}"),
+                },
+                "vars" => {
+                    let mut names: Vec<_> = interpreter.variables().keys().collect();
+                    names.sort();
+                    if names.is_empty() {
+                        println!("No variables");
+                    }
+                    for name in names {
+                        println!("{name} = {}", interpreter.variables()[name]);
+                    }
+                }
+                "backtrace" | "bt" => {
+                    // Outermost first, each call indented under its caller
+                    println!("<main>");
+                    for (depth, name) in interpreter.call_stack().iter().enumerate() {
+                        println!("{}{name}()", "  ".repeat(depth + 1));
+                    }
+                    println!("Paused on line {line}");
+                }
+                "list" | "l" => self.list(line),
+                "quit" | "q" => return Err(QUIT.to_string()),
+                "help" | "h" => println!("{HELP}"),
+                other => println!("Unknown command '{other}'. Type 'help' for commands."),
+            }
+        }
+    }
+
+    // Parse a line number given to 'break' or 'delete'
+    fn parse_line(&self, argument: &str) -> Result<usize, String> {
+        match argument.parse::<usize>() {
+            Ok(number) if (1..=self.lines.len()).contains(&number) => Ok(number),
+            _ => Err(format!(
+                "Expected a line number between 1 and {}",
+                self.lines.len()
+            )),
+        }
+    }
+}
+
+// Implement StatementHook for Debugger, deciding before each statement whether to pause
+impl StatementHook for Debugger {
+    fn before_statement(
+        &mut self,
+        stmt: &Stmt,
+        interpreter: &mut Interpreter,
+    ) -> Result<(), String> {
+        let span = stmt.span();
+        let line = span.line;
+
+        // A breakpoint pauses once per visit to its line, at the line's first statement
+        let at_breakpoint =
+            self.breakpoints.contains(&line) && self.line_starts.get(&line) == Some(&span);
+        let pause = at_breakpoint
+            || match self.mode {
+                Mode::Step => true,
+                Mode::Next(depth) => interpreter.call_stack().len() <= depth,
+                Mode::Continue => false,
+            };
+        if !pause {
+            return Ok(());
+        }
+
+        io::stdout().flush().unwrap(); // Show the program's output so far
+        if at_breakpoint {
+            println!("Breakpoint hit on line {line}");
+        }
+        if let Some(text) = self.lines.get(line - 1) {
+            println!("-> {line:>4} | {text}");
+        }
+        self.prompt(line, interpreter)
+    }
+}
@@ -0,0 +1,275 @@
+// Diagnostics: errors and warnings tied to a place in a source file (--error-format)
+//
+// Errors from the lexer, parser, and interpreter are plain messages that end with their
+// position ("... at line 3 column 7"). Here they are split back into a file, line, column, and
+// message (translated if --lang chose another language), so they can be written as JSON lines that editors and CI wrappers read reliably,
+// or as text showing the offending source line with the problem underlined.
+use crate::ast::Span;
+use crate::error::{ErrorKind, PidginError};
+use crate::lexer::Lexer;
+use crate::locale;
+use serde::Serialize;
+use std::env;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+const RED: &str = "\x1b[1;31m"; // Errors and their underline
+const YELLOW: &str = "\x1b[1;33m"; // Warnings and their underline
+const BLUE: &str = "\x1b[1;34m"; // The location arrow and line number gutter
+const BOLD: &str = "\x1b[1m"; // The message
+const RESET: &str = "\x1b[0m"; // Ends a color
+
+// Define the ErrorFormat enum, how errors and warnings are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorFormat {
+    #[default]
+    Text, // Human-readable messages (the default)
+    Json, // One JSON object per line (--error-format json)
+}
+
+// Implement methods for ErrorFormat
+impl ErrorFormat {
+    // Find a format by the name given to --error-format
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "text" => Some(ErrorFormat::Text),
+            "json" => Some(ErrorFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+static JSON_ERRORS: AtomicBool = AtomicBool::new(false); // Whether --error-format json was given
+
+// Set the error format for the rest of the process
+pub fn set_error_format(format: ErrorFormat) {
+    JSON_ERRORS.store(format == ErrorFormat::Json, Ordering::Relaxed);
+}
+
+// Get the current error format
+pub fn error_format() -> ErrorFormat {
+    if JSON_ERRORS.load(Ordering::Relaxed) {
+        ErrorFormat::Json
+    } else {
+        ErrorFormat::Text
+    }
+}
+
+// Define the Severity enum, how serious a diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,   // Stops the program or fails the check
+    Warning, // Suspicious, but not wrong (pidgin lint)
+}
+
+// Define the Diagnostic struct, one error or warning as written in JSON
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnostic {
+    pub file: Option<String>,  // Source file, if the problem is in one
+    pub line: Option<usize>,   // Line number (1-based), if known
+    pub column: Option<usize>, // Column number (1-based), if known
+    pub length: Option<usize>, // Characters covered, starting at the column, if known
+    pub severity: Severity,    // Error or warning
+    pub code: String,          // Kind of problem: error class or lint rule name
+    pub message: String,       // Explanation, without the position
+    #[serde(skip)]
+    pub source: Option<String>, // Text of the file, when it is not on disk (REPL input)
+}
+
+// Implement methods for Diagnostic
+impl Diagnostic {
+    // Create a diagnostic for a problem found at a span of a file
+    pub fn at(file: &str, span: Span, severity: Severity, code: &str, message: &str) -> Self {
+        Self {
+            file: Some(file.to_string()),
+            line: Some(span.line),
+            column: Some(span.column),
+            length: Some(token_length(file, span.line, span.column).unwrap_or(1)),
+            severity,
+            code: code.to_string(),
+            message: locale::translate(message),
+            source: None,
+        }
+    }
+
+    // Create a diagnostic from an error, finding the position at the end of its message
+    pub fn from_error(error: &PidginError) -> Self {
+        let mut file = error.file.clone();
+        let mut message = error.message.as_str();
+
+        // Errors in imported modules name the module's file
+        for prefix in ["Failed to parse module '", "Failed to read module '"] {
+            if let Some((module, rest)) = message
+                .strip_prefix(prefix)
+                .and_then(|rest| rest.split_once("': "))
+            {
+                file = Some(module.to_string());
+                message = rest;
+            }
+        }
+        if let Some(rest) = file
+            .as_deref()
+            .and_then(|file| message.strip_prefix(file)?.strip_prefix(": "))
+        {
+            message = rest; // Already in the file field
+        }
+        message = message.strip_prefix("Parse error: ").unwrap_or(message);
+
+        let (message, position) = split_position(message);
+        let length = match (&file, position) {
+            (Some(file), Some((line, column))) => token_length(file, line, column),
+            _ => None,
+        };
+        Self {
+            file,
+            line: position.map(|(line, _)| line),
+            column: position.map(|(_, column)| column),
+            length,
+            severity: Severity::Error,
+            code: error_code(error.kind).to_string(),
+            message: locale::translate(message),
+            source: None,
+        }
+    }
+
+    // Point the diagnostic at source text that is not in a file, such as a line typed into the
+    // REPL, under the given name; diagnostics about another file (a module) keep pointing there
+    pub fn in_source(mut self, name: &str, source: &str) -> Self {
+        if self.file.is_some() {
+            return self;
+        }
+        self.file = Some(name.to_string());
+        if let (Some(line), Some(column)) = (self.line, self.column) {
+            self.length = token_length_in(source, line, column);
+        }
+        self.source = Some(source.to_string());
+        self
+    }
+
+    // Write the diagnostic as a single line of JSON
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    // Write the diagnostic as text, with the source line and a caret under the problem:
+    //
+    //   Error: Expect expression. Got Semicolon
+    //     --> main.pg:2:13
+    //      |
+    //    2 | let y = x + ;
+    //      |             ^
+    pub fn render(&self, color: bool) -> String {
+        let paint = |code: &str, text: &str| {
+            if color {
+                format!("{code}{text}{RESET}")
+            } else {
+                text.to_string()
+            }
+        };
+        let (label, accent) = match self.severity {
+            Severity::Error => ("Error", RED),
+            Severity::Warning => ("Warning", YELLOW),
+        };
+        let label = locale::translate(label);
+        let mut out = format!(
+            "{}{}\n",
+            paint(accent, &format!("{label}:")),
+            paint(BOLD, &format!(" {}", self.message))
+        );
+
+        let (Some(file), Some(line), Some(column)) = (&self.file, self.line, self.column) else {
+            return out;
+        };
+        let Some(length) = self.length else {
+            // The position may belong to another file, so only mention it
+            out.push_str(&format!("  (at line {line}, column {column})\n"));
+            return out;
+        };
+        let source = match &self.source {
+            Some(source) => Some(source.clone()),
+            None => fs::read_to_string(file).ok(),
+        };
+        let source_line =
+            source.and_then(|source| source.lines().nth(line.wrapping_sub(1)).map(str::to_string));
+        let gutter = " ".repeat(line.to_string().len());
+        out.push_str(&format!(
+            "{gutter}{} {file}:{line}:{column}\n",
+            paint(BLUE, "-->")
+        ));
+        let Some(source_line) = source_line else {
+            return out; // The file changed or cannot be read; the location is still useful
+        };
+
+        // Keep tabs before the column, so the caret lines up however wide they are shown
+        let padding: String = source_line
+            .chars()
+            .take(column.saturating_sub(1))
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let underline = "^".repeat(length);
+        out.push_str(&format!("{gutter} {}\n", paint(BLUE, "|")));
+        out.push_str(&format!(
+            "{} {source_line}\n",
+            paint(BLUE, &format!("{line} |"))
+        ));
+        out.push_str(&format!(
+            "{gutter} {} {padding}{}\n",
+            paint(BLUE, "|"),
+            paint(accent, &underline)
+        ));
+        out
+    }
+}
+
+// Check whether diagnostics written to stderr should be colored (a terminal, and NO_COLOR unset)
+pub fn use_color() -> bool {
+    io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none()
+}
+
+// Name the class of an error, used as the code of its diagnostic
+fn error_code(kind: ErrorKind) -> &'static str {
+    match kind {
+        ErrorKind::Runtime => "runtime",
+        ErrorKind::Usage => "usage",
+        ErrorKind::Syntax => "syntax",
+        ErrorKind::Internal => "internal",
+    }
+}
+
+// Split "message at line L column C" (or "..., column C") into the message and the position
+fn split_position(message: &str) -> (&str, Option<(usize, usize)>) {
+    let Some(index) = message.rfind(" at line ") else {
+        return (message, None);
+    };
+    let position = &message[index + " at line ".len()..];
+    let parsed = position.split_once(" column ").and_then(|(line, column)| {
+        let line = line.trim_end_matches(',').parse().ok()?;
+        let column = column.trim_end_matches('.').parse().ok()?;
+        Some((line, column))
+    });
+    match parsed {
+        Some(position) => (message[..index].trim_end_matches(','), Some(position)),
+        None => (message, None),
+    }
+}
+
+// Find how many characters the token at a position covers: 1 if the file cannot be lexed (the
+// error is in the lexer), or None if no token starts there, as when a runtime error in an
+// imported module is reported against the main file
+fn token_length(file: &str, line: usize, column: usize) -> Option<usize> {
+    token_length_in(&fs::read_to_string(file).ok()?, line, column)
+}
+
+// Find how many characters the token at a position of some source text covers (see
+// token_length)
+fn token_length_in(source: &str, line: usize, column: usize) -> Option<usize> {
+    let Ok(tokens) = Lexer::new(source).tokenize() else {
+        return Some(1);
+    };
+    tokens
+        .into_iter()
+        .find(|info| info.line == line && info.column == column)
+        .map(|info| info.length.max(1))
+}
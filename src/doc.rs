@@ -0,0 +1,141 @@
+// API documentation generated from /// doc comments (pidgin doc)
+//
+// Every top-level function of a program and of the modules it imports is listed with its
+// parameters and the /// comments written just above it, as Markdown or as an HTML page.
+// Doc comment text is Markdown, so it is copied into Markdown output as it is.
+use crate::ast::Stmt;
+use crate::highlight::escape_html;
+use crate::pgc::parse_file;
+use crate::watch::program_files;
+
+// Define the DocFormat enum, the kind of document to generate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocFormat {
+    Markdown, // A Markdown file (the default)
+    Html,     // A standalone HTML page
+}
+
+// Implement methods for DocFormat
+impl DocFormat {
+    // Find a format by the name given to --format
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "markdown" | "md" => Some(DocFormat::Markdown),
+            "html" => Some(DocFormat::Html),
+            _ => None,
+        }
+    }
+}
+
+// Define the FunctionDoc struct, the documentation of one function
+struct FunctionDoc {
+    name: String,            // Function name
+    parameters: Vec<String>, // Parameter names
+    doc: Option<String>,     // Text of its /// comments
+}
+
+// Implement methods for FunctionDoc
+impl FunctionDoc {
+    // Get the function as it is called: name(a, b)
+    fn signature(&self) -> String {
+        format!("{}({})", self.name, self.parameters.join(", "))
+    }
+}
+
+// Define the FileDoc struct, the documented functions of one source file
+struct FileDoc {
+    path: String,                // Path of the source file
+    functions: Vec<FunctionDoc>, // Its top-level functions, in source order
+}
+
+// Generate documentation for a program and every module it imports
+pub fn generate(path: &str, format: DocFormat) -> Result<String, String> {
+    let files = program_files(path)
+        .iter()
+        .map(|file| document_file(file))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(match format {
+        DocFormat::Markdown => render_markdown(path, &files),
+        DocFormat::Html => render_html(path, &files),
+    })
+}
+
+// Collect the top-level functions of one file
+fn document_file(path: &str) -> Result<FileDoc, String> {
+    let program = parse_file(path)?;
+    let functions = program
+        .statements
+        .into_iter()
+        .filter_map(|stmt| match stmt {
+            Stmt::FunctionDeclaration {
+                name,
+                parameters,
+                doc,
+                ..
+            } => Some(FunctionDoc {
+                name,
+                parameters,
+                doc,
+            }),
+            _ => None,
+        })
+        .collect();
+
+    Ok(FileDoc {
+        path: path.to_string(),
+        functions,
+    })
+}
+
+// Render the documentation as Markdown
+fn render_markdown(title: &str, files: &[FileDoc]) -> String {
+    let mut out = format!("# API documentation: {title}\n");
+    for file in files.iter().filter(|file| !file.functions.is_empty()) {
+        out.push_str(&format!("\n## {}\n", file.path));
+        for function in &file.functions {
+            out.push_str(&format!("\n### `{}`\n\n", function.signature()));
+            match &function.doc {
+                Some(doc) => out.push_str(&format!("{doc}\n")),
+                None => out.push_str("_Not documented._\n"),
+            }
+        }
+    }
+    if files.iter().all(|file| file.functions.is_empty()) {
+        out.push_str("\nNo functions found.\n");
+    }
+    out
+}
+
+// Render the documentation as a standalone HTML page
+fn render_html(title: &str, files: &[FileDoc]) -> String {
+    let title = escape_html(&format!("API documentation: {title}"));
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n\
+         <style>body {{ font-family: sans-serif; max-width: 50em; margin: 2em auto; }} \
+         h3 {{ font-family: monospace; }}</style>\n</head>\n<body>\n<h1>{title}</h1>\n"
+    );
+    for file in files.iter().filter(|file| !file.functions.is_empty()) {
+        out.push_str(&format!("<h2>{}</h2>\n", escape_html(&file.path)));
+        for function in &file.functions {
+            out.push_str(&format!(
+                "<h3>{}</h3>\n",
+                escape_html(&function.signature())
+            ));
+            match &function.doc {
+                // Blank lines separate paragraphs, as in Markdown
+                Some(doc) => {
+                    for paragraph in doc.split("\n\n").filter(|p| !p.trim().is_empty()) {
+                        out.push_str(&format!("<p>{}</p>\n", escape_html(paragraph.trim())));
+                    }
+                }
+                None => out.push_str("<p><em>Not documented.</em></p>\n"),
+            }
+        }
+    }
+    if files.iter().all(|file| file.functions.is_empty()) {
+        out.push_str("<p>No functions found.</p>\n");
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
@@ -0,0 +1,98 @@
+// Errors reported by Pidgin, each class mapped to the exit code the pidgin command line uses
+//
+// Exit codes:
+//   0  success
+//   1  runtime error (also: lint warnings found, files need formatting)
+//   2  usage error (bad arguments or flags, unreadable input files)
+//   3  syntax error (lexing or parsing failed, or `pidgin check` found errors)
+//   4  internal error (a bug or environment failure inside pidgin itself)
+use std::fmt;
+
+// Define the ErrorKind enum, the class of an error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    Runtime,  // The program failed while running, or a check found problems
+    Usage,    // The command line was wrong or an input file could not be read
+    Syntax,   // The source could not be lexed, parsed, or resolved
+    Internal, // Pidgin itself failed (writing files, self-update, panics)
+}
+
+// Implement methods for ErrorKind
+impl ErrorKind {
+    // Get the process exit code for this class of error
+    pub fn exit_code(self) -> i32 {
+        match self {
+            ErrorKind::Runtime => 1,
+            ErrorKind::Usage => 2,
+            ErrorKind::Syntax => 3,
+            ErrorKind::Internal => 4,
+        }
+    }
+}
+
+// Define the PidginError struct, an error message together with its class
+#[derive(Debug, Clone)]
+pub struct PidginError {
+    pub kind: ErrorKind,      // Class of the error, which decides the exit code
+    pub message: String,      // Human-readable explanation
+    pub file: Option<String>, // Source file the error is about, if any (for diagnostics)
+}
+
+// Implement methods for PidginError
+impl PidginError {
+    // Create a new error of the given class
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            file: None,
+        }
+    }
+
+    // Create an error that has already been reported (as diagnostics), so only its exit code
+    // is left to give
+    pub fn reported(kind: ErrorKind) -> Self {
+        Self::new(kind, "")
+    }
+
+    // Note which source file the error is about
+    pub fn in_file(mut self, path: &str) -> Self {
+        self.file.get_or_insert_with(|| path.to_string());
+        self
+    }
+
+    // Create a runtime error
+    pub fn runtime(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Runtime, message)
+    }
+
+    // Create a usage error
+    pub fn usage(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Usage, message)
+    }
+
+    // Create a syntax error
+    pub fn syntax(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Syntax, message)
+    }
+
+    // Create an internal error
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Internal, message)
+    }
+
+    // Get the process exit code for this error
+    pub fn exit_code(&self) -> i32 {
+        self.kind.exit_code()
+    }
+}
+
+// Implement Display trait for PidginError (just the message)
+impl fmt::Display for PidginError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+// Implement Error for PidginError, so embedders can use `?` with other error types
+impl std::error::Error for PidginError {}
@@ -0,0 +1,112 @@
+// C API for embedding Pidgin in programs written in other languages (the `ffi` feature)
+//
+// The declarations are in include/pidgin.h, and docs/FFI.md shows how to build the shared
+// library and call it. Strings cross the boundary as NUL-terminated UTF-8; every string this
+// API returns is owned by the caller and must be given back to pidgin_free_string.
+#![allow(clippy::missing_safety_doc)] // The safety rules are in docs/FFI.md and pidgin.h
+
+use crate::error::ErrorKind;
+use crate::interpreter::Interpreter;
+use crate::run_with_interpreter;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+// Define the PidginInterpreter struct, the handle C code holds (opaque on the C side)
+pub struct PidginInterpreter {
+    interpreter: Interpreter, // Keeps variables and functions between pidgin_eval calls
+    last_error: Option<CString>, // Message of the last failed pidgin_eval
+}
+
+// Create an interpreter; free it with pidgin_free
+#[no_mangle]
+pub extern "C" fn pidgin_new_interpreter() -> *mut PidginInterpreter {
+    Box::into_raw(Box::new(PidginInterpreter {
+        interpreter: Interpreter::new(),
+        last_error: None,
+    }))
+}
+
+// Run source code in an interpreter; returns 0 on success, or the exit code the pidgin command
+// line would use for the error (1 runtime, 2 bad arguments, 3 syntax, 4 internal)
+#[no_mangle]
+pub unsafe extern "C" fn pidgin_eval(
+    interpreter: *mut PidginInterpreter,
+    source: *const c_char,
+) -> c_int {
+    let Some(handle) = interpreter.as_mut() else {
+        return ErrorKind::Usage.exit_code();
+    };
+    let result = match to_str(source) {
+        Some(source) => {
+            // A panic must not unwind into C, so it is reported as an internal error
+            panic::catch_unwind(AssertUnwindSafe(|| {
+                run_with_interpreter(source, &mut handle.interpreter)
+                    .map_err(|e| (e.kind, e.message))
+            }))
+            .unwrap_or_else(|_| Err((ErrorKind::Internal, "Pidgin panicked".to_string())))
+        }
+        None => Err((ErrorKind::Usage, "Source is NULL or not UTF-8".to_string())),
+    };
+    match result {
+        Ok(()) => {
+            handle.last_error = None;
+            0
+        }
+        Err((kind, message)) => {
+            handle.last_error = CString::new(message.replace('\0', " ")).ok();
+            kind.exit_code()
+        }
+    }
+}
+
+// Get the message of the last failed pidgin_eval, or NULL if it succeeded; the string stays
+// owned by the interpreter and is valid until the next pidgin_eval or pidgin_free
+#[no_mangle]
+pub unsafe extern "C" fn pidgin_last_error(interpreter: *const PidginInterpreter) -> *const c_char {
+    interpreter
+        .as_ref()
+        .and_then(|handle| handle.last_error.as_ref())
+        .map_or(ptr::null(), |message| message.as_ptr())
+}
+
+// Get a global variable as text (as print would show it), or NULL if it is not defined;
+// free the result with pidgin_free_string
+#[no_mangle]
+pub unsafe extern "C" fn pidgin_get_var_as_string(
+    interpreter: *const PidginInterpreter,
+    name: *const c_char,
+) -> *mut c_char {
+    let (Some(handle), Some(name)) = (interpreter.as_ref(), to_str(name)) else {
+        return ptr::null_mut();
+    };
+    match handle.interpreter.variables().get(name) {
+        Some(value) => CString::new(value.to_string().replace('\0', " "))
+            .map_or(ptr::null_mut(), CString::into_raw),
+        None => ptr::null_mut(),
+    }
+}
+
+// Free a string returned by pidgin_get_var_as_string (NULL is ignored)
+#[no_mangle]
+pub unsafe extern "C" fn pidgin_free_string(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
+
+// Free an interpreter made by pidgin_new_interpreter (NULL is ignored)
+#[no_mangle]
+pub unsafe extern "C" fn pidgin_free(interpreter: *mut PidginInterpreter) {
+    if !interpreter.is_null() {
+        drop(Box::from_raw(interpreter));
+    }
+}
+
+// Read a C string as UTF-8 (None for NULL or invalid text)
+unsafe fn to_str<'a>(string: *const c_char) -> Option<&'a str> {
+    if string.is_null() {
+        return None;
+    }
+    CStr::from_ptr(string).to_str().ok()
+}
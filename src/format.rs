@@ -0,0 +1,289 @@
+// Format strings for print, printLn, and printErr: text with placeholders filled in
+//
+// {} takes the next argument, {0} and {1} take an argument by position, and {name} shows the
+// variable called name. {{ and }} write a literal brace. A placeholder with no argument to
+// show, or an argument that no placeholder uses, is an error instead of being skipped.
+//
+// A string printed without arguments is more forgiving, since it may just contain braces, as in
+// print "set = { 1, 2 }";. Its {name}s whose variables are defined are filled in, and any other
+// braces are printed as they are.
+//
+// After a ':' a placeholder can say how to show its value, as in Rust: {:.2} rounds a number to
+// 2 decimals (or cuts text to 2 characters; arrays and objects cannot be cut), {:8} pads to 8
+// characters, and {:<8}, {:>8} and {:^8} align left, right or centre, optionally with a fill
+// character as in {:*^8}. {:08} pads a number with zeros after its sign, so -1.5 shows as
+// -00001.5; other values ignore the 0.
+use crate::interpreter::Value;
+
+// Define the Placeholder enum, what a {...} in a format string refers to
+#[derive(Debug, Clone, PartialEq)]
+enum Placeholder {
+    Next,             // {}: the argument after the one the previous {} took
+    Index(usize),     // {0}: an argument by position, counting from 0
+    Variable(String), // {name}: a variable
+}
+
+// Define the Align enum, where a value goes when it is narrower than the width
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Align {
+    Left,   // <
+    Right,  // >
+    Center, // ^
+}
+
+// Define the Spec struct, how a placeholder shows its value (the part after ':')
+#[derive(Debug, Clone, PartialEq)]
+struct Spec {
+    fill: char,               // Character the padding is made of
+    align: Option<Align>,     // None: numbers go right, everything else left
+    zero: bool,               // Pad a number with zeros after its sign (the 0 flag)
+    width: usize,             // Minimum number of characters
+    precision: Option<usize>, // Decimals of a number, or the most characters of anything else
+}
+
+// Implement Default for Spec: no padding and no rounding
+impl Default for Spec {
+    fn default() -> Self {
+        Self {
+            fill: ' ',
+            align: None,
+            zero: false,
+            width: 0,
+            precision: None,
+        }
+    }
+}
+
+// Fill in the placeholders of a format string; `variable` looks up the value of a {name}
+pub fn format(
+    format: &str,
+    arguments: &[Value],
+    variable: impl Fn(&str) -> Option<Value>,
+) -> Result<String, String> {
+    let mut output = String::new();
+    let mut used = vec![false; arguments.len()]; // Which arguments a placeholder showed
+    let mut next = 0; // Argument the next {} takes
+    let lenient = arguments.is_empty(); // Whether braces that are not placeholders are text
+    let mut chars = format.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => {
+                let rest = chars.as_str();
+                if let Some(after) = rest.strip_prefix('{') {
+                    output.push('{');
+                    chars = after.chars();
+                    continue;
+                }
+                let filled = match rest.find('}') {
+                    Some(end) => fill(&rest[..end], arguments, &mut used, &mut next, &variable)
+                        .map(|text| (text, end)),
+                    None => Err(
+                        "Unclosed '{' in format string (write '{{' for a literal brace)"
+                            .to_string(),
+                    ),
+                };
+                match filled {
+                    Ok((text, end)) => {
+                        output.push_str(&text);
+                        chars = rest[end + 1..].chars();
+                    }
+                    // Without arguments, a brace that does not start a placeholder is just text
+                    Err(_) if lenient => output.push('{'),
+                    Err(error) => return Err(error),
+                }
+            }
+            // A lone '}' has nothing to close, so it is kept as text
+            '}' => {
+                output.push('}');
+                if chars.as_str().starts_with('}') {
+                    chars.next();
+                }
+            }
+            c => output.push(c),
+        }
+    }
+
+    if let Some(unused) = used.iter().position(|used| !used) {
+        return Err(format!(
+            "Argument {} is not used in the format string ({})",
+            unused + 1,
+            given(arguments.len())
+        ));
+    }
+    Ok(output)
+}
+
+// Show the value of a placeholder, given what is between its braces
+fn fill(
+    text: &str,
+    arguments: &[Value],
+    used: &mut [bool],
+    next: &mut usize,
+    variable: impl Fn(&str) -> Option<Value>,
+) -> Result<String, String> {
+    let (placeholder, spec) = parse_placeholder(text)?;
+    let value = match placeholder {
+        Placeholder::Next => {
+            *next += 1;
+            argument(arguments, used, *next - 1, "{}")?
+        }
+        Placeholder::Index(index) => argument(arguments, used, index, &format!("{{{index}}}"))?,
+        Placeholder::Variable(name) => variable(&name)
+            .ok_or_else(|| format!("Undefined variable '{name}' in format string"))?,
+    };
+    apply_spec(&value, &spec)
+}
+
+// Read what is between the braces of a placeholder
+fn parse_placeholder(text: &str) -> Result<(Placeholder, Spec), String> {
+    let (target, spec) = match text.split_once(':') {
+        Some((target, spec)) => (target, parse_spec(spec)?),
+        None => (text, Spec::default()),
+    };
+    Ok((parse_target(target)?, spec))
+}
+
+// Read the part of a placeholder before the ':', which says what to show
+fn parse_target(text: &str) -> Result<Placeholder, String> {
+    let text = text.trim();
+    if text.is_empty() {
+        Ok(Placeholder::Next)
+    } else if text.chars().all(|c| c.is_ascii_digit()) {
+        text.parse()
+            .map(Placeholder::Index)
+            .map_err(|_| format!("Invalid placeholder '{{{text}}}' in format string"))
+    } else if text.starts_with(|c: char| c.is_alphabetic() || c == '_')
+        && text.chars().all(|c| c.is_alphanumeric() || c == '_')
+    {
+        Ok(Placeholder::Variable(text.to_string()))
+    } else {
+        Err(format!(
+            "Invalid placeholder '{{{text}}}' in format string (write '{{{{' for a literal brace)"
+        ))
+    }
+}
+
+// Read the part of a placeholder after the ':': [[fill]align][0][width][.precision]
+fn parse_spec(text: &str) -> Result<Spec, String> {
+    let invalid = || format!("Invalid format specifier ':{text}' in format string");
+    let align_of = |c: char| match c {
+        '<' => Some(Align::Left),
+        '>' => Some(Align::Right),
+        '^' => Some(Align::Center),
+        _ => None,
+    };
+    let mut spec = Spec::default();
+    let mut rest = text;
+
+    let mut chars = text.chars();
+    let first = chars.next();
+    let second = chars.next();
+    if let (Some(fill), Some(align)) = (first, second.and_then(align_of)) {
+        spec.fill = fill;
+        spec.align = Some(align);
+        rest = &text[fill.len_utf8() + 1..];
+    } else if let Some(align) = first.and_then(align_of) {
+        spec.align = Some(align);
+        rest = &text[1..];
+    }
+
+    if let Some(after) = rest.strip_prefix('0') {
+        spec.zero = true;
+        rest = after;
+    }
+
+    let digits = |text: &str| {
+        text.find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(text.len())
+    };
+    let width_end = digits(rest);
+    if width_end > 0 {
+        spec.width = rest[..width_end].parse().map_err(|_| invalid())?;
+    }
+    rest = &rest[width_end..];
+
+    if let Some(after) = rest.strip_prefix('.') {
+        let precision_end = digits(after);
+        if precision_end == 0 {
+            return Err(invalid());
+        }
+        spec.precision = Some(after[..precision_end].parse().map_err(|_| invalid())?);
+        rest = &after[precision_end..];
+    }
+
+    if rest.is_empty() {
+        Ok(spec)
+    } else {
+        Err(invalid())
+    }
+}
+
+// Show a value the way a placeholder's specifier says
+fn apply_spec(value: &Value, spec: &Spec) -> Result<String, String> {
+    let text = match (value.thawed(), spec.precision) {
+        (Value::Number(n), Some(precision)) if n.is_finite() => format!("{n:.precision$}"),
+        (Value::Number(_), _) | (_, None) => value.to_string(),
+        // Cutting the text of an array or object would leave half of it
+        (Value::FixedArray(_) | Value::DynamicArray(_) | Value::Object(_), Some(precision)) => {
+            return Err(format!(
+                "Cannot use a precision ('.{precision}') with a value of type {}",
+                value.type_name()
+            ))
+        }
+        (_, Some(precision)) => value.to_string().chars().take(precision).collect(),
+    };
+
+    let length = text.chars().count();
+    if length >= spec.width {
+        return Ok(text);
+    }
+    let padding = spec.width - length;
+    if spec.zero && matches!(value, Value::Number(_)) {
+        // The zeros go between the sign and the digits
+        let (sign, digits) = match text.strip_prefix('-') {
+            Some(digits) => ("-", digits),
+            None => ("", text.as_str()),
+        };
+        return Ok(format!("{sign}{}{digits}", "0".repeat(padding)));
+    }
+    let default = match value {
+        Value::Number(_) => Align::Right,
+        _ => Align::Left,
+    };
+    let (before, after) = match spec.align.unwrap_or(default) {
+        Align::Left => (0, padding),
+        Align::Right => (padding, 0),
+        Align::Center => (padding / 2, padding - padding / 2),
+    };
+    let fill = |count: usize| spec.fill.to_string().repeat(count);
+    Ok(format!("{}{text}{}", fill(before), fill(after)))
+}
+
+// Take an argument for a placeholder, noting that it was used
+fn argument(
+    arguments: &[Value],
+    used: &mut [bool],
+    index: usize,
+    placeholder: &str,
+) -> Result<Value, String> {
+    match arguments.get(index) {
+        Some(value) => {
+            used[index] = true;
+            Ok(value.clone())
+        }
+        None => Err(format!(
+            "Placeholder {placeholder} has no argument: it takes argument {}, but only {}",
+            index + 1,
+            given(arguments.len())
+        )),
+    }
+}
+
+// Say how many arguments were given, for messages (which count arguments from 1)
+fn given(count: usize) -> String {
+    match count {
+        1 => "1 argument was given".to_string(),
+        _ => format!("{count} arguments were given"),
+    }
+}
@@ -0,0 +1,402 @@
+// Source code formatter for Pidgin programs (`pidgin fmt`)
+//
+// The formatter works on the token stream (lexed with comments kept) rather than the AST,
+// so comments and intentional blank lines survive a round trip. The file is parsed first
+// so that only syntactically valid programs are rewritten.
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use crate::token::{Token, TokenInfo};
+
+const INDENT: &str = "    "; // Four spaces per indentation level
+
+// What an opening brace starts, so the matching '}' is laid out the same way
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BraceKind {
+    Block,   // Statement block: if/while/function bodies
+    Literal, // Dynamic array literal or import list: {a, b, c}
+    Cases,   // Cases of a match expression, one per line
+}
+
+// Format a Pidgin source string, returning the formatted source
+pub fn format_source(source: &str) -> Result<String, String> {
+    // Make sure the program parses before touching it
+    let tokens = Lexer::new(source).tokenize()?;
+    let mut parser = Parser::new(&tokens);
+    parser.parse().map_err(|e| format!("Parse error: {e}"))?;
+
+    let tokens = Lexer::with_comments(source).tokenize()?;
+    Ok(Formatter::new(&tokens, parser.line_ends()).format())
+}
+
+// Define the Formatter struct, which lays out a token stream
+struct Formatter<'a> {
+    tokens: &'a [TokenInfo], // Tokens to format (including comments and newlines)
+    line_ends: &'a [(usize, usize)], // Positions of the line breaks that end a statement without ';'
+    out: String,                     // The formatted output
+    indent: usize,                   // Current indentation level
+    braces: Vec<BraceKind>,          // Kinds of the currently open braces
+    paren_depth: usize,              // Nesting depth of '(' and '['
+    match_depths: Vec<usize>,        // Paren depths of 'match' keywords whose '{' is still to come
+    in_backtick: bool,               // Inside a `from -> to` transform
+    at_line_start: bool,             // Nothing written on the current output line yet
+    pending_newline: bool,           // A line break is due before the next token
+    newline_run: usize,              // Consecutive source newlines seen since the last token
+    previous: Option<&'a Token>,     // Last significant (non-comment) token written
+    before_previous: Option<&'a Token>, // Significant token written before `previous`
+    previous_line: usize,            // Source line of the last token written
+    previous_brace: Option<BraceKind>, // Kind of the last brace written, if it was a brace
+}
+
+impl<'a> Formatter<'a> {
+    // Create a new Formatter over a token stream
+    fn new(tokens: &'a [TokenInfo], line_ends: &'a [(usize, usize)]) -> Self {
+        Self {
+            tokens,
+            line_ends,
+            out: String::new(),
+            indent: 0,
+            braces: Vec::new(),
+            paren_depth: 0,
+            match_depths: Vec::new(),
+            in_backtick: false,
+            at_line_start: true,
+            pending_newline: false,
+            newline_run: 0,
+            previous: None,
+            before_previous: None,
+            previous_line: 0,
+            previous_brace: None,
+        }
+    }
+
+    // Format every token and return the resulting source
+    fn format(mut self) -> String {
+        for (index, info) in self.tokens.iter().enumerate() {
+            match &info.token {
+                Token::Eof => break,
+                Token::Newline => {
+                    self.newline_run += 1;
+                    // A statement written without its ';' still ends its line
+                    if self.line_ends.contains(&(info.line, info.column)) {
+                        self.pending_newline = true;
+                    }
+                }
+                Token::Comment(text) => self.write_comment(text, info.line),
+                Token::DocComment(text) => self.write_comment(&format!("/{text}"), info.line),
+                token => self.write_token(token, info.line, index),
+            }
+        }
+
+        let mut out = self.out.trim_end().to_string();
+        out.push('\n');
+        out
+    }
+
+    // Write a comment, either trailing the current line or on a line of its own
+    fn write_comment(&mut self, text: &str, line: usize) {
+        let comment = if text.is_empty() {
+            "//".to_string()
+        } else {
+            format!("//{text}")
+        };
+
+        if self.previous.is_some() && line == self.previous_line && !self.at_line_start {
+            self.out.push(' ');
+            self.out.push_str(&comment);
+            self.pending_newline = true;
+        } else {
+            self.start_line();
+            self.out.push_str(&comment);
+            self.at_line_start = false;
+            self.pending_newline = true;
+        }
+        self.newline_run = 0;
+        self.previous_line = line;
+    }
+
+    // Write a significant token with the spacing and line breaks around it
+    fn write_token(&mut self, token: &'a Token, line: usize, index: usize) {
+        let brace = match token {
+            Token::LeftBrace if self.in_backtick => Some(BraceKind::Literal),
+            Token::LeftBrace => Some(self.opening_brace_kind()),
+            Token::RightBrace => Some(self.braces.last().copied().unwrap_or(BraceKind::Block)),
+            _ => None,
+        };
+
+        match (token, brace) {
+            (Token::RightBrace, Some(BraceKind::Block)) => {
+                self.braces.pop();
+                self.indent = self.indent.saturating_sub(1);
+                self.newline_run = 0; // No blank line before a closing brace
+                self.start_line();
+                self.out.push('}');
+                self.at_line_start = false;
+                // Keep `} else {` together, otherwise end the line
+                if !matches!(self.next_significant(index), Some(Token::Else)) {
+                    self.pending_newline = true;
+                }
+            }
+            (Token::RightBrace, Some(BraceKind::Cases)) => {
+                // The match is an expression, so what follows it stays on its line
+                self.braces.pop();
+                self.indent = self.indent.saturating_sub(1);
+                self.newline_run = 0;
+                self.start_line();
+                self.out.push('}');
+                self.at_line_start = false;
+            }
+            (Token::LeftBrace, Some(BraceKind::Cases)) => {
+                self.match_depths.pop();
+                self.braces.push(BraceKind::Cases);
+                self.out.push_str(" {");
+                self.at_line_start = false;
+                self.indent += 1;
+                self.pending_newline = true;
+            }
+            (Token::LeftBrace, Some(BraceKind::Block)) => {
+                self.braces.push(BraceKind::Block);
+                if self.pending_newline || self.at_line_start {
+                    self.start_line();
+                } else {
+                    self.out.push(' ');
+                }
+                self.out.push('{');
+                self.at_line_start = false;
+                self.indent += 1;
+                self.pending_newline = true;
+            }
+            _ => {
+                // Each case of a match starts a line
+                let new_case =
+                    matches!(token, Token::Case) && self.braces.last() == Some(&BraceKind::Cases);
+                if self.pending_newline || self.at_line_start || new_case {
+                    self.start_line();
+                } else if self.needs_space(token, brace) {
+                    self.out.push(' ');
+                }
+                if matches!(token, Token::Get) && matches!(self.previous, Some(Token::Dot)) {
+                    self.out.push_str("get"); // obj.get(key) is a method, not an import
+                } else {
+                    self.out.push_str(&token_text(token));
+                }
+                self.at_line_start = false;
+
+                match token {
+                    Token::LeftBrace => self.braces.push(BraceKind::Literal),
+                    Token::RightBrace => {
+                        self.braces.pop();
+                    }
+                    Token::LeftParen | Token::LeftBracket => self.paren_depth += 1,
+                    Token::RightParen | Token::RightBracket => {
+                        self.paren_depth = self.paren_depth.saturating_sub(1)
+                    }
+                    Token::Backtick => self.in_backtick = !self.in_backtick,
+                    Token::Match => self.match_depths.push(self.paren_depth),
+                    Token::Semicolon if self.paren_depth == 0 => self.pending_newline = true,
+                    _ => {}
+                }
+            }
+        }
+
+        self.before_previous = self.previous;
+        self.previous = Some(token);
+        self.previous_line = line;
+        self.previous_brace = brace;
+        self.newline_run = 0;
+    }
+
+    // Begin a fresh, indented output line (keeping at most one blank line from the source)
+    fn start_line(&mut self) {
+        if !self.out.is_empty() {
+            if !self.at_line_start {
+                self.out.push('\n');
+            }
+            let after_open_brace = self.previous_brace == Some(BraceKind::Block)
+                && matches!(self.previous, Some(Token::LeftBrace));
+            if self.newline_run >= 2 && !after_open_brace {
+                self.out.push('\n');
+            }
+        }
+        for _ in 0..self.indent {
+            self.out.push_str(INDENT);
+        }
+        self.at_line_start = true;
+        self.pending_newline = false;
+        self.newline_run = 0;
+    }
+
+    // Decide whether a '{' opens a statement block or a literal
+    fn opening_brace_kind(&self) -> BraceKind {
+        if self.match_depths.last() == Some(&self.paren_depth) {
+            return BraceKind::Cases;
+        }
+        match self.previous {
+            None | Some(Token::Semicolon) | Some(Token::RightParen) | Some(Token::Else) => {
+                BraceKind::Block
+            }
+            Some(Token::LeftBrace) | Some(Token::RightBrace)
+                if self.previous_brace == Some(BraceKind::Block) =>
+            {
+                BraceKind::Block
+            }
+            _ => BraceKind::Literal,
+        }
+    }
+
+    // Decide whether a space separates the previous token from this one
+    fn needs_space(&self, token: &Token, brace: Option<BraceKind>) -> bool {
+        let previous = match self.previous {
+            Some(previous) => previous,
+            None => return false,
+        };
+
+        // Transform bodies: `from -> to`
+        if self.in_backtick {
+            return matches!(token, Token::Arrow) || matches!(previous, Token::Arrow);
+        }
+        if matches!(token, Token::Backtick) {
+            return !matches!(previous, Token::Identifier(_));
+        }
+
+        match token {
+            Token::Semicolon
+            | Token::Comma
+            | Token::Colon
+            | Token::RightParen
+            | Token::RightBracket
+            | Token::Dot => return false,
+            Token::RightBrace if brace == Some(BraceKind::Literal) => return false,
+            _ => {}
+        }
+
+        match previous {
+            Token::LeftParen | Token::LeftBracket | Token::Dot => return false,
+            Token::LeftBrace if self.previous_brace == Some(BraceKind::Literal) => return false,
+            Token::Minus if self.previous_minus_is_unary() => return false,
+            _ => {}
+        }
+
+        match token {
+            // Calls and indexing hug their callee: foo(x), arr[0], print("...")
+            Token::LeftParen => !matches!(
+                previous,
+                Token::Identifier(_)
+                    | Token::Get
+                    | Token::Print
+                    | Token::PrintLn
+                    | Token::PrintErr
+                    | Token::RightParen
+                    | Token::RightBracket
+            ),
+            Token::LeftBracket => !matches!(
+                previous,
+                Token::Identifier(_) | Token::RightParen | Token::RightBracket
+            ),
+            _ => true,
+        }
+    }
+
+    // A '-' is unary when the token before it cannot end an operand
+    fn previous_minus_is_unary(&self) -> bool {
+        !matches!(
+            self.before_previous,
+            Some(
+                Token::Number(_)
+                    | Token::String(_)
+                    | Token::Identifier(_)
+                    | Token::True
+                    | Token::False
+                    | Token::RightParen
+                    | Token::RightBracket
+                    | Token::RightBrace
+            )
+        )
+    }
+
+    // Find the next significant (non-newline, non-comment) token after an index
+    fn next_significant(&self, index: usize) -> Option<&'a Token> {
+        self.tokens[index + 1..]
+            .iter()
+            .map(|info| &info.token)
+            .find(|token| {
+                !matches!(
+                    token,
+                    Token::Newline | Token::Comment(_) | Token::DocComment(_)
+                )
+            })
+    }
+}
+
+// Render a single token back to source text
+fn token_text(token: &Token) -> String {
+    match token {
+        Token::Number(n) => n.to_string(),
+        Token::Identifier(name) => name.clone(),
+        Token::String(s) => format!("\"{}\"", escape_string(s)),
+        Token::Plus => "+".to_string(),
+        Token::Minus => "-".to_string(),
+        Token::Star => "*".to_string(),
+        Token::Slash => "/".to_string(),
+        Token::Assign => "=".to_string(),
+        Token::Equal => "==".to_string(),
+        Token::NotEqual => "!=".to_string(),
+        Token::Less => "<".to_string(),
+        Token::Greater => ">".to_string(),
+        Token::LessEqual => "<=".to_string(),
+        Token::GreaterEqual => ">=".to_string(),
+        Token::Let => "let".to_string(),
+        Token::If => "if".to_string(),
+        Token::Else => "else".to_string(),
+        Token::While => "while".to_string(),
+        Token::For => "for".to_string(),
+        Token::In => "in".to_string(),
+        Token::Match => "match".to_string(),
+        Token::Case => "case".to_string(),
+        Token::Break => "break".to_string(),
+        Token::Print => "print".to_string(),
+        Token::PrintLn => "printLn".to_string(),
+        Token::PrintErr => "printErr".to_string(),
+        Token::Function => "function".to_string(),
+        Token::True => "true".to_string(),
+        Token::False => "false".to_string(),
+        Token::Return => "return".to_string(),
+        Token::Get => "GET".to_string(),
+        Token::From => "from".to_string(),
+        Token::LeftParen => "(".to_string(),
+        Token::RightParen => ")".to_string(),
+        Token::LeftBrace => "{".to_string(),
+        Token::RightBrace => "}".to_string(),
+        Token::LeftBracket => "[".to_string(),
+        Token::RightBracket => "]".to_string(),
+        Token::Semicolon => ";".to_string(),
+        Token::Comma => ",".to_string(),
+        Token::Dot => ".".to_string(),
+        Token::Backtick => "`".to_string(),
+        Token::Arrow => "->".to_string(),
+        Token::ArrowLeft => "<-".to_string(),
+        Token::Colon => ":".to_string(),
+        Token::ColonEqual => ":=".to_string(),
+        Token::AssignRight => "=>".to_string(),
+        Token::Imply => "<=>".to_string(),
+        Token::Comment(text) => format!("//{text}"),
+        Token::DocComment(text) => format!("///{text}"),
+        Token::Newline => "\n".to_string(),
+        Token::Eof => String::new(),
+    }
+}
+
+// Re-escape a string literal's contents
+fn escape_string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,208 @@
+// Syntax highlighting of Pidgin source code (pidgin highlight)
+//
+// The source is split with the lexer (keeping comments), and each token is wrapped in a
+// color according to its kind. Everything between tokens, such as spaces and line breaks,
+// is copied unchanged, so the highlighted text reads exactly like the original.
+use crate::interpreter::is_builtin;
+use crate::lexer::Lexer;
+use crate::token::Token;
+
+// Define the HighlightFormat enum, how colors are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HighlightFormat {
+    Ansi, // Terminal escape codes
+    Html, // <span class="pg-..."> elements inside a <pre> block
+}
+
+// Implement methods for HighlightFormat
+impl HighlightFormat {
+    // Find a format by the name given to --format
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "ansi" => Some(HighlightFormat::Ansi),
+            "html" => Some(HighlightFormat::Html),
+            _ => None,
+        }
+    }
+}
+
+// Define the Category enum, the kinds of token that get their own color
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Category {
+    Keyword,  // let, if, function, ...
+    Constant, // true, false
+    Number,   // Number literals
+    String,   // String literals
+    Builtin,  // Built-in functions: readLine, Date, ...
+    Operator, // + - * / = == -> ...
+    Comment,  // Comments and doc comments
+}
+
+// Implement methods for Category
+impl Category {
+    // Find a category by name, e.g. "keyword" (as used in ~/.pidginrc themes)
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "keyword" => Some(Category::Keyword),
+            "constant" => Some(Category::Constant),
+            "number" => Some(Category::Number),
+            "string" => Some(Category::String),
+            "builtin" => Some(Category::Builtin),
+            "operator" => Some(Category::Operator),
+            "comment" => Some(Category::Comment),
+            _ => None,
+        }
+    }
+
+    // Get the HTML class name for this category
+    pub fn class(self) -> &'static str {
+        match self {
+            Category::Keyword => "pg-keyword",
+            Category::Constant => "pg-constant",
+            Category::Number => "pg-number",
+            Category::String => "pg-string",
+            Category::Builtin => "pg-builtin",
+            Category::Operator => "pg-operator",
+            Category::Comment => "pg-comment",
+        }
+    }
+
+    // Get the terminal escape code that starts this category's color
+    pub fn ansi(self) -> &'static str {
+        match self {
+            Category::Keyword => "\x1b[35m",  // Magenta
+            Category::Constant => "\x1b[33m", // Yellow
+            Category::Number => "\x1b[36m",   // Cyan
+            Category::String => "\x1b[32m",   // Green
+            Category::Builtin => "\x1b[34m",  // Blue
+            Category::Operator => "\x1b[1m",  // Bold
+            Category::Comment => "\x1b[90m",  // Gray
+        }
+    }
+}
+
+const ANSI_RESET: &str = "\x1b[0m"; // Ends a color
+
+// Get the color category of a token (None for plain text such as names and punctuation)
+pub fn category(token: &Token) -> Option<Category> {
+    match token {
+        Token::Let
+        | Token::If
+        | Token::Else
+        | Token::While
+        | Token::For
+        | Token::In
+        | Token::Match
+        | Token::Case
+        | Token::Break
+        | Token::Print
+        | Token::PrintLn
+        | Token::PrintErr
+        | Token::Function
+        | Token::Return
+        | Token::Get
+        | Token::From => Some(Category::Keyword),
+        Token::True | Token::False => Some(Category::Constant),
+        Token::Number(_) => Some(Category::Number),
+        Token::String(_) => Some(Category::String),
+        Token::Identifier(name) if is_builtin(name) => Some(Category::Builtin),
+        Token::Plus
+        | Token::Minus
+        | Token::Star
+        | Token::Slash
+        | Token::Assign
+        | Token::Equal
+        | Token::NotEqual
+        | Token::Less
+        | Token::Greater
+        | Token::LessEqual
+        | Token::GreaterEqual
+        | Token::Arrow
+        | Token::ArrowLeft
+        | Token::ColonEqual
+        | Token::AssignRight
+        | Token::Imply => Some(Category::Operator),
+        Token::Comment(_) | Token::DocComment(_) => Some(Category::Comment),
+        _ => None,
+    }
+}
+
+// Highlight source code, failing only if it cannot be lexed
+pub fn highlight(source: &str, format: HighlightFormat) -> Result<String, String> {
+    highlight_with(source, format, Category::ansi)
+}
+
+// Highlight source code, taking each category's terminal escape code from `ansi` (a color theme)
+pub fn highlight_with<'a>(
+    source: &str,
+    format: HighlightFormat,
+    ansi: impl Fn(Category) -> &'a str,
+) -> Result<String, String> {
+    let tokens = Lexer::with_comments(source).tokenize()?;
+    let chars: Vec<char> = source.chars().collect();
+
+    // Where each line starts, to turn a token's line and column into a character offset
+    let mut line_starts = vec![0];
+    line_starts.extend(
+        chars
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| **c == '\n')
+            .map(|(i, _)| i + 1),
+    );
+
+    let mut out = String::new();
+    if format == HighlightFormat::Html {
+        out.push_str("<pre class=\"pidgin\"><code>");
+    }
+
+    let mut position = 0; // Characters of the source written so far
+    for info in &tokens {
+        let Some(category) = category(&info.token) else {
+            continue; // Written as plain text along with the gaps between tokens
+        };
+        let start = line_starts[info.line - 1] + info.column - 1;
+        let end = (start + info.length).min(chars.len());
+        write_plain(&mut out, &chars[position..start], format);
+
+        let text: String = chars[start..end].iter().collect();
+        match format {
+            HighlightFormat::Ansi => {
+                out.push_str(ansi(category));
+                out.push_str(&text);
+                out.push_str(ANSI_RESET);
+            }
+            HighlightFormat::Html => {
+                out.push_str(&format!(
+                    "<span class=\"{}\">{}</span>",
+                    category.class(),
+                    escape_html(&text)
+                ));
+            }
+        }
+        position = end;
+    }
+    write_plain(&mut out, &chars[position..], format);
+
+    if format == HighlightFormat::Html {
+        out.push_str("</code></pre>\n");
+    }
+    Ok(out)
+}
+
+// Write source text that is not colored
+fn write_plain(out: &mut String, text: &[char], format: HighlightFormat) {
+    let text: String = text.iter().collect();
+    match format {
+        HighlightFormat::Ansi => out.push_str(&text),
+        HighlightFormat::Html => out.push_str(&escape_html(&text)),
+    }
+}
+
+// Escape text for use inside HTML
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
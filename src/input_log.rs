@@ -0,0 +1,98 @@
+// Recording a program's input and playing it back (--record-input, --replay-input)
+//
+// A recording is the text readLine, confirm, and prompt read during a run, exactly as they read
+// it: one answer per line. It is written as the input is read, so a run stopped part way still
+// leaves the answers given so far. Replaying it gives the program the same answers in the same
+// order; a program that asks for more than was recorded (because it has changed since) stops
+// with an error rather than waiting for input that will never come.
+use std::fs::File;
+use std::io::{self, BufRead, Read, Write};
+
+// Define the Recorder struct, a reader that copies whatever is read from it to a file
+pub struct Recorder<R> {
+    input: R,  // Where the input comes from (stdin, or a file)
+    log: File, // Where it is copied
+}
+
+// Implement methods for Recorder
+impl<R: BufRead> Recorder<R> {
+    // Record what is read from `input` in `log`
+    pub fn new(input: R, log: File) -> Self {
+        Recorder { input, log }
+    }
+}
+
+// Implement Read for Recorder (input is only read line by line, through BufRead)
+impl<R: BufRead> Read for Recorder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let count = available.len().min(buf.len());
+        buf[..count].copy_from_slice(&available[..count]);
+        self.consume(count);
+        Ok(count)
+    }
+}
+
+// Implement BufRead for Recorder, copying input to the file as it is consumed, so text read
+// ahead but never used is not recorded
+impl<R: BufRead> BufRead for Recorder<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.input.fill_buf()
+    }
+
+    fn consume(&mut self, amount: usize) {
+        if let Ok(available) = self.input.fill_buf() {
+            let _ = self
+                .log
+                .write_all(&available[..amount.min(available.len())]);
+        }
+        self.input.consume(amount);
+    }
+}
+
+// Define the Replay struct, a reader of a recording that fails once it runs out
+pub struct Replay<R> {
+    recording: R, // The recorded input
+    path: String, // The recording's file, for the error
+}
+
+// Implement methods for Replay
+impl<R: BufRead> Replay<R> {
+    // Play back the recording read from `recording`, which came from the file `path`
+    pub fn new(recording: R, path: &str) -> Self {
+        Replay {
+            recording,
+            path: path.to_string(),
+        }
+    }
+}
+
+// Implement Read for Replay (input is only read line by line, through BufRead)
+impl<R: BufRead> Read for Replay<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let count = available.len().min(buf.len());
+        buf[..count].copy_from_slice(&available[..count]);
+        self.consume(count);
+        Ok(count)
+    }
+}
+
+// Implement BufRead for Replay
+impl<R: BufRead> BufRead for Replay<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        let path = &self.path;
+        let available = self.recording.fill_buf()?;
+        if available.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("the program asked for more input than '{path}' recorded"),
+            ));
+        }
+        Ok(available)
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.recording.consume(amount);
+    }
+}
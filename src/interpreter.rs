@@ -1,9 +1,175 @@
-use crate::ast::{BinaryOp, Expr, Program, Stmt, UnaryOp}; // Import AST types
+use crate::ast::{BinaryOp, Expr, MatchCase, Program, Span, Stmt, UnaryOp}; // Import AST types
+use crate::async_host::{AsyncBridge, AsyncHostFunction, Event, HostFuture};
+use crate::error::PidginError;
+use crate::lexer::Lexer;
+use crate::logger::debug; // Import diagnostic logging (shown with --verbose)
+use crate::methods::{extension_type, MethodRegistry};
+use crate::parser::Parser;
+use crate::pattern;
+use crate::symbols::{Slot, SymbolMap, Variables};
 use crate::token::TokenInfo; // Import necessary types from the Token module
-use chrono::{DateTime, Datelike, Local};
+use chrono::{DateTime, Local};
 use std::collections::HashMap; // Import HashMap for variable storage
 use std::fmt;
-use std::io::{self, Write};
+use std::future::Future;
+use std::io::{self, BufRead, BufWriter, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+
+static EMBEDDED_MODULES: OnceLock<HashMap<String, ModuleSource>> = OnceLock::new(); // Modules shipped with the program
+static MODULE_PATHS: OnceLock<Vec<String>> = OnceLock::new(); // More directories to find modules in
+
+// Define the ModuleSource enum, a module provided by the program instead of a file on disk
+#[derive(Debug, Clone)]
+pub enum ModuleSource {
+    Source(String), // Source code (bundled executables)
+    Ast(Program),   // Already parsed (compiled .pgc files)
+}
+
+// Directory where installed packages live (see pidgin install)
+pub const PACKAGES_DIR: &str = "pg_modules";
+
+// Define the Builtin struct, a function built into the interpreter
+#[derive(Debug, Clone, Copy)]
+pub struct Builtin {
+    pub name: &'static str,
+    pub params: &'static str, // Its parameters, as shown to users ("?" if optional)
+    pub min_args: usize,      // Fewest arguments it takes
+    pub max_args: Option<usize>, // Most arguments it takes (None: any number)
+    pub description: &'static str, // What it does, in one line (pidgin builtins, editor hovers)
+}
+
+// Implement methods for Builtin
+impl Builtin {
+    // Describe how many arguments it takes ("1", "0-1", "1+")
+    pub fn arity(&self) -> String {
+        match self.max_args {
+            Some(max) if max == self.min_args => max.to_string(),
+            Some(max) => format!("{}-{max}", self.min_args),
+            None => format!("{}+", self.min_args),
+        }
+    }
+}
+
+// The functions built into the interpreter
+pub const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "readLine",
+        params: "prompt?",
+        min_args: 0,
+        max_args: Some(1),
+        description: "Read a line of input, after showing the prompt",
+    },
+    Builtin {
+        name: "confirm",
+        params: "message",
+        min_args: 1,
+        max_args: Some(1),
+        description: "Ask a yes/no question and return true or false",
+    },
+    Builtin {
+        name: "prompt",
+        params: "message, default?",
+        min_args: 1,
+        max_args: Some(2),
+        description: "Read a line of input, or return the default if it is empty",
+    },
+    Builtin {
+        name: "Date",
+        params: "...",
+        min_args: 0,
+        max_args: Some(3),
+        description: "Create a date: now, from a string (and format), or from year, month, day",
+    },
+    Builtin {
+        name: "Duration",
+        params: "hours, minutes, seconds",
+        min_args: 3,
+        max_args: Some(3),
+        description: "Create a length of time",
+    },
+    Builtin {
+        name: "Object",
+        params: "key, value, ...",
+        min_args: 0,
+        max_args: None,
+        description: "Create an object from key-value pairs",
+    },
+    Builtin {
+        name: "args",
+        params: "",
+        min_args: 0,
+        max_args: Some(0),
+        description: "Get the command-line arguments passed to the program",
+    },
+    Builtin {
+        name: "deepCopy",
+        params: "value",
+        min_args: 1,
+        max_args: Some(1),
+        description: "Copy a value along with every array and object inside it",
+    },
+    Builtin {
+        name: "freeze",
+        params: "value",
+        min_args: 1,
+        max_args: Some(1),
+        description: "Make an array or object, and everything inside it, unchangeable",
+    },
+    Builtin {
+        name: "parallelMap",
+        params: "array, function",
+        min_args: 2,
+        max_args: Some(2),
+        description: "Call a function on every item at once, on several threads",
+    },
+    Builtin {
+        name: "spawn",
+        params: "function, arguments...",
+        min_args: 1,
+        max_args: None,
+        description: "Start calling a function on another thread and return a task for it",
+    },
+    Builtin {
+        name: "wait",
+        params: "task",
+        min_args: 1,
+        max_args: Some(1),
+        description: "Wait for a spawned task to finish and return its result",
+    },
+    Builtin {
+        name: "setTimeout",
+        params: "function, delay",
+        min_args: 2,
+        max_args: Some(2),
+        description: "Call a function once, delay milliseconds from now, and return the timer's id",
+    },
+    Builtin {
+        name: "setInterval",
+        params: "function, delay",
+        min_args: 2,
+        max_args: Some(2),
+        description: "Call a function every delay milliseconds and return the timer's id",
+    },
+    Builtin {
+        name: "clearTimer",
+        params: "id",
+        min_args: 1,
+        max_args: Some(1),
+        description: "Cancel a timer set by setTimeout or setInterval",
+    },
+];
+
+// Find a function built into the interpreter by name
+pub fn find_builtin(name: &str) -> Option<&'static Builtin> {
+    BUILTINS.iter().find(|builtin| builtin.name == name)
+}
+
+// Check whether a name is that of a function built into the interpreter
+pub fn is_builtin(name: &str) -> bool {
+    find_builtin(name).is_some()
+}
 
 // Define a custom result type for handling returns
 #[derive(Debug, Clone)]
@@ -22,8 +188,109 @@ pub enum Value {
     DynamicArray(Vec<Value>),
     Object(HashMap<String, Value>),   // Object with key-value pairs
     Date(DateTime<Local>),            // Date/time value
+    Duration(f64),                    // Length of time, in seconds (Duration(h, m, s))
     Nil,                              // Nil (no value)
-    Function(Vec<String>, Box<Stmt>), // Function value
+    Function(Vec<String>, Arc<Stmt>), // Function value (its body is shared by every copy)
+    Iterator(HostIterator),           // Values produced on demand by the host (for-in)
+    Task(TaskHandle),                 // A function call running on another thread (spawn)
+    Frozen(Box<Value>),               // An array or object that cannot be changed (freeze)
+}
+
+// Define the Traversal enum, where a for-in loop takes its next item from
+enum Traversal {
+    Items(std::vec::IntoIter<Value>), // Elements, characters, or [key, value] entries
+    Host(HostIterator),               // An iterator from the host program
+    Protocol {
+        params: Vec<String>, // The iterator's next function (its one parameter is the state)
+        body: Arc<Stmt>,
+        state: Value, // Passed to next, which returns the state for the following item
+    },
+}
+
+// Define the HostIterator struct, a sequence of values the host program produces one at a time
+// (see Value::iterator). Copies share their position, so traversing one uses up all of them.
+#[derive(Clone)]
+pub struct HostIterator(Arc<Mutex<Box<dyn Iterator<Item = Value> + Send>>>);
+
+// Implement methods for HostIterator
+impl HostIterator {
+    // Take the next value, or None once the sequence is used up
+    pub fn next_value(&self) -> Option<Value> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).next()
+    }
+}
+
+// Implement Debug for HostIterator (the values it will produce are not known yet)
+impl fmt::Debug for HostIterator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "HostIterator")
+    }
+}
+
+// Define the TaskHandle struct, a function call spawn started on a thread of its own. Copies
+// share the task, and once one of them is waited for they all give its result.
+#[derive(Clone)]
+pub struct TaskHandle(Arc<Mutex<TaskState>>);
+
+// Define the TaskState enum, how far a task has got
+enum TaskState {
+    Running(thread::JoinHandle<TaskOutcome>),
+    Finished(Result<Value, String>), // Already waited for (or run straight away)
+}
+
+// Define the TaskOutcome struct, what a task's thread hands back when it finishes
+struct TaskOutcome {
+    result: Result<Value, String>, // The function's return value or error
+    steps: usize,                  // Steps it took, counted against the spawner's limit
+    output: String,                // What it printed, if the spawner's output is captured
+}
+
+// Implement methods for TaskHandle
+impl TaskHandle {
+    // Wait for the task to finish and get its result, plus its outcome the first time (when
+    // its steps and output are still to be passed on)
+    fn finish(&self) -> (Result<Value, String>, Option<TaskOutcome>) {
+        let mut state = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        let running = std::mem::replace(&mut *state, TaskState::Finished(Ok(Value::Nil)));
+        match running {
+            TaskState::Running(handle) => {
+                let outcome = handle.join().unwrap_or_else(|_| TaskOutcome {
+                    result: Err("Task thread panicked".to_string()),
+                    steps: 0,
+                    output: String::new(),
+                });
+                *state = TaskState::Finished(outcome.result.clone());
+                (outcome.result.clone(), Some(outcome))
+            }
+            TaskState::Finished(result) => {
+                *state = TaskState::Finished(result.clone());
+                (result, None)
+            }
+        }
+    }
+
+    // Check whether this is the same task as another handle
+    fn same_task(&self, other: &TaskHandle) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+// Define the Timer struct, a call setTimeout or setInterval scheduled for later
+struct Timer {
+    id: usize,           // What setTimeout/setInterval returned, for clearTimer
+    due: f64,            // When it next runs, in milliseconds on the timer clock
+    every: Option<f64>,  // How often an interval repeats, in milliseconds
+    name: String,        // The function's name, for the call stack
+    params: Vec<String>, // The function (it takes no arguments, or the run count)
+    body: Arc<Stmt>,
+    runs: usize, // How many times it has run
+}
+
+// Implement Debug for TaskHandle (its result is not known until it is waited for)
+impl fmt::Debug for TaskHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "TaskHandle")
+    }
 }
 
 // Implement Display trait for Value
@@ -35,41 +302,162 @@ impl fmt::Display for Value {
             Value::Boolean(b) => write!(f, "{b}"), // Convert bool to string
             Value::Nil => write!(f, "nil"),       // Nil as "nil"
             Value::Function(params, _body) => {
-                let params_str = params.join(", ");
-                write!(f, "function({params_str}) {{ ... }}")
+                write!(f, "function(")?;
+                write_separated(f, params)?;
+                write!(f, ") {{ ... }}")
             }
+            // Containers write their elements straight to the formatter, one at a time
             Value::FixedArray(arr) => {
-                let elements = arr
-                    .iter()
-                    .map(|v| v.to_string())
-                    .collect::<Vec<String>>()
-                    .join(", ");
-                write!(f, "[{elements}]")
+                write!(f, "[")?;
+                write_separated(f, arr)?;
+                write!(f, "]")
             }
             Value::DynamicArray(arr) => {
-                let elements = arr
-                    .iter()
-                    .map(|v| v.to_string())
-                    .collect::<Vec<String>>()
-                    .join(", ");
-                write!(f, "{{{elements}}}")
+                write!(f, "{{")?;
+                write_separated(f, arr)?;
+                write!(f, "}}")
             }
             Value::Object(obj) => {
-                let mut pairs = Vec::new();
-                for (key, value) in obj {
-                    pairs.push(format!("{key}: {value}"));
+                write!(f, "{{ ")?;
+                for (index, (key, value)) in obj.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{key}: {value}")?;
                 }
-                write!(f, "{{ {} }}", pairs.join(", "))
+                write!(f, " }}")
             }
             Value::Date(dt) => {
                 write!(f, "{}", dt.format("%Y-%m-%d %H:%M:%S"))
             }
+            Value::Duration(seconds) => write!(f, "{}", format_duration(*seconds)),
+            Value::Iterator(_) => write!(f, "<iterator>"),
+            Value::Task(_) => write!(f, "<task>"),
+            Value::Frozen(inner) => write!(f, "{inner}"), // Shown like any other value
         }
     }
 }
 
+// Write formatted text to a writer, returning the last byte written and whether a line break
+// was among them. Output is best-effort, as with print!: a failing writer does not stop the
+// program.
+fn write_tracked(writer: impl Write, text: fmt::Arguments<'_>) -> (Option<u8>, bool) {
+    // A writer that remembers what passed through it
+    struct Tracked<W> {
+        inner: W,
+        last: Option<u8>,
+        newline: bool,
+    }
+
+    impl<W: Write> Write for Tracked<W> {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let written = &buf[..self.inner.write(buf)?];
+            if let Some(&last) = written.last() {
+                self.last = Some(last);
+                self.newline |= written.contains(&b'\n');
+            }
+            Ok(written.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.inner.flush()
+        }
+    }
+
+    let mut tracked = Tracked {
+        inner: writer,
+        last: None,
+        newline: false,
+    };
+    let _ = tracked.write_fmt(text);
+    (tracked.last, tracked.newline)
+}
+
+// Write items separated by commas, as arrays and parameter lists are shown
+fn write_separated<T: fmt::Display>(f: &mut fmt::Formatter<'_>, items: &[T]) -> fmt::Result {
+    for (index, item) in items.iter().enumerate() {
+        if index > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{item}")?;
+    }
+    Ok(())
+}
+
 // Implement methods for Value
 impl Value {
+    // Get the name of the value's type, as shown to users (e.g. "number", "dynamic array")
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Boolean(_) => "boolean",
+            Value::FixedArray(_) => "fixed array",
+            Value::DynamicArray(_) => "dynamic array",
+            Value::Object(_) => "object",
+            Value::Date(_) => "date",
+            Value::Duration(_) => "duration",
+            Value::Nil => "nil",
+            Value::Function(..) => "function",
+            Value::Iterator(_) => "iterator",
+            Value::Task(_) => "task",
+            Value::Frozen(inner) => inner.type_name(),
+        }
+    }
+
+    // Get the value a frozen value wraps, or the value itself if it is not frozen
+    pub fn thawed(&self) -> &Value {
+        match self {
+            Value::Frozen(inner) => inner,
+            other => other,
+        }
+    }
+
+    // Make an array or object, and every array and object inside it, unchangeable (freeze);
+    // other values cannot be changed anyway and are returned as they are
+    fn freeze(self) -> Value {
+        let all = |items: Vec<Value>| items.into_iter().map(Value::freeze).collect();
+        let frozen = match self {
+            Value::FixedArray(items) => Value::FixedArray(all(items)),
+            Value::DynamicArray(items) => Value::DynamicArray(all(items)),
+            Value::Object(fields) => Value::Object(
+                fields
+                    .into_iter()
+                    .map(|(key, value)| (key, value.freeze()))
+                    .collect(),
+            ),
+            other => return other,
+        };
+        Value::Frozen(Box::new(frozen))
+    }
+
+    // Wrap a sequence the host program produces (lines of a file, a range, ...) in a value that
+    // for-in traverses, taking one item at a time
+    pub fn iterator(items: impl Iterator<Item = Value> + Send + 'static) -> Value {
+        Value::Iterator(HostIterator(Arc::new(Mutex::new(Box::new(items)))))
+    }
+
+    // Copy the value and everything nested inside it (deepCopy). Values never share their
+    // contents, so a copy cannot contain itself; only iterators, whose copies share a position,
+    // cannot be copied
+    fn deep_copy(&self) -> Result<Value, String> {
+        let all = |items: &[Value]| items.iter().map(Value::deep_copy).collect::<Result<_, _>>();
+        match self {
+            Value::FixedArray(items) => Ok(Value::FixedArray(all(items)?)),
+            Value::DynamicArray(items) => Ok(Value::DynamicArray(all(items)?)),
+            Value::Object(fields) => fields
+                .iter()
+                .map(|(key, value)| Ok((key.clone(), value.deep_copy()?)))
+                .collect::<Result<_, String>>()
+                .map(Value::Object),
+            Value::Iterator(_) => Err(
+                "deepCopy() cannot copy an iterator: copies would share its position".to_string(),
+            ),
+            Value::Frozen(inner) => inner.deep_copy(), // A copy can be changed again
+            other => Ok(other.clone()),
+        }
+    }
+
     // Check if the value is truthy (for conditionals)
     fn is_truthy(&self) -> bool {
         match self {
@@ -81,11 +469,13 @@ impl Value {
 
     // Optimized equality check
     fn is_equal(&self, other: &Value) -> bool {
-        match (self, other) {
+        match (self.thawed(), other.thawed()) {
             (Value::Number(a), Value::Number(b)) => a == b,
             (Value::String(a), Value::String(b)) => a == b,
             (Value::Boolean(a), Value::Boolean(b)) => a == b,
+            (Value::Duration(a), Value::Duration(b)) => a == b,
             (Value::Nil, Value::Nil) => true,
+            (Value::Task(a), Value::Task(b)) => a.same_task(b),
             (Value::FixedArray(a), Value::FixedArray(b))
             | (Value::DynamicArray(a), Value::DynamicArray(b)) => {
                 if a.len() != b.len() {
@@ -96,30 +486,647 @@ impl Value {
             _ => false,
         }
     }
+
+    // Count this value plus every value nested inside it (for live-value limits)
+    fn count_values(&self) -> usize {
+        match self.thawed() {
+            Value::FixedArray(arr) | Value::DynamicArray(arr) => {
+                1 + arr.iter().map(Value::count_values).sum::<usize>()
+            }
+            Value::Object(obj) => 1 + obj.values().map(Value::count_values).sum::<usize>(),
+            _ => 1,
+        }
+    }
+}
+
+// Write a duration as hours, minutes, and seconds: 1:30:00, -0:00:05, 0:00:02.5
+fn format_duration(seconds: f64) -> String {
+    let sign = if seconds < 0.0 { "-" } else { "" };
+    let total = seconds.abs();
+    let hours = (total / 3600.0).floor();
+    let minutes = ((total - hours * 3600.0) / 60.0).floor();
+    let seconds = total - hours * 3600.0 - minutes * 60.0;
+    let pad = |n: f64| format!("{}{n}", if n < 10.0 { "0" } else { "" });
+    format!("{sign}{hours}:{}:{}", pad(minutes), pad(seconds))
+}
+
+// Move a date by a number of seconds (date + duration), to the millisecond
+fn shift_date(date: &DateTime<Local>, seconds: f64) -> Result<Value, String> {
+    chrono::TimeDelta::try_milliseconds((seconds * 1000.0) as i64)
+        .and_then(|delta| date.checked_add_signed(delta))
+        .map(Value::Date)
+        .ok_or_else(|| "Date out of range".to_string())
+}
+
+// A function provided by the program embedding Pidgin (see Interpreter::register_builtin); it
+// can be shared between threads, so a Snapshot can carry it
+pub type HostFunction = Arc<dyn Fn(&[Value]) -> Result<Value, String> + Send + Sync>;
+
+// Resource limits applied while executing a program (None means unlimited)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Limits {
+    pub max_steps: Option<usize>, // Maximum number of evaluated statements/expressions
+    pub max_array_len: Option<usize>, // Maximum number of elements in a single array
+    pub max_string_len: Option<usize>, // Maximum length (in bytes) of a single string
+    pub max_values: Option<usize>, // Maximum number of values stored across all variables
 }
 
+// A writer that program output goes to instead of stdout or stderr (see set_output)
+pub type OutputStream = Arc<Mutex<Box<dyn Write + Send>>>;
+
+// A reader that readLine, confirm, and prompt read from instead of stdin (see set_input)
+pub type InputStream = Arc<Mutex<Box<dyn BufRead + Send>>>;
+
+// Define the Output enum, where the text a program prints goes
+#[derive(Clone, Default)]
+pub enum Output {
+    #[default]
+    Stdio, // Straight to the process's stdout and stderr
+    Captured(String), // Collected in memory, stdout and stderr together (see take_output)
+    Streams(OutputStream, OutputStream), // Written to the given replacements for stdout and stderr
+}
+
+// Implement Debug for Output (writers have no Debug form of their own)
+impl fmt::Debug for Output {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Output::Stdio => write!(f, "Stdio"),
+            Output::Captured(text) => f.debug_tuple("Captured").field(text).finish(),
+            Output::Streams(..) => write!(f, "Streams(..)"),
+        }
+    }
+}
+
+// Define the StatementHook trait, code that observes a program as it runs: every statement,
+// function call and error (pidgin debug, --trace, --coverage, and embedders' profilers)
+pub trait StatementHook: Send {
+    // Called with each statement about to run (blocks excepted); an error stops the program
+    fn before_statement(
+        &mut self,
+        stmt: &Stmt,
+        interpreter: &mut Interpreter,
+    ) -> Result<(), String>;
+
+    // Called after a statement has run without error (default: nothing to do)
+    fn after_statement(
+        &mut self,
+        _stmt: &Stmt,
+        _interpreter: &mut Interpreter,
+    ) -> Result<(), String> {
+        Ok(())
+    }
+
+    // Called when a user-defined function starts, after its arguments are bound (default:
+    // nothing to do); an error stops the program
+    fn function_enter(
+        &mut self,
+        _name: &str,
+        _arguments: &[Value],
+        _interpreter: &mut Interpreter,
+    ) -> Result<(), String> {
+        Ok(())
+    }
+
+    // Called when a user-defined function finishes, with its return value or the error that
+    // ended it (default: nothing to do)
+    fn function_exit(
+        &mut self,
+        _name: &str,
+        _result: Result<&Value, &str>,
+        _interpreter: &mut Interpreter,
+    ) -> Result<(), String> {
+        Ok(())
+    }
+
+    // Called once when a statement fails, with the innermost failing statement (default:
+    // nothing to do); the error then stops the program as usual
+    fn on_error(&mut self, _stmt: &Stmt, _error: &str, _interpreter: &mut Interpreter) {}
+
+    // Get the hook for a module the program imports (by default, modules run without one)
+    fn module_hook(&mut self, _file: &str, _program: &Program) -> Option<Box<dyn StatementHook>> {
+        None
+    }
+}
+
+// Define the Snapshot struct, an interpreter's definitions frozen so that any number of fresh
+// interpreters can start from them, on any thread, or one can roll back to them (see
+// Interpreter::snapshot and Interpreter::restore; src/snapshot_file.rs saves them to disk)
+#[derive(Clone)]
+pub struct Snapshot {
+    globals: Arc<Variables>, // Variables and functions defined when it was taken
+    limits: Limits,
+    script_args: Vec<String>,
+    host_functions: HashMap<String, HostFunction>,
+    async_functions: HashMap<String, AsyncHostFunction>,
+    methods: MethodRegistry,
+}
+
+// Implement methods for Snapshot
+impl Snapshot {
+    // Create an interpreter that starts with the snapshot's definitions; what it does
+    // afterwards affects neither the snapshot nor other interpreters made from it
+    pub fn interpreter(&self) -> Interpreter {
+        let mut interpreter = Interpreter::new();
+        interpreter.globals = (*self.globals).clone();
+        interpreter.set_limits(self.limits); // Also counts the live values it starts with
+        interpreter.script_args = self.script_args.clone();
+        interpreter.host_functions = self.host_functions.clone();
+        interpreter.async_functions = self.async_functions.clone();
+        interpreter.methods = self.methods.clone();
+        interpreter
+    }
+
+    // Get the variables and functions the snapshot holds
+    pub fn variables(&self) -> &Variables {
+        &self.globals
+    }
+
+    // Create a snapshot holding only variables and functions (one read back from disk)
+    pub(crate) fn from_variables(globals: Variables) -> Self {
+        Snapshot {
+            globals: Arc::new(globals),
+            limits: Limits::default(),
+            script_args: Vec::new(),
+            host_functions: HashMap::new(),
+            async_functions: HashMap::new(),
+            methods: MethodRegistry::new(),
+        }
+    }
+}
+
+// Values and snapshots hold no thread-bound state, so they can be sent to and shared between
+// threads, and an Interpreter can be moved to another thread (checked here at compile time)
+const _: () = {
+    fn assert_send_sync<T: Send + Sync>() {}
+    fn assert_send<T: Send>() {}
+    let _ = assert_send_sync::<Value>;
+    let _ = assert_send_sync::<Snapshot>;
+    let _ = assert_send::<Interpreter>;
+};
+
 // Define the Interpreter struct, which executes the AST
 pub struct Interpreter {
-    globals: HashMap<String, Value>, // Store global variables
-    tokens: Option<Vec<TokenInfo>>,
-    current: usize, // Current position in the token stream
+    globals: Variables,                                  // Store global variables
+    saved: Vec<(usize, Option<Value>, usize)>, // Variables running functions changed (see store_symbol)
+    saved_by: SymbolMap<usize>, // For each symbol, the depth of the call that saved its value
+    frames: Vec<usize>,         // For each running function, the length of saved when it started
+    lexer: Lexer, // Lexes the source given to run and evaluate_source, keeping its memory
+    tokens: Vec<TokenInfo>, // The tokens of that source (emptied once parsed, keeping its memory)
+    limits: Limits, // Resource limits for sandboxed execution
+    steps: usize, // Statements/expressions evaluated in the current run
+    live_values: usize, // Values currently stored in variables (tracked when max_values is set)
+    script_args: Vec<String>, // Command-line arguments passed to the program (see args())
+    output: Output, // Where print, printLn, and printErr write
+    input: Option<InputStream>, // Where readLine, confirm, and prompt read (stdin if None)
+    stdout: BufWriter<io::Stdout>, // Output::Stdio's stdout text, written out in blocks (see write_output)
+    stdout_is_terminal: bool,      // Whether stdout is a terminal, which gets each line as it ends
+    call_stack: Vec<String>,       // Names of the user-defined functions currently running
+    hook: Option<Box<dyn StatementHook>>, // Called around each statement, if set
+    mid_line: bool,                // Whether the last text printed did not end with a newline
+    interrupt: Option<Arc<AtomicBool>>, // Set from outside (e.g. on Ctrl-C) to stop the run
+    host_functions: HashMap<String, HostFunction>, // Native functions registered by an embedder
+    async_functions: HashMap<String, AsyncHostFunction>, // Async ones (see interpret_async)
+    methods: MethodRegistry,       // Methods of each type of value, built-in and registered
+    async_bridge: Option<AsyncBridge>, // Where async calls go while interpret_async runs
+    tasks: Vec<TaskHandle>,        // Tasks spawned and not yet waited for
+    timers: Vec<Timer>, // Calls scheduled by setTimeout and setInterval, run once the program ends
+    next_timer: usize,  // The id the next timer gets
+    timer_clock: f64,   // The time of the timer running now (where the clock does not run)
+    error_observed: bool, // Whether the hook has been told about the error now unwinding
+    stopped_by_hook: bool, // Whether the error now unwinding came from the hook itself
+}
+
+// Implement Default for Interpreter (the same as Interpreter::new)
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 // Implement methods for Interpreter
 impl Interpreter {
     // Create a new Interpreter
-    pub fn new(tokens: Option<Vec<TokenInfo>>) -> Self {
+    pub fn new() -> Self {
         Self {
-            globals: HashMap::new(), // Start with empty globals
-            tokens: Some(tokens.unwrap_or_default()),
-            current: 0, // Start at the first token
+            globals: Variables::default(), // Start with empty globals
+            saved: Vec::new(),
+            saved_by: SymbolMap::default(),
+            frames: Vec::new(),
+            lexer: Lexer::new(""),
+            tokens: Vec::new(),
+            limits: Limits::default(),
+            steps: 0,
+            live_values: 0,
+            script_args: Vec::new(),
+            output: Output::Stdio,
+            input: None,
+            stdout: BufWriter::new(io::stdout()),
+            stdout_is_terminal: io::stdout().is_terminal(),
+            call_stack: Vec::new(),
+            hook: None,
+            mid_line: false,
+            interrupt: None,
+            host_functions: HashMap::new(),
+            async_functions: HashMap::new(),
+            methods: MethodRegistry::new(),
+            async_bridge: None,
+            tasks: Vec::new(),
+            timers: Vec::new(),
+            next_timer: 1,
+            timer_clock: 0.0,
+            error_observed: false,
+            stopped_by_hook: false,
+        }
+    }
+
+    // Run a hook before every statement from now on
+    pub fn set_statement_hook(&mut self, hook: Box<dyn StatementHook>) {
+        self.hook = Some(hook);
+    }
+
+    // Get the current variables (inside a function: the globals plus its parameters)
+    pub fn variables(&self) -> &Variables {
+        &self.globals
+    }
+
+    // Freeze the current definitions (variables, functions, limits, arguments and registered
+    // builtins and methods) into a Snapshot; hooks and output settings are not included
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            globals: Arc::new(self.globals.clone()),
+            limits: self.limits,
+            script_args: self.script_args.clone(),
+            host_functions: self.host_functions.clone(),
+            async_functions: self.async_functions.clone(),
+            methods: self.methods.clone(),
+        }
+    }
+
+    // Roll the variables and functions back to those of a snapshot, discarding everything
+    // defined since; limits, arguments, builtins, hooks and output settings are kept as they are
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.globals = (*snapshot.globals).clone();
+        self.live_values = self.globals.values().map(Value::count_values).sum();
+    }
+
+    // Define or replace a global variable from outside a program (e.g. to pass data in)
+    pub fn set_variable(&mut self, name: &str, value: Value) -> Result<(), String> {
+        self.store_variable(name, value)
+    }
+
+    // Get the names of the functions currently running, outermost first
+    pub fn call_stack(&self) -> &[String] {
+        &self.call_stack
+    }
+
+    // Evaluate a single expression given as source code, e.g. "x + 1"
+    pub fn evaluate_source(&mut self, source: &str) -> Result<Value, String> {
+        // Parsed as an expression rather than a statement, so {1, 2} is an array, not a block
+        self.lexer.reset(source);
+        self.lexer.tokenize_into(&mut self.tokens)?;
+        let parsed = Parser::new(&self.tokens).parse_expression();
+        self.tokens.clear(); // Drop the names and strings the tokens hold
+        let result = parsed.and_then(|expr| self.evaluate_expr(&expr));
+        self.flush_stdout();
+        result
+    }
+
+    // Set the resource limits used for subsequent runs
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+        self.live_values = self.globals.values().map(Value::count_values).sum();
+    }
+
+    // Stop the running program at its next step whenever the flag is set (the flag is then cleared)
+    pub fn set_interrupt(&mut self, flag: Arc<AtomicBool>) {
+        self.interrupt = Some(flag);
+    }
+
+    // Make a Rust function callable from scripts by name, like the built-in functions (which
+    // keep their names: registering "Date" has no effect)
+    pub fn register_builtin(
+        &mut self,
+        name: &str,
+        function: impl Fn(&[Value]) -> Result<Value, String> + Send + Sync + 'static,
+    ) {
+        self.host_functions
+            .insert(name.to_string(), Arc::new(function));
+    }
+
+    // Add a method that scripts can call on values of one type (a name from
+    // methods::TYPE_NAMES, e.g. "string" or "dynamic array"), or replace a built-in one
+    pub fn register_method(
+        &mut self,
+        type_name: &str,
+        name: &str,
+        arity: usize,
+        function: impl Fn(&Value, &[Value]) -> Result<Value, String> + Send + Sync + 'static,
+    ) -> Result<(), String> {
+        self.methods.register(type_name, name, arity, function)
+    }
+
+    // Make an async Rust function callable from scripts by name; scripts that call it must be
+    // run with interpret_async, which awaits the future it returns
+    pub fn register_async_builtin<F, Fut>(&mut self, name: &str, function: F)
+    where
+        F: Fn(Vec<Value>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Value, String>> + Send + 'static,
+    {
+        let function = move |arguments| Box::pin(function(arguments)) as HostFuture;
+        self.async_functions
+            .insert(name.to_string(), Arc::new(function));
+    }
+
+    // Run source code, awaiting the async builtins it calls (see src/async_host.rs); the
+    // program itself runs on a thread of its own, so the caller's executor is never blocked.
+    // If the returned future is dropped before it finishes, the interpreter is left empty
+    pub async fn interpret_async(&mut self, source: &str) -> Result<(), PidginError> {
+        let bridge = AsyncBridge::default();
+        let functions = self.async_functions.clone();
+        let mut interpreter = std::mem::take(self);
+        interpreter.async_bridge = Some(bridge.clone());
+
+        let source = source.to_string();
+        let program_bridge = bridge.clone();
+        std::thread::spawn(move || {
+            // A panic must still hand the interpreter back, so the awaiting task finishes
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                crate::run_with_interpreter(&source, &mut interpreter)
+            }))
+            .unwrap_or_else(|_| Err(PidginError::internal("Pidgin panicked")));
+            interpreter.async_bridge = None;
+            program_bridge.send(Event::Done(Box::new(interpreter), result));
+        });
+
+        loop {
+            match bridge.next().await {
+                Event::Call(name, arguments, reply) => {
+                    let result = match functions.get(&name) {
+                        Some(function) => function(arguments).await,
+                        None => Err(format!("Undefined function '{name}'")),
+                    };
+                    let _ = reply.send(result);
+                }
+                Event::Done(interpreter, result) => {
+                    *self = *interpreter;
+                    return result;
+                }
+            }
+        }
+    }
+
+    // Set the command-line arguments returned by the args() builtin
+    pub fn set_script_args(&mut self, args: Vec<String>) {
+        self.script_args = args;
+    }
+
+    // Collect printed text in memory instead of writing it to stdout and stderr
+    pub fn capture_output(&mut self) {
+        self.output = Output::Captured(String::new());
+    }
+
+    // Send what print and printLn write to `stdout`, and what printErr writes to `stderr`
+    pub fn set_output(
+        &mut self,
+        stdout: impl Write + Send + 'static,
+        stderr: impl Write + Send + 'static,
+    ) {
+        self.output = Output::Streams(
+            Arc::new(Mutex::new(Box::new(stdout))),
+            Arc::new(Mutex::new(Box::new(stderr))),
+        );
+    }
+
+    // Read the input of readLine, confirm, and prompt from `input` instead of stdin (modules and
+    // tasks share it, taking lines from it in turn)
+    pub fn set_input(&mut self, input: impl BufRead + Send + 'static) {
+        self.input = Some(Arc::new(Mutex::new(Box::new(input))));
+    }
+
+    // Take the text printed since output was captured (empty when not capturing)
+    pub fn take_output(&mut self) -> String {
+        match &mut self.output {
+            Output::Captured(text) => std::mem::take(text),
+            Output::Stdio | Output::Streams(..) => String::new(),
+        }
+    }
+
+    // Check whether the program has printed part of a line (print without a newline), and
+    // forget it, for callers that end the line before writing their own output
+    pub fn take_mid_line(&mut self) -> bool {
+        std::mem::take(&mut self.mid_line)
+    }
+
+    // Write printed text to the current output
+    fn write_output(&mut self, text: &str, is_err: bool) {
+        self.write_formatted(format_args!("{text}"), is_err);
+    }
+
+    // Write printed text to the current output, formatting it straight into the output so
+    // values are never built up as a String first. Text for the process's stdout is held in a
+    // buffer, so a loop printing many lines does not lock and write stdout for each of them; a
+    // terminal still gets each line as it ends, and anything else when the buffer fills or the
+    // program is done (see flush_stdout).
+    fn write_formatted(&mut self, text: fmt::Arguments<'_>, is_err: bool) {
+        let last = match &mut self.output {
+            Output::Captured(captured) => {
+                let start = captured.len();
+                let _ = fmt::Write::write_fmt(captured, text);
+                captured[start..].bytes().last()
+            }
+            Output::Stdio if is_err => {
+                let _ = self.stdout.flush(); // Text printed earlier shows first
+                write_tracked(io::stderr().lock(), text).0
+            }
+            Output::Stdio => {
+                let (last, newline) = write_tracked(&mut self.stdout, text);
+                if newline && self.stdout_is_terminal {
+                    let _ = self.stdout.flush();
+                }
+                last
+            }
+            Output::Streams(_, stderr) if is_err => stderr
+                .lock()
+                .map_or(None, |mut stream| write_tracked(&mut **stream, text).0),
+            Output::Streams(stdout, _) => stdout
+                .lock()
+                .map_or(None, |mut stream| write_tracked(&mut **stream, text).0),
+        };
+        if let Some(last) = last {
+            self.mid_line = last != b'\n';
+        }
+    }
+
+    // Write out the stdout text still held in the buffer, before anything else can write to
+    // stdout: the host once a run returns, hooks, tasks on other threads, and imported modules
+    fn flush_stdout(&mut self) {
+        let _ = self.stdout.flush();
+    }
+
+    // Flush printed text, so a prompt shows before reading input
+    fn flush_output(&mut self) {
+        match &self.output {
+            Output::Captured(_) => {}
+            Output::Stdio => self.flush_stdout(),
+            Output::Streams(stdout, _) => {
+                let _ = stdout.lock().map(|mut stream| stream.flush());
+            }
+        }
+    }
+
+    // Count one evaluation step, failing once the step limit is exceeded
+    fn tick(&mut self) -> Result<(), String> {
+        if let Some(flag) = &self.interrupt {
+            if flag.swap(false, Ordering::SeqCst) {
+                return Err("Interrupted".to_string());
+            }
+        }
+        self.steps += 1;
+        self.check_steps()
+    }
+
+    // Fail if the steps taken so far exceed the step limit
+    fn check_steps(&self) -> Result<(), String> {
+        if let Some(max_steps) = self.limits.max_steps {
+            if self.steps > max_steps {
+                return Err(format!("Step limit exceeded ({max_steps} steps)"));
+            }
+        }
+        Ok(())
+    }
+
+    // Reject values that exceed the configured array or string size limits
+    fn check_value_size(&self, value: &Value) -> Result<(), String> {
+        match value.thawed() {
+            Value::FixedArray(arr) | Value::DynamicArray(arr) => {
+                if let Some(max_len) = self.limits.max_array_len {
+                    if arr.len() > max_len {
+                        return Err(format!(
+                            "Array length limit exceeded ({} elements, limit is {max_len})",
+                            arr.len()
+                        ));
+                    }
+                }
+            }
+            Value::String(s) => {
+                if let Some(max_len) = self.limits.max_string_len {
+                    if s.len() > max_len {
+                        return Err(format!(
+                            "String length limit exceeded ({} bytes, limit is {max_len})",
+                            s.len()
+                        ));
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    // Store a variable, enforcing the live-value limit when one is configured
+    fn store_variable(&mut self, name: &str, value: Value) -> Result<(), String> {
+        let symbol = self.globals.intern(name);
+        self.store_symbol(symbol, value)
+    }
+
+    // Store a variable by its symbol. Inside a function, the value it replaces is saved the
+    // first time the call changes it, so that leave_call can put it back: a function cannot
+    // change the caller's variables, and undoing just what it changed costs far less than
+    // copying all of them for every call.
+    fn store_symbol(&mut self, symbol: usize, value: Value) -> Result<(), String> {
+        if let Some(max_values) = self.limits.max_values {
+            let old_count = self
+                .globals
+                .get_symbol(symbol)
+                .map_or(0, Value::count_values);
+            let total = self.live_values - old_count + value.count_values();
+            if total > max_values {
+                return Err(format!(
+                    "Value limit exceeded ({total} live values, limit is {max_values})"
+                ));
+            }
+            self.live_values = total;
+        }
+        let depth = self.frames.len();
+        if depth == 0 || self.saved_by.get(&symbol) == Some(&depth) {
+            self.globals.set(symbol, Some(value));
+            return Ok(());
+        }
+        let saved_by = self.saved_by.insert(symbol, depth).unwrap_or(0);
+        let old = self.globals.set(symbol, Some(value));
+        self.saved.push((symbol, old, saved_by));
+        Ok(())
+    }
+
+    // Start a function call: the variables it changes from now on are put back by leave_call
+    fn enter_call(&mut self) {
+        self.frames.push(self.saved.len());
+    }
+
+    // End a function call, putting back the variables it changed
+    fn leave_call(&mut self) {
+        let start = self.frames.pop().unwrap_or_default();
+        for (symbol, value, saved_by) in self.saved.drain(start..).rev() {
+            self.globals.set(symbol, value);
+            if saved_by == 0 {
+                self.saved_by.remove(&symbol); // No running call has saved it any more
+            } else {
+                self.saved_by.insert(symbol, saved_by);
+            }
+        }
+    }
+
+    // Lex, parse, and run source code, keeping the variables from earlier runs. The memory used
+    // to lex and parse it is kept for the next call, so a REPL or an embedder running many
+    // small snippets does not allocate it again for each one.
+    pub fn run(&mut self, source: &str) -> Result<(), PidginError> {
+        let program = self.parse_source(source).map_err(PidginError::syntax)?;
+        self.interpret(program).map_err(PidginError::runtime)
+    }
+
+    // Parse source code with the interpreter's own lexer and token buffer
+    fn parse_source(&mut self, source: &str) -> Result<Program, String> {
+        self.lexer.reset(source);
+        self.lexer.tokenize_into(&mut self.tokens)?;
+        let parsed = Parser::new(&self.tokens).parse();
+        self.tokens.clear(); // Drop the names and strings the tokens hold
+        parsed
+    }
+
+    // Forget everything the programs run so far left behind: variables, tasks not waited for,
+    // timers not yet run, and captured output. The interpreter's settings stay (limits, where
+    // output goes, the hook, script arguments, and registered functions and methods), and so
+    // does the memory it has grown, ready for the next program.
+    pub fn clear(&mut self) {
+        self.flush_stdout();
+        self.globals.clear();
+        self.saved.clear();
+        self.saved_by.clear();
+        self.frames.clear();
+        self.live_values = 0;
+        self.steps = 0;
+        self.call_stack.clear();
+        self.mid_line = false;
+        self.tasks.clear(); // Still-running tasks finish on their own, unwaited for
+        self.timers.clear();
+        self.next_timer = 1;
+        self.timer_clock = 0.0;
+        if let Output::Captured(text) = &mut self.output {
+            text.clear();
         }
     }
 
     // Interpret a program (execute all statements)
-    pub fn interpret(&mut self, program: Program, tokens: Vec<TokenInfo>) -> Result<(), String> {
-        self.tokens = Some(tokens);
-        self.current = 0; // Reset to the beginning of the token stream
+    pub fn interpret(&mut self, program: Program) -> Result<(), String> {
+        let result = self.run_program(program);
+        self.flush_stdout(); // Before the host reports an error or prints anything itself
+        result
+    }
+
+    // Execute a program's statements, then wait for its tasks and timers
+    fn run_program(&mut self, program: Program) -> Result<(), String> {
+        self.steps = 0; // Each run gets a fresh step budget
+        self.stopped_by_hook = false;
         for statement in program.statements {
             // Loop through all statements
             match self.execute_stmt(&statement)? {
@@ -130,57 +1137,170 @@ impl Interpreter {
                 ControlFlow::None => continue,
             }
         }
-        Ok(()) // Return Ok if all statements executed
+        // The program is not done until the tasks it spawned and the timers it set are
+        self.wait_for_tasks()?;
+        self.run_timers()
     }
 
-    // Execute a statement
+    // Run a program given as its syntax tree in JSON (a serialized ast::Program), for frontends
+    // that build programs without writing Pidgin source (see docs/Embedding.md)
+    pub fn interpret_ast_json(&mut self, json: &str) -> Result<(), PidginError> {
+        let program: Program = serde_json::from_str(json)
+            .map_err(|e| PidginError::syntax(format!("Invalid program JSON: {e}")))?;
+        self.interpret(program).map_err(PidginError::runtime)
+    }
+
+    // Call a user-defined function that takes no arguments, by name
+    pub fn call(&mut self, name: &str) -> Result<Value, String> {
+        self.steps = 0; // Each call gets a fresh step budget
+        self.stopped_by_hook = false;
+        let result = self.call_function(name, &Slot::default(), &[]);
+        self.flush_stdout();
+        result
+    }
+
+    // Execute a statement, calling the statement hook (if any) before and after it
     fn execute_stmt(&mut self, stmt: &Stmt) -> Result<ControlFlow, String> {
+        self.tick()?;
+        if matches!(stmt, Stmt::Block(..)) || self.hook.is_none() {
+            return self.locate_stmt(stmt);
+        }
+
+        self.error_observed = false;
+        self.call_hook(|hook, interpreter| hook.before_statement(stmt, interpreter))?;
+        let flow = match self.locate_stmt(stmt) {
+            Ok(flow) => flow,
+            Err(e) => {
+                // Only the innermost statement reports it, not each one it unwinds through
+                if !std::mem::replace(&mut self.error_observed, true) {
+                    self.call_hook(|hook, interpreter| {
+                        hook.on_error(stmt, &e, interpreter);
+                        Ok(())
+                    })?;
+                }
+                return Err(e);
+            }
+        };
+        self.call_hook(|hook, interpreter| hook.after_statement(stmt, interpreter))?;
+        Ok(flow)
+    }
+
+    // Call the hook, if there is one; it is taken out while it runs, so statements it evaluates
+    // do not call it again
+    fn call_hook(
+        &mut self,
+        call: impl FnOnce(&mut dyn StatementHook, &mut Interpreter) -> Result<(), String>,
+    ) -> Result<(), String> {
+        let Some(mut hook) = self.hook.take() else {
+            return Ok(());
+        };
+        self.flush_stdout(); // Hooks write to the terminal themselves
+        let result = call(hook.as_mut(), self);
+        self.hook = Some(hook);
+        if result.is_err() {
+            self.error_observed = true; // The hook's own errors are not reported back to it
+            self.stopped_by_hook = true;
+        }
+        result
+    }
+
+    // Execute a statement itself, giving errors that have no position yet the statement's
+    // (such as a failed import)
+    fn locate_stmt(&mut self, stmt: &Stmt) -> Result<ControlFlow, String> {
+        self.run_stmt(stmt)
+            .map_err(|e| self.locate(e, Some(stmt.span())))
+    }
+
+    // Execute a statement itself
+    fn run_stmt(&mut self, stmt: &Stmt) -> Result<ControlFlow, String> {
         match stmt {
-            Stmt::Return(expr) => {
+            Stmt::Return(expr, _) => {
                 let value = self.evaluate_expr(expr)?;
                 Ok(ControlFlow::Return(value))
             }
-            Stmt::Expression(expr) => {
+            Stmt::Expression(expr, _) => {
                 self.evaluate_expr(expr)?; // Evaluate the expression
                 Ok(ControlFlow::None) // No value to return
             }
-            Stmt::PrintLn { format, arguments } => {
+            Stmt::PrintLn {
+                format, arguments, ..
+            } => {
                 self.print_value(format, arguments, false)?;
-                println!();
+                self.write_output("\n", false);
                 Ok(ControlFlow::None)
             }
-            Stmt::Print { format, arguments } => {
+            Stmt::Print {
+                format, arguments, ..
+            } => {
                 self.print_value(format, arguments, false)?;
+                // Show an unfinished line right away, so prompts and progress updates written
+                // with print (and "\r") appear before the line is ended
+                if self.mid_line {
+                    self.flush_output();
+                }
                 Ok(ControlFlow::None)
             }
-            Stmt::PrintErr { format, arguments } => {
+            Stmt::PrintErr {
+                format, arguments, ..
+            } => {
                 self.print_value(format, arguments, true)?;
-                println!();
+                self.write_output("\n", true);
                 Ok(ControlFlow::None)
             }
-            Stmt::VarDeclaration { name, initializer } => {
+            Stmt::VarDeclaration {
+                name,
+                initializer,
+                slot,
+                ..
+            } => {
                 let value = if let Some(init) = initializer {
                     self.evaluate_expr(init)? // Evaluate initializer if present
                 } else {
                     Value::Nil // Otherwise, use Nil
                 };
-                self.globals.insert(name.clone(), value); // Store variable in globals
+                let symbol = self.globals.symbol(slot, name);
+                self.store_symbol(symbol, value)?; // Store variable in globals
+                Ok(ControlFlow::None)
+            }
+            Stmt::Unpack { names, value, .. } => {
+                let value = self.evaluate_expr(value)?;
+                let items = match value.thawed() {
+                    Value::FixedArray(items) | Value::DynamicArray(items) => items,
+                    other => {
+                        return Err(format!(
+                            "Cannot unpack a {} into {} variables",
+                            other.type_name(),
+                            names.len()
+                        ))
+                    }
+                };
+                if items.len() != names.len() {
+                    return Err(format!(
+                        "Cannot unpack {} values into {} variables",
+                        items.len(),
+                        names.len()
+                    ));
+                }
+                for (name, item) in names.iter().zip(items.clone()) {
+                    self.store_variable(name, item)?;
+                }
                 Ok(ControlFlow::None)
             }
             Stmt::FunctionDeclaration {
                 name,
                 parameters,
                 body,
+                ..
             } => {
-                let function_value = Value::Function(parameters.clone(), body.clone()); // Create function value
-                self.globals.insert(name.clone(), function_value); // Store function in globals
+                let function_value = Value::Function(parameters.clone(), Arc::from(body.clone())); // Create function value
+                self.store_variable(name, function_value)?; // Store function in globals
                 Ok(ControlFlow::None)
             }
-            Stmt::Import { names, module } => {
+            Stmt::Import { names, module, .. } => {
                 self.load_module(names, module)?;
                 Ok(ControlFlow::None)
             }
-            Stmt::Block(statements) => {
+            Stmt::Block(statements, _) => {
                 for stmt in statements {
                     match self.execute_stmt(stmt)? {
                         // Execute each statement in the block
@@ -194,6 +1314,7 @@ impl Interpreter {
                 condition,
                 then_branch,
                 else_branch,
+                ..
             } => {
                 let condition_value = self.evaluate_expr(condition)?; // Evaluate the condition
 
@@ -205,7 +1326,9 @@ impl Interpreter {
                     Ok(ControlFlow::None)
                 }
             }
-            Stmt::While { condition, body } => {
+            Stmt::While {
+                condition, body, ..
+            } => {
                 loop {
                     let condition_value = self.evaluate_expr(condition)?; // Evaluate the condition
                     if !condition_value.is_truthy() {
@@ -219,6 +1342,24 @@ impl Interpreter {
                 }
                 Ok(ControlFlow::None)
             }
+            Stmt::ForIn {
+                variable,
+                iterable,
+                body,
+                slot,
+                ..
+            } => {
+                let iterable = self.evaluate_expr(iterable)?;
+                let mut traversal = Self::traverse(iterable)?;
+                let symbol = self.globals.symbol(slot, variable);
+                while let Some(item) = self.next_item(&mut traversal)? {
+                    self.store_symbol(symbol, item)?; // The loop variable is a global, like any other
+                    if let ControlFlow::Return(value) = self.execute_stmt(body)? {
+                        return Ok(ControlFlow::Return(value));
+                    }
+                }
+                Ok(ControlFlow::None)
+            }
         }
     }
 
@@ -230,62 +1371,69 @@ impl Interpreter {
     ) -> Result<(), String> {
         let format_value = self.evaluate_expr(format)?;
 
-        if arguments.is_empty() {
+        // A string literal is always a format string, so {name} works without arguments (other
+        // braces in it are then printed as text); any other value is printed as it is unless
+        // arguments follow it
+        if arguments.is_empty() && !matches!(format, Expr::String(_)) {
             // Simple print: print value;
-            if is_err {
-                eprint!("{format_value}");
-            } else {
-                print!("{format_value}");
-            }
-            Ok(())
-        } else {
-            // Format string print: print "{}", value;
-            let format_str = match format_value {
-                Value::String(s) => s,
-                _ => return Err("Format string must be a string".to_string()),
-            };
+            self.write_formatted(format_args!("{format_value}"), is_err);
+            return Ok(());
+        }
 
-            // Evaluate all arguments
-            let arg_values: Vec<String> = arguments
-                .iter()
-                .map(|arg| self.evaluate_expr(arg).map(|v| v.to_string()))
-                .collect::<Result<_, _>>()?;
+        // Format string print: print "{}", value;
+        let format_str = match format_value {
+            Value::String(s) => s,
+            _ => return Err("Format string must be a string".to_string()),
+        };
 
-            // Replace each '{}' in format_str with the corresponding argument
-            let mut formatted = String::new();
-            let mut parts = format_str.split("{}");
-            let mut args_iter = arg_values.iter();
+        // Evaluate all arguments
+        let arg_values: Vec<Value> = arguments
+            .iter()
+            .map(|arg| self.evaluate_expr(arg))
+            .collect::<Result<_, _>>()?;
 
-            if let Some(first) = parts.next() {
-                formatted.push_str(first);
-            }
-            for part in parts {
-                if let Some(arg) = args_iter.next() {
-                    formatted.push_str(arg);
-                } else {
-                    formatted.push_str("{}"); // Not enough arguments, keep as is
-                }
-                formatted.push_str(part);
-            }
-            // If there are extra arguments, ignore them
+        let globals = &self.globals;
+        let formatted =
+            crate::format::format(&format_str, &arg_values, |name| globals.get(name).cloned())?;
+        self.write_output(&formatted, is_err); // Print the value
+        Ok(())
+    }
 
-            if is_err {
-                eprint!("{formatted}"); // Print the value
-            } else {
-                print!("{formatted}"); // Print the value
+    // Evaluate an expression and return its value; errors without a position get the
+    // expression's
+    fn evaluate_expr(&mut self, expr: &Expr) -> Result<Value, String> {
+        let result = self.tick().and_then(|_| {
+            let value = self.evaluate(expr)?;
+            self.check_value_size(&value)?; // Catch runaway strings/arrays as soon as they are built
+            Ok(value)
+        });
+        result.map_err(|e| self.locate(e, expr.span()))
+    }
+
+    // Add a position to an error message that has none yet, in the form diagnostics read back
+    // ("... at line 3 column 7"), so the innermost expression or statement that failed names
+    // it. Errors raised by the hook, such as the debugger stopping the program, are left as
+    // they are, and so are positions of programs built without source (line 0).
+    fn locate(&self, error: String, span: Option<Span>) -> String {
+        match span {
+            Some(span)
+                if span.line > 0 && !self.stopped_by_hook && !error.contains(" at line ") =>
+            {
+                format!("{error} at line {} column {}", span.line, span.column)
             }
-            Ok(())
+            _ => error,
         }
     }
 
-    // Evaluate an expression and return its value
-    fn evaluate_expr(&mut self, expr: &Expr) -> Result<Value, String> {
+    // Compute the value of a single expression node
+    fn evaluate(&mut self, expr: &Expr) -> Result<Value, String> {
         match expr {
             Expr::Number(n) => Ok(Value::Number(*n)), // Numeric literal
             Expr::String(s) => Ok(Value::String(s.clone())), // String literal
             Expr::Boolean(b) => Ok(Value::Boolean(*b)), // Boolean literal
-            Expr::Identifier(name) => {
-                if let Some(value) = self.globals.get(name) {
+            Expr::Identifier(name, _, slot) => {
+                let symbol = self.globals.symbol(slot, name);
+                if let Some(value) = self.globals.get_symbol(symbol) {
                     Ok(value.clone()) // Return variable value if found
                 } else {
                     Err(format!("Undefined variable '{name}'")) // Error if not found
@@ -305,7 +1453,7 @@ impl Interpreter {
                 }
                 Ok(Value::DynamicArray(vals))
             }
-            Expr::Index { array, index } => {
+            Expr::Index { array, index, .. } => {
                 let array_val = self.evaluate_expr(array)?;
                 let index_val = self.evaluate_expr(index)?;
 
@@ -314,7 +1462,7 @@ impl Interpreter {
                     _ => return Err("Array index must be a number".to_string()),
                 };
 
-                match array_val {
+                match array_val.thawed() {
                     Value::FixedArray(arr) | Value::DynamicArray(arr) => {
                         if index_num >= arr.len() {
                             Err(format!(
@@ -332,8 +1480,7 @@ impl Interpreter {
                 left,
                 operator,
                 right,
-                line,
-                column,
+                ..
             } => {
                 let left_val = &self.evaluate_expr(left)?; // Evaluate left operand
                 let right_val = &self.evaluate_expr(right)?; // Evaluate right operand
@@ -341,26 +1488,48 @@ impl Interpreter {
                 match operator {
                     BinaryOp::Add => match (left_val, right_val) {
                         (Value::Number(a), Value::Number(b)) => Ok(Value::Number(a + b)), // Add numbers
-                        (Value::String(a), Value::String(b)) => Ok(Value::String(format!("{a}{b}"))), // Concatenate strings
-                        (Value::String(a), Value::Number(b)) => Ok(Value::String(format!("{a}{b}"))), // String + number
-                        (Value::Number(a), Value::String(b)) => Ok(Value::String(format!("{a}{b}"))), // Number + string
-                        (Value::String(a), Value::Boolean(b)) => Ok(Value::String(format!("{a}{b}"))), // String + bool
-                        (Value::Boolean(a), Value::String(b)) => Ok(Value::String(format!("{a}{b}"))), // Bool + string
-                        _ => {
-                            Err(format!("Invalid operands for addition: {left_val:?} + {right_val:?} at line {line} column {column}"))
-                        }
+                        (Value::String(a), Value::String(b)) => {
+                            Ok(Value::String(format!("{a}{b}")))
+                        } // Concatenate strings
+                        (Value::String(a), Value::Number(b)) => {
+                            Ok(Value::String(format!("{a}{b}")))
+                        } // String + number
+                        (Value::Number(a), Value::String(b)) => {
+                            Ok(Value::String(format!("{a}{b}")))
+                        } // Number + string
+                        (Value::String(a), Value::Boolean(b)) => {
+                            Ok(Value::String(format!("{a}{b}")))
+                        } // String + bool
+                        (Value::Boolean(a), Value::String(b)) => {
+                            Ok(Value::String(format!("{a}{b}")))
+                        } // Bool + string
+                        (Value::Duration(a), Value::Duration(b)) => Ok(Value::Duration(a + b)),
+                        (Value::Date(date), Value::Duration(seconds))
+                        | (Value::Duration(seconds), Value::Date(date)) => {
+                            shift_date(date, *seconds)
+                        } // Date + duration
+                        _ => Err(format!(
+                            "Invalid operands for addition: {left_val:?} + {right_val:?}"
+                        )),
                     },
                     BinaryOp::Subtract => match (left_val, right_val) {
                         (Value::Number(a), Value::Number(b)) => Ok(Value::Number(a - b)), // Subtract numbers
-                        _ => {
-                            Err(format!("Invalid operands for subtraction: {left_val:?} - {right_val:?} at line {line} column {column}"))
-                        }
+                        (Value::Duration(a), Value::Duration(b)) => Ok(Value::Duration(a - b)),
+                        (Value::Date(date), Value::Duration(seconds)) => shift_date(date, -seconds), // Date - duration
+                        (Value::Date(a), Value::Date(b)) => Ok(Value::Duration(
+                            (*a - *b).num_milliseconds() as f64 / 1000.0,
+                        )), // Time between two dates
+                        _ => Err(format!(
+                            "Invalid operands for subtraction: {left_val:?} - {right_val:?}"
+                        )),
                     },
                     BinaryOp::Multiply => match (left_val, right_val) {
                         (Value::Number(a), Value::Number(b)) => Ok(Value::Number(a * b)), // Multiply numbers
-                        _ => {
-                            Err(format!("Invalid operands for multiplication: {left_val:?} * {right_val:?} at line {line} column {column}"))
-                        }
+                        (Value::Duration(a), Value::Number(b))
+                        | (Value::Number(b), Value::Duration(a)) => Ok(Value::Duration(a * b)), // Scale a duration
+                        _ => Err(format!(
+                            "Invalid operands for multiplication: {left_val:?} * {right_val:?}"
+                        )),
                     },
                     BinaryOp::Divide => match (left_val, right_val) {
                         (Value::Number(a), Value::Number(b)) => {
@@ -370,504 +1539,520 @@ impl Interpreter {
                                 Ok(Value::Number(a / b)) // Divide numbers
                             }
                         }
-                        _ => {
-                            Err(format!("Invalid operands for division: {left_val:?} / {right_val:?} at line {line} column {column}"))
+                        (Value::Duration(_), Value::Number(b) | Value::Duration(b))
+                            if *b == 0.0 =>
+                        {
+                            Err("Division by zero".to_string())
                         }
+                        (Value::Duration(a), Value::Number(b)) => Ok(Value::Duration(a / b)), // Split a duration
+                        (Value::Duration(a), Value::Duration(b)) => Ok(Value::Number(a / b)), // How many times one fits
+
+                        _ => Err(format!(
+                            "Invalid operands for division: {left_val:?} / {right_val:?}"
+                        )),
                     },
                     BinaryOp::Equal => Ok(Value::Boolean(left_val.is_equal(right_val))), // Equality check
                     BinaryOp::NotEqual => Ok(Value::Boolean(!left_val.is_equal(right_val))), // Not-equal check
                     BinaryOp::Greater => match (left_val, right_val) {
-                        (Value::Number(a), Value::Number(b)) => Ok(Value::Boolean(a > b)), // Greater than
-                        _ => {
-                            Err(format!("Invalid operands for comparison: {left_val:?} > {right_val:?} at line {line} column {column}"))
-                        }
+                        (Value::Number(a), Value::Number(b))
+                        | (Value::Duration(a), Value::Duration(b)) => Ok(Value::Boolean(a > b)), // Greater than
+                        _ => Err(format!(
+                            "Invalid operands for comparison: {left_val:?} > {right_val:?}"
+                        )),
                     },
                     BinaryOp::GreaterEqual => match (left_val, right_val) {
-                        (Value::Number(a), Value::Number(b)) => Ok(Value::Boolean(a >= b)), // Greater or equal
-                        _ => {
-                            Err(format!("Invalid operands for comparison: {left_val:?} >= {right_val:?} at line {line} column {column}"))
-                        }
+                        (Value::Number(a), Value::Number(b))
+                        | (Value::Duration(a), Value::Duration(b)) => Ok(Value::Boolean(a >= b)), // Greater or equal
+                        _ => Err(format!(
+                            "Invalid operands for comparison: {left_val:?} >= {right_val:?}"
+                        )),
                     },
                     BinaryOp::Less => match (left_val, right_val) {
-                        (Value::Number(a), Value::Number(b)) => Ok(Value::Boolean(a < b)), // Less than
-                        _ => {
-                            Err(format!("Invalid operands for comparison: {left_val:?} < {right_val:?} at line {line} column {column}"))
-                        }
+                        (Value::Number(a), Value::Number(b))
+                        | (Value::Duration(a), Value::Duration(b)) => Ok(Value::Boolean(a < b)), // Less than
+                        _ => Err(format!(
+                            "Invalid operands for comparison: {left_val:?} < {right_val:?}"
+                        )),
                     },
                     BinaryOp::LessEqual => match (left_val, right_val) {
-                        (Value::Number(a), Value::Number(b)) => Ok(Value::Boolean(a <= b)), // Less or equal
-                        _ => {
-                            Err(format!("Invalid operands for comparison: {left_val:?} <= {right_val:?} at line {line} column {column}"))
-                        }
+                        (Value::Number(a), Value::Number(b))
+                        | (Value::Duration(a), Value::Duration(b)) => Ok(Value::Boolean(a <= b)), // Less or equal
+                        _ => Err(format!(
+                            "Invalid operands for comparison: {left_val:?} <= {right_val:?}"
+                        )),
                     },
                 }
             }
-            Expr::Unary { operator, operand } => {
+            Expr::Unary {
+                operator, operand, ..
+            } => {
                 let operand_val = self.evaluate_expr(operand)?; // Evaluate operand
 
                 match operator {
                     UnaryOp::Minus => match operand_val {
                         Value::Number(n) => Ok(Value::Number(-n)), // Negate number
+                        Value::Duration(seconds) => Ok(Value::Duration(-seconds)), // Reverse a duration
                         _ => Err("Invalid operand for unary minus".to_string()), // Error for invalid type
                     },
                 }
             }
-            Expr::Assignment { name, value } => {
+            Expr::Assignment {
+                name, value, slot, ..
+            } => {
                 let val = self.evaluate_expr(value)?; // Evaluate right-hand side
-                self.globals.insert(name.clone(), val.clone()); // Assign to variable
+                let symbol = self.globals.symbol(slot, name);
+                self.store_symbol(symbol, val.clone())?; // Assign to variable
                 Ok(val) // Return the value
             }
             Expr::MethodCall {
                 object,
                 method,
-                argument,
+                arguments,
+                ..
             } => {
                 // Evaluate object once at the beginning
                 let object_val = self.evaluate_expr(object)?;
 
-                // Remove debug print in production
-                // eprintln!("DEBUG: Method '{}' called on object type: {:?}", method, std::mem::discriminant(&object_val));
-
-                match method.as_str() {
-                    "replaceChar" => {
-                        if let Value::String(original) = &object_val {
-                            if let Expr::Transform { from, to } = argument.as_ref() {
-                                // Try to resolve 'from' as a variable, fallback to literal if not found
-                                let from_value = if let Some(val) = self.globals.get(from) {
-                                    match val {
-                                        Value::String(s) => s.clone(),
-                                        Value::Number(n) => n.to_string(),
-                                        Value::Boolean(b) => b.to_string(),
-                                        _ => {
-                                            return Err(format!(
-                                                "Variable '{from}' is not a valid replacement value"
-                                            ))
-                                        }
-                                    }
-                                } else {
-                                    from.clone() // Use as literal if not a variable
-                                };
-                                // Try to resolve 'to' as a variable, fallback to literal if not found
-                                let to_value = if let Some(val) = self.globals.get(to) {
-                                    match val {
-                                        Value::String(s) => s.clone(),
-                                        Value::Number(n) => n.to_string(),
-                                        Value::Boolean(b) => b.to_string(),
-                                        _ => {
-                                            return Err(format!(
-                                                "Variable '{to}' is not a valid replacement value"
-                                            ))
-                                        }
-                                    }
-                                } else {
-                                    to.clone() // Use as literal if not a variable
-                                };
-                                let result = original.replace(&from_value, &to_value);
-                                Ok(Value::String(result))
-                            } else {
-                                Err("ReplaceChar method requires a transform argument".to_string())
-                            }
-                        } else {
-                            Err("ReplaceChar method can only be called on strings".to_string())
-                        }
-                    }
-                    "push" => {
-                        // Array push method: arr.push(value)
-                        let arg_val = self.evaluate_expr(argument)?;
+                // Methods the program defined on this type come before the built-in ones
+                if let Some(Value::Function(params, body)) = extension_type(&object_val)
+                    .and_then(|type_name| self.globals.extension(type_name, method))
+                    .cloned()
+                {
+                    return self.call_extension(object_val, method, &params, &body, arguments);
+                }
 
-                        if let Value::DynamicArray(mut arr) = object_val {
-                            arr.push(arg_val);
-                            Ok(Value::DynamicArray(arr))
-                        } else {
-                            Err("Push method can only be called on dynamic arrays".to_string())
-                        }
-                    }
-                    "pop" => {
-                        // Array pop method: arr.pop()
-                        // Verify no argument was provided
-                        if let Expr::Nil = argument.as_ref() {
-                            if let Value::DynamicArray(mut arr) = object_val {
-                                if arr.is_empty() {
-                                    Err("Cannot pop from empty array".to_string())
-                                } else {
-                                    let popped = arr.pop().unwrap();
-                                    Ok(popped)
-                                }
-                            } else {
-                                Err("Pop method can only be called on dynamic arrays".to_string())
-                            }
-                        } else {
-                            Err("Pop method does not take arguments".to_string())
-                        }
-                    }
-                    "length" => {
-                        // Array length method: arr.length()
-                        // Verify no argument was provided
-                        if let Expr::Nil = argument.as_ref() {
-                            match object_val {
-                                Value::FixedArray(arr) | Value::DynamicArray(arr) => {
-                                    Ok(Value::Number(arr.len() as f64))
-                                }
-                                _ => Err("Length method can only be called on arrays".to_string()),
-                            }
-                        } else {
-                            Err("Length method does not take arguments".to_string())
-                        }
-                    }
-                    "clear" => {
-                        // Array clear method: arr.clear()
-                        // Verify no argument was provided
-                        if let Expr::Nil = argument.as_ref() {
-                            match object_val {
-                                Value::DynamicArray(_) => Ok(Value::DynamicArray(Vec::new())),
-                                _ => {
-                                    Err("Clear method can only be called on dynamic arrays"
-                                        .to_string())
-                                }
-                            }
-                        } else {
-                            Err("Clear method does not take arguments".to_string())
-                        }
-                    }
-                    "format" => {
-                        // Date format method: date.format("%Y-%m-%d")
-                        if let Value::Date(dt) = object_val {
-                            let fmt_val = self.evaluate_expr(argument)?;
-                            if let Value::String(fmt) = fmt_val {
-                                Ok(Value::String(dt.format(&fmt).to_string()))
-                            } else {
-                                Err("Date.format() requires a string argument".to_string())
-                            }
-                        } else {
-                            Err("format method can only be called on Date objects".to_string())
-                        }
-                    }
-                    "getYear" => {
-                        // Date getYear method: date.getYear()
-                        if let Expr::Nil = argument.as_ref() {
-                            if let Value::Date(dt) = object_val {
-                                Ok(Value::Number(dt.year() as f64))
-                            } else {
-                                Err("getYear method can only be called on Date objects".to_string())
-                            }
-                        } else {
-                            Err("getYear method does not take arguments".to_string())
-                        }
-                    }
-                    "getMonth" => {
-                        // Date getMonth method: date.getMonth()
-                        if let Expr::Nil = argument.as_ref() {
-                            if let Value::Date(dt) = object_val {
-                                Ok(Value::Number(dt.month() as f64))
-                            } else {
-                                Err("getMonth method can only be called on Date objects"
-                                    .to_string())
-                            }
-                        } else {
-                            Err("getMonth method does not take arguments".to_string())
-                        }
-                    }
-                    "getDay" => {
-                        // Date getDay method: date.getDay()
-                        if let Expr::Nil = argument.as_ref() {
-                            if let Value::Date(dt) = object_val {
-                                Ok(Value::Number(dt.day() as f64))
-                            } else {
-                                Err("getDay method can only be called on Date objects".to_string())
-                            }
-                        } else {
-                            Err("getDay method does not take arguments".to_string())
-                        }
-                    }
-                    "keys" => {
-                        // Object keys method: obj.keys()
-                        if let Expr::Nil = argument.as_ref() {
-                            if let Value::Object(obj) = object_val {
-                                let keys = obj.keys().map(|k| Value::String(k.clone())).collect();
-                                Ok(Value::DynamicArray(keys))
-                            } else {
-                                Err("keys method can only be called on Object".to_string())
-                            }
-                        } else {
-                            Err("keys method does not take arguments".to_string())
-                        }
-                    }
-                    "insert" => {
-                        // Array insert method: arr.insert(index, value)
-                        if let Value::DynamicArray(mut arr) = object_val {
-                            if let Expr::Binary {
-                                left,
-                                operator: _,
-                                right,
-                                ..
-                            } = argument.as_ref()
-                            {
-                                if let (Expr::Number(index), _) = (left.as_ref(), right.as_ref()) {
-                                    let index = *index as usize;
-                                    if index > arr.len() {
-                                        return Err(format!(
-                                            "Insert index {index} out of bounds (array length: {})",
-                                            arr.len()
-                                        ));
-                                    }
-                                    let value = self.evaluate_expr(right)?;
-                                    arr.insert(index, value);
-                                    Ok(Value::DynamicArray(arr))
-                                } else {
-                                    Err("insert() requires (index, value) arguments".to_string())
-                                }
-                            } else {
-                                Err("insert() requires exactly two arguments".to_string())
-                            }
-                        } else {
-                            Err("insert method can only be called on dynamic arrays".to_string())
-                        }
-                    }
-                    "remove" => {
-                        // Array remove method: arr.remove(index)
-                        if let Value::DynamicArray(mut arr) = object_val {
-                            if let Expr::Number(index) = argument.as_ref() {
-                                let index = *index as usize;
-                                if index >= arr.len() {
-                                    return Err(format!(
-                                        "Remove index {index} out of bounds (array length: {})",
-                                        arr.len()
-                                    ));
-                                }
-                                let removed = arr.remove(index);
-                                Ok(removed)
-                            } else {
-                                Err("remove() requires a numeric index argument".to_string())
-                            }
-                        } else {
-                            Err("remove method can only be called on dynamic arrays".to_string())
-                        }
-                    }
-                    "reverse" => {
-                        // Array reverse method: arr.reverse()
-                        if let Expr::Nil = argument.as_ref() {
-                            match object_val {
-                                Value::DynamicArray(mut arr) => {
-                                    arr.reverse();
-                                    Ok(Value::DynamicArray(arr))
-                                }
-                                Value::FixedArray(mut arr) => {
-                                    arr.reverse();
-                                    Ok(Value::FixedArray(arr))
-                                }
-                                _ => Err("reverse method can only be called on arrays".to_string()),
-                            }
-                        } else {
-                            Err("reverse method does not take arguments".to_string())
-                        }
-                    }
-                    "toUpper" => {
-                        // String toUpper method: str.toUpper()
-                        if let Expr::Nil = argument.as_ref() {
-                            if let Value::String(s) = object_val {
-                                Ok(Value::String(s.to_uppercase()))
-                            } else {
-                                Err("toUpper method can only be called on strings".to_string())
-                            }
-                        } else {
-                            Err("toUpper method does not take arguments".to_string())
-                        }
-                    }
-                    "toLower" => {
-                        // String toLower method: str.toLower()
-                        if let Expr::Nil = argument.as_ref() {
-                            if let Value::String(s) = object_val {
-                                Ok(Value::String(s.to_lowercase()))
-                            } else {
-                                Err("toLower method can only be called on strings".to_string())
-                            }
-                        } else {
-                            Err("toLower method does not take arguments".to_string())
-                        }
-                    }
-                    "trim" => {
-                        // String trim method: str.trim()
-                        if let Expr::Nil = argument.as_ref() {
-                            if let Value::String(s) = object_val {
-                                Ok(Value::String(s.trim().to_string()))
-                            } else {
-                                Err("trim method can only be called on strings".to_string())
-                            }
-                        } else {
-                            Err("trim method does not take arguments".to_string())
-                        }
-                    }
-                    "set" => {
-                        // Object set method: obj.set(key, value)
-                        if let Value::Object(mut obj) = object_val {
-                            if let Expr::Binary {
-                                left,
-                                operator: _,
-                                right,
-                                ..
-                            } = argument.as_ref()
-                            {
-                                // Evaluate the key to get its string value
-                                let key_val = self.evaluate_expr(left)?;
-                                let key = match key_val {
-                                    Value::String(s) => s,
-                                    _ => return Err("set() requires (key, value) arguments where key is a string".to_string()),
-                                };
-                                let value = self.evaluate_expr(right)?;
-                                obj.insert(key, value);
-                                Ok(Value::Object(obj))
-                            } else {
-                                Err("set() requires exactly two arguments".to_string())
-                            }
-                        } else {
-                            Err("set method can only be called on objects".to_string())
-                        }
-                    }
-                    "get" => {
-                        // Object get method: obj.get(key)
-                        if let Value::Object(obj) = object_val {
-                            let key_val = self.evaluate_expr(argument)?;
-                            let key = match key_val {
-                                Value::String(s) => s,
-                                _ => return Err("get() requires a string key argument".to_string()),
-                            };
-                            if let Some(value) = obj.get(&key) {
-                                Ok(value.clone())
-                            } else {
-                                Ok(Value::Nil) // Return nil if key doesn't exist
-                            }
-                        } else {
-                            Err("get method can only be called on objects".to_string())
-                        }
-                    }
-                    "has" => {
-                        // Object has method: obj.has(key)
-                        if let Value::Object(obj) = object_val {
-                            let key_val = self.evaluate_expr(argument)?;
-                            let key = match key_val {
-                                Value::String(s) => s,
-                                _ => return Err("has() requires a string key argument".to_string()),
-                            };
-                            Ok(Value::Boolean(obj.contains_key(&key)))
-                        } else {
-                            Err("has method can only be called on objects".to_string())
-                        }
-                    }
-                    _ => Err(format!("Unsupported method: {method}")),
+                // replaceChar takes a transform, whose sides are not evaluated like arguments
+                if method == "replaceChar" {
+                    return self.replace_char(object_val, arguments);
+                }
+
+                // Find the method and check its number of arguments before evaluating any
+                let handler = self.methods.lookup(&object_val, method)?.clone();
+                if handler.updates && matches!(object_val, Value::Frozen(_)) {
+                    return Err(format!(
+                        "Cannot call {method}() on a frozen {}: it cannot be changed",
+                        object_val.type_name()
+                    ));
                 }
+                if arguments.len() != handler.arity {
+                    return Err(format!(
+                        "Method '{method}' expects {} arguments, got {}",
+                        handler.arity,
+                        arguments.len()
+                    ));
+                }
+                let values = arguments
+                    .iter()
+                    .map(|argument| self.evaluate_expr(argument))
+                    .collect::<Result<Vec<Value>, String>>()?;
+                handler.call(object_val.thawed(), &values)
             }
-            Expr::Transform { from: _, to: _ } => {
-                Err("Transform should not be evaluated directly".to_string())
-            }
-            Expr::FunctionCall { name, arguments } => self.call_function(name, arguments),
+            Expr::Transform { .. } => Err("Transform should not be evaluated directly".to_string()),
+            Expr::FunctionCall {
+                name,
+                arguments,
+                slot,
+                ..
+            } => self.call_function(name, slot, arguments),
             Expr::Nil => Ok(Value::Nil),
+            Expr::Match { subject, cases, .. } => {
+                let value = self.evaluate_expr(subject)?;
+                self.match_value(&value, cases)
+            }
+        }
+    }
+
+    // Choose the first case whose pattern the value has and whose guard (if any) holds, and
+    // evaluate its result. A pattern's names hold their parts of the value only while the
+    // case's guard and result are evaluated; then they get back what they held before, so a
+    // case leaves the variables it names as it found them, whether it is chosen or not.
+    fn match_value(&mut self, value: &Value, cases: &[MatchCase]) -> Result<Value, String> {
+        for case in cases {
+            let decision = case.decision();
+            if !decision.matches(value) {
+                continue;
+            }
+            let mut shadowed = Vec::with_capacity(decision.bindings.len());
+            let mut result = Ok(None);
+            for (name, path) in &decision.bindings {
+                let symbol = self.globals.intern(name);
+                shadowed.push((symbol, self.globals.get_symbol(symbol).cloned()));
+                if let Err(e) = self.store_symbol(symbol, pattern::part(value, path).clone()) {
+                    result = Err(e);
+                    break;
+                }
+            }
+            if result.is_ok() {
+                result = self.evaluate_case(case);
+            }
+            self.unbind(shadowed);
+            if let Some(result) = result.transpose() {
+                return result;
+            }
+        }
+        Err(format!("No case matched the value {value}"))
+    }
+
+    // Evaluate a case's result if its guard (if any) holds
+    fn evaluate_case(&mut self, case: &MatchCase) -> Result<Option<Value>, String> {
+        if let Some(guard) = &case.guard {
+            if !self.evaluate_expr(guard)?.is_truthy() {
+                return Ok(None);
+            }
+        }
+        self.evaluate_expr(&case.body).map(Some)
+    }
+
+    // Give variables back the values a match case's names shadowed (None: not defined), last
+    // assigned first. A running call has already saved them if it needs to, since the case
+    // assigned them with store_symbol.
+    fn unbind(&mut self, shadowed: Vec<(usize, Option<Value>)>) {
+        for (symbol, previous) in shadowed.into_iter().rev() {
+            if self.limits.max_values.is_some() {
+                let current = self
+                    .globals
+                    .get_symbol(symbol)
+                    .map_or(0, Value::count_values);
+                self.live_values =
+                    self.live_values - current + previous.as_ref().map_or(0, Value::count_values);
+            }
+            self.globals.set(symbol, previous);
         }
     }
 
+    // Replace text in a string: str.replaceChar`from -> to`, where each side is a variable's
+    // value or, if there is no such variable, the name itself
+    fn replace_char(&self, object: Value, arguments: &[Expr]) -> Result<Value, String> {
+        let Value::String(original) = object else {
+            return Err("ReplaceChar method can only be called on strings".to_string());
+        };
+        let [Expr::Transform { from, to, .. }] = arguments else {
+            return Err("ReplaceChar method requires a transform argument".to_string());
+        };
+        let side = |name: &String| match self.globals.get(name) {
+            Some(Value::String(s)) => Ok(s.clone()),
+            Some(Value::Number(n)) => Ok(n.to_string()),
+            Some(Value::Boolean(b)) => Ok(b.to_string()),
+            Some(_) => Err(format!(
+                "Variable '{name}' is not a valid replacement value"
+            )),
+            None => Ok(name.clone()), // Use as literal if not a variable
+        };
+        Ok(Value::String(original.replace(&side(from)?, &side(to)?)))
+    }
+
     // Call a function with given arguments
-    fn call_function(&mut self, name: &str, arguments: &[Expr]) -> Result<Value, String> {
-        // Check for built-in functions first
+    fn call_function(
+        &mut self,
+        name: &str,
+        slot: &Slot,
+        arguments: &[Expr],
+    ) -> Result<Value, String> {
+        // A function the program defines takes precedence over a builtin of the same name, so
+        // scripts written before a builtin was added keep calling their own
+        let symbol = self.globals.symbol(slot, name);
+        let defined = matches!(self.globals.get_symbol(symbol), Some(Value::Function(..)));
         match name {
+            _ if defined => self.call_defined_function(name, slot, arguments),
             "readLine" => self.builtin_read_line(arguments),
+            "confirm" => self.builtin_confirm(arguments),
+            "prompt" => self.builtin_prompt(arguments),
             "Date" => self.builtin_date(arguments),
+            "Duration" => self.builtin_duration(arguments),
             "Object" => self.builtin_object(arguments),
-            _ => {
-                // Check for user-defined functions
-                let function =
-                    if let Some(Value::Function(params, body)) = self.globals.get(name).cloned() {
-                        (params, body)
-                    } else {
-                        return Err(format!("Undefined function '{name}'"));
-                    };
+            "args" => self.builtin_args(arguments),
+            "deepCopy" => self.builtin_deep_copy(arguments),
+            "freeze" => self.builtin_freeze(arguments),
+            "parallelMap" => self.builtin_parallel_map(arguments),
+            "spawn" => self.builtin_spawn(arguments),
+            "wait" => self.builtin_wait(arguments),
+            "setTimeout" => self.builtin_set_timer(arguments, false),
+            "setInterval" => self.builtin_set_timer(arguments, true),
+            "clearTimer" => self.builtin_clear_timer(arguments),
+            _ if self.async_functions.contains_key(name) => {
+                let values = arguments
+                    .iter()
+                    .map(|argument| self.evaluate_expr(argument))
+                    .collect::<Result<Vec<Value>, String>>()?;
+                match &self.async_bridge {
+                    Some(bridge) => bridge.call(name, values),
+                    None => Err(format!(
+                        "'{name}' is an async function; run the program with interpret_async"
+                    )),
+                }
+            }
+            _ if self.host_functions.contains_key(name) => {
+                let function = Arc::clone(&self.host_functions[name]);
+                let values = arguments
+                    .iter()
+                    .map(|argument| self.evaluate_expr(argument))
+                    .collect::<Result<Vec<Value>, String>>()?;
+                self.flush_stdout(); // It may print too
+                function(&values)
+            }
+            _ => self.call_defined_function(name, slot, arguments),
+        }
+    }
 
-                let (params, body) = function;
+    // Call a function the program defined
+    fn call_defined_function(
+        &mut self,
+        name: &str,
+        slot: &Slot,
+        arguments: &[Expr],
+    ) -> Result<Value, String> {
+        let symbol = self.globals.symbol(slot, name);
+        let (params, body) = match self.globals.get_symbol(symbol) {
+            Some(Value::Function(params, body)) => (params.clone(), Arc::clone(body)),
+            _ => return Err(format!("Undefined function '{name}'")),
+        };
 
-                // Check argument count
-                if arguments.len() != params.len() {
-                    return Err(format!(
-                        "Function '{name}' expects {} arguments, got {}",
-                        params.len(),
-                        arguments.len()
-                    ));
-                }
+        // Check argument count
+        if arguments.len() != params.len() {
+            return Err(format!(
+                "Function '{name}' expects {} arguments, got {}",
+                params.len(),
+                arguments.len()
+            ));
+        }
 
-                // Save current global state
-                let saved_globals = self.globals.clone();
+        // Save current global state
+        self.enter_call();
+        let saved_live_values = self.live_values;
 
-                // Evaluate arguments and bind to parameters
-                for (param, arg) in params.iter().zip(arguments.iter()) {
-                    let arg_value = self.evaluate_expr(arg)?;
-                    self.globals.insert(param.clone(), arg_value);
-                }
+        // Evaluate arguments and bind to parameters, then execute the body
+        let result = self.bind_arguments(&params, arguments).and_then(|_| {
+            self.call_stack.push(name.to_string());
+            let result = self.call_function_body(name, &params, &body);
+            self.call_stack.pop();
+            result
+        });
+
+        // Restore global state
+        self.leave_call();
+        self.live_values = saved_live_values;
+
+        // Handle return value
+        match result? {
+            ControlFlow::Return(value) => Ok(value),
+            ControlFlow::None => Ok(Value::Nil),
+        }
+    }
 
-                // Execute function body with return handling
-                let result = self.execute_stmt(&body);
+    // Call a method the program defined in Pidgin (function String.shout(self) { ... }): the
+    // receiver is bound to its first parameter and the arguments, evaluated first like those of
+    // a built-in method, to the rest
+    fn call_extension(
+        &mut self,
+        receiver: Value,
+        method: &str,
+        params: &[String],
+        body: &Stmt,
+        arguments: &[Expr],
+    ) -> Result<Value, String> {
+        if arguments.len() + 1 != params.len() {
+            return Err(format!(
+                "Method '{method}' expects {} arguments, got {}",
+                params.len() - 1,
+                arguments.len()
+            ));
+        }
+        let values = arguments
+            .iter()
+            .map(|argument| self.evaluate_expr(argument))
+            .collect::<Result<Vec<Value>, String>>()?;
+        let name = format!("{}.{method}", extension_type(&receiver).unwrap_or_default());
+        let values = std::iter::once(receiver).chain(values).collect();
+        self.call_function_value(&name, params, body, values)
+    }
+
+    // Call a function value with its arguments already evaluated (one per parameter); like a
+    // call by name, it cannot change the caller's globals
+    fn call_function_value(
+        &mut self,
+        name: &str,
+        params: &[String],
+        body: &Stmt,
+        values: Vec<Value>,
+    ) -> Result<Value, String> {
+        self.enter_call();
+        let saved_live_values = self.live_values;
+        let result = values
+            .into_iter()
+            .zip(params)
+            .try_for_each(|(value, param)| self.store_variable(param, value))
+            .and_then(|_| {
+                self.call_stack.push(name.to_string());
+                let result = self.call_function_body(name, params, body);
+                self.call_stack.pop();
+                result
+            });
+        self.leave_call();
+        self.live_values = saved_live_values;
+
+        match result? {
+            ControlFlow::Return(value) => Ok(value),
+            ControlFlow::None => Ok(Value::Nil),
+        }
+    }
 
-                // Restore global state
-                self.globals = saved_globals;
+    // Start traversing a value for a for-in loop
+    fn traverse(value: Value) -> Result<Traversal, String> {
+        Ok(match value {
+            Value::FixedArray(items) | Value::DynamicArray(items) => {
+                Traversal::Items(items.into_iter())
+            }
+            Value::String(text) => Traversal::Items(
+                text.chars()
+                    .map(|c| Value::String(c.to_string()))
+                    .collect::<Vec<Value>>()
+                    .into_iter(),
+            ),
+            // An object with a next function is an iterator; any other object gives its entries
+            Value::Object(fields) => match fields.get("next") {
+                Some(Value::Function(params, body)) => {
+                    if params.len() != 1 {
+                        return Err(format!(
+                            "An iterator's next function must take 1 argument (its state), not {}",
+                            params.len()
+                        ));
+                    }
+                    Traversal::Protocol {
+                        params: params.clone(),
+                        body: body.clone(),
+                        state: fields.get("state").cloned().unwrap_or(Value::Nil),
+                    }
+                }
+                _ => {
+                    let mut entries: Vec<(String, Value)> = fields.into_iter().collect();
+                    entries.sort_by(|a, b| a.0.cmp(&b.0)); // By key, so every run gives the same order
+                    let entries = entries
+                        .into_iter()
+                        .map(|(key, value)| Value::FixedArray(vec![Value::String(key), value]));
+                    Traversal::Items(entries.collect::<Vec<Value>>().into_iter())
+                }
+            },
+            Value::Iterator(iterator) => Traversal::Host(iterator),
+            Value::Frozen(inner) => return Self::traverse(*inner),
+            other => return Err(format!("Cannot iterate over a {}", other.type_name())),
+        })
+    }
 
-                // Handle return value
-                match result? {
-                    ControlFlow::Return(value) => Ok(value),
-                    ControlFlow::None => Ok(Value::Nil),
+    // Take the next item of a for-in loop, or None when there are no more
+    fn next_item(&mut self, traversal: &mut Traversal) -> Result<Option<Value>, String> {
+        match traversal {
+            Traversal::Items(items) => Ok(items.next()),
+            Traversal::Host(iterator) => Ok(iterator.next_value()),
+            Traversal::Protocol {
+                params,
+                body,
+                state,
+            } => {
+                let (params, body) = (params.clone(), body.clone());
+                let step = self.call_function_value("next", &params, &body, vec![state.clone()])?;
+                let step = match step {
+                    Value::Frozen(inner) => *inner,
+                    other => other,
+                };
+                let Value::Object(mut fields) = step else {
+                    return Err(format!(
+                        "An iterator's next function must return an object, not a {}",
+                        step.type_name()
+                    ));
+                };
+                let Some(done) = fields.get("done") else {
+                    return Err(
+                        "An iterator's next function must return an object with a 'done' field"
+                            .to_string(),
+                    );
+                };
+                if done.is_truthy() {
+                    return Ok(None);
                 }
+                *state = fields.remove("state").unwrap_or(Value::Nil);
+                Ok(Some(fields.remove("value").unwrap_or(Value::Nil)))
             }
         }
     }
 
-    // Load a module and import specified names
-    fn load_module(&mut self, names: &[String], module_path: &str) -> Result<(), String> {
-        use crate::lexer::Lexer;
-        use crate::parser::Parser;
-        use std::fs;
-        use std::path::Path;
-
-        // Ensure the module has .pg extension
-        let full_path = if module_path.ends_with(".pg") {
-            module_path.to_string()
-        } else {
-            format!("{module_path}.pg")
-        };
+    // Run a function's body, telling the hook (if any) when it starts and finishes
+    fn call_function_body(
+        &mut self,
+        name: &str,
+        params: &[String],
+        body: &Stmt,
+    ) -> Result<ControlFlow, String> {
+        if self.hook.is_none() {
+            return self.execute_stmt(body);
+        }
+        let arguments: Vec<Value> = params
+            .iter()
+            .map(|param| self.globals.get(param).cloned().unwrap_or(Value::Nil))
+            .collect();
+        self.call_hook(|hook, interpreter| hook.function_enter(name, &arguments, interpreter))?;
 
-        // Try to find the module file
-        let module_file = if Path::new(&full_path).exists() {
-            full_path.clone()
-        } else {
-            // Try in examples directory
-            let examples_path = format!("examples/{full_path}");
-            if Path::new(&examples_path).exists() {
-                examples_path
-            } else {
-                return Err(format!(
-                    "Module '{module_path}' not found. Tried: {full_path}, {examples_path}"
-                ));
-            }
+        let result = self.execute_stmt(body);
+        let returned = match &result {
+            Ok(ControlFlow::Return(value)) => Ok(value.clone()),
+            Ok(ControlFlow::None) => Ok(Value::Nil),
+            Err(e) => Err(e.clone()),
         };
+        self.call_hook(|hook, interpreter| {
+            hook.function_exit(name, returned.as_ref().map_err(String::as_str), interpreter)
+        })?;
+        result
+    }
 
-        // Read the module file
-        let source = fs::read_to_string(&module_file)
-            .map_err(|e| format!("Failed to read module '{module_file}': {e}"))?;
+    // Evaluate call arguments and bind them to the function's parameters
+    fn bind_arguments(&mut self, params: &[String], arguments: &[Expr]) -> Result<(), String> {
+        for (param, arg) in params.iter().zip(arguments.iter()) {
+            let arg_value = self.evaluate_expr(arg)?;
+            self.store_variable(param, arg_value)?;
+        }
+        Ok(())
+    }
 
-        // Parse the module
-        let mut lexer = Lexer::new(&source);
-        let tokens = lexer.tokenize()?;
-        let mut parser = Parser::new(tokens);
-        let program = parser
-            .parse()
-            .map_err(|e| format!("Failed to parse module '{module_file}': {e}"))?;
+    // Load a module and import specified names
+    fn load_module(&mut self, names: &[String], module_path: &str) -> Result<(), String> {
+        let (module_file, program) = parse_module(module_path)?;
+        debug!("Loading module '{module_file}'");
 
-        // Create a temporary interpreter to execute the module
-        let mut module_interpreter = Interpreter::new(None);
+        // Create a temporary interpreter to execute the module, sharing our remaining step budget
+        let mut module_interpreter = Interpreter::new();
+        module_interpreter.set_limits(Limits {
+            max_steps: self
+                .limits
+                .max_steps
+                .map(|max_steps| max_steps.saturating_sub(self.steps)),
+            ..self.limits
+        });
+        module_interpreter.set_script_args(self.script_args.clone());
+        module_interpreter.interrupt = self.interrupt.clone();
+        module_interpreter.host_functions = self.host_functions.clone();
+        module_interpreter.methods = self.methods.clone();
+        module_interpreter.async_functions = self.async_functions.clone();
+        module_interpreter.async_bridge = self.async_bridge.clone();
+        module_interpreter.input = self.input.clone();
+        match &self.output {
+            Output::Captured(_) => module_interpreter.capture_output(),
+            Output::Streams(..) => module_interpreter.output = self.output.clone(), // Shared writers
+            Output::Stdio => self.flush_stdout(), // The module writes to stdout through its own buffer
+        }
+        if let Some(hook) = self
+            .hook
+            .as_mut()
+            .and_then(|hook| hook.module_hook(&module_file, &program))
+        {
+            module_interpreter.set_statement_hook(hook);
+        }
 
         // Execute the module to populate its globals
-        for stmt in program.statements {
-            match module_interpreter.execute_stmt(&stmt)? {
-                ControlFlow::Return(_) => {
-                    return Err("Return statement not allowed at module level".to_string());
-                }
-                ControlFlow::None => continue,
-            }
-        }
+        let result = module_interpreter.execute_module(&program);
+        module_interpreter.flush_stdout();
+        self.steps += module_interpreter.steps;
+        let module_output = module_interpreter.take_output();
+        self.write_output(&module_output, false); // Anything the module printed
+        result?;
 
         // Import the requested names (only if they start with uppercase)
         for name in names {
@@ -879,7 +2064,9 @@ impl Interpreter {
                     .map(|c| c.is_uppercase())
                     .unwrap_or(false)
                 {
-                    self.globals.insert(name.clone(), value.clone());
+                    let value = value.clone();
+                    self.store_variable(name, value)?;
+                    debug!("Imported '{name}' from '{module_file}'");
                 } else {
                     return Err(format!("Cannot import '{name}' - only names starting with uppercase letters can be imported"));
                 }
@@ -888,19 +2075,95 @@ impl Interpreter {
             }
         }
 
+        // Methods the module defined on built-in types come along with any import from it
+        for (name, value) in &module_interpreter.globals {
+            if name.contains('.') {
+                self.store_variable(name, value.clone())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Execute the top-level statements of an imported module
+    fn execute_module(&mut self, program: &Program) -> Result<(), String> {
+        for stmt in &program.statements {
+            match self.execute_stmt(stmt)? {
+                ControlFlow::Return(_) => {
+                    return Err("Return statement not allowed at module level".to_string());
+                }
+                ControlFlow::None => continue,
+            }
+        }
         Ok(())
     }
 
     // Built-in function: readLine() - Read input from console
     fn builtin_read_line(&mut self, arguments: &[Expr]) -> Result<Value, String> {
-        if !arguments.is_empty() {
-            print!("{}", self.evaluate_expr(&arguments[0])?);
+        let prompt = match arguments.first() {
+            Some(argument) => self.evaluate_expr(argument)?.to_string(),
+            None => String::new(),
+        };
+        Ok(Value::String(self.read_input(&prompt)?.unwrap_or_default()))
+    }
+
+    // Built-in function: confirm(message) - Ask a yes/no question until it is answered
+    fn builtin_confirm(&mut self, arguments: &[Expr]) -> Result<Value, String> {
+        if arguments.len() != 1 {
+            return Err(format!(
+                "confirm() expects 1 argument, got {}",
+                arguments.len()
+            ));
+        }
+        let question = format!("{} (y/n) ", self.evaluate_expr(&arguments[0])?);
+        loop {
+            // The end of input counts as "no"
+            let Some(answer) = self.read_input(&question)? else {
+                return Ok(Value::Boolean(false));
+            };
+            match answer.trim().to_lowercase().as_str() {
+                "y" | "yes" => return Ok(Value::Boolean(true)),
+                "n" | "no" => return Ok(Value::Boolean(false)),
+                _ => {}
+            }
+        }
+    }
+
+    // Built-in function: prompt(message, default?) - Read a line, or the default if it is empty
+    fn builtin_prompt(&mut self, arguments: &[Expr]) -> Result<Value, String> {
+        if arguments.is_empty() || arguments.len() > 2 {
+            return Err(format!(
+                "prompt() expects 1 or 2 arguments, got {}",
+                arguments.len()
+            ));
+        }
+        let message = self.evaluate_expr(&arguments[0])?.to_string();
+        let default = match arguments.get(1) {
+            Some(argument) => self.evaluate_expr(argument)?.to_string(),
+            None => String::new(),
+        };
+        match self.read_input(&message)? {
+            Some(answer) if !answer.is_empty() => Ok(Value::String(answer)),
+            _ => Ok(Value::String(default)),
         }
+    }
 
-        io::stdout().flush().unwrap();
+    // Show a prompt and read one line of input without its line ending, or None at the end of
+    // input
+    fn read_input(&mut self, prompt: &str) -> Result<Option<String>, String> {
+        self.write_output(prompt, false);
+        self.flush_output();
 
         let mut input = String::new();
-        match io::stdin().read_line(&mut input) {
+        let read = match &self.input {
+            Some(reader) => reader
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .read_line(&mut input),
+            None => io::stdin().read_line(&mut input),
+        };
+        match read {
+            Ok(0) => Ok(None),
             Ok(_) => {
                 // Remove trailing newline
                 if input.ends_with('\n') {
@@ -909,12 +2172,409 @@ impl Interpreter {
                         input.pop();
                     }
                 }
-                Ok(Value::String(input))
+                Ok(Some(input))
             }
             Err(e) => Err(format!("Error reading input: {e}")),
         }
     }
 
+    // Built-in function: args() - Get the program's command-line arguments as strings
+    fn builtin_args(&mut self, arguments: &[Expr]) -> Result<Value, String> {
+        if !arguments.is_empty() {
+            return Err(format!(
+                "args() expects 0 arguments, got {}",
+                arguments.len()
+            ));
+        }
+        Ok(Value::FixedArray(
+            self.script_args
+                .iter()
+                .map(|arg| Value::String(arg.clone()))
+                .collect(),
+        ))
+    }
+
+    // Built-in function: deepCopy(value) - Copy a value and everything nested inside it
+    fn builtin_deep_copy(&mut self, arguments: &[Expr]) -> Result<Value, String> {
+        let [argument] = arguments else {
+            return Err(format!(
+                "deepCopy() expects 1 argument, got {}",
+                arguments.len()
+            ));
+        };
+        self.evaluate_expr(argument)?.deep_copy()
+    }
+
+    // Built-in function: parallelMap(array, function) - Call the function on every item, spread
+    // over one thread per CPU, and collect the results in order. Each thread has an interpreter of
+    // its own, started from the current variables, so calls cannot see each other's changes; what
+    // they print is written once they all finish, in item order.
+    fn builtin_parallel_map(&mut self, arguments: &[Expr]) -> Result<Value, String> {
+        if arguments.len() != 2 {
+            return Err(format!(
+                "parallelMap() expects 2 arguments, got {}",
+                arguments.len()
+            ));
+        }
+        let (items, dynamic) = match self.evaluate_expr(&arguments[0])?.thawed() {
+            Value::FixedArray(items) => (items.clone(), false),
+            Value::DynamicArray(items) => (items.clone(), true),
+            other => {
+                return Err(format!(
+                    "parallelMap() needs an array as its first argument, not a {}",
+                    other.type_name()
+                ))
+            }
+        };
+        let (params, body) = match self.evaluate_expr(&arguments[1])? {
+            Value::Function(params, body) => (params, body),
+            other => {
+                return Err(format!(
+                    "parallelMap() needs a function as its second argument, not a {}",
+                    other.type_name()
+                ))
+            }
+        };
+        if params.len() != 1 {
+            return Err(format!(
+                "parallelMap() function must take 1 argument (an item), not {}",
+                params.len()
+            ));
+        }
+        let name = match &arguments[1] {
+            Expr::Identifier(name, _, _) => name.clone(),
+            _ => "parallelMap".to_string(),
+        };
+
+        let workers = thread::available_parallelism()
+            .map_or(1, |count| count.get())
+            .min(items.len());
+        let mut results = Vec::with_capacity(items.len());
+        if workers <= 1 || cfg!(target_arch = "wasm32") {
+            // Not worth a thread (or none are available): call it here, one item at a time
+            for item in items {
+                results.push(self.call_function_value(&name, &params, &body, vec![item])?);
+            }
+        } else {
+            let snapshot = self.snapshot();
+            let chunk_size = (items.len() + workers - 1) / workers;
+            let (name, params, body) = (&name, &params, &body);
+            let finished = thread::scope(|scope| {
+                let handles: Vec<_> = items
+                    .chunks(chunk_size)
+                    .map(|chunk| {
+                        let mut worker = self.thread_interpreter(&snapshot);
+                        worker.capture_output();
+                        scope.spawn(move || {
+                            let mapped = chunk
+                                .iter()
+                                .map(|item| {
+                                    worker.call_function_value(
+                                        name,
+                                        params,
+                                        body,
+                                        vec![item.clone()],
+                                    )
+                                })
+                                .collect::<Result<Vec<Value>, String>>();
+                            (mapped, worker.steps, worker.take_output())
+                        })
+                    })
+                    .collect();
+                handles
+                    .into_iter()
+                    .map(|handle| {
+                        handle.join().unwrap_or_else(|_| {
+                            (
+                                Err("parallelMap() worker thread panicked".to_string()),
+                                0,
+                                String::new(),
+                            )
+                        })
+                    })
+                    .collect::<Vec<_>>()
+            });
+
+            // The first failing item (in order) is the error
+            for (mapped, steps, output) in finished {
+                self.steps += steps;
+                self.write_output(&output, false);
+                results.extend(mapped?);
+            }
+            self.check_steps()?;
+        }
+
+        let result = if dynamic {
+            Value::DynamicArray(results)
+        } else {
+            Value::FixedArray(results)
+        };
+        self.check_value_size(&result)?;
+        Ok(result)
+    }
+
+    // Built-in function: spawn(function, arguments...) - Start calling the function on a thread
+    // of its own and return a task to wait for. Like parallelMap's calls, it starts from the
+    // current variables and cannot change the spawner's.
+    fn builtin_spawn(&mut self, arguments: &[Expr]) -> Result<Value, String> {
+        let Some((function, rest)) = arguments.split_first() else {
+            return Err("spawn() expects a function and its arguments".to_string());
+        };
+        let (params, body) = match self.evaluate_expr(function)? {
+            Value::Function(params, body) => (params, body),
+            other => {
+                return Err(format!(
+                    "spawn() needs a function as its first argument, not a {}",
+                    other.type_name()
+                ))
+            }
+        };
+        let name = match function {
+            Expr::Identifier(name, _, _) => name.clone(),
+            _ => "spawn".to_string(),
+        };
+        if rest.len() != params.len() {
+            return Err(format!(
+                "spawn() function expects {} arguments, got {}",
+                params.len(),
+                rest.len()
+            ));
+        }
+        let values = rest
+            .iter()
+            .map(|argument| self.evaluate_expr(argument))
+            .collect::<Result<Vec<Value>, String>>()?;
+
+        let state = if cfg!(target_arch = "wasm32") {
+            // No threads to start: run it now, keeping any error for wait()
+            TaskState::Finished(self.call_function_value(&name, &params, &body, values))
+        } else {
+            let mut worker = self.thread_interpreter(&self.snapshot());
+            match &self.output {
+                Output::Captured(_) => worker.capture_output(), // Passed on when waited for
+                Output::Streams(..) => worker.output = self.output.clone(), // Shared writers
+                Output::Stdio => self.flush_stdout(), // So what was printed before shows first
+            }
+            TaskState::Running(thread::spawn(move || {
+                let result = worker.call_function_value(&name, &params, &body, values);
+                // Tasks it spawned and timers it set itself
+                let finished = worker.wait_for_tasks().and_then(|_| worker.run_timers());
+                TaskOutcome {
+                    result: result.and_then(|value| finished.map(|_| value)),
+                    steps: worker.steps,
+                    output: worker.take_output(),
+                }
+            }))
+        };
+        let task = TaskHandle(Arc::new(Mutex::new(state)));
+        self.tasks.push(task.clone());
+        Ok(Value::Task(task))
+    }
+
+    // Built-in function: wait(task) - Wait for a spawned task and get its return value; an error
+    // in the task is raised here
+    fn builtin_wait(&mut self, arguments: &[Expr]) -> Result<Value, String> {
+        if arguments.len() != 1 {
+            return Err(format!(
+                "wait() expects 1 argument, got {}",
+                arguments.len()
+            ));
+        }
+        match self.evaluate_expr(&arguments[0])? {
+            Value::Task(task) => self.finish_task(&task),
+            other => Err(format!(
+                "wait() needs a task (from spawn), not a {}",
+                other.type_name()
+            )),
+        }
+    }
+
+    // Wait for a task, passing its steps and output on the first time
+    fn finish_task(&mut self, task: &TaskHandle) -> Result<Value, String> {
+        let (result, outcome) = task.finish();
+        self.tasks.retain(|other| !other.same_task(task));
+        if let Some(outcome) = outcome {
+            self.steps += outcome.steps;
+            self.write_output(&outcome.output, false);
+            self.check_steps()?;
+        }
+        result
+    }
+
+    // Wait for every task not yet waited for, in the order they were spawned
+    fn wait_for_tasks(&mut self) -> Result<(), String> {
+        while let Some(task) = self.tasks.first().cloned() {
+            self.finish_task(&task)?;
+        }
+        Ok(())
+    }
+
+    // Built-in functions: setTimeout(function, delay) and setInterval(function, delay) - Call the
+    // function once after the delay, or every time the delay passes, once the program has
+    // finished; the delay is in milliseconds or a duration. Returns the timer's id.
+    fn builtin_set_timer(&mut self, arguments: &[Expr], repeat: bool) -> Result<Value, String> {
+        let builtin = if repeat { "setInterval" } else { "setTimeout" };
+        if arguments.len() != 2 {
+            return Err(format!(
+                "{builtin}() expects 2 arguments, got {}",
+                arguments.len()
+            ));
+        }
+        let (params, body) = match self.evaluate_expr(&arguments[0])? {
+            Value::Function(params, body) => (params, body),
+            other => {
+                return Err(format!(
+                    "{builtin}() needs a function as its first argument, not a {}",
+                    other.type_name()
+                ))
+            }
+        };
+        if params.len() > 1 {
+            return Err(format!(
+                "{builtin}() function must take no arguments or 1 (how many times it has run), not {}",
+                params.len()
+            ));
+        }
+        let delay = match self.evaluate_expr(&arguments[1])? {
+            Value::Number(milliseconds) => milliseconds,
+            Value::Duration(seconds) => seconds * 1000.0,
+            other => {
+                return Err(format!(
+                    "{builtin}() delay must be a number of milliseconds or a duration, not a {}",
+                    other.type_name()
+                ))
+            }
+        }
+        .max(0.0); // Also turns NaN into 0
+        let name = match &arguments[0] {
+            Expr::Identifier(name, _, _) => name.clone(),
+            _ => builtin.to_string(),
+        };
+
+        let id = self.next_timer;
+        self.next_timer += 1;
+        self.timers.push(Timer {
+            id,
+            due: self.timer_now() + delay,
+            every: repeat.then_some(delay),
+            name,
+            params,
+            body,
+            runs: 0,
+        });
+        Ok(Value::Number(id as f64))
+    }
+
+    // Built-in function: clearTimer(id) - Cancel a timer (an unknown id is ignored)
+    fn builtin_clear_timer(&mut self, arguments: &[Expr]) -> Result<Value, String> {
+        if arguments.len() != 1 {
+            return Err(format!(
+                "clearTimer() expects 1 argument, got {}",
+                arguments.len()
+            ));
+        }
+        match self.evaluate_expr(&arguments[0])? {
+            Value::Number(id) => {
+                self.timers.retain(|timer| timer.id as f64 != id);
+                Ok(Value::Nil)
+            }
+            other => Err(format!(
+                "clearTimer() needs a timer id (from setTimeout or setInterval), not a {}",
+                other.type_name()
+            )),
+        }
+    }
+
+    // Run the timers, earliest first, until none are left; a callback can set more
+    fn run_timers(&mut self) -> Result<(), String> {
+        while let Some(index) = (0..self.timers.len()).min_by(|&a, &b| {
+            let (a, b) = (&self.timers[a], &self.timers[b]);
+            a.due.total_cmp(&b.due).then(a.id.cmp(&b.id))
+        }) {
+            let due = self.timers[index].due;
+            self.timer_clock = due;
+            self.sleep_until(due)?;
+
+            // Reschedule (or drop) the timer first, so its callback can clear it
+            let timer = &mut self.timers[index];
+            timer.runs += 1;
+            let (name, params, body) =
+                (timer.name.clone(), timer.params.clone(), timer.body.clone());
+            let runs = timer.runs;
+            match timer.every {
+                Some(every) => timer.due = due + every,
+                None => {
+                    self.timers.remove(index);
+                }
+            }
+            let values = params.iter().map(|_| Value::Number(runs as f64)).collect();
+            self.call_function_value(&name, &params, &body, values)?;
+            self.wait_for_tasks()?;
+        }
+        Ok(())
+    }
+
+    // Get the time on the timer clock, in milliseconds: real time, except in WebAssembly, which
+    // has no clock to read, so timers run one after another without waiting
+    fn timer_now(&self) -> f64 {
+        if cfg!(target_arch = "wasm32") {
+            return self.timer_clock;
+        }
+        static START: OnceLock<std::time::Instant> = OnceLock::new();
+        START
+            .get_or_init(std::time::Instant::now)
+            .elapsed()
+            .as_secs_f64()
+            * 1000.0
+    }
+
+    // Wait until the timer clock reaches a time, stopping early if the program is interrupted
+    fn sleep_until(&mut self, due: f64) -> Result<(), String> {
+        loop {
+            let remaining = due - self.timer_now();
+            if remaining <= 0.0 {
+                return Ok(());
+            }
+            if let Some(flag) = &self.interrupt {
+                if flag.swap(false, Ordering::SeqCst) {
+                    return Err("Interrupted".to_string());
+                }
+            }
+            self.flush_output();
+            thread::sleep(std::time::Duration::from_secs_f64(
+                remaining.min(50.0) / 1000.0,
+            ));
+        }
+    }
+
+    // Create an interpreter for another thread (parallelMap, spawn), starting from a snapshot of
+    // this one and sharing what is left of its step budget and its interrupt flag
+    fn thread_interpreter(&self, snapshot: &Snapshot) -> Interpreter {
+        let mut worker = snapshot.interpreter();
+        worker.set_limits(Limits {
+            max_steps: self
+                .limits
+                .max_steps
+                .map(|max_steps| max_steps.saturating_sub(self.steps)),
+            ..self.limits
+        });
+        worker.interrupt = self.interrupt.clone();
+        worker.async_bridge = self.async_bridge.clone();
+        worker.input = self.input.clone();
+        worker
+    }
+
+    // Built-in function: freeze(value) - Make an array or object (and everything in it) unchangeable
+    fn builtin_freeze(&mut self, arguments: &[Expr]) -> Result<Value, String> {
+        let [argument] = arguments else {
+            return Err(format!(
+                "freeze() expects 1 argument, got {}",
+                arguments.len()
+            ));
+        };
+        Ok(self.evaluate_expr(argument)?.freeze())
+    }
+
     // Built-in function: Date() - Create a new Date object
     fn builtin_date(&mut self, arguments: &[Expr]) -> Result<Value, String> {
         match arguments.len() {
@@ -947,12 +2607,38 @@ impl Interpreter {
                                 .unwrap_or(Local::now()),
                         ))
                     } else {
-                        Err(format!("Unable to parse date: '{s}'"))
+                        Err(format!("Unable to parse date: '{s}' (expected YYYY-MM-DD or YYYY-MM-DD HH:MM:SS; give a format for others, e.g. Date(text, \"%d/%m/%Y\"))"))
                     }
                 } else {
                     Err("Date() argument must be a string".to_string())
                 }
             }
+            2 => {
+                // Parse date from string with a format: Date("31/12/2024", "%d/%m/%Y")
+                let text = self.evaluate_expr(&arguments[0])?;
+                let format = self.evaluate_expr(&arguments[1])?;
+                let (Value::String(text), Value::String(format)) = (text, format) else {
+                    return Err("Date() text and format must be strings".to_string());
+                };
+                use chrono::{NaiveDate, NaiveDateTime, TimeZone};
+
+                // A format without a time of day gives midnight
+                let naive = NaiveDateTime::parse_from_str(&text, &format).or_else(|_| {
+                    NaiveDate::parse_from_str(&text, &format)
+                        .map(|date| date.and_hms_opt(0, 0, 0).unwrap())
+                });
+                match naive {
+                    Ok(naive) => Ok(Value::Date(
+                        Local
+                            .from_local_datetime(&naive)
+                            .single()
+                            .unwrap_or(Local::now()),
+                    )),
+                    Err(_) => Err(format!(
+                        "Unable to parse date: '{text}' with format '{format}'"
+                    )),
+                }
+            }
             3 => {
                 // Year, month, day
                 let year = self.evaluate_expr(&arguments[0])?;
@@ -977,7 +2663,24 @@ impl Interpreter {
                     Err("Date() year, month, and day must be numbers".to_string())
                 }
             }
-            _ => Err("Date() takes 0, 1, or 3 arguments".to_string()),
+            _ => Err("Date() takes 0, 1, 2, or 3 arguments".to_string()),
+        }
+    }
+
+    // Built-in function: Duration(hours, minutes, seconds) - Create a length of time
+    fn builtin_duration(&mut self, arguments: &[Expr]) -> Result<Value, String> {
+        if arguments.len() != 3 {
+            return Err("Duration() takes 3 arguments (hours, minutes, seconds)".to_string());
+        }
+        let parts = arguments
+            .iter()
+            .map(|argument| self.evaluate_expr(argument))
+            .collect::<Result<Vec<Value>, String>>()?;
+        match parts[..] {
+            [Value::Number(hours), Value::Number(minutes), Value::Number(seconds)] => {
+                Ok(Value::Duration(hours * 3600.0 + minutes * 60.0 + seconds))
+            }
+            _ => Err("Duration() hours, minutes, and seconds must be numbers".to_string()),
         }
     }
 
@@ -1021,3 +2724,113 @@ impl Interpreter {
         Ok(Value::Object(obj))
     }
 }
+
+// Find the file for an imported module, trying the path as given, then examples/, then
+// installed packages
+pub fn find_module(module_path: &str) -> Result<String, String> {
+    use std::path::Path;
+
+    // Ensure the module has .pg extension
+    let full_path = if module_path.ends_with(".pg") {
+        module_path.to_string()
+    } else {
+        format!("{module_path}.pg")
+    };
+
+    // Try to find the module file (modules bundled into the executable come first)
+    let exists = |path: &str| is_embedded_module(path) || Path::new(path).exists();
+    if exists(&full_path) {
+        Ok(full_path)
+    } else {
+        // Try in examples directory, then the module paths, then the installed packages
+        let mut tried = vec![full_path];
+        tried.push(format!("examples/{}", tried[0]));
+        for dir in MODULE_PATHS.get().into_iter().flatten() {
+            tried.push(format!("{}/{}", dir.trim_end_matches('/'), tried[0]));
+        }
+        tried.push(package_module_path(module_path));
+        match tried[1..].iter().find(|path| exists(path)) {
+            Some(path) => Ok(path.clone()),
+            None => Err(format!(
+                "Module '{module_path}' not found. Tried: {}",
+                tried.join(", ")
+            )),
+        }
+    }
+}
+
+// Get the file a module path names inside the installed packages: the first part is the
+// package, the rest a file in it ("strings.text" is pg_modules/strings/text.pg), and a package
+// name alone means the package's lib.pg
+fn package_module_path(module_path: &str) -> String {
+    let name = module_path.strip_suffix(".pg").unwrap_or(module_path);
+    match name.split_once('.') {
+        Some((package, file)) => {
+            format!("{PACKAGES_DIR}/{package}/{}.pg", file.replace('.', "/"))
+        }
+        None => format!("{PACKAGES_DIR}/{name}/lib.pg"),
+    }
+}
+
+// Find, read, and parse an imported module, returning its file path and AST
+pub fn parse_module(module_path: &str) -> Result<(String, Program), String> {
+    use std::fs;
+
+    let module_file = find_module(module_path)?;
+
+    // Read the module file (or use the copy shipped with the program)
+    let (source, from_disk) = match EMBEDDED_MODULES
+        .get()
+        .and_then(|modules| modules.get(&module_file))
+    {
+        Some(ModuleSource::Ast(program)) => return Ok((module_file, program.clone())),
+        Some(ModuleSource::Source(source)) => (source.clone(), false),
+        None => {
+            let source = fs::read_to_string(&module_file)
+                .map_err(|e| format!("Failed to read module '{module_file}': {e}"))?;
+            (source, true)
+        }
+    };
+
+    // Reuse the AST saved the last time the file was parsed, if it has not changed since
+    #[cfg(not(target_arch = "wasm32"))]
+    if from_disk {
+        if let Some(program) = crate::module_cache::load(&module_file, &source) {
+            return Ok((module_file, program));
+        }
+    }
+
+    // Parse the module
+    let mut lexer = Lexer::new(&source);
+    let tokens = lexer.tokenize()?;
+    let mut parser = Parser::new(&tokens);
+    let program = parser
+        .parse()
+        .map_err(|e| format!("Failed to parse module '{module_file}': {e}"))?;
+
+    #[cfg(not(target_arch = "wasm32"))]
+    if from_disk {
+        crate::module_cache::store(&module_file, &source, &program);
+    }
+    #[cfg(target_arch = "wasm32")]
+    let _ = from_disk; // No cache without a file system
+    Ok((module_file, program))
+}
+
+// Make modules available without reading files (used by bundles and .pgc files)
+pub fn set_embedded_modules(modules: HashMap<String, ModuleSource>) {
+    let _ = EMBEDDED_MODULES.set(modules); // Only the first call takes effect
+}
+
+// Add directories to look for modules in, after the current and examples directories (used by
+// pidgin run)
+pub fn set_module_paths(paths: Vec<String>) {
+    let _ = MODULE_PATHS.set(paths); // Only the first call takes effect
+}
+
+// Check whether a module file was shipped with the program
+fn is_embedded_module(path: &str) -> bool {
+    EMBEDDED_MODULES
+        .get()
+        .is_some_and(|modules| modules.contains_key(path))
+}
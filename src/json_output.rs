@@ -0,0 +1,364 @@
+// JSON renderings of tokens and the AST for external tools (`--format json`)
+//
+// The structures produced here are documented in docs/JSON_Output.md. Bump
+// FORMAT_VERSION whenever a field is renamed or removed.
+use crate::ast::{Expr, Pattern, Program, Span, Stmt};
+use crate::outline::{Symbol, SymbolKind};
+use crate::token::{Token, TokenInfo};
+use serde_json::{json, Value as Json};
+
+pub const FORMAT_VERSION: u32 = 2; // Version of the JSON structures below
+
+// Render a token stream as a JSON document
+pub fn tokens_to_json(tokens: &[TokenInfo]) -> Json {
+    let tokens: Vec<Json> = tokens.iter().map(token_to_json).collect();
+    json!({ "version": FORMAT_VERSION, "tokens": tokens })
+}
+
+// Render a parsed program as a JSON document
+pub fn program_to_json(program: &Program) -> Json {
+    let statements: Vec<Json> = program.statements.iter().map(stmt_to_json).collect();
+    json!({ "version": FORMAT_VERSION, "statements": statements })
+}
+
+// Render the top-level definitions of a file (--symbols) as a JSON document
+pub fn symbols_to_json(symbols: &[Symbol]) -> Json {
+    let symbols: Vec<Json> = symbols
+        .iter()
+        .map(|symbol| {
+            let mut entry = json!({
+                "kind": symbol.kind.name(),
+                "name": symbol.name,
+                "exported": symbol.exported,
+                "span": span_to_json(symbol.span),
+            });
+            match symbol.kind {
+                SymbolKind::Function | SymbolKind::Method => {
+                    entry["parameters"] = json!(symbol.parameters)
+                }
+                SymbolKind::Import => entry["module"] = json!(symbol.module),
+                SymbolKind::Variable => {}
+            }
+            entry
+        })
+        .collect();
+    json!({ "version": FORMAT_VERSION, "symbols": symbols })
+}
+
+// Render a single token with its span
+fn token_to_json(info: &TokenInfo) -> Json {
+    let mut token = json!({
+        "kind": token_kind(&info.token),
+        "span": { "line": info.line, "column": info.column, "length": info.length },
+    });
+    let value = match &info.token {
+        Token::Number(n) => Some(json!(n)),
+        Token::Identifier(s) | Token::String(s) | Token::Comment(s) | Token::DocComment(s) => {
+            Some(json!(s))
+        }
+        _ => None,
+    };
+    if let Some(value) = value {
+        token["value"] = value;
+    }
+    token
+}
+
+// Name of a token kind as it appears in the JSON output
+fn token_kind(token: &Token) -> &'static str {
+    match token {
+        Token::Number(_) => "Number",
+        Token::Identifier(_) => "Identifier",
+        Token::String(_) => "String",
+        Token::Plus => "Plus",
+        Token::Minus => "Minus",
+        Token::Star => "Star",
+        Token::Slash => "Slash",
+        Token::Assign => "Assign",
+        Token::Equal => "Equal",
+        Token::NotEqual => "NotEqual",
+        Token::Less => "Less",
+        Token::Greater => "Greater",
+        Token::LessEqual => "LessEqual",
+        Token::GreaterEqual => "GreaterEqual",
+        Token::Let => "Let",
+        Token::If => "If",
+        Token::Else => "Else",
+        Token::While => "While",
+        Token::For => "For",
+        Token::In => "In",
+        Token::Match => "Match",
+        Token::Case => "Case",
+        Token::Break => "Break",
+        Token::Print => "Print",
+        Token::PrintLn => "PrintLn",
+        Token::PrintErr => "PrintErr",
+        Token::Function => "Function",
+        Token::True => "True",
+        Token::False => "False",
+        Token::Return => "Return",
+        Token::Get => "Get",
+        Token::From => "From",
+        Token::LeftParen => "LeftParen",
+        Token::RightParen => "RightParen",
+        Token::LeftBrace => "LeftBrace",
+        Token::RightBrace => "RightBrace",
+        Token::LeftBracket => "LeftBracket",
+        Token::RightBracket => "RightBracket",
+        Token::Semicolon => "Semicolon",
+        Token::Comma => "Comma",
+        Token::Dot => "Dot",
+        Token::Backtick => "Backtick",
+        Token::Arrow => "Arrow",
+        Token::ArrowLeft => "ArrowLeft",
+        Token::Colon => "Colon",
+        Token::ColonEqual => "ColonEqual",
+        Token::AssignRight => "AssignRight",
+        Token::Imply => "Imply",
+        Token::Comment(_) => "Comment",
+        Token::DocComment(_) => "DocComment",
+        Token::Newline => "Newline",
+        Token::Eof => "Eof",
+    }
+}
+
+// Render a span (line and column of the node's first token)
+fn span_to_json(span: Span) -> Json {
+    json!({ "line": span.line, "column": span.column })
+}
+
+// Render a list of statements
+fn stmts_to_json(statements: &[Stmt]) -> Vec<Json> {
+    statements.iter().map(stmt_to_json).collect()
+}
+
+// Render a list of expressions
+fn exprs_to_json(expressions: &[Expr]) -> Vec<Json> {
+    expressions.iter().map(expr_to_json).collect()
+}
+
+// Render a statement node
+fn stmt_to_json(stmt: &Stmt) -> Json {
+    let span = span_to_json(stmt.span());
+    match stmt {
+        Stmt::Expression(expr, _) => {
+            json!({ "type": "Expression", "span": span, "expression": expr_to_json(expr) })
+        }
+        Stmt::Return(expr, _) => {
+            json!({ "type": "Return", "span": span, "value": expr_to_json(expr) })
+        }
+        Stmt::Print {
+            format, arguments, ..
+        } => print_to_json("Print", span, format, arguments),
+        Stmt::PrintLn {
+            format, arguments, ..
+        } => print_to_json("PrintLn", span, format, arguments),
+        Stmt::PrintErr {
+            format, arguments, ..
+        } => print_to_json("PrintErr", span, format, arguments),
+        Stmt::Import { names, module, .. } => {
+            json!({ "type": "Import", "span": span, "names": names, "module": module })
+        }
+        Stmt::VarDeclaration {
+            name, initializer, ..
+        } => json!({
+            "type": "VarDeclaration",
+            "span": span,
+            "name": name,
+            "initializer": initializer.as_ref().map(expr_to_json),
+        }),
+        Stmt::Unpack {
+            names,
+            value,
+            declares,
+            ..
+        } => json!({
+            "type": "Unpack",
+            "span": span,
+            "names": names,
+            "declares": declares,
+            "value": expr_to_json(value),
+        }),
+        Stmt::FunctionDeclaration {
+            name,
+            parameters,
+            body,
+            doc,
+            ..
+        } => json!({
+            "type": "FunctionDeclaration",
+            "span": span,
+            "name": name,
+            "parameters": parameters,
+            "body": stmt_to_json(body),
+            "doc": doc,
+        }),
+        Stmt::Block(statements, _) => {
+            json!({ "type": "Block", "span": span, "statements": stmts_to_json(statements) })
+        }
+        Stmt::If {
+            condition,
+            then_branch,
+            else_branch,
+            ..
+        } => json!({
+            "type": "If",
+            "span": span,
+            "condition": expr_to_json(condition),
+            "then": stmt_to_json(then_branch),
+            "else": else_branch.as_deref().map(stmt_to_json),
+        }),
+        Stmt::While {
+            condition, body, ..
+        } => json!({
+            "type": "While",
+            "span": span,
+            "condition": expr_to_json(condition),
+            "body": stmt_to_json(body),
+        }),
+        Stmt::ForIn {
+            variable,
+            iterable,
+            body,
+            ..
+        } => json!({
+            "type": "ForIn",
+            "span": span,
+            "variable": variable,
+            "iterable": expr_to_json(iterable),
+            "body": stmt_to_json(body),
+        }),
+    }
+}
+
+// Render one of the print statements
+fn print_to_json(kind: &str, span: Json, format: &Expr, arguments: &[Expr]) -> Json {
+    json!({
+        "type": kind,
+        "span": span,
+        "format": expr_to_json(format),
+        "arguments": exprs_to_json(arguments),
+    })
+}
+
+// Render an expression node
+fn expr_to_json(expr: &Expr) -> Json {
+    match expr {
+        Expr::Number(n) => json!({ "type": "Number", "value": n }),
+        Expr::String(s) => json!({ "type": "String", "value": s }),
+        Expr::Boolean(b) => json!({ "type": "Boolean", "value": b }),
+        Expr::Identifier(name, span, _) => {
+            json!({ "type": "Identifier", "span": span_to_json(*span), "name": name })
+        }
+        Expr::Nil => json!({ "type": "Nil" }),
+        Expr::FixedArray(elements) => {
+            json!({ "type": "FixedArray", "elements": exprs_to_json(elements) })
+        }
+        Expr::DynamicArray(elements) => {
+            json!({ "type": "DynamicArray", "elements": exprs_to_json(elements) })
+        }
+        Expr::Index { array, index, span } => json!({
+            "type": "Index",
+            "span": span_to_json(*span),
+            "array": expr_to_json(array),
+            "index": expr_to_json(index),
+        }),
+        Expr::Binary {
+            left,
+            operator,
+            right,
+            span,
+        } => json!({
+            "type": "Binary",
+            "span": span_to_json(*span),
+            "operator": operator.symbol(),
+            "left": expr_to_json(left),
+            "right": expr_to_json(right),
+        }),
+        Expr::Unary {
+            operator,
+            operand,
+            span,
+        } => json!({
+            "type": "Unary",
+            "span": span_to_json(*span),
+            "operator": operator.symbol(),
+            "operand": expr_to_json(operand),
+        }),
+        Expr::Assignment {
+            name, value, span, ..
+        } => json!({
+            "type": "Assignment",
+            "span": span_to_json(*span),
+            "name": name,
+            "value": expr_to_json(value),
+        }),
+        Expr::MethodCall {
+            object,
+            method,
+            arguments,
+            span,
+        } => json!({
+            "type": "MethodCall",
+            "span": span_to_json(*span),
+            "object": expr_to_json(object),
+            "method": method,
+            "arguments": exprs_to_json(arguments),
+        }),
+        Expr::Transform { from, to, span } => {
+            json!({ "type": "Transform", "span": span_to_json(*span), "from": from, "to": to })
+        }
+        Expr::FunctionCall {
+            name,
+            arguments,
+            span,
+            ..
+        } => json!({
+            "type": "FunctionCall",
+            "span": span_to_json(*span),
+            "name": name,
+            "arguments": exprs_to_json(arguments),
+        }),
+        Expr::Match {
+            subject,
+            cases,
+            span,
+        } => json!({
+            "type": "Match",
+            "span": span_to_json(*span),
+            "subject": expr_to_json(subject),
+            "cases": cases
+                .iter()
+                .map(|case| json!({
+                    "pattern": pattern_to_json(&case.pattern),
+                    "guard": case.guard.as_ref().map(expr_to_json),
+                    "body": expr_to_json(&case.body),
+                }))
+                .collect::<Vec<Json>>(),
+        }),
+    }
+}
+
+// Render a pattern of a match case
+fn pattern_to_json(pattern: &Pattern) -> Json {
+    match pattern {
+        Pattern::Wildcard => json!({ "type": "Wildcard" }),
+        Pattern::Binding(name, span) => {
+            json!({ "type": "Binding", "span": span_to_json(*span), "name": name })
+        }
+        Pattern::Number(n) => json!({ "type": "Number", "value": n }),
+        Pattern::String(s) => json!({ "type": "String", "value": s }),
+        Pattern::Boolean(b) => json!({ "type": "Boolean", "value": b }),
+        Pattern::Array(elements) => json!({
+            "type": "Array",
+            "elements": elements.iter().map(pattern_to_json).collect::<Vec<Json>>(),
+        }),
+        Pattern::Object(fields) => json!({
+            "type": "Object",
+            "fields": fields
+                .iter()
+                .map(|(key, field)| json!({ "key": key, "pattern": pattern_to_json(field) }))
+                .collect::<Vec<Json>>(),
+        }),
+    }
+}
@@ -1,12 +1,39 @@
 // Import the Token and TokenInfo types from the token module
 use crate::token::{Token, TokenInfo};
 
+// Keywords, recognized whatever their case: LET, Let, and let are all `let` (keywords are ASCII,
+// and so are identifiers, so ASCII case folding is all it takes)
+const KEYWORDS: &[(&str, Token)] = &[
+    ("let", Token::Let),
+    ("if", Token::If),
+    ("else", Token::Else),
+    ("while", Token::While),
+    ("for", Token::For),
+    ("in", Token::In), // for-in loops
+    ("match", Token::Match),
+    ("case", Token::Case), // match cases
+    ("break", Token::Break),
+    ("print", Token::Print),
+    ("function", Token::Function),
+    ("true", Token::True),
+    ("false", Token::False),
+    ("return", Token::Return),
+    ("get", Token::Get),   // imports
+    ("from", Token::From), // imports
+];
+
+// Keywords recognized only as written, since their capital letter is part of the name (println
+// is an ordinary identifier)
+const EXACT_KEYWORDS: &[(&str, Token)] =
+    &[("printLn", Token::PrintLn), ("printErr", Token::PrintErr)];
+
 // Define the Lexer struct, which will be responsible for tokenizing input source code
 pub struct Lexer {
-    input: Vec<char>, // The input source code as a vector of characters
-    position: usize,  // The current position in the input
-    line: usize,      // The current line number (for error reporting)
-    column: usize,    // The current column number (for error reporting)
+    input: Vec<char>,    // The input source code as a vector of characters
+    position: usize,     // The current position in the input
+    line: usize,         // The current line number (for error reporting)
+    column: usize,       // The current column number (for error reporting)
+    keep_comments: bool, // Whether to emit Comment tokens (used by tooling like the formatter)
 }
 
 // Implement methods for the Lexer struct
@@ -18,12 +45,37 @@ impl Lexer {
             position: 0,                    // Start at the beginning of the input
             line: 1,                        // Start at line 1
             column: 1,                      // Start at column 1
+            keep_comments: false,           // Comments are skipped by default
+        }
+    }
+
+    // Create a new Lexer that keeps comments in the token stream
+    pub fn with_comments(input: &str) -> Self {
+        Self {
+            keep_comments: true,
+            ..Self::new(input)
         }
     }
 
+    // Start over on new input, keeping the memory the previous input took
+    pub fn reset(&mut self, input: &str) {
+        self.input.clear();
+        self.input.extend(input.chars());
+        self.position = 0;
+        self.line = 1;
+        self.column = 1;
+    }
+
     // Tokenize the input and return a vector of TokenInfo
     pub fn tokenize(&mut self) -> Result<Vec<TokenInfo>, String> {
         let mut tokens = Vec::new(); // Create a vector to store tokens
+        self.tokenize_into(&mut tokens)?;
+        Ok(tokens)
+    }
+
+    // Tokenize the input into an existing vector (emptied first), reusing its memory
+    pub fn tokenize_into(&mut self, tokens: &mut Vec<TokenInfo>) -> Result<(), String> {
+        tokens.clear();
 
         while !self.is_at_end() {
             // Loop until the end of input
@@ -36,6 +88,8 @@ impl Lexer {
 
             let line = self.line; // Store the current line for the token
             let column = self.column; // Store the current column for the token
+            let start = self.position; // Store the start position to measure the token
+            let token_count = tokens.len(); // Detect whether this iteration added a token
 
             match self.current_char() {
                 // Match on the current character
@@ -70,9 +124,27 @@ impl Lexer {
                     self.advance();
                     if self.current_char() == '/' {
                         // Check for comment
+                        self.advance(); // Skip the second '/'
+
+                        // A third '/' (but not a fourth) makes it a doc comment
+                        let is_doc = self.current_char() == '/'
+                            && self.input.get(self.position + 1) != Some(&'/');
+                        if is_doc {
+                            self.advance(); // Skip the third '/'
+                        }
+
                         // Skip comment until end of line
+                        let mut text = String::new();
                         while !self.is_at_end() && self.current_char() != '\n' {
-                            self.advance(); // Skip each character in the comment
+                            text.push(self.advance()); // Collect each character in the comment
+                        }
+                        if is_doc {
+                            // Always kept: the parser attaches it to the function below
+                            let text = text.trim_end().to_string();
+                            tokens.push(TokenInfo::new(Token::DocComment(text), line, column));
+                        } else if self.keep_comments {
+                            let text = text.trim_end().to_string();
+                            tokens.push(TokenInfo::new(Token::Comment(text), line, column));
                         }
                     } else {
                         tokens.push(TokenInfo::new(Token::Slash, line, column));
@@ -200,8 +272,7 @@ impl Lexer {
                     // Add a Number token
                 }
                 c if c.is_ascii_alphabetic() || c == '_' => {
-                    let identifier = self.scan_identifier(); // Parse an identifier or keyword
-                    let token = self.keyword_or_identifier(identifier); // Determine if it's a keyword or identifier
+                    let token = self.scan_identifier(); // Parse an identifier or keyword
                     tokens.push(TokenInfo::new(token, line, column)); // Add the token
                 }
                 _ => {
@@ -213,10 +284,17 @@ impl Lexer {
                     )); // Error for unknown character
                 }
             }
+
+            // Record how many characters the new token covers
+            if tokens.len() > token_count {
+                if let Some(token) = tokens.last_mut() {
+                    token.length = self.position - start;
+                }
+            }
         }
 
         tokens.push(TokenInfo::new(Token::Eof, self.line, self.column)); // Add an EOF token at the end
-        Ok(tokens) // Return the vector of tokens
+        Ok(())
     }
 
     // Get the current character, or '\0' if at the end
@@ -320,44 +398,32 @@ impl Lexer {
     }
 
     // Scan and return an identifier (or keyword) as a String
-    fn scan_identifier(&mut self) -> String {
-        let mut value = String::new(); // Store the identifier
-
+    // Scan an identifier or keyword; only identifiers need their text copied into a String
+    fn scan_identifier(&mut self) -> Token {
+        let start = self.position;
         while !self.is_at_end()
             && (self.current_char().is_ascii_alphanumeric() || self.current_char() == '_')
         {
-            value.push(self.current_char()); // Add character
             self.advance(); // Move to next character
         }
 
-        value // Return the identifier
+        let text = &self.input[start..self.position];
+        keyword(text).unwrap_or_else(|| Token::Identifier(text.iter().collect()))
     }
+}
 
-    // Determine if a string is a keyword or an identifier
-    fn keyword_or_identifier(&self, text: String) -> Token {
-        // Handle case-sensitive keywords first
-        match text.as_str() {
-            "printLn" => Token::PrintLn,   // printLn keyword (case-sensitive)
-            "printErr" => Token::PrintErr, // printErr keyword (case-sensitive)
-            _ => {
-                // Handle case-insensitive keywords
-                match text.to_lowercase().as_str() {
-                    "let" => Token::Let,           // let keyword
-                    "if" => Token::If,             // if keyword
-                    "else" => Token::Else,         // else keyword
-                    "while" => Token::While,       // while keyword
-                    "break" => Token::Break,       // break keyword
-                    "print" => Token::Print,       // print keyword
-                    "function" => Token::Function, // function keyword
-                    "true" => Token::True,         // true keyword
-                    "false" => Token::False,       // false keyword
-                    "return" => Token::Return,     // return keyword
-                    "get" => Token::Get,           // get keyword for imports
-                    "from" => Token::From,         // from keyword for imports
-                    "<-" => Token::ArrowLeft,      // <- arrow token for imports
-                    _ => Token::Identifier(text),  // Otherwise, it's an identifier
-                }
-            }
-        }
-    }
+// Find the keyword a word spells, if any (see KEYWORDS and EXACT_KEYWORDS)
+pub fn keyword(text: &[char]) -> Option<Token> {
+    let spells = |word: &str, same: fn(&char, &char) -> bool| {
+        word.len() == text.len() && word.chars().zip(text).all(|(a, b)| same(&a, b))
+    };
+    EXACT_KEYWORDS
+        .iter()
+        .find(|(word, _)| spells(word, char::eq))
+        .or_else(|| {
+            KEYWORDS
+                .iter()
+                .find(|(word, _)| spells(word, char::eq_ignore_ascii_case))
+        })
+        .map(|(_, token)| token.clone())
 }
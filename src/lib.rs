@@ -0,0 +1,68 @@
+// The Pidgin language as a library: lexing, parsing, and interpreting programs
+//
+// The pidgin command line (src/main.rs) is built on top of this crate, and so is the
+// WebAssembly build behind the online playground (src/wasm.rs, enabled by the `wasm` feature).
+// Other Rust programs can embed the language the same way (see docs/Embedding.md):
+//
+//     let interpreter = pidgin::run_source("let answer = 6 * 7;")?;
+//     println!("{}", interpreter.variables()["answer"]);
+pub mod token; // Handles token definitions and tokenization
+
+pub mod lexer; // Handles lexical analysis (tokenizing source code)
+
+pub mod ast; // Defines the abstract syntax tree (AST) structures
+
+pub mod parser; // Handles parsing tokens into AST
+
+pub mod interpreter; // Handles interpreting/executing the AST
+
+pub mod symbols; // Handles numbering variable names and the tables of variables they index
+
+pub mod format; // Handles format strings for print, printLn, and printErr
+
+pub mod methods; // Handles the built-in methods of strings, arrays, objects, and dates
+
+pub mod pattern; // Handles compiling match patterns into the tests a value must pass
+
+pub mod logger; // Handles diagnostic output levels (--quiet, --verbose)
+
+pub mod error; // Handles error classes and exit codes
+
+pub mod value_json; // Handles converting runtime values to and from JSON (serde)
+
+pub mod async_host; // Handles async host functions (interpret_async)
+
+pub mod builder; // Handles building programs from Rust code (ProgramBuilder)
+
+pub mod snapshot_file; // Handles saving interpreter snapshots to disk and reading them back
+
+#[cfg(not(target_arch = "wasm32"))]
+pub mod module_cache; // Handles caching the parsed ASTs of imported modules on disk
+
+#[cfg(feature = "wasm")]
+pub mod wasm; // Handles the JavaScript-facing API of the WebAssembly build
+
+#[cfg(feature = "ffi")]
+pub mod ffi; // Handles the C API for embedding in other languages
+
+// The types most embedders need, at the top of the crate
+pub use builder::ProgramBuilder;
+pub use error::{ErrorKind, PidginError};
+pub use interpreter::{Interpreter, Snapshot, StatementHook, Value};
+pub use lexer::Lexer;
+pub use parser::Parser;
+
+// Run a program in a fresh interpreter, returning it so its variables can be read afterwards
+pub fn run_source(source: &str) -> Result<Interpreter, PidginError> {
+    let mut interpreter = Interpreter::new();
+    run_with_interpreter(source, &mut interpreter)?;
+    Ok(interpreter)
+}
+
+// Run a program in an existing interpreter, keeping the variables it already has
+pub fn run_with_interpreter(
+    source: &str,
+    interpreter: &mut Interpreter,
+) -> Result<(), PidginError> {
+    interpreter.run(source) // Lex, parse, and interpret it
+}
@@ -0,0 +1,320 @@
+// Linter for Pidgin programs (`pidgin lint`), built on the AST visitor
+use crate::ast::{Expr, Program, Span, Stmt, UnaryOp};
+use crate::interpreter::is_builtin;
+use crate::pattern;
+use crate::visitor::{self, Visitor};
+use std::collections::HashMap;
+
+// Every lint rule the linter knows, with a short description
+pub const RULES: &[(&str, &str)] = &[
+    (
+        "assign-in-condition",
+        "assignment used as an if/while condition",
+    ),
+    (
+        "unreachable-code",
+        "statements after a return in the same block, or a while loop whose condition is always false",
+    ),
+    ("empty-block", "block with no statements"),
+    (
+        "shadowed-builtin",
+        "variable, function, or parameter named like a builtin function",
+    ),
+    (
+        "shadowed-variable",
+        "'let' that re-declares a visible variable, parameter, function, or import",
+    ),
+    (
+        "split-expression",
+        "statement starting with a unary '-' on the line after another, as if it continued it",
+    ),
+];
+
+// Define the LintWarning struct, a single suspicious pattern found in a program
+#[derive(Debug, Clone)]
+pub struct LintWarning {
+    pub rule: &'static str, // Name of the rule that fired
+    pub message: String,    // Human-readable explanation
+    pub span: Span,         // Where the problem starts
+}
+
+// Lint a program, skipping any rules listed in `allowed`
+pub fn lint_program(program: &Program, allowed: &[String]) -> Vec<LintWarning> {
+    let mut linter = Linter {
+        allowed,
+        warnings: Vec::new(),
+        current_span: Span::default(),
+        scopes: vec![HashMap::new()],
+    };
+    linter.check_split_lines(&program.statements);
+    visitor::walk_program(&mut linter, program);
+    linter.warnings
+}
+
+// Find only the code that can never run (the unreachable-code rule), which `pidgin check`
+// reports as warnings
+pub fn unreachable_code(program: &Program) -> Vec<LintWarning> {
+    let others: Vec<String> = RULES
+        .iter()
+        .map(|(rule, _)| rule.to_string())
+        .filter(|rule| rule != "unreachable-code")
+        .collect();
+    lint_program(program, &others)
+}
+
+// Check whether a rule name is known to the linter
+pub fn is_known_rule(name: &str) -> bool {
+    RULES.iter().any(|(rule, _)| *rule == name)
+}
+
+// Define the Linter struct, which collects warnings while visiting the AST
+struct Linter<'a> {
+    allowed: &'a [String],                  // Rules disabled with --allow
+    warnings: Vec<LintWarning>,             // Warnings found so far
+    current_span: Span,                     // Span of the statement being visited
+    scopes: Vec<HashMap<String, Declared>>, // Names declared at the top level, then in each enclosing function
+}
+
+// Define the Declared struct, where a name visible at this point was declared
+#[derive(Debug, Clone, Copy)]
+struct Declared {
+    kind: &'static str, // "variable", "parameter", "function" or "import"
+    span: Span,         // Statement that declared it
+}
+
+impl Linter<'_> {
+    // Record a warning unless its rule has been allowed
+    fn warn(&mut self, rule: &'static str, message: String, span: Span) {
+        if !self.allowed.iter().any(|allowed| allowed == rule) {
+            self.warnings.push(LintWarning {
+                rule,
+                message,
+                span,
+            });
+        }
+    }
+
+    // Warn when a condition is an assignment (`if (x = 1)` instead of `if (x == 1)`)
+    fn check_condition(&mut self, condition: &Expr, keyword: &str, span: Span) {
+        if let Expr::Assignment { name, .. } = condition {
+            self.warn(
+                "assign-in-condition",
+                format!("assignment to '{name}' used as '{keyword}' condition; did you mean '=='?"),
+                span,
+            );
+        }
+    }
+
+    // Warn when a user definition reuses the name of a builtin function. A function the program
+    // defines is called in place of the builtin; any other value leaves calls to the builtin
+    // alone unless it is a function.
+    fn check_shadowing(&mut self, name: &str, kind: &str, span: Span) {
+        if !is_builtin(name) {
+            return;
+        }
+        let message = if kind == "function" {
+            format!("function '{name}' hides the builtin function '{name}': {name}() calls this function instead")
+        } else {
+            format!("{kind} '{name}' has the name of the builtin function '{name}'; {name}() calls it instead of the builtin whenever it holds a function")
+        };
+        self.warn("shadowed-builtin", message, span);
+    }
+
+    // Warn when a 'let' declares a name that is already visible, naming where that was
+    // declared; blocks do not start a scope in Pidgin, so only function bodies do
+    fn check_redeclaration(&mut self, name: &str, span: Span) {
+        let innermost = self.scopes.len().saturating_sub(1);
+        let visible = self
+            .scopes
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(depth, scope)| Some((depth, *scope.get(name)?)));
+        if let Some((depth, previous)) = visible.filter(|(_, previous)| previous.span != span) {
+            let what = match previous.kind {
+                "variable" if depth == innermost => "re-declares the variable".to_string(),
+                "variable" => "shadows the global variable".to_string(),
+                kind => format!("shadows the {kind}"),
+            };
+            self.warn(
+                "shadowed-variable",
+                format!(
+                    "variable '{name}' {what} '{name}' declared on line {}, column {}",
+                    previous.span.line, previous.span.column
+                ),
+                span,
+            );
+        }
+        self.declare(name, "variable", span);
+    }
+
+    // Warn about a statement that starts with a unary '-' on a line after another statement. A
+    // line break ends a statement that is already complete, so `let z = x` with `- 1` on the
+    // next line is two statements, and the second (whose value is discarded) is usually meant
+    // to continue the first.
+    fn check_split_lines(&mut self, statements: &[Stmt]) {
+        for pair in statements.windows(2) {
+            let (Stmt::Expression(expr, span), previous) = (&pair[1], &pair[0]) else {
+                continue;
+            };
+            if starts_with_minus(expr) && span.line > previous.span().line {
+                self.warn(
+                    "split-expression",
+                    "this line starts a new statement with '-', whose value is discarded; to continue the line above instead, end that line with the operator".to_string(),
+                    *span,
+                );
+            }
+        }
+    }
+
+    // Note that a name is visible from here on, in the innermost scope
+    fn declare(&mut self, name: &str, kind: &'static str, span: Span) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), Declared { kind, span });
+        }
+    }
+}
+
+// Check whether the first token of an expression is a unary '-' (-x, or -x * 2)
+fn starts_with_minus(expr: &Expr) -> bool {
+    match expr {
+        Expr::Unary {
+            operator: UnaryOp::Minus,
+            ..
+        } => true,
+        Expr::Binary { left, .. } => starts_with_minus(left),
+        _ => false,
+    }
+}
+
+impl Visitor for Linter<'_> {
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        self.current_span = stmt.span();
+        match stmt {
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+                span,
+            } => {
+                self.check_condition(condition, "if", *span);
+                // Only one branch runs, so names declared in one are not visible in the other
+                self.visit_expr(condition);
+                let before = self.scopes.last().cloned().unwrap_or_default();
+                self.visit_stmt(then_branch);
+                if let Some(else_branch) = else_branch {
+                    let after_then = self.scopes.pop().unwrap_or_default();
+                    self.scopes.push(before);
+                    self.visit_stmt(else_branch);
+                    if let Some(scope) = self.scopes.last_mut() {
+                        for (name, declared) in after_then {
+                            scope.entry(name).or_insert(declared);
+                        }
+                    }
+                }
+                return;
+            }
+            Stmt::ForIn { variable, span, .. } => {
+                self.check_shadowing(variable, "loop variable", *span);
+            }
+            Stmt::While {
+                condition,
+                body,
+                span,
+            } => {
+                self.check_condition(condition, "while", *span);
+                // false and nil are the only falsy values, so the body can never run
+                if matches!(condition, Expr::Boolean(false) | Expr::Nil) {
+                    self.warn(
+                        "unreachable-code",
+                        "loop body never runs: the 'while' condition is always false".to_string(),
+                        body.span(),
+                    );
+                }
+            }
+            Stmt::VarDeclaration { name, span, .. } => {
+                self.check_shadowing(name, "variable", *span);
+                self.check_redeclaration(name, *span);
+            }
+            Stmt::Unpack {
+                names,
+                declares,
+                span,
+                ..
+            } => {
+                for name in names {
+                    self.check_shadowing(name, "variable", *span);
+                    if *declares {
+                        self.check_redeclaration(name, *span);
+                    }
+                }
+            }
+            Stmt::Import { names, span, .. } => {
+                for name in names {
+                    self.declare(name, "import", *span);
+                }
+            }
+            Stmt::FunctionDeclaration {
+                name,
+                parameters,
+                parameter_spans,
+                span,
+                ..
+            } => {
+                // Each parameter is reported where it is named (a program read from JSON may
+                // not say, so its function's position stands in)
+                let parameter_span = |index: usize| *parameter_spans.get(index).unwrap_or(span);
+                self.check_shadowing(name, "function", *span);
+                for (index, parameter) in parameters.iter().enumerate() {
+                    self.check_shadowing(parameter, "parameter", parameter_span(index));
+                }
+                // The body gets a scope of its own, starting with the parameters
+                self.declare(name, "function", *span);
+                self.scopes.push(HashMap::new());
+                for (index, parameter) in parameters.iter().enumerate() {
+                    self.declare(parameter, "parameter", parameter_span(index));
+                }
+                visitor::walk_stmt(self, stmt);
+                self.scopes.pop();
+                return;
+            }
+            Stmt::Block(statements, span) => {
+                if statements.is_empty() {
+                    self.warn("empty-block", "empty block".to_string(), *span);
+                }
+                self.check_split_lines(statements);
+                if let Some(index) = statements
+                    .iter()
+                    .position(|stmt| matches!(stmt, Stmt::Return(..)))
+                {
+                    if let Some(unreachable) = statements.get(index + 1) {
+                        self.warn(
+                            "unreachable-code",
+                            "unreachable statement after 'return'".to_string(),
+                            unreachable.span(),
+                        );
+                    }
+                }
+            }
+            _ => {}
+        }
+        visitor::walk_stmt(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Assignment { name, .. } => {
+                self.check_shadowing(name, "variable", self.current_span);
+            }
+            Expr::Match { cases, .. } => {
+                for case in cases {
+                    for (name, span) in pattern::bound_names(&case.pattern) {
+                        self.check_shadowing(name, "pattern variable", span);
+                    }
+                }
+            }
+            _ => {}
+        }
+        visitor::walk_expr(self, expr);
+    }
+}
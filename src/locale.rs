@@ -0,0 +1,154 @@
+// Error messages in other languages (--lang, PIDGIN_LANG)
+//
+// The lexer, parser, and interpreter always produce their errors in English. Just before one
+// is shown, its message is looked up in the catalog of the chosen language: each entry is an
+// English message with `{}` where it varies, and the translation, which puts those parts back
+// with `{0}`, `{1}`, ... (in whatever order the language needs). A message the catalog does not
+// know stays in English, so a partly translated catalog is still useful.
+use std::env;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+// Define the Language enum, the languages error messages can be shown in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Language {
+    #[default]
+    English, // The messages as written (the default)
+    Pidgin, // Nigerian Pidgin (pcm)
+}
+
+// Implement methods for Language
+impl Language {
+    // Find a language by the name given to --lang or PIDGIN_LANG: a language code, optionally
+    // followed by a region or encoding as in locale names (pcm, pcm_NG, pcm-NG.UTF-8)
+    pub fn from_name(name: &str) -> Option<Self> {
+        let code = name.split(['_', '-', '.']).next().unwrap_or(name);
+        match code.to_lowercase().as_str() {
+            "en" | "english" => Some(Language::English),
+            "pcm" | "pidgin" => Some(Language::Pidgin),
+            _ => None,
+        }
+    }
+
+    // Find the language named by the PIDGIN_LANG environment variable, if it names one
+    pub fn from_env() -> Option<Self> {
+        env::var("PIDGIN_LANG")
+            .ok()
+            .and_then(|name| Self::from_name(&name))
+    }
+
+    // Get the catalog of translated messages
+    fn catalog(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Language::English => &[],
+            Language::Pidgin => PIDGIN,
+        }
+    }
+}
+
+// Nigerian Pidgin, more specific messages first
+const PIDGIN: &[(&str, &str)] = &[
+    ("Error", "Wahala"),
+    ("Warning", "Shine your eye"),
+    (
+        "Undefined variable '{}'",
+        "Wetin be dis? I no sabi variable '{0}'",
+    ),
+    (
+        "Undefined function '{}'",
+        "Wetin be dis? I no sabi function '{0}'",
+    ),
+    (
+        "Function '{}' expects {} arguments, got {}",
+        "Function '{0}' want {1} argument(s), but na {2} you give am",
+    ),
+    ("Division by zero", "You no fit divide by zero"),
+    (
+        "Array index {} out of bounds (array length: {})",
+        "Index {0} no dey inside dis array (na only {1} tin dey inside)",
+    ),
+    (
+        "No case matched the value {}",
+        "No case gree match the value {0}",
+    ),
+    (
+        "Cannot unpack {} values into {} variables",
+        "I no fit share {0} values give {1} variables",
+    ),
+    (
+        "Invalid operands for {}: {}",
+        "Dis values no fit do {0}: {1}",
+    ),
+    (
+        "Step limit exceeded ({} steps)",
+        "Di program don waka pass im limit ({0} steps)",
+    ),
+    (
+        "Expect expression. Got {}",
+        "I dey expect expression, but na {0} I see",
+    ),
+    ("Expect '{}' after {}", "I dey expect '{0}' after {1}"),
+    ("Expect '{}' before {}", "I dey expect '{0}' before {1}"),
+    ("Expect {}", "I dey expect {0}"),
+    (
+        "Invalid assignment target.",
+        "You no fit put value inside dis one.",
+    ),
+    (
+        "Unterminated string",
+        "Dis string no get end; close am with '\"'",
+    ),
+    (
+        "Unexpected character '{}'",
+        "I no understand dis character '{0}'",
+    ),
+    ("Invalid number '{}'", "'{0}' no be correct number"),
+];
+
+static LANGUAGE: AtomicU8 = AtomicU8::new(0); // The chosen Language, by its position in the enum
+
+// Set the language of error messages for the rest of the process
+pub fn set_language(language: Language) {
+    LANGUAGE.store(language as u8, Ordering::Relaxed);
+}
+
+// Get the language error messages are shown in
+pub fn language() -> Language {
+    match LANGUAGE.load(Ordering::Relaxed) {
+        1 => Language::Pidgin,
+        _ => Language::English,
+    }
+}
+
+// Translate a message into the chosen language, or return it unchanged if the catalog has no
+// entry for it
+pub fn translate(message: &str) -> String {
+    for (english, translated) in language().catalog() {
+        if let Some(parts) = match_template(english, message) {
+            let mut text = translated.to_string();
+            for (index, part) in parts.iter().enumerate() {
+                text = text.replace(&format!("{{{index}}}"), part);
+            }
+            return text;
+        }
+    }
+    message.to_string()
+}
+
+// Match a message against a catalog entry's English text, returning the parts standing in for
+// its `{}`s; each part but the last ends at the first place the text after it appears
+fn match_template<'a>(template: &str, message: &'a str) -> Option<Vec<&'a str>> {
+    let pieces: Vec<&str> = template.split("{}").collect();
+    let (first, rest) = pieces.split_first()?;
+    let Some((last, middle)) = rest.split_last() else {
+        return (message == template).then(Vec::new); // Nothing varies
+    };
+    let mut remaining = message.strip_prefix(first)?.strip_suffix(last)?;
+    let mut parts = Vec::new();
+    for piece in middle {
+        let end = remaining.find(piece)?;
+        parts.push(&remaining[..end]);
+        remaining = &remaining[end + piece.len()..];
+    }
+    parts.push(remaining);
+    Some(parts)
+}
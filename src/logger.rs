@@ -0,0 +1,62 @@
+// Diagnostic logging controlled by the global --quiet and --verbose flags
+//
+// Program output (print, printLn, ...) and errors never go through here; this is only for
+// the compiler's own chatter, such as progress messages and debugging details.
+use std::fmt;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+// Define the Verbosity enum, how much diagnostic output is shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Verbosity {
+    Quiet, // Only results and errors (--quiet)
+    #[default]
+    Normal, // Progress messages as well
+    Verbose, // Debugging details as well (--verbose)
+}
+
+static VERBOSITY: AtomicU8 = AtomicU8::new(Verbosity::Normal as u8); // Current process-wide level
+
+// Set the verbosity for the rest of the process
+pub fn set_verbosity(verbosity: Verbosity) {
+    VERBOSITY.store(verbosity as u8, Ordering::Relaxed);
+}
+
+// Get the current verbosity
+pub fn verbosity() -> Verbosity {
+    match VERBOSITY.load(Ordering::Relaxed) {
+        0 => Verbosity::Quiet,
+        1 => Verbosity::Normal,
+        _ => Verbosity::Verbose,
+    }
+}
+
+// Print a message if the current verbosity includes its level: progress messages go to
+// stdout, debugging details to stderr so they never mix with a program's output
+pub fn log(level: Verbosity, args: fmt::Arguments) {
+    if verbosity() < level {
+        return;
+    }
+    match level {
+        Verbosity::Verbose => eprintln!("[debug] {args}"),
+        _ => println!("{args}"),
+    }
+}
+
+// Log a progress message: info!("Checking {path}...")
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)*) => {
+        $crate::logger::log($crate::logger::Verbosity::Normal, format_args!($($arg)*))
+    };
+}
+
+// Log a debugging detail: debug!("Loaded module {file}")
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        $crate::logger::log($crate::logger::Verbosity::Verbose, format_args!($($arg)*))
+    };
+}
+
+pub use crate::debug; // Exported at the crate root, re-exported here as logger::debug
+pub use crate::info;
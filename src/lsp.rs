@@ -0,0 +1,844 @@
+// Language server for editors (pidgin lsp)
+//
+// Speaks the Language Server Protocol over stdin/stdout: JSON-RPC messages, each preceded by
+// a Content-Length header. Open documents are re-analyzed on every change, giving:
+//
+//   - diagnostics: syntax errors, `pidgin check` errors, and lint warnings
+//   - go to definition for variables, functions, parameters, and imported names
+//   - hover showing what a name is (with a function's doc comment, or a variable's kind)
+//   - document symbols: the functions and variables of a file, for outlines and search
+//
+// Lines and columns inside Pidgin count characters from 1; LSP counts UTF-16 code units
+// from 0, so positions are converted at the edges.
+use crate::ast::{BinaryOp, Expr, Span, Stmt};
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::error::PidginError;
+use crate::interpreter::{find_builtin, parse_module};
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use crate::pattern;
+use crate::token::{Token, TokenInfo};
+use crate::visitor::{self, Visitor};
+use crate::{lint, resolver};
+use serde_json::{json, Value as Json};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+// Define the SymbolKind enum, the kinds of names a program can define
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SymbolKind {
+    Variable,  // let x = ...
+    Function,  // function f(...) { ... }
+    Parameter, // A function's parameter
+    Import,    // GET x from module.pg
+}
+
+// Implement methods for SymbolKind
+impl SymbolKind {
+    // Get the LSP SymbolKind number used in document symbols
+    fn lsp_kind(self) -> u32 {
+        match self {
+            SymbolKind::Function => 12,
+            SymbolKind::Variable | SymbolKind::Parameter => 13,
+            SymbolKind::Import => 2, // Module
+        }
+    }
+}
+
+// Define the Definition struct, one place where a name is defined
+#[derive(Debug, Clone)]
+struct Definition {
+    name: String,                     // The name defined
+    kind: SymbolKind,                 // What kind of name it is
+    token: TokenInfo,                 // The name's token where it is defined
+    start: Span,                      // Start of the whole definition (its statement)
+    end: Span,                        // Just past the end of the definition
+    scope: Option<(Span, Span)>,      // Function it is local to (start, end), or None if global
+    parameters: Vec<String>,          // Parameter names (functions only)
+    doc: Option<String>,              // Doc comment (functions only)
+    value_kind: Option<&'static str>, // Inferred kind of the initial value (variables only)
+    module: Option<String>,           // Module it comes from (imports only)
+}
+
+// Define the Analysis struct, everything known about one version of a document
+struct Analysis {
+    lines: Vec<String>,           // Source lines, to convert positions
+    tokens: Vec<TokenInfo>,       // Tokens (empty if the source cannot be lexed)
+    definitions: Vec<Definition>, // Every name the program defines, in source order
+    diagnostics: Vec<Diagnostic>, // Errors and warnings
+}
+
+// Implement methods for Analysis
+impl Analysis {
+    // Lex, parse, and check a document
+    fn new(text: &str) -> Self {
+        let mut analysis = Self {
+            lines: text.lines().map(str::to_string).collect(),
+            tokens: Vec::new(),
+            definitions: Vec::new(),
+            diagnostics: Vec::new(),
+        };
+
+        let tokens = match Lexer::new(text).tokenize() {
+            Ok(tokens) => tokens,
+            Err(e) => {
+                analysis.add_error(e);
+                return analysis;
+            }
+        };
+        analysis.tokens = tokens;
+        let parsed = Parser::new(&analysis.tokens).parse();
+        let program = match parsed {
+            Ok(program) => program,
+            Err(e) => {
+                analysis.add_error(e);
+                return analysis;
+            }
+        };
+
+        for error in resolver::resolve_program(&program) {
+            // Point at the name the error is about rather than the start of its statement
+            let span = error
+                .message
+                .split('\'')
+                .nth(1)
+                .and_then(|name| analysis.name_after(name, error.span))
+                .unwrap_or(error.span);
+            analysis.add(span, Severity::Error, "name", error.message);
+        }
+        for warning in lint::lint_program(&program, &[]) {
+            analysis.add(
+                warning.span,
+                Severity::Warning,
+                warning.rule,
+                warning.message,
+            );
+        }
+
+        let mut collector = DefinitionCollector {
+            tokens: &analysis.tokens,
+            definitions: Vec::new(),
+            scope: None,
+        };
+        visitor::walk_program(&mut collector, &program);
+        analysis.definitions = collector.definitions;
+        analysis
+    }
+
+    // Record a lexer or parser error, whose position is at the end of its message
+    fn add_error(&mut self, message: String) {
+        let mut diagnostic = Diagnostic::from_error(&PidginError::syntax(message));
+        if let (Some(line), Some(column)) = (diagnostic.line, diagnostic.column) {
+            diagnostic.length = self.token_at(line, column).map(|token| token.length);
+        }
+        self.diagnostics.push(diagnostic);
+    }
+
+    // Record a problem found at a statement or expression
+    fn add(&mut self, span: Span, severity: Severity, code: &str, message: String) {
+        self.diagnostics.push(Diagnostic {
+            file: None,
+            line: Some(span.line),
+            column: Some(span.column),
+            length: self
+                .token_at(span.line, span.column)
+                .map(|token| token.length),
+            severity,
+            code: code.to_string(),
+            message,
+            source: None,
+        });
+    }
+
+    // Find the token that starts at a position
+    fn token_at(&self, line: usize, column: usize) -> Option<&TokenInfo> {
+        self.tokens
+            .iter()
+            .find(|token| token.line == line && token.column == column)
+    }
+
+    // Find where a name is next used at or after a position
+    fn name_after(&self, name: &str, from: Span) -> Option<Span> {
+        self.tokens
+            .iter()
+            .find(|token| {
+                (token.line, token.column) >= (from.line, from.column)
+                    && matches!(&token.token, Token::Identifier(n) if n == name)
+            })
+            .map(|token| Span {
+                line: token.line,
+                column: token.column,
+            })
+    }
+
+    // Find the name under the cursor (the cursor may also sit just after it)
+    fn identifier_at(&self, line: usize, column: usize) -> Option<(&TokenInfo, &str)> {
+        self.tokens.iter().find_map(|token| match &token.token {
+            Token::Identifier(name)
+                if token.line == line
+                    && (token.column..=token.column + token.length).contains(&column) =>
+            {
+                Some((token, name.as_str()))
+            }
+            _ => None,
+        })
+    }
+
+    // Find the definition a name refers to at a position: the innermost function's own
+    // parameter or variable if there is one, otherwise the first global definition
+    fn resolve(&self, name: &str, at: Span) -> Option<&Definition> {
+        let position = (at.line, at.column);
+        let local = self
+            .definitions
+            .iter()
+            .filter(|definition| definition.name == name)
+            .filter(|definition| {
+                definition.scope.is_some_and(|(start, end)| {
+                    (start.line, start.column) <= position && position < (end.line, end.column)
+                })
+            })
+            .max_by_key(|definition| {
+                let (start, _) = definition.scope.unwrap_or_default();
+                (start.line, start.column)
+            });
+        local.or_else(|| {
+            self.definitions
+                .iter()
+                .find(|definition| definition.name == name && definition.scope.is_none())
+        })
+    }
+
+    // Convert a Pidgin position (1-based, in characters) to an LSP position
+    fn lsp_position(&self, line: usize, column: usize) -> Json {
+        let character = match self.lines.get(line.wrapping_sub(1)) {
+            Some(text) => text
+                .chars()
+                .take(column.saturating_sub(1))
+                .map(char::len_utf16)
+                .sum(),
+            None => column.saturating_sub(1),
+        };
+        json!({ "line": line.saturating_sub(1), "character": character })
+    }
+
+    // Convert an LSP position to a Pidgin position
+    fn pidgin_position(&self, position: &Json) -> Span {
+        let line = position["line"].as_u64().unwrap_or(0) as usize;
+        let character = position["character"].as_u64().unwrap_or(0) as usize;
+        let mut units = 0;
+        let column = self.lines.get(line).map_or(character, |text| {
+            text.chars()
+                .take_while(|c| {
+                    units += c.len_utf16();
+                    units <= character
+                })
+                .count()
+        });
+        Span {
+            line: line + 1,
+            column: column + 1,
+        }
+    }
+
+    // Get the LSP range covered by a token
+    fn token_range(&self, token: &TokenInfo) -> Json {
+        json!({
+            "start": self.lsp_position(token.line, token.column),
+            "end": self.lsp_position(token.line, token.column + token.length),
+        })
+    }
+
+    // Convert the diagnostics to LSP form
+    fn lsp_diagnostics(&self) -> Json {
+        let diagnostics: Vec<Json> = self
+            .diagnostics
+            .iter()
+            .map(|diagnostic| {
+                let line = diagnostic.line.unwrap_or(1);
+                let column = diagnostic.column.unwrap_or(1);
+                let length = diagnostic.length.unwrap_or(1);
+                json!({
+                    "range": {
+                        "start": self.lsp_position(line, column),
+                        "end": self.lsp_position(line, column + length),
+                    },
+                    "severity": match diagnostic.severity {
+                        Severity::Error => 1,
+                        Severity::Warning => 2,
+                    },
+                    "code": diagnostic.code,
+                    "source": "pidgin",
+                    "message": diagnostic.message,
+                })
+            })
+            .collect();
+        json!(diagnostics)
+    }
+
+    // Describe a definition in Markdown, for hover
+    fn describe(&self, definition: &Definition) -> String {
+        let signature = match definition.kind {
+            SymbolKind::Function => format!(
+                "function {}({})",
+                definition.name,
+                definition.parameters.join(", ")
+            ),
+            SymbolKind::Variable => format!("let {}", definition.name),
+            SymbolKind::Parameter => format!("(parameter) {}", definition.name),
+            SymbolKind::Import => format!(
+                "GET {} from {}",
+                definition.name,
+                definition.module.as_deref().unwrap_or("?")
+            ),
+        };
+        let mut text = format!("```pidgin\n{signature}\n```");
+        if let Some(kind) = &definition.value_kind {
+            text.push_str(&format!("\n\nInitial value: {kind}"));
+        }
+        if let Some(doc) = &definition.doc {
+            text.push_str(&format!("\n\n{doc}"));
+        }
+        text
+    }
+}
+
+// Define the DefinitionCollector struct, a visitor that finds where names are defined
+struct DefinitionCollector<'a> {
+    tokens: &'a [TokenInfo], // The document's tokens, to find each name's position
+    definitions: Vec<Definition>, // Definitions found so far
+    scope: Option<(Span, Span)>, // Function currently being visited, if any
+}
+
+// Implement methods for DefinitionCollector
+impl DefinitionCollector<'_> {
+    // Find the index of the first token naming `name` at or after a position
+    fn name_token(&self, name: &str, from: Span) -> Option<usize> {
+        self.tokens.iter().position(|token| {
+            (token.line, token.column) >= (from.line, from.column)
+                && matches!(&token.token, Token::Identifier(n) if n == name)
+        })
+    }
+
+    // Find the end of a function: just past the brace that closes its body
+    fn function_end(&self, name_index: usize) -> Span {
+        let mut depth = 0;
+        for token in &self.tokens[name_index..] {
+            match token.token {
+                Token::LeftBrace => depth += 1,
+                Token::RightBrace => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Span {
+                            line: token.line,
+                            column: token.column + 1,
+                        };
+                    }
+                }
+                _ => {}
+            }
+        }
+        let last = self.tokens.last().map_or(Span::default(), |token| Span {
+            line: token.line,
+            column: token.column + token.length,
+        });
+        last
+    }
+
+    // Record a definition of a name found at or after a position
+    fn define(&mut self, name: &str, kind: SymbolKind, start: Span) -> Option<usize> {
+        let index = self.name_token(name, start)?;
+        let token = self.tokens[index].clone();
+        let end = Span {
+            line: token.line,
+            column: token.column + token.length,
+        };
+        self.definitions.push(Definition {
+            name: name.to_string(),
+            kind,
+            token,
+            start,
+            end,
+            scope: self.scope,
+            parameters: Vec::new(),
+            doc: None,
+            value_kind: None,
+            module: None,
+        });
+        Some(index)
+    }
+}
+
+// Implement Visitor for DefinitionCollector
+impl Visitor for DefinitionCollector<'_> {
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::VarDeclaration {
+                name,
+                initializer,
+                span,
+                ..
+            } => {
+                let value_kind = initializer
+                    .as_ref()
+                    .and_then(|expr| value_kind(expr, &self.definitions));
+                if self.define(name, SymbolKind::Variable, *span).is_some() {
+                    if let Some(definition) = self.definitions.last_mut() {
+                        definition.value_kind = value_kind;
+                    }
+                }
+            }
+            Stmt::ForIn { variable, span, .. } => {
+                self.define(variable, SymbolKind::Variable, *span);
+            }
+            Stmt::Unpack {
+                names,
+                declares: true,
+                span,
+                ..
+            } => {
+                for name in names {
+                    self.define(name, SymbolKind::Variable, *span);
+                }
+            }
+            Stmt::FunctionDeclaration {
+                name,
+                parameters,
+                body,
+                doc,
+                span,
+                ..
+            } => {
+                let Some(index) = self.define(name, SymbolKind::Function, *span) else {
+                    return;
+                };
+                let end = self.function_end(index);
+                if let Some(definition) = self.definitions.last_mut() {
+                    definition.end = end;
+                    definition.parameters = parameters.clone();
+                    definition.doc = doc.clone();
+                }
+
+                // Parameters and variables of the body are local to the function
+                let outer = self.scope.replace((*span, end));
+                let after_name = Span {
+                    line: self.tokens[index].line,
+                    column: self.tokens[index].column + 1,
+                };
+                for parameter in parameters {
+                    self.define(parameter, SymbolKind::Parameter, after_name);
+                }
+                self.visit_stmt(body);
+                self.scope = outer;
+                return;
+            }
+            Stmt::Import {
+                names,
+                module,
+                span,
+            } => {
+                for name in names {
+                    if self.define(name, SymbolKind::Import, *span).is_some() {
+                        if let Some(definition) = self.definitions.last_mut() {
+                            definition.module = Some(module.clone());
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+        visitor::walk_stmt(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        if let Expr::Match { cases, .. } = expr {
+            for case in cases {
+                for (name, span) in pattern::bound_names(&case.pattern) {
+                    self.define(name, SymbolKind::Variable, span);
+                }
+            }
+        }
+        visitor::walk_expr(self, expr);
+    }
+}
+
+// Infer the kind of value an expression produces, where that is clear without running it
+fn value_kind(expr: &Expr, definitions: &[Definition]) -> Option<&'static str> {
+    match expr {
+        Expr::Number(_) | Expr::Unary { .. } => Some("number"),
+        Expr::String(_) | Expr::Transform { .. } => Some("string"),
+        Expr::Boolean(_) => Some("boolean"),
+        Expr::Nil => Some("nil"),
+        Expr::FixedArray(_) => Some("fixed array"),
+        Expr::DynamicArray(_) => Some("dynamic array"),
+        Expr::FunctionCall { name, .. } => match name.as_str() {
+            "Date" => Some("date"),
+            "Duration" => Some("duration"),
+            "Object" => Some("object"),
+            "readLine" | "prompt" => Some("string"),
+            "confirm" => Some("boolean"),
+            "spawn" => Some("task"),
+            "setTimeout" | "setInterval" => Some("number"),
+            "args" => Some("dynamic array"),
+            _ => None,
+        },
+        Expr::Binary {
+            left,
+            operator,
+            right,
+            ..
+        } => match operator {
+            BinaryOp::Add => match (
+                value_kind(left, definitions),
+                value_kind(right, definitions),
+            ) {
+                (Some("string"), _) | (_, Some("string")) => Some("string"),
+                (Some("number"), Some("number")) => Some("number"),
+                _ => None,
+            },
+            // Dates and durations take part in these too (date - date is a duration)
+            BinaryOp::Subtract | BinaryOp::Multiply | BinaryOp::Divide => match (
+                value_kind(left, definitions),
+                value_kind(right, definitions),
+            ) {
+                (Some("number"), Some("number")) => Some("number"),
+                _ => None,
+            },
+            _ => Some("boolean"),
+        },
+        Expr::Identifier(name, _, _) => {
+            // The kind of the variable as declared so far
+            definitions
+                .iter()
+                .rev()
+                .find(|definition| {
+                    definition.name == *name && definition.kind == SymbolKind::Variable
+                })?
+                .value_kind
+        }
+        _ => None,
+    }
+}
+
+// Define the Server struct, the state of a language server session
+#[derive(Default)]
+struct Server {
+    documents: HashMap<String, Analysis>, // Open documents by URI
+    shutting_down: bool,                  // Whether 'shutdown' has been received
+}
+
+// Implement methods for Server
+impl Server {
+    // Handle one message, returning false once the client asks the server to exit
+    fn handle(&mut self, message: &Json) -> Result<bool, String> {
+        let method = message["method"].as_str().unwrap_or("");
+        let params = &message["params"];
+        let id = message.get("id").cloned();
+
+        let result = match method {
+            "initialize" => {
+                // Imports are found relative to the project root, as when running pidgin there
+                if let Some(root) = params["rootUri"].as_str().and_then(uri_to_path) {
+                    std::env::set_current_dir(root).ok();
+                }
+                json!({
+                    "capabilities": {
+                        "textDocumentSync": 1, // Full text on every change
+                        "definitionProvider": true,
+                        "hoverProvider": true,
+                        "documentSymbolProvider": true,
+                    },
+                    "serverInfo": { "name": "pidgin", "version": env!("CARGO_PKG_VERSION") },
+                })
+            }
+            "shutdown" => {
+                self.shutting_down = true;
+                Json::Null
+            }
+            "exit" => return Ok(false),
+            "textDocument/didOpen" => {
+                let document = &params["textDocument"];
+                self.update(document["uri"].as_str(), document["text"].as_str())?;
+                return Ok(true);
+            }
+            "textDocument/didChange" => {
+                let text = params["contentChanges"]
+                    .as_array()
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str());
+                self.update(params["textDocument"]["uri"].as_str(), text)?;
+                return Ok(true);
+            }
+            "textDocument/didClose" => {
+                if let Some(uri) = params["textDocument"]["uri"].as_str() {
+                    self.documents.remove(uri);
+                    notify(
+                        "textDocument/publishDiagnostics",
+                        json!({ "uri": uri, "diagnostics": [] }),
+                    )?;
+                }
+                return Ok(true);
+            }
+            "textDocument/definition" => self.definition(params),
+            "textDocument/hover" => self.hover(params),
+            "textDocument/documentSymbol" => self.document_symbols(params),
+            _ => {
+                // Unknown notifications are ignored; unknown requests get an error
+                if let Some(id) = id {
+                    send(&json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "error": { "code": -32601, "message": format!("Unknown method '{method}'") },
+                    }))?;
+                }
+                return Ok(true);
+            }
+        };
+
+        if let Some(id) = id {
+            send(&json!({ "jsonrpc": "2.0", "id": id, "result": result }))?;
+        }
+        Ok(true)
+    }
+
+    // Re-analyze a document after it was opened or changed, and publish its diagnostics
+    fn update(&mut self, uri: Option<&str>, text: Option<&str>) -> Result<(), String> {
+        let (Some(uri), Some(text)) = (uri, text) else {
+            return Ok(());
+        };
+        let analysis = Analysis::new(text);
+        let diagnostics = analysis.lsp_diagnostics();
+        self.documents.insert(uri.to_string(), analysis);
+        notify(
+            "textDocument/publishDiagnostics",
+            json!({ "uri": uri, "diagnostics": diagnostics }),
+        )
+    }
+
+    // Find the document and the name under the cursor for a position request
+    fn lookup<'a>(
+        &'a self,
+        params: &'a Json,
+    ) -> Option<(&'a str, &'a Analysis, &'a TokenInfo, String, Span)> {
+        let uri = params["textDocument"]["uri"].as_str()?;
+        let analysis = self.documents.get(uri)?;
+        let position = analysis.pidgin_position(&params["position"]);
+        let (token, name) = analysis.identifier_at(position.line, position.column)?;
+        Some((uri, analysis, token, name.to_string(), position))
+    }
+
+    // Answer textDocument/definition
+    fn definition(&self, params: &Json) -> Json {
+        let Some((uri, analysis, _, name, position)) = self.lookup(params) else {
+            return Json::Null;
+        };
+        let Some(definition) = analysis.resolve(&name, position) else {
+            return Json::Null;
+        };
+
+        // An imported name is defined in its module
+        if let Some(module) = &definition.module {
+            if let Some((file, module_analysis)) = analyze_module(module) {
+                if let Some(target) = module_analysis.resolve(&name, Span::default()) {
+                    return json!({
+                        "uri": path_to_uri(&file),
+                        "range": module_analysis.token_range(&target.token),
+                    });
+                }
+            }
+        }
+        json!({ "uri": uri, "range": analysis.token_range(&definition.token) })
+    }
+
+    // Answer textDocument/hover
+    fn hover(&self, params: &Json) -> Json {
+        let Some((_, analysis, token, name, position)) = self.lookup(params) else {
+            return Json::Null;
+        };
+        let text = match analysis.resolve(&name, position) {
+            Some(definition) => {
+                let mut text = analysis.describe(definition);
+                // Show what an imported name is in its module as well
+                if let Some(module) = &definition.module {
+                    if let Some((_, module_analysis)) = analyze_module(module) {
+                        if let Some(target) = module_analysis.resolve(&name, Span::default()) {
+                            text.push_str(&format!(
+                                "\n\n---\n\n{}",
+                                module_analysis.describe(target)
+                            ));
+                        }
+                    }
+                }
+                text
+            }
+            None => match find_builtin(&name) {
+                Some(builtin) => format!(
+                    "```pidgin\n{name}({})\n```\n\nBuilt-in function: {}",
+                    builtin.params, builtin.description
+                ),
+                None => return Json::Null,
+            },
+        };
+        json!({
+            "contents": { "kind": "markdown", "value": text },
+            "range": analysis.token_range(token),
+        })
+    }
+
+    // Answer textDocument/documentSymbol: global names, with each function's locals inside it
+    fn document_symbols(&self, params: &Json) -> Json {
+        let Some(analysis) = params["textDocument"]["uri"]
+            .as_str()
+            .and_then(|uri| self.documents.get(uri))
+        else {
+            return json!([]);
+        };
+
+        let symbol = |definition: &Definition, children: Vec<Json>| {
+            json!({
+                "name": definition.name,
+                "kind": definition.kind.lsp_kind(),
+                "range": {
+                    "start": analysis.lsp_position(definition.start.line, definition.start.column),
+                    "end": analysis.lsp_position(definition.end.line, definition.end.column),
+                },
+                "selectionRange": analysis.token_range(&definition.token),
+                "children": children,
+            })
+        };
+        let symbols: Vec<Json> = analysis
+            .definitions
+            .iter()
+            .filter(|definition| definition.scope.is_none())
+            .map(|definition| {
+                let children = analysis
+                    .definitions
+                    .iter()
+                    .filter(|local| {
+                        definition.kind == SymbolKind::Function
+                            && local
+                                .scope
+                                .is_some_and(|(start, _)| start == definition.start)
+                    })
+                    .map(|local| symbol(local, Vec::new()))
+                    .collect();
+                symbol(definition, children)
+            })
+            .collect();
+        json!(symbols)
+    }
+}
+
+// Find, read, and analyze an imported module, returning its path and analysis
+fn analyze_module(module: &str) -> Option<(String, Analysis)> {
+    let (file, _) = parse_module(module).ok()?;
+    let source = fs::read_to_string(&file).ok()?;
+    Some((file, Analysis::new(&source)))
+}
+
+// Run the language server until the client disconnects or asks it to exit
+pub fn run() -> Result<(), String> {
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    let mut server = Server::default();
+
+    while let Some(message) = read_message(&mut input)? {
+        if !server.handle(&message)? {
+            break;
+        }
+    }
+    if server.shutting_down {
+        Ok(())
+    } else {
+        Err("Client exited without shutting down the language server".to_string())
+    }
+}
+
+// Read one message: headers, a blank line, then Content-Length bytes of JSON (the Debug
+// Adapter Protocol frames its messages the same way, see dap.rs)
+pub fn read_message(input: &mut impl BufRead) -> Result<Option<Json>, String> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header).map_err(|e| e.to_string())? == 0 {
+            return Ok(None); // The client closed the connection
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = Some(
+                value
+                    .trim()
+                    .parse::<usize>()
+                    .map_err(|_| format!("Invalid header '{header}'"))?,
+            );
+        }
+    }
+
+    let length = length.ok_or("Message without a Content-Length header")?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body).map_err(|e| e.to_string())?;
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|e| format!("Invalid message: {e}"))
+}
+
+// Write one message to the client
+pub fn send(message: &Json) -> Result<(), String> {
+    let body = message.to_string();
+    let mut stdout = io::stdout().lock();
+    write!(stdout, "Content-Length: {}\r\n\r\n{body}", body.len())
+        .and_then(|_| stdout.flush())
+        .map_err(|e| e.to_string())
+}
+
+// Send a notification (a message that expects no reply)
+fn notify(method: &str, params: Json) -> Result<(), String> {
+    send(&json!({ "jsonrpc": "2.0", "method": method, "params": params }))
+}
+
+// Convert a file:// URI to a path, decoding %XX escapes
+fn uri_to_path(uri: &str) -> Option<String> {
+    let encoded = uri.strip_prefix("file://")?;
+    let bytes = encoded.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| encoded.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+// Convert a path to a file:// URI, escaping characters that URIs cannot contain
+fn path_to_uri(path: &str) -> String {
+    let absolute = Path::new(path).canonicalize().map_or_else(
+        |_| path.to_string(),
+        |path| path.to_string_lossy().to_string(),
+    );
+    let mut uri = String::from("file://");
+    for byte in absolute.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => {
+                uri.push(byte as char)
+            }
+            _ => uri.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    uri
+}
@@ -1,23 +1,120 @@
-// Import the token module
-mod token; // Handles token definitions and tokenization
-           // Import the lexer module
-mod lexer; // Handles lexical analysis (tokenizing source code)
-           // Import the ast module
-mod ast; // Defines the abstract syntax tree (AST) structures
-         // Import the parser module
-mod parser; // Handles parsing tokens into AST
-            // Import the interpreter module
-mod interpreter; // Handles interpreting/executing the AST
-                 // Import the update module
+// Import the language itself (lexer, parser, interpreter, ...) from the library in src/lib.rs
+use pidgin::{
+    ast, error, interpreter, lexer, logger, methods, parser, pattern, run_with_interpreter, token,
+};
+
+// Import the update module
 mod update; // Handles compiler updates
-use crate::interpreter::Interpreter;
+            // Import the formatter module
+mod formatter; // Handles source code formatting (pidgin fmt)
+               // Import the visitor module
+mod visitor; // Handles generic AST traversal
+             // Import the lint module
+mod lint; // Handles static checks for suspicious code (pidgin lint)
+          // Import the resolver module
+mod resolver; // Handles static name resolution (pidgin check)
+
+mod json_output; // Handles JSON output for --tokens and --ast
+
+mod ast_printer; // Handles the tree-style AST display for --ast
+
+mod outline; // Handles listing a file's top-level definitions (--symbols)
+
+mod bench; // Handles repeated timed runs (pidgin bench)
+
+mod watch; // Handles re-running programs when files change (pidgin watch)
+
+mod repl; // Handles the interactive prompt (pidgin repl)
+
+mod repl_config; // Handles the settings file (~/.pidginrc) for the REPL and pidgin update
+
+mod codegen; // Handles compiling programs to other languages (pidgin build)
+
+mod bundle; // Handles standalone executables with an embedded program (pidgin bundle)
+
+mod pgc; // Handles compiled program files (pidgin compile, pidgin <file.pgc>)
+
+mod doc; // Handles API documentation generated from /// comments (pidgin doc)
+
+mod highlight; // Handles syntax highlighting for terminals and HTML (pidgin highlight)
+
+mod package; // Handles pidgin.toml dependencies and pg_modules/ (pidgin add, pidgin install)
+
+mod debugger; // Handles pausing and inspecting a running program (pidgin debug)
+
+mod coverage; // Handles recording which lines a run executed (--coverage)
+
+mod trace; // Handles printing each statement and variable change as a program runs (--trace)
+
+mod diagnostic; // Handles errors and warnings as JSON lines (--error-format json)
+
+mod locale; // Handles translating error messages into other languages (--lang, PIDGIN_LANG)
+
+mod lsp; // Handles the language server for editors (pidgin lsp)
+
+mod dap; // Handles debugging from editors over the Debug Adapter Protocol (pidgin dap)
+
+mod input_log; // Handles recording a program's input and playing it back (--record-input, --replay-input)
+use crate::ast::Program;
+use crate::diagnostic::{Diagnostic, ErrorFormat, Severity};
+use crate::error::{ErrorKind, PidginError};
+use crate::interpreter::{Interpreter, Limits, StatementHook};
+use crate::locale::Language;
+use crate::logger::{info, Verbosity};
 use std::env; // Import for reading command-line arguments
 use std::fs; // Import for file system operations
-use std::io::{self, Write}; // Import for input/output
+use std::io::{self, Write}; // Import for flushing program output
+use std::time::{Duration, Instant}; // Import for --time measurements
+
+// Define the GlobalOptions struct, options accepted anywhere before the program's file
+#[derive(Debug, Clone, Default)]
+struct GlobalOptions {
+    limits: Limits,                // Resource limits for sandboxed runs (--max-steps, ...)
+    time: bool,                    // Report how long each phase of a run took (--time)
+    verbosity: Verbosity,          // How much diagnostic output to show (--quiet, --verbose)
+    coverage: bool,                // Report which lines of the program ran (--coverage)
+    coverage_lcov: Option<String>, // Also write the coverage to this lcov file (--coverage-lcov)
+    trace: bool,                   // Print each statement and variable change as it runs (--trace)
+    error_format: ErrorFormat,     // How errors and warnings are written (--error-format)
+    language: Option<Language>,    // Language of error messages (--lang, else PIDGIN_LANG)
+    stdin_from: Option<String>,    // File the program's input is read from (--stdin-from)
+    record_input: Option<String>,  // File the input the program reads is copied to (--record-input)
+    replay_input: Option<String>, // Recording the program's input is played back from (--replay-input)
+}
 
 // The main entry point of the program
 fn main() {
-    let args: Vec<String> = env::args().collect(); // Collect command-line arguments
+    // A panic is a bug in Pidgin itself, so report it as an internal error
+    std::panic::set_hook(Box::new(|info| {
+        eprintln!("Internal error: {info}");
+        eprintln!("Please report this at https://github.com/ojutalayomi/pidgin/issues");
+        std::process::exit(ErrorKind::Internal.exit_code());
+    }));
+
+    let raw_args: Vec<String> = env::args().collect(); // Collect command-line arguments
+
+    // A bundled executable runs its embedded program, passing every argument through to it
+    if let Some(bundle) = bundle::embedded_bundle() {
+        if let Err(e) = run_bundle(bundle, &raw_args[1..]) {
+            exit_with(e);
+        }
+        return;
+    }
+
+    // Pull out global options, which may appear anywhere before the file of a program to run
+    let (args, options) = match parse_global_options(raw_args) {
+        Ok(parsed) => parsed,
+        Err(e) => exit_with(PidginError::usage(e)),
+    };
+    logger::set_verbosity(options.verbosity);
+    diagnostic::set_error_format(options.error_format);
+    locale::set_language(
+        options
+            .language
+            .or_else(Language::from_env)
+            .unwrap_or_default(),
+    );
+
     if args.len() > 1 {
         let first_arg = &args[1]; // Get the first argument
 
@@ -31,10 +128,142 @@ fn main() {
                 display_version();
                 return;
             }
+            "-e" | "--eval" => {
+                let code = match args.get(2) {
+                    Some(code) => code,
+                    None => exit_with(PidginError::usage(format!(
+                        "{first_arg} requires code to run. Usage: pidgin -e '<code>'"
+                    ))),
+                };
+                if let Err(e) = run(code, &options, script_args(&args[3..])) {
+                    exit_with(e);
+                }
+                return;
+            }
             "update" => {
-                if let Err(e) = update::compiler::update_compiler() {
-                    eprintln!("Error: {e}");
-                    std::process::exit(1);
+                let options = match update::compiler::parse_options(&args[2..]) {
+                    Ok(options) => options,
+                    Err(e) => exit_with(PidginError::usage(e)),
+                };
+                let options = match repl_config::ReplConfig::load() {
+                    Ok(config) => options.with_config(&config.update),
+                    Err(e) => exit_with(PidginError::usage(e)),
+                };
+                if let Err(e) = update::compiler::update_compiler(&options) {
+                    exit_with(PidginError::internal(e.to_string()));
+                }
+                return;
+            }
+            "fmt" => {
+                if let Err(e) = format_files(&args[2..]) {
+                    exit_with(e);
+                }
+                return;
+            }
+            "check" => {
+                if let Err(e) = check_files(&args[2..]) {
+                    exit_with(e);
+                }
+                return;
+            }
+            "bench" => {
+                if let Err(e) = bench_file(&args[2..], options.limits) {
+                    exit_with(e);
+                }
+                return;
+            }
+            "repl" => match repl::parse_options(&args[2..], options.limits) {
+                Ok(options) => {
+                    repl::run_prompt(&options);
+                    return;
+                }
+                Err(e) => exit_with(PidginError::usage(e)),
+            },
+            "watch" => {
+                let path = match args.get(2) {
+                    Some(path) if path.ends_with(".pg") => path,
+                    _ => exit_with(PidginError::usage(
+                        "Usage: pidgin watch <file.pg> [--] [args]...",
+                    )),
+                };
+                if let Err(e) = watch::watch_file(path, &options, script_args(&args[3..])) {
+                    exit_with(PidginError::internal(e));
+                }
+                return;
+            }
+            "lint" => {
+                if let Err(e) = lint_files(&args[2..]) {
+                    exit_with(e);
+                }
+                return;
+            }
+            "build" => {
+                if let Err(e) = build_file(&args[2..]) {
+                    exit_with(e);
+                }
+                return;
+            }
+            "bundle" => {
+                if let Err(e) = bundle_file(&args[2..]) {
+                    exit_with(e);
+                }
+                return;
+            }
+            "compile" => {
+                if let Err(e) = compile_file(&args[2..]) {
+                    exit_with(e);
+                }
+                return;
+            }
+            "doc" => {
+                if let Err(e) = doc_file(&args[2..]) {
+                    exit_with(e);
+                }
+                return;
+            }
+            "highlight" => {
+                if let Err(e) = highlight_file(&args[2..]) {
+                    exit_with(e);
+                }
+                return;
+            }
+            "debug" => {
+                if let Err(e) = debug_file(&args[2..]) {
+                    exit_with(e);
+                }
+                return;
+            }
+            "add" => {
+                if let Err(e) = add_package(&args[2..]) {
+                    exit_with(e);
+                }
+                return;
+            }
+            "install" => {
+                if let Err(e) = install_packages(&args[2..]) {
+                    exit_with(e);
+                }
+                return;
+            }
+            "run" => {
+                if let Err(e) = run_project(&args[2..], &options) {
+                    exit_with(e);
+                }
+                return;
+            }
+            "builtins" => {
+                print_builtins();
+                return;
+            }
+            "lsp" => {
+                if let Err(e) = lsp::run() {
+                    exit_with(PidginError::internal(e));
+                }
+                return;
+            }
+            "dap" => {
+                if let Err(e) = dap::run() {
+                    exit_with(PidginError::internal(e));
                 }
                 return;
             }
@@ -44,28 +273,53 @@ fn main() {
         // If not a standalone flag, treat as file path
         let path = first_arg;
 
+        // Compiled programs are run directly (see pidgin compile)
+        if path.ends_with(".pgc") {
+            if let Err(e) = run_compiled(path, &options, script_args(&args[2..])) {
+                exit_with(e);
+            }
+            return;
+        }
+
         // Check file extension (for all file operations)
         if !path.ends_with(".pg") {
             let dot_index = path.rfind('.').unwrap_or(path.len());
             let ext = &path[dot_index..];
-            eprintln!("Error: Expected .pg file but got {ext} from {path}");
-            std::process::exit(1);
+            exit_with(PidginError::usage(format!(
+                "Expected .pg file but got {ext} from {path}"
+            )));
         }
 
-        // Check for file-specific flags
+        // Check for file-specific flags; any other arguments are passed to the program
         if args.len() > 2 {
             match args[2].as_str() {
                 "--tokens" => {
-                    if let Err(e) = display_tokens(path) {
-                        eprintln!("Error: {e}");
-                        std::process::exit(1);
+                    if let Err(e) = parse_output_format(&args[3..])
+                        .and_then(|format| display_tokens(path, format))
+                    {
+                        exit_with(e);
                     }
                     return;
                 }
                 "--ast" => {
-                    if let Err(e) = display_ast(path) {
-                        eprintln!("Error: {e}");
-                        std::process::exit(1);
+                    if let Err(e) =
+                        parse_output_format(&args[3..]).and_then(|format| display_ast(path, format))
+                    {
+                        exit_with(e);
+                    }
+                    return;
+                }
+                "--symbols" => {
+                    if let Err(e) = parse_output_format(&args[3..])
+                        .and_then(|format| display_symbols(path, format))
+                    {
+                        exit_with(e);
+                    }
+                    return;
+                }
+                "--check" => {
+                    if let Err(e) = check_files(&args[1..2]) {
+                        exit_with(e);
                     }
                     return;
                 }
@@ -77,96 +331,310 @@ fn main() {
                     display_version();
                     return;
                 }
-                _ => {
-                    eprintln!("Unknown flag: {flag}", flag = args[2]);
-                    eprintln!("Available flags: --tokens, --ast, --help, --version");
-                    eprintln!("Usage: pidgin <file.pg> [--tokens|--ast|--help|--version]");
-                    std::process::exit(1);
-                }
+                _ => {}
             }
         }
 
         // Run the file if no flags were provided
-        if let Err(e) = run_file(path) {
-            eprintln!("Error: {e}");
-            std::process::exit(1);
+        if let Err(e) = run_file(path, &options, script_args(&args[2..])) {
+            exit_with(e.in_file(path));
         }
     } else {
-        run_prompt(); // If no file is given, start REPL prompt
+        // If no file is given, start REPL prompt
+        repl::run_prompt(&repl::ReplOptions {
+            limits: options.limits,
+            ..repl::ReplOptions::default()
+        });
     }
 }
 
-// Run a Pidgin source file
-fn run_file(path: &str) -> Result<(), String> {
-    let source = fs::read_to_string(path).map_err(|e| format!("Failed to read file: {e}"))?; // Read file contents
-    run(&source) // Run the source code
-}
-
-// Start a REPL (Read-Eval-Print Loop) prompt
-fn run_prompt() {
-    println!("Welcome to Pidgin REPL! Type 'exit' or 'quit' to exit, 'help' for help.");
-    let mut interpreter = Interpreter::new(None); // Create a new interpreter
-    let stdin = io::stdin(); // Get standard input
-    let mut stdout = io::stdout(); // Get standard output
-    loop {
-        print!("pidgin> "); // Print prompt
-        stdout.flush().unwrap(); // Flush output buffer
-        let mut buffer = String::new(); // Buffer for user input
-        match stdin.read_line(&mut buffer) {
-            Ok(0) => {
-                println!("\nExiting...");
-                break; // Exit on EOF
-            }
-            Ok(_) => {
-                let input = buffer.trim();
-                if input.is_empty() {
-                    continue; // Skip empty lines
-                }
+// Print an error and exit with the code for its class (see error.rs)
+fn exit_with(error: PidginError) -> ! {
+    report_error(&error);
+    std::process::exit(error.exit_code());
+}
 
-                match input {
-                    ":version" | ":v" => {
-                        display_version();
-                        continue;
-                    }
-                    "exit" | "quit" => {
-                        println!("Goodbye!");
-                        break;
-                    }
-                    "help" => {
-                        print_help();
-                        continue;
-                    }
-                    "clear" => {
-                        // Clear screen (works on most terminals)
-                        print!("\x1B[2J\x1B[1;1H");
-                        stdout.flush().unwrap();
-                        continue;
-                    }
-                    _ => {
-                        if let Err(e) = run_with_interpreter(&buffer, &mut interpreter) {
-                            eprintln!("Error: {e}");
-                        }
-                    }
-                }
+// Write an error to stderr in the format --error-format asked for
+fn report_error(error: &PidginError) {
+    if !error.message.is_empty() {
+        match diagnostic::error_format() {
+            ErrorFormat::Text => {
+                eprint!(
+                    "{}",
+                    Diagnostic::from_error(error).render(diagnostic::use_color())
+                )
             }
-            Err(e) => {
-                eprintln!("Error reading input: {e}");
+            ErrorFormat::Json => eprintln!("{}", Diagnostic::from_error(error).to_json()),
+        }
+    }
+}
+
+// Extract global options (e.g. --max-steps 10000, --time) from the argument list
+fn parse_global_options(args: Vec<String>) -> Result<(Vec<String>, GlobalOptions), String> {
+    let mut options = GlobalOptions::default();
+    let limits = &mut options.limits;
+    let mut remaining = Vec::new();
+    let mut iter = args.into_iter();
+
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--" => {
+                // Everything after `--` belongs to the program, even if it looks like a limit
+                remaining.push(arg);
+                remaining.extend(iter);
                 break;
             }
+            _ if is_program_file(&arg, &remaining) => {
+                // So does everything after the file to run (and flags such as --tokens)
+                remaining.push(arg);
+                remaining.extend(iter);
+                break;
+            }
+            "--max-steps" => limits.max_steps = Some(parse_limit_value(&arg, iter.next())?),
+            "--max-array-len" => limits.max_array_len = Some(parse_limit_value(&arg, iter.next())?),
+            "--max-string-len" => {
+                limits.max_string_len = Some(parse_limit_value(&arg, iter.next())?)
+            }
+            "--max-values" => limits.max_values = Some(parse_limit_value(&arg, iter.next())?),
+            "--time" => options.time = true,
+            "--quiet" => options.verbosity = Verbosity::Quiet,
+            "--verbose" => options.verbosity = Verbosity::Verbose,
+            "--trace" => options.trace = true,
+            "--error-format" => {
+                let name = iter.next().ok_or("--error-format requires text or json")?;
+                options.error_format = ErrorFormat::from_name(&name).ok_or_else(|| {
+                    format!("Unknown error format '{name}' (expected text or json)")
+                })?;
+            }
+            "--lang" => {
+                let name = iter
+                    .next()
+                    .ok_or("--lang requires a language (en or pcm)")?;
+                options.language =
+                    Some(Language::from_name(&name).ok_or_else(|| {
+                        format!("Unknown language '{name}' (expected en or pcm)")
+                    })?);
+            }
+            "--stdin-from" => {
+                let path = iter.next().ok_or("--stdin-from requires a file path")?;
+                options.stdin_from = Some(path);
+            }
+            "--record-input" => {
+                let path = iter.next().ok_or("--record-input requires a file path")?;
+                options.record_input = Some(path);
+            }
+            "--replay-input" => {
+                let path = iter.next().ok_or("--replay-input requires a file path")?;
+                options.replay_input = Some(path);
+            }
+            "--coverage" => options.coverage = true,
+            "--coverage-lcov" => {
+                let path = iter.next().ok_or("--coverage-lcov requires a file path")?;
+                options.coverage = true;
+                options.coverage_lcov = Some(path);
+            }
+            _ => remaining.push(arg),
+        }
+    }
+
+    Ok((remaining, options))
+}
+
+// Check whether an argument is the file of a program to run, given the arguments before it
+// other than global options: `pidgin <file>` or `pidgin watch <file>`
+fn is_program_file(arg: &str, before: &[String]) -> bool {
+    (arg.ends_with(".pg") || arg.ends_with(".pgc"))
+        && match before {
+            [_] => true,
+            [_, command] => command == "watch",
+            _ => false,
         }
+}
+
+// Parse the numeric value that follows a limit flag
+fn parse_limit_value(flag: &str, value: Option<String>) -> Result<usize, String> {
+    let value = value.ok_or_else(|| format!("{flag} requires a number"))?;
+    value
+        .parse::<usize>()
+        .map_err(|_| format!("Invalid value for {flag}: '{value}'"))
+}
+
+// Collect the arguments passed to the program, dropping a leading `--` separator
+fn script_args(args: &[String]) -> Vec<String> {
+    match args.split_first() {
+        Some((first, rest)) if first == "--" => rest.to_vec(),
+        _ => args.to_vec(),
+    }
+}
+
+// Run a compiled .pgc file, skipping lexing and parsing
+fn run_compiled(
+    path: &str,
+    options: &GlobalOptions,
+    script_args: Vec<String>,
+) -> Result<(), PidginError> {
+    let start = Instant::now();
+    let program = pgc::load_file(path).map_err(PidginError::usage)?;
+    let load_time = start.elapsed();
+
+    let mut interpreter = new_interpreter(options, script_args)?;
+    let interpret_start = Instant::now();
+    let result = interpreter.interpret(program).map_err(PidginError::runtime);
+    if options.time {
+        let phases = [
+            ("load", load_time),
+            ("interpret", interpret_start.elapsed()),
+        ];
+        report_times(&phases, start.elapsed());
+    }
+    result
+}
+
+// Run a Pidgin source file
+fn run_file(
+    path: &str,
+    options: &GlobalOptions,
+    script_args: Vec<String>,
+) -> Result<(), PidginError> {
+    let source = read_source(path)?; // Read file contents
+    if options.trace && options.coverage {
+        return Err(PidginError::usage(
+            "--trace and --coverage cannot be used together",
+        ));
+    }
+    if options.trace {
+        let tracer = trace::Tracer::new(path, &source);
+        return run_with_hook(&source, options, script_args, |_| Box::new(tracer));
+    }
+    if options.coverage {
+        return run_with_coverage(path, &source, options, script_args);
+    }
+    run(&source, options, script_args) // Run the source code
+}
+
+// Run source code with a statement hook, made once the program has been parsed
+fn run_with_hook(
+    source: &str,
+    options: &GlobalOptions,
+    script_args: Vec<String>,
+    make_hook: impl FnOnce(&Program) -> Box<dyn StatementHook>,
+) -> Result<(), PidginError> {
+    let tokens = lexer::Lexer::new(source)
+        .tokenize()
+        .map_err(PidginError::syntax)?;
+    let program = parser::Parser::new(&tokens)
+        .parse()
+        .map_err(PidginError::syntax)?;
+
+    let mut interpreter = new_interpreter(options, script_args)?;
+    interpreter.set_statement_hook(make_hook(&program));
+    interpreter.interpret(program).map_err(PidginError::runtime)
+}
+
+// Run a program while recording which of its lines (and its modules' lines) execute
+fn run_with_coverage(
+    path: &str,
+    source: &str,
+    options: &GlobalOptions,
+    script_args: Vec<String>,
+) -> Result<(), PidginError> {
+    let coverage = coverage::Coverage::default();
+    let result = run_with_hook(source, options, script_args, |program| {
+        coverage.hook(path, program)
+    });
+    if result.as_ref().is_err_and(|e| e.kind == ErrorKind::Syntax) {
+        return result; // Nothing ran
+    }
+
+    // Report even when the program fails, since the lines run before the error still count
+    io::stdout().flush().ok(); // Keep the program's output ahead of the report
+    eprintln!();
+    eprint!("{}", coverage.summary());
+    if let Some(lcov_path) = &options.coverage_lcov {
+        fs::write(lcov_path, coverage.lcov())
+            .map_err(|e| PidginError::internal(format!("Failed to write '{lcov_path}': {e}")))?;
+        info!("Wrote coverage to {lcov_path}");
     }
+    result
 }
 
 // Print help information for the REPL
 fn print_help() {
     println!("Pidgin Compiler Usage:");
     println!("  pidgin <file.pg>              - Run a Pidgin program");
+    println!("  pidgin <file.pg> [--] <args>... - Run a program with arguments (see args())");
     println!("  pidgin <file.pg> --tokens     - Show tokens for a file");
-    println!("  pidgin <file.pg> --ast        - Show AST for a file");
+    println!("  pidgin <file.pg> --ast        - Show AST for a file as a tree");
+    println!("  pidgin <file.pg> --ast --format debug - Raw debug dump of the AST");
+    println!("  pidgin <file.pg> --symbols    - List the file's functions, variables, and imports");
+    println!("  pidgin <file.pg> --tokens --format json - Tokens as JSON (also --ast)");
     println!("  pidgin <file.pg> --help       - Show this help message");
     println!("  pidgin <file.pg> --version    - Show version information");
+    println!(
+        "  (Options for running a program go before its file; what follows it is the program's)"
+    );
+    println!("  pidgin --max-steps <n> <file.pg> - Abort after <n> evaluation steps");
+    println!("  pidgin --max-array-len <n> <file.pg>  - Limit array length");
+    println!("  pidgin --max-string-len <n> <file.pg> - Limit string length (bytes)");
+    println!("  pidgin --max-values <n> <file.pg>     - Limit values stored in variables");
+    println!("  pidgin --time <file.pg>       - Report lex/parse/interpret durations");
+    println!("  pidgin --coverage <file.pg>   - Report which lines ran (--coverage-lcov <file>)");
+    println!(
+        "  pidgin --trace <file.pg>      - Print each statement and variable change as it runs"
+    );
+    println!(
+        "  pidgin --stdin-from <file> <file.pg> - Read readLine/confirm/prompt input from a file"
+    );
+    println!(
+        "  pidgin --record-input <file> <file.pg> - Save the input the program reads to a file"
+    );
+    println!("  pidgin --replay-input <file> <file.pg> - Give the program the input saved by --record-input");
+    println!("  pidgin --quiet ... / --verbose ... - Less or more diagnostic output");
+    println!("  pidgin --error-format json ... - Write errors and warnings as JSON lines (stderr)");
+    println!("  pidgin --lang pcm ...         - Show error messages in Nigerian Pidgin (also PIDGIN_LANG)");
+    println!("  pidgin -e '<code>' [args]...  - Run inline code (also --eval)");
     println!("  pidgin update                 - Update to latest version");
+    println!("  pidgin update --check [--json] - Only report whether a newer version exists");
+    println!("  pidgin update --proxy <url>   - Connect through a proxy (default: HTTPS_PROXY/HTTP_PROXY)");
+    println!("  pidgin update --mirror <url>  - Get releases from a mirror instead of GitHub");
+    println!("  pidgin update --from-file <file> - Install a downloaded release, offline");
+    println!("  pidgin fmt <file.pg>...       - Format files in place (--check, --stdout)");
+    println!("  pidgin lint <file.pg>...      - Report suspicious code (--allow <rule>, --rules)");
+    println!("  pidgin check <file.pg>...     - Check syntax and names without running");
+    println!(
+        "  pidgin watch <file.pg>        - Re-run a program whenever it or its imports change"
+    );
+    println!("  pidgin bench <file.pg>        - Time repeated runs (--runs <n>, --warmup <n>, --function <name>)");
+    println!("  pidgin build --target js <file.pg> [-o <out.js>] - Compile to JavaScript");
+    println!(
+        "  pidgin build --target c <file.pg> [-o <out.c>]   - Compile to C (then: cc out.c -lm)"
+    );
+    println!("  pidgin bundle <file.pg> [-o <app>] - Create a standalone executable");
+    println!(
+        "  pidgin compile <file.pg> [-o <out.pgc>] - Save the parsed program for faster startup"
+    );
+    println!("  pidgin <file.pgc> [args]...   - Run a compiled program");
+    println!("  pidgin doc <file.pg> [--format markdown|html] [-o <out>] - Generate API docs from /// comments");
+    println!("  pidgin highlight <file.pg> [--format ansi|html] [-o <out>] - Print syntax-highlighted source");
+    println!("  pidgin debug <file.pg> [args]... - Run a program in the interactive debugger");
+    println!("  pidgin add <name> --git <url> [--rev <rev>] - Add a library from git to pidgin.toml and install it");
+    println!(
+        "  pidgin add <name> --path <dir> - Add a local library to pidgin.toml and install it"
+    );
+    println!(
+        "  pidgin install                - Install the libraries in pidgin.toml into pg_modules/"
+    );
+    println!("  pidgin builtins               - List the builtin functions and methods");
+    println!(
+        "  pidgin run [--] [args]...     - Run the project's entry file, as set in pidgin.toml"
+    );
+    println!(
+        "  pidgin lsp                    - Start the language server (for editors, over stdio)"
+    );
+    println!("  pidgin dap                    - Start the debug adapter (for editors, over stdio)");
+    println!("  pidgin <file.pg> --check      - Same as pidgin check <file.pg>");
     println!("  pidgin                         - Start interactive REPL");
+    println!("  pidgin repl [--preload <file.pg>] [--no-banner] [--history <file> | --no-history] - Start the REPL with options");
     println!();
     println!("Pidgin REPL Commands:");
     println!("  exit, quit    - Exit the REPL");
@@ -189,25 +657,787 @@ fn print_help() {
 }
 
 // Run source code (used for files)
-fn run(source: &str) -> Result<(), String> {
-    let mut interpreter = Interpreter::new(None); // Create a new interpreter
-    run_with_interpreter(source, &mut interpreter) // Run the code
+fn run(source: &str, options: &GlobalOptions, script_args: Vec<String>) -> Result<(), PidginError> {
+    let mut interpreter = new_interpreter(options, script_args)?; // Create a new interpreter
+    if !options.time {
+        return run_with_interpreter(source, &mut interpreter); // Run the code
+    }
+
+    // Same as run_with_interpreter, but timing each phase
+    let start = Instant::now();
+    let mut phases = Vec::new();
+    let result = run_timed(source, &mut interpreter, &mut phases);
+    report_times(&phases, start.elapsed());
+    result
+}
+
+// Create an interpreter with the limits, input, and program arguments from the command line
+fn new_interpreter(
+    options: &GlobalOptions,
+    script_args: Vec<String>,
+) -> Result<Interpreter, PidginError> {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_limits(options.limits); // Apply any sandboxing limits
+    interpreter.set_script_args(script_args); // Make the arguments available to args()
+
+    // readLine, confirm, and prompt read the file's lines instead of the terminal
+    let open = |path: &str| {
+        fs::File::open(path)
+            .map(io::BufReader::new)
+            .map_err(|e| PidginError::usage(format!("Failed to read input file '{path}': {e}")))
+    };
+    let mut input: Option<Box<dyn io::BufRead + Send>> =
+        match (&options.stdin_from, &options.replay_input) {
+            (Some(_), Some(_)) => {
+                return Err(PidginError::usage(
+                    "--stdin-from and --replay-input cannot be used together",
+                ))
+            }
+            (Some(path), None) => Some(Box::new(open(path)?)),
+            (None, Some(path)) => Some(Box::new(input_log::Replay::new(open(path)?, path))),
+            (None, None) => None,
+        };
+    if let Some(path) = &options.record_input {
+        let log = fs::File::create(path)
+            .map_err(|e| PidginError::usage(format!("Failed to create '{path}': {e}")))?;
+        let source = input.unwrap_or_else(|| Box::new(io::BufReader::new(io::stdin())));
+        input = Some(Box::new(input_log::Recorder::new(source, log)));
+    }
+    if let Some(input) = input {
+        interpreter.set_input(input);
+    }
+    Ok(interpreter)
+}
+
+// Lex, parse, and interpret source code, recording how long each phase took
+fn run_timed(
+    source: &str,
+    interpreter: &mut Interpreter,
+    phases: &mut Vec<(&'static str, Duration)>,
+) -> Result<(), PidginError> {
+    let phase_start = Instant::now();
+    let tokens = lexer::Lexer::new(source).tokenize();
+    phases.push(("lex", phase_start.elapsed()));
+    let tokens = tokens.map_err(PidginError::syntax)?;
+
+    let phase_start = Instant::now();
+    let program = parser::Parser::new(&tokens).parse();
+    phases.push(("parse", phase_start.elapsed()));
+    let program = program.map_err(PidginError::syntax)?;
+
+    let phase_start = Instant::now();
+    let result = interpreter.interpret(program);
+    phases.push(("interpret", phase_start.elapsed()));
+    result.map_err(PidginError::runtime)
+}
+
+// Print the phase durations collected by run_timed (to stderr, so program output stays clean)
+fn report_times(phases: &[(&'static str, Duration)], total: Duration) {
+    eprintln!();
+    eprintln!("Timing:");
+    for (phase, duration) in phases {
+        eprintln!(
+            "  {:<10} {}",
+            format!("{phase}:"),
+            bench::format_duration(*duration)
+        );
+    }
+    eprintln!("  {:<10} {}", "total:", bench::format_duration(total));
 }
 
 // Run source code with a given interpreter (used for REPL and files)
-fn run_with_interpreter(source: &str, interpreter: &mut Interpreter) -> Result<(), String> {
-    let mut lexer = lexer::Lexer::new(source); // Create a lexer
-    let tokens = lexer.tokenize()?; // Tokenize the source code
-    let mut parser = parser::Parser::new(tokens.clone()); // Create a parser
-    let program = parser.parse()?; // Parse tokens into AST
-    interpreter.interpret(program, tokens) // Interpret the AST
+// Read a source file named on the command line
+fn read_source(path: &str) -> Result<String, PidginError> {
+    fs::read_to_string(path)
+        .map_err(|e| PidginError::usage(format!("Failed to read file '{path}': {e}")))
+}
+
+// Format Pidgin files in place, or report unformatted files with --check
+fn format_files(args: &[String]) -> Result<(), PidginError> {
+    let mut check = false; // Only report files that would change
+    let mut to_stdout = false; // Print formatted source instead of rewriting files
+    let mut paths = Vec::new();
+
+    for arg in args {
+        match arg.as_str() {
+            "--check" => check = true,
+            "--stdout" => to_stdout = true,
+            flag if flag.starts_with("--") => {
+                return Err(PidginError::usage(format!(
+                    "Unknown fmt flag: {flag}. Usage: pidgin fmt <file.pg>... [--check|--stdout]"
+                )))
+            }
+            path => paths.push(path),
+        }
+    }
+
+    if paths.is_empty() {
+        return Err(PidginError::usage(
+            "Usage: pidgin fmt <file.pg>... [--check|--stdout]",
+        ));
+    }
+
+    let mut unformatted = 0; // Files whose formatting differs (for --check)
+    for path in paths {
+        let source = read_source(path)?;
+        let formatted = formatter::format_source(&source)
+            .map_err(|e| PidginError::syntax(format!("{path}: {e}")))?;
+
+        if check {
+            if formatted != source {
+                println!("Would reformat: {path}");
+                unformatted += 1;
+            }
+        } else if to_stdout {
+            print!("{formatted}");
+        } else if formatted != source {
+            fs::write(path, &formatted).map_err(|e| {
+                PidginError::internal(format!("Failed to write file '{path}': {e}"))
+            })?;
+            info!("Formatted: {path}");
+        }
+    }
+
+    if unformatted > 0 {
+        return Err(PidginError::runtime(format!(
+            "{unformatted} file(s) need formatting"
+        )));
+    }
+    Ok(())
+}
+
+// Run a Pidgin file (or one of its functions) several times and report the timings
+fn bench_file(args: &[String], limits: Limits) -> Result<(), PidginError> {
+    const USAGE: &str =
+        "Usage: pidgin bench <file.pg> [--runs <n>] [--warmup <n>] [--function <name>]";
+    let mut options = bench::BenchOptions {
+        runs: 10,
+        warmup: 1,
+        function: None,
+        limits,
+    };
+    let mut path = None;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--runs" => {
+                options.runs =
+                    parse_limit_value("--runs", iter.next().cloned()).map_err(PidginError::usage)?
+            }
+            "--warmup" => {
+                options.warmup = parse_limit_value("--warmup", iter.next().cloned())
+                    .map_err(PidginError::usage)?
+            }
+            "--function" => {
+                let name = iter
+                    .next()
+                    .ok_or_else(|| PidginError::usage("--function requires a function name"))?;
+                options.function = Some(name.clone());
+            }
+            flag if flag.starts_with("--") => {
+                return Err(PidginError::usage(format!(
+                    "Unknown bench flag: {flag}. {USAGE}"
+                )))
+            }
+            file if path.is_none() => path = Some(file),
+            _ => return Err(PidginError::usage(USAGE)),
+        }
+    }
+    let path = path.ok_or_else(|| PidginError::usage(USAGE))?;
+
+    let source = read_source(path)?;
+    let tokens = lexer::Lexer::new(&source)
+        .tokenize()
+        .map_err(PidginError::syntax)?;
+    let program = parser::Parser::new(&tokens)
+        .parse()
+        .map_err(|e| PidginError::syntax(format!("Parse error: {e}")))?;
+
+    let stats = bench::bench_program(&program, &options).map_err(PidginError::runtime)?;
+    let target = match &options.function {
+        Some(name) => format!("{path} ({name})"),
+        None => path.to_string(),
+    };
+    println!();
+    println!("Benchmark: {target}, {} runs", stats.runs);
+    println!("  min:  {}", bench::format_duration(stats.min));
+    println!("  mean: {}", bench::format_duration(stats.mean));
+    println!("  max:  {}", bench::format_duration(stats.max));
+    Ok(())
+}
+
+// Lex, parse, and resolve Pidgin files without running them, printing any errors
+fn check_files(paths: &[String]) -> Result<(), PidginError> {
+    if paths.is_empty() {
+        return Err(PidginError::usage("Usage: pidgin check <file.pg>..."));
+    }
+
+    let json = diagnostic::error_format() == ErrorFormat::Json;
+    let mut error_count = 0;
+    for path in paths {
+        let source = read_source(path)?;
+
+        let program = lexer::Lexer::new(&source)
+            .tokenize()
+            .and_then(|tokens| parser::Parser::new(&tokens).parse());
+        let program = match program {
+            Ok(program) => program,
+            Err(e) if json => {
+                let error = PidginError::syntax(e).in_file(path);
+                eprintln!("{}", Diagnostic::from_error(&error).to_json());
+                error_count += 1;
+                continue;
+            }
+            Err(e) => {
+                eprintln!("{path}: error: {e}");
+                error_count += 1;
+                continue;
+            }
+        };
+
+        let errors = resolver::resolve_program(&program);
+        for error in &errors {
+            if json {
+                let diagnostic =
+                    Diagnostic::at(path, error.span, Severity::Error, "name", &error.message);
+                eprintln!("{}", diagnostic.to_json());
+                continue;
+            }
+            eprintln!(
+                "{path}:{line}:{column}: error: {message}",
+                line = error.span.line,
+                column = error.span.column,
+                message = error.message
+            );
+        }
+        // Code that can never run is suspicious, but the program is still valid
+        for warning in lint::unreachable_code(&program) {
+            if json {
+                let diagnostic = Diagnostic::at(
+                    path,
+                    warning.span,
+                    Severity::Warning,
+                    warning.rule,
+                    &warning.message,
+                );
+                eprintln!("{}", diagnostic.to_json());
+                continue;
+            }
+            eprintln!(
+                "{path}:{line}:{column}: warning[{rule}]: {message}",
+                line = warning.span.line,
+                column = warning.span.column,
+                rule = warning.rule,
+                message = warning.message
+            );
+        }
+        if errors.is_empty() {
+            info!("{path}: no errors found");
+        }
+        error_count += errors.len();
+    }
+
+    if error_count > 0 && json {
+        return Err(PidginError::reported(ErrorKind::Syntax)); // Each error was its own diagnostic
+    }
+    if error_count > 0 {
+        return Err(PidginError::syntax(format!("{error_count} error(s) found")));
+    }
+    Ok(())
+}
+
+// Lint Pidgin files, printing one warning per line
+fn lint_files(args: &[String]) -> Result<(), PidginError> {
+    let usage = "Usage: pidgin lint <file.pg>... [--allow <rule>]...";
+    let mut allowed = Vec::new(); // Rules disabled with --allow
+    let mut paths = Vec::new();
+    let mut iter = args.iter();
+
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--allow" => {
+                let rule = iter.next().ok_or_else(|| {
+                    PidginError::usage(format!("--allow requires a rule name. {usage}"))
+                })?;
+                if !lint::is_known_rule(rule) {
+                    let rules: Vec<&str> = lint::RULES.iter().map(|(name, _)| *name).collect();
+                    return Err(PidginError::usage(format!(
+                        "Unknown lint rule '{rule}'. Available rules: {}",
+                        rules.join(", ")
+                    )));
+                }
+                allowed.push(rule.clone());
+            }
+            "--rules" => {
+                for (name, description) in lint::RULES {
+                    println!("  {name:<20} - {description}");
+                }
+                return Ok(());
+            }
+            flag if flag.starts_with("--") => {
+                return Err(PidginError::usage(format!(
+                    "Unknown lint flag: {flag}. {usage}"
+                )))
+            }
+            path => paths.push(path),
+        }
+    }
+
+    if paths.is_empty() {
+        return Err(PidginError::usage(usage));
+    }
+
+    let json = diagnostic::error_format() == ErrorFormat::Json;
+    let mut warning_count = 0;
+    for path in paths {
+        let source = read_source(path)?;
+        let tokens = lexer::Lexer::new(&source)
+            .tokenize()
+            .map_err(|e| PidginError::syntax(format!("{path}: {e}")).in_file(path))?;
+        let program = parser::Parser::new(&tokens)
+            .parse()
+            .map_err(|e| PidginError::syntax(format!("{path}: Parse error: {e}")).in_file(path))?;
+
+        for warning in lint::lint_program(&program, &allowed) {
+            warning_count += 1;
+            if json {
+                let diagnostic = Diagnostic::at(
+                    path,
+                    warning.span,
+                    Severity::Warning,
+                    warning.rule,
+                    &warning.message,
+                );
+                eprintln!("{}", diagnostic.to_json());
+                continue;
+            }
+            println!(
+                "{path}:{line}:{column}: warning[{rule}]: {message}",
+                line = warning.span.line,
+                column = warning.span.column,
+                rule = warning.rule,
+                message = warning.message
+            );
+        }
+    }
+
+    if warning_count > 0 && json {
+        return Err(PidginError::reported(ErrorKind::Runtime)); // Each warning was its own diagnostic
+    }
+    if warning_count > 0 {
+        return Err(PidginError::runtime(format!(
+            "{warning_count} lint warning(s) found"
+        )));
+    }
+    Ok(())
+}
+
+// Compile a Pidgin file for another platform (pidgin build --target js|c)
+fn build_file(args: &[String]) -> Result<(), PidginError> {
+    const USAGE: &str = "Usage: pidgin build --target js|c <file.pg> [-o <output>]";
+    let mut target = None;
+    let mut output = None; // Output path, or "-" for stdout
+    let mut path = None;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--target" => {
+                let value = iter.next().ok_or_else(|| {
+                    PidginError::usage(format!("--target requires a value. {USAGE}"))
+                })?;
+                target = Some(value.as_str());
+            }
+            "-o" | "--output" => {
+                let value = iter
+                    .next()
+                    .ok_or_else(|| PidginError::usage(format!("{arg} requires a path. {USAGE}")))?;
+                output = Some(value.clone());
+            }
+            flag if flag.starts_with('-') && flag != "-" => {
+                return Err(PidginError::usage(format!(
+                    "Unknown build flag: {flag}. {USAGE}"
+                )))
+            }
+            file if path.is_none() => path = Some(file),
+            _ => return Err(PidginError::usage(USAGE)),
+        }
+    }
+    let path = path.ok_or_else(|| PidginError::usage(USAGE))?;
+    let target = target.ok_or_else(|| PidginError::usage(format!("Missing --target. {USAGE}")))?;
+    let (transpile, extension) = codegen::find_target(target).ok_or_else(|| {
+        let targets: Vec<&str> = codegen::TARGETS.iter().map(|(name, _, _)| *name).collect();
+        PidginError::usage(format!(
+            "Unknown build target '{target}' (available: {})",
+            targets.join(", ")
+        ))
+    })?;
+
+    let source = read_source(path)?;
+    let tokens = lexer::Lexer::new(&source)
+        .tokenize()
+        .map_err(PidginError::syntax)?;
+    let program = parser::Parser::new(&tokens)
+        .parse()
+        .map_err(|e| PidginError::syntax(format!("Parse error: {e}")))?;
+    let code = transpile(&program, path).map_err(PidginError::syntax)?;
+
+    // Write next to the source file unless told otherwise
+    let output = output.unwrap_or_else(|| format!("{}.{extension}", path.trim_end_matches(".pg")));
+    if output == "-" {
+        print!("{code}");
+    } else {
+        fs::write(&output, code)
+            .map_err(|e| PidginError::internal(format!("Failed to write file '{output}': {e}")))?;
+        info!("Built: {output}");
+    }
+    Ok(())
+}
+
+// Save a program's parsed form to a .pgc file (pidgin compile)
+fn compile_file(args: &[String]) -> Result<(), PidginError> {
+    const USAGE: &str = "Usage: pidgin compile <file.pg> [-o <output.pgc>]";
+    let (path, output) = match args {
+        [path] => (path, format!("{}.pgc", path.trim_end_matches(".pg"))),
+        [path, flag, output] if flag == "-o" || flag == "--output" => (path, output.clone()),
+        _ => return Err(PidginError::usage(USAGE)),
+    };
+    if !path.ends_with(".pg") {
+        return Err(PidginError::usage(format!("Expected a .pg file. {USAGE}")));
+    }
+
+    read_source(path)?; // Report a missing file as a usage error
+    let compiled = pgc::compile(path).map_err(PidginError::syntax)?;
+    fs::write(&output, compiled)
+        .map_err(|e| PidginError::internal(format!("Failed to write file '{output}': {e}")))?;
+    info!("Compiled: {output}");
+    Ok(())
+}
+
+// Generate API documentation from a program's /// comments (pidgin doc)
+fn doc_file(args: &[String]) -> Result<(), PidginError> {
+    const USAGE: &str = "Usage: pidgin doc <file.pg> [--format markdown|html] [-o <output>]";
+    let mut format = None;
+    let mut output = None; // Output path; stdout if not given
+    let mut path = None;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--format" => {
+                let value = iter.next().ok_or_else(|| {
+                    PidginError::usage(format!("--format requires a value. {USAGE}"))
+                })?;
+                format = Some(doc::DocFormat::from_name(value).ok_or_else(|| {
+                    PidginError::usage(format!(
+                        "Unknown doc format '{value}' (available: markdown, html)"
+                    ))
+                })?);
+            }
+            "-o" | "--output" => {
+                let value = iter
+                    .next()
+                    .ok_or_else(|| PidginError::usage(format!("{arg} requires a path. {USAGE}")))?;
+                output = Some(value.clone());
+            }
+            flag if flag.starts_with('-') => {
+                return Err(PidginError::usage(format!(
+                    "Unknown doc flag: {flag}. {USAGE}"
+                )))
+            }
+            file if path.is_none() => path = Some(file),
+            _ => return Err(PidginError::usage(USAGE)),
+        }
+    }
+    let path = path.ok_or_else(|| PidginError::usage(USAGE))?;
+
+    // Without --format, an output file ending in .html gets HTML
+    let format = format.unwrap_or(match &output {
+        Some(output) if output.ends_with(".html") => doc::DocFormat::Html,
+        _ => doc::DocFormat::Markdown,
+    });
+
+    read_source(path)?; // Report a missing file as a usage error
+    let docs = doc::generate(path, format).map_err(PidginError::syntax)?;
+    match output {
+        Some(output) => {
+            fs::write(&output, docs).map_err(|e| {
+                PidginError::internal(format!("Failed to write file '{output}': {e}"))
+            })?;
+            info!("Documented: {output}");
+        }
+        None => print!("{docs}"),
+    }
+    Ok(())
+}
+
+// Print a source file with syntax highlighting (pidgin highlight)
+fn highlight_file(args: &[String]) -> Result<(), PidginError> {
+    const USAGE: &str = "Usage: pidgin highlight <file.pg> [--format ansi|html] [-o <output>]";
+    let mut format = highlight::HighlightFormat::Ansi;
+    let mut output = None; // Output path; stdout if not given
+    let mut path = None;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--format" => {
+                let value = iter.next().ok_or_else(|| {
+                    PidginError::usage(format!("--format requires a value. {USAGE}"))
+                })?;
+                format = highlight::HighlightFormat::from_name(value).ok_or_else(|| {
+                    PidginError::usage(format!(
+                        "Unknown highlight format '{value}' (available: ansi, html)"
+                    ))
+                })?;
+            }
+            "-o" | "--output" => {
+                let value = iter
+                    .next()
+                    .ok_or_else(|| PidginError::usage(format!("{arg} requires a path. {USAGE}")))?;
+                output = Some(value.clone());
+            }
+            flag if flag.starts_with('-') => {
+                return Err(PidginError::usage(format!(
+                    "Unknown highlight flag: {flag}. {USAGE}"
+                )))
+            }
+            file if path.is_none() => path = Some(file),
+            _ => return Err(PidginError::usage(USAGE)),
+        }
+    }
+    let path = path.ok_or_else(|| PidginError::usage(USAGE))?;
+
+    let source = read_source(path)?;
+    let highlighted = highlight::highlight(&source, format).map_err(PidginError::syntax)?;
+    match output {
+        Some(output) => fs::write(&output, highlighted)
+            .map_err(|e| PidginError::internal(format!("Failed to write file '{output}': {e}")))?,
+        None => print!("{highlighted}"),
+    }
+    Ok(())
+}
+
+// Run a program under the interactive debugger (pidgin debug)
+fn debug_file(args: &[String]) -> Result<(), PidginError> {
+    let path = args
+        .first()
+        .ok_or_else(|| PidginError::usage("Usage: pidgin debug <file.pg> [args]..."))?;
+    let source = read_source(path)?;
+    let tokens = lexer::Lexer::new(&source)
+        .tokenize()
+        .map_err(PidginError::syntax)?;
+    let program = parser::Parser::new(&tokens)
+        .parse()
+        .map_err(PidginError::syntax)?;
+
+    let mut interpreter = Interpreter::new();
+    interpreter.set_script_args(args[1..].to_vec());
+    let debugger = debugger::Debugger::new(&source, &program);
+    interpreter.set_statement_hook(Box::new(debugger));
+    println!("Debugging {path}. Type 'help' for commands.");
+
+    match interpreter.interpret(program) {
+        Ok(()) => {
+            println!("Program finished.");
+            Ok(())
+        }
+        Err(e) if e == debugger::QUIT => Ok(()),
+        Err(e) => Err(PidginError::runtime(e)),
+    }
+}
+
+// Add a dependency to pidgin.toml and install it (pidgin add)
+fn add_package(args: &[String]) -> Result<(), PidginError> {
+    const USAGE: &str =
+        "Usage: pidgin add <name> --git <url> [--rev <rev>] | pidgin add <name> --path <dir>";
+    let mut dependency = package::Dependency::default();
+    let mut name = None;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let field = match arg.as_str() {
+            "--git" => &mut dependency.git,
+            "--rev" => &mut dependency.rev,
+            "--path" => &mut dependency.path,
+            flag if flag.starts_with('-') => {
+                return Err(PidginError::usage(format!(
+                    "Unknown add flag: {flag}. {USAGE}"
+                )))
+            }
+            package if name.is_none() => {
+                name = Some(package);
+                continue;
+            }
+            _ => return Err(PidginError::usage(USAGE)),
+        };
+        let value = iter
+            .next()
+            .ok_or_else(|| PidginError::usage(format!("{arg} requires a value. {USAGE}")))?;
+        *field = Some(value.clone());
+    }
+    let name = name.ok_or_else(|| PidginError::usage(USAGE))?;
+
+    package::validate(name, &dependency)
+        .map_err(|e| PidginError::usage(format!("{e}. {USAGE}")))?;
+    package::add(name, dependency).map_err(PidginError::internal)
+}
+
+// Install every dependency listed in pidgin.toml (pidgin install)
+fn install_packages(args: &[String]) -> Result<(), PidginError> {
+    if !args.is_empty() {
+        return Err(PidginError::usage("Usage: pidgin install"));
+    }
+    if !std::path::Path::new(package::MANIFEST).exists() {
+        return Err(PidginError::usage(format!(
+            "No {} in the current directory (pidgin add creates one)",
+            package::MANIFEST
+        )));
+    }
+    let manifest = package::load_manifest(std::path::Path::new(".")).map_err(PidginError::usage)?;
+    package::install(&manifest).map_err(PidginError::internal)
+}
+
+// Run the project in the current directory, with the entry file, module paths, and arguments
+// given in its pidgin.toml (pidgin run)
+fn run_project(args: &[String], options: &GlobalOptions) -> Result<(), PidginError> {
+    if !std::path::Path::new(package::MANIFEST).exists() {
+        return Err(PidginError::usage(format!(
+            "No {} in the current directory (to run a single file, use pidgin <file.pg>)",
+            package::MANIFEST
+        )));
+    }
+    let manifest = package::load_manifest(std::path::Path::new(".")).map_err(PidginError::usage)?;
+    let settings = manifest.run.unwrap_or_default();
+    let entry = settings
+        .entry
+        .unwrap_or_else(|| package::DEFAULT_ENTRY.to_string());
+    if !entry.ends_with(".pg") {
+        return Err(PidginError::usage(format!(
+            "The entry in {} must be a .pg file, not '{entry}'",
+            package::MANIFEST
+        )));
+    }
+
+    interpreter::set_module_paths(settings.module_paths);
+    let mut program_args = settings.args;
+    program_args.extend(script_args(args));
+    run_file(&entry, options, program_args).map_err(|e| e.in_file(&entry))
+}
+
+// Create a standalone executable containing a program (pidgin bundle)
+fn bundle_file(args: &[String]) -> Result<(), PidginError> {
+    const USAGE: &str = "Usage: pidgin bundle <file.pg> [-o <output>]";
+    let mut output = None;
+    let mut path = None;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-o" | "--output" => {
+                let value = iter
+                    .next()
+                    .ok_or_else(|| PidginError::usage(format!("{arg} requires a path. {USAGE}")))?;
+                output = Some(value.clone());
+            }
+            flag if flag.starts_with('-') => {
+                return Err(PidginError::usage(format!(
+                    "Unknown bundle flag: {flag}. {USAGE}"
+                )))
+            }
+            file if path.is_none() => path = Some(file),
+            _ => return Err(PidginError::usage(USAGE)),
+        }
+    }
+    let path = path.ok_or_else(|| PidginError::usage(USAGE))?;
+
+    // Refuse to bundle a program that would only fail with a syntax error
+    let source = read_source(path)?;
+    let tokens = lexer::Lexer::new(&source)
+        .tokenize()
+        .map_err(PidginError::syntax)?;
+    parser::Parser::new(&tokens)
+        .parse()
+        .map_err(|e| PidginError::syntax(format!("Parse error: {e}")))?;
+
+    // By default, name the executable after the program: hello.pg -> hello
+    let output = output.unwrap_or_else(|| {
+        let stem = std::path::Path::new(path)
+            .file_stem()
+            .map_or("app".into(), |stem| stem.to_string_lossy());
+        format!("{stem}{}", env::consts::EXE_SUFFIX)
+    });
+    bundle::bundle_file(path, &output).map_err(PidginError::internal)?;
+    info!("Bundled: {output}");
+    Ok(())
+}
+
+// Run the program embedded in a bundled executable
+fn run_bundle(bundle: bundle::Bundle, args: &[String]) -> Result<(), PidginError> {
+    let modules = bundle.modules.into_iter();
+    interpreter::set_embedded_modules(
+        modules
+            .map(|(file, source)| (file, interpreter::ModuleSource::Source(source)))
+            .collect(),
+    );
+    run(&bundle.main.1, &GlobalOptions::default(), args.to_vec())
+}
+
+// Define the OutputFormat enum, how --tokens and --ast print their results
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum OutputFormat {
+    Text,  // Readable output for humans (a tree for --ast)
+    Debug, // Raw Rust debug dump of the data structures
+    Json,  // Stable JSON for tools (see docs/JSON_Output.md)
+}
+
+// Parse the options following --tokens, --ast, or --symbols (currently only --format
+// text|debug|json)
+fn parse_output_format(args: &[String]) -> Result<OutputFormat, PidginError> {
+    let mut format = OutputFormat::Text;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--format" => {
+                format = match iter.next().map(String::as_str) {
+                    Some("text") => OutputFormat::Text,
+                    Some("debug") => OutputFormat::Debug,
+                    Some("json") => OutputFormat::Json,
+                    Some(other) => {
+                        return Err(PidginError::usage(format!(
+                            "Unknown format '{other}' (expected text, debug or json)"
+                        )))
+                    }
+                    None => {
+                        return Err(PidginError::usage(
+                            "--format requires a value (text, debug or json)",
+                        ))
+                    }
+                }
+            }
+            other => return Err(PidginError::usage(format!("Unknown option: {other}"))),
+        }
+    }
+    Ok(format)
+}
+
+// Print a JSON document, pretty-printed
+fn print_json(value: &serde_json::Value) -> Result<(), PidginError> {
+    let json =
+        serde_json::to_string_pretty(value).map_err(|e| PidginError::internal(e.to_string()))?;
+    println!("{json}");
+    Ok(())
 }
 
 // Display tokens for a given file
-fn display_tokens(path: &str) -> Result<(), String> {
-    let source = fs::read_to_string(path).map_err(|e| format!("Failed to read file: {e}"))?; // Read file contents
+fn display_tokens(path: &str, format: OutputFormat) -> Result<(), PidginError> {
+    let source = read_source(path)?; // Read file contents
     let mut lexer = lexer::Lexer::new(&source); // Create a lexer
-    let tokens = lexer.tokenize()?; // Tokenize the source code
+    let tokens = lexer.tokenize().map_err(PidginError::syntax)?; // Tokenize the source code
+    if format == OutputFormat::Json {
+        return print_json(&json_output::tokens_to_json(&tokens));
+    }
     for token in tokens {
         println!("{token:?}"); // Print each token
     }
@@ -215,18 +1445,79 @@ fn display_tokens(path: &str) -> Result<(), String> {
 }
 
 // Display AST for a given file
-fn display_ast(path: &str) -> Result<(), String> {
-    let source = fs::read_to_string(path).map_err(|e| format!("Failed to read file: {e}"))?; // Read file contents
+fn display_ast(path: &str, format: OutputFormat) -> Result<(), PidginError> {
+    let source = read_source(path)?; // Read file contents
     let mut lexer = lexer::Lexer::new(&source); // Create a lexer
-    let tokens = lexer.tokenize()?; // Tokenize the source code
-    let mut parser = parser::Parser::new(tokens); // Create a parser
+    let tokens = lexer.tokenize().map_err(PidginError::syntax)?; // Tokenize the source code
+    let mut parser = parser::Parser::new(&tokens); // Create a parser
     match parser.parse() {
-        Ok(program) => println!("{program:?}"), // Print AST if parsing succeeds
-        Err(e) => return Err(format!("Parse error: {e}")), // Print error if parsing fails
+        Ok(program) => match format {
+            OutputFormat::Text => print!("{}", ast_printer::print_tree(&program)), // Print AST as a tree
+            OutputFormat::Debug => println!("{program:?}"), // Print the raw AST
+            OutputFormat::Json => print_json(&json_output::program_to_json(&program))?,
+        },
+        Err(e) => return Err(PidginError::syntax(format!("Parse error: {e}"))), // Print error if parsing fails
     }
     Ok(())
 }
 
+// Display the top-level functions, variables, and imports of a given file
+fn display_symbols(path: &str, format: OutputFormat) -> Result<(), PidginError> {
+    let source = read_source(path)?;
+    let tokens = lexer::Lexer::new(&source)
+        .tokenize()
+        .map_err(PidginError::syntax)?;
+    let program = parser::Parser::new(&tokens)
+        .parse()
+        .map_err(PidginError::syntax)?;
+    let symbols = outline::symbols(&program);
+    match format {
+        OutputFormat::Text => print!("{}", outline::to_text(&symbols)),
+        OutputFormat::Debug => println!("{symbols:#?}"),
+        OutputFormat::Json => print_json(&json_output::symbols_to_json(&symbols))?,
+    }
+    Ok(())
+}
+
+// List every builtin function and method, with how many arguments it takes and what it does
+// (pidgin builtins, and :builtins in the REPL)
+fn print_builtins() {
+    println!("Functions:");
+    let signatures: Vec<String> = interpreter::BUILTINS
+        .iter()
+        .map(|builtin| format!("{}({})", builtin.name, builtin.params))
+        .collect();
+    let width = signatures.iter().map(String::len).max().unwrap_or(0);
+    for (builtin, signature) in interpreter::BUILTINS.iter().zip(&signatures) {
+        println!(
+            "  {signature:<width$}  {:<4} {}",
+            builtin.arity(),
+            builtin.description
+        );
+    }
+
+    let registry = methods::MethodRegistry::new();
+    let list = registry.list();
+    let width = list
+        .iter()
+        .map(|(_, name, _)| name.len())
+        .max()
+        .unwrap_or(0);
+    let mut current_type = "";
+    for (type_name, name, method) in list {
+        if type_name != current_type {
+            println!("Methods of {type_name} values:");
+            current_type = type_name;
+        }
+        println!(
+            "  .{name:<width$}  {:<4} {}",
+            method.arity, method.description
+        );
+    }
+    // Not in the registry, as its argument is not a value (see methods.rs)
+    println!("Also on strings: .replaceChar`from -> to` returns a copy with every `from` replaced");
+}
+
 // Display the version of the compiler
 fn display_version() {
     println!("Pidgin Compiler v{}", env!("CARGO_PKG_VERSION"));
@@ -0,0 +1,485 @@
+// Built-in methods: what `value.name(arguments)` does for each type of value
+//
+// Methods live in a MethodRegistry, a table from (receiver type, method name) to a handler and
+// the number of arguments it takes, so adding one is a matter of registering it. Every
+// interpreter starts with the methods below; embedders add their own with
+// Interpreter::register_method. Methods never change the value they are called on: they return
+// a new one, which the program assigns back (arr = arr.push(4);).
+//
+// replaceChar is not in the table: its transform argument (`from -> to`) is not a value, so the
+// interpreter handles it itself. Methods a program defines in Pidgin
+// (function String.shout(self) { ... }) are not in it either: they are functions, stored with
+// the program's globals under their full name, and are looked up before the table.
+use crate::interpreter::Value;
+use chrono::Datelike;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+// Receiver types methods can be registered for, in the order error messages list them
+pub const TYPE_NAMES: [&str; 11] = [
+    "string",
+    "number",
+    "boolean",
+    "fixed array",
+    "dynamic array",
+    "object",
+    "date",
+    "duration",
+    "nil",
+    "function",
+    "iterator",
+];
+
+// Types a program can define methods on, by the name it uses for them
+pub const EXTENSION_TYPES: [&str; 7] = [
+    "String", "Number", "Boolean", "Array", "Object", "Date", "Duration",
+];
+
+// Built-in methods that return an updated copy of their receiver, which frozen values refuse
+const UPDATING_METHODS: [&str; 4] = ["push", "clear", "insert", "set"];
+
+// A method's handler: it gets the receiver and the evaluated arguments (as many as the method
+// was registered with) and returns the result or an error message
+pub type MethodFunction = Arc<dyn Fn(&Value, &[Value]) -> Result<Value, String> + Send + Sync>;
+
+// Define the Method struct, one registered method
+#[derive(Clone)]
+pub struct Method {
+    pub arity: usize,              // Number of arguments it takes
+    pub updates: bool, // Returns an updated copy of the receiver (not allowed when frozen)
+    pub description: &'static str, // What it does, in one line (empty for registered methods)
+    function: MethodFunction, // What it does
+}
+
+// Implement methods for Method
+impl Method {
+    // Run the method on a receiver with its (already counted) arguments
+    pub fn call(&self, receiver: &Value, arguments: &[Value]) -> Result<Value, String> {
+        (self.function)(receiver, arguments)
+    }
+}
+
+// Define the MethodRegistry struct, the methods available on each type of value
+#[derive(Clone)]
+pub struct MethodRegistry {
+    methods: HashMap<String, HashMap<&'static str, Method>>, // Name -> receiver type -> method
+}
+
+// Implement methods for MethodRegistry
+impl MethodRegistry {
+    // Create a registry holding the built-in methods
+    pub fn new() -> Self {
+        let mut registry = Self {
+            methods: HashMap::new(),
+        };
+        registry.register_builtins();
+        registry
+    }
+
+    // Add a method for a type of value (a name from TYPE_NAMES), replacing any method of the
+    // same name on that type
+    pub fn register(
+        &mut self,
+        type_name: &str,
+        name: &str,
+        arity: usize,
+        function: impl Fn(&Value, &[Value]) -> Result<Value, String> + Send + Sync + 'static,
+    ) -> Result<(), String> {
+        let type_name = TYPE_NAMES
+            .iter()
+            .find(|known| **known == type_name)
+            .ok_or_else(|| {
+                format!(
+                    "Unknown type '{type_name}' (expected one of: {})",
+                    TYPE_NAMES.join(", ")
+                )
+            })?;
+        let method = Method {
+            arity,
+            updates: false,
+            description: "",
+            function: Arc::new(function),
+        };
+        self.methods
+            .entry(name.to_string())
+            .or_default()
+            .insert(type_name, method);
+        Ok(())
+    }
+
+    // Find the method a call on this receiver runs, or say why there is none
+    pub fn lookup(&self, receiver: &Value, name: &str) -> Result<&Method, String> {
+        let by_type = self
+            .methods
+            .get(name)
+            .ok_or_else(|| format!("Unsupported method: {name}"))?;
+        if let Some(method) = by_type.get(receiver.thawed().type_name()) {
+            return Ok(method);
+        }
+        let mut types: Vec<&str> = TYPE_NAMES
+            .iter()
+            .copied()
+            .filter(|type_name| by_type.contains_key(type_name))
+            .collect();
+        // A method on both kinds of array is simply an array method
+        if types.contains(&"fixed array") && types.contains(&"dynamic array") {
+            types.retain(|type_name| *type_name != "fixed array");
+            types.iter_mut().for_each(|type_name| {
+                if *type_name == "dynamic array" {
+                    *type_name = "array";
+                }
+            });
+        }
+        let types: Vec<String> = types.iter().map(|type_name| plural(type_name)).collect();
+        Err(format!(
+            "{name} method can only be called on {}",
+            types.join(" or ")
+        ))
+    }
+
+    // List the methods by receiver type (in TYPE_NAMES order) and name, for pidgin builtins. A
+    // method on both kinds of array is listed once, for "array".
+    pub fn list(&self) -> Vec<(&'static str, &str, &Method)> {
+        let mut names: Vec<&String> = self.methods.keys().collect();
+        names.sort();
+        let mut list = Vec::new();
+        for type_name in TYPE_NAMES {
+            for name in &names {
+                let by_type = &self.methods[*name];
+                let Some(method) = by_type.get(type_name) else {
+                    continue;
+                };
+                let both_arrays =
+                    by_type.contains_key("fixed array") && by_type.contains_key("dynamic array");
+                match type_name {
+                    "fixed array" if both_arrays => list.push(("array", name.as_str(), method)),
+                    "dynamic array" if both_arrays => {}
+                    _ => list.push((type_name, name.as_str(), method)),
+                }
+            }
+        }
+        list
+    }
+
+    // Register the methods every interpreter starts with
+    fn register_builtins(&mut self) {
+        self.builtin(
+            &["dynamic array"],
+            "push",
+            1,
+            "Return a copy with the item added at the end",
+            |receiver, arguments| {
+                let mut items = items(receiver).to_vec();
+                items.push(arguments[0].clone());
+                Ok(Value::DynamicArray(items))
+            },
+        );
+        self.builtin(
+            &["dynamic array"],
+            "pop",
+            0,
+            "Return the last item (the array itself is unchanged)",
+            |receiver, _| {
+                items(receiver)
+                    .last()
+                    .cloned()
+                    .ok_or_else(|| "Cannot pop from empty array".to_string())
+            },
+        );
+        self.builtin(
+            &["dynamic array"],
+            "clear",
+            0,
+            "Return an empty array",
+            |_, _| Ok(Value::DynamicArray(Vec::new())),
+        );
+        self.builtin(
+            &["dynamic array"],
+            "insert",
+            2,
+            "Return a copy with the item inserted at the index",
+            |receiver, arguments| {
+                // Array insert method: arr.insert(index, value)
+                let Value::Number(index) = arguments[0] else {
+                    return Err("insert() requires (index, value) arguments".to_string());
+                };
+                let mut items = items(receiver).to_vec();
+                let index = index as usize;
+                if index > items.len() {
+                    return Err(format!(
+                        "Insert index {index} out of bounds (array length: {})",
+                        items.len()
+                    ));
+                }
+                items.insert(index, arguments[1].clone());
+                Ok(Value::DynamicArray(items))
+            },
+        );
+        self.builtin(
+            &["dynamic array"],
+            "remove",
+            1,
+            "Return the item at the index (the array itself is unchanged)",
+            |receiver, arguments| {
+                // Array remove method: arr.remove(index), returning the element removed
+                let Value::Number(index) = arguments[0] else {
+                    return Err("remove() requires a numeric index argument".to_string());
+                };
+                let items = items(receiver);
+                let index = index as usize;
+                items.get(index).cloned().ok_or_else(|| {
+                    format!(
+                        "Remove index {index} out of bounds (array length: {})",
+                        items.len()
+                    )
+                })
+            },
+        );
+
+        let arrays = ["fixed array", "dynamic array"];
+        self.builtin(
+            &arrays,
+            "length",
+            0,
+            "Return the number of items",
+            |receiver, _| Ok(Value::Number(items(receiver).len() as f64)),
+        );
+        self.builtin(
+            &arrays,
+            "reverse",
+            0,
+            "Return a copy with the items in reverse order",
+            |receiver, _| {
+                let mut items = items(receiver).to_vec();
+                items.reverse();
+                Ok(match receiver {
+                    Value::FixedArray(_) => Value::FixedArray(items),
+                    _ => Value::DynamicArray(items),
+                })
+            },
+        );
+
+        self.builtin(
+            &["string"],
+            "toUpper",
+            0,
+            "Return the string in upper case",
+            |receiver, _| Ok(Value::String(text(receiver).to_uppercase())),
+        );
+        self.builtin(
+            &["string"],
+            "toLower",
+            0,
+            "Return the string in lower case",
+            |receiver, _| Ok(Value::String(text(receiver).to_lowercase())),
+        );
+        self.builtin(
+            &["string"],
+            "trim",
+            0,
+            "Return the string without spaces at either end",
+            |receiver, _| Ok(Value::String(text(receiver).trim().to_string())),
+        );
+
+        self.builtin(
+            &["date"],
+            "format",
+            1,
+            "Format the date with strftime codes (\"%Y-%m-%d\")",
+            |receiver, arguments| {
+                // Date format method: date.format("%Y-%m-%d")
+                match (receiver, &arguments[0]) {
+                    (Value::Date(date), Value::String(format)) => {
+                        Ok(Value::String(date.format(format).to_string()))
+                    }
+                    _ => Err("Date.format() requires a string argument".to_string()),
+                }
+            },
+        );
+        self.builtin(&["date"], "getYear", 0, "Return the year", |receiver, _| {
+            Ok(Value::Number(date(receiver).year() as f64))
+        });
+        self.builtin(
+            &["date"],
+            "getMonth",
+            0,
+            "Return the month (1-12)",
+            |receiver, _| Ok(Value::Number(date(receiver).month() as f64)),
+        );
+        self.builtin(
+            &["date"],
+            "getDay",
+            0,
+            "Return the day of the month",
+            |receiver, _| Ok(Value::Number(date(receiver).day() as f64)),
+        );
+
+        self.builtin(
+            &["duration"],
+            "toHours",
+            0,
+            "Return the length of time in hours",
+            |receiver, _| Ok(Value::Number(seconds(receiver) / 3600.0)),
+        );
+        self.builtin(
+            &["duration"],
+            "toMinutes",
+            0,
+            "Return the length of time in minutes",
+            |receiver, _| Ok(Value::Number(seconds(receiver) / 60.0)),
+        );
+        self.builtin(
+            &["duration"],
+            "toSeconds",
+            0,
+            "Return the length of time in seconds",
+            |receiver, _| Ok(Value::Number(seconds(receiver))),
+        );
+
+        self.builtin(
+            &["object"],
+            "keys",
+            0,
+            "Return the keys as an array",
+            |receiver, _| {
+                let keys = fields(receiver).keys().cloned().map(Value::String);
+                Ok(Value::DynamicArray(keys.collect()))
+            },
+        );
+        self.builtin(
+            &["object"],
+            "set",
+            2,
+            "Return a copy with the key set to the value",
+            |receiver, arguments| {
+                // Object set method: obj.set(key, value)
+                let Value::String(key) = &arguments[0] else {
+                    return Err(
+                        "set() requires (key, value) arguments where key is a string".to_string(),
+                    );
+                };
+                let mut fields = fields(receiver).clone();
+                fields.insert(key.clone(), arguments[1].clone());
+                Ok(Value::Object(fields))
+            },
+        );
+        self.builtin(
+            &["object"],
+            "get",
+            1,
+            "Return the value of a key, or nil if there is none",
+            |receiver, arguments| {
+                let Value::String(key) = &arguments[0] else {
+                    return Err("get() requires a string key argument".to_string());
+                };
+                Ok(fields(receiver).get(key).cloned().unwrap_or(Value::Nil)) // nil if no such key
+            },
+        );
+        self.builtin(
+            &["object"],
+            "has",
+            1,
+            "Check whether the object has a key",
+            |receiver, arguments| {
+                let Value::String(key) = &arguments[0] else {
+                    return Err("has() requires a string key argument".to_string());
+                };
+                Ok(Value::Boolean(fields(receiver).contains_key(key)))
+            },
+        );
+    }
+
+    // Register a built-in method on each of the given types
+    fn builtin(
+        &mut self,
+        types: &[&str],
+        name: &str,
+        arity: usize,
+        description: &'static str,
+        function: fn(&Value, &[Value]) -> Result<Value, String>,
+    ) {
+        for type_name in types {
+            self.register(type_name, name, arity, function)
+                .expect("built-in methods use known type names");
+            if let Some(method) = self
+                .methods
+                .get_mut(name)
+                .and_then(|m| m.get_mut(*type_name))
+            {
+                method.updates = UPDATING_METHODS.contains(&name);
+                method.description = description;
+            }
+        }
+    }
+}
+
+// Implement Default for MethodRegistry: the built-in methods
+impl Default for MethodRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Get the name (from EXTENSION_TYPES) a program defines methods on a value's type under, if it
+// can; both kinds of array share the Array methods
+pub fn extension_type(value: &Value) -> Option<&'static str> {
+    match value {
+        Value::String(_) => Some("String"),
+        Value::Number(_) => Some("Number"),
+        Value::Boolean(_) => Some("Boolean"),
+        Value::FixedArray(_) | Value::DynamicArray(_) => Some("Array"),
+        Value::Object(_) => Some("Object"),
+        Value::Date(_) => Some("Date"),
+        Value::Duration(_) => Some("Duration"),
+        Value::Frozen(inner) => extension_type(inner),
+        Value::Nil | Value::Function(..) | Value::Iterator(_) | Value::Task(_) => None,
+    }
+}
+
+// Get the name of a type in the plural, for messages ("dynamic arrays")
+fn plural(type_name: &str) -> String {
+    match type_name {
+        "nil" => "nil".to_string(),
+        _ => format!("{type_name}s"),
+    }
+}
+
+// Get the text of a string receiver (the registry only calls string methods on strings)
+fn text(receiver: &Value) -> &str {
+    match receiver {
+        Value::String(text) => text,
+        _ => unreachable!("string methods are only called on strings"),
+    }
+}
+
+// Get the elements of an array receiver
+fn items(receiver: &Value) -> &[Value] {
+    match receiver {
+        Value::FixedArray(items) | Value::DynamicArray(items) => items,
+        _ => unreachable!("array methods are only called on arrays"),
+    }
+}
+
+// Get the fields of an object receiver
+fn fields(receiver: &Value) -> &HashMap<String, Value> {
+    match receiver {
+        Value::Object(fields) => fields,
+        _ => unreachable!("object methods are only called on objects"),
+    }
+}
+
+// Get the date of a date receiver
+fn date(receiver: &Value) -> &chrono::DateTime<chrono::Local> {
+    match receiver {
+        Value::Date(date) => date,
+        _ => unreachable!("date methods are only called on dates"),
+    }
+}
+
+// Get the length of a duration receiver, in seconds
+fn seconds(receiver: &Value) -> f64 {
+    match receiver {
+        Value::Duration(seconds) => *seconds,
+        _ => unreachable!("duration methods are only called on durations"),
+    }
+}
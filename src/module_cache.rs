@@ -0,0 +1,111 @@
+// The parse cache for imported modules (.pidgin-cache)
+//
+// Each module a program imports has its AST saved as JSON in a .pidgin-cache directory beside
+// it, named after the module file and a hash of its source (and of the pidgin version and the
+// AST format version, as the AST changes shape between them). Importing the module again while its source is
+// unchanged reads the AST back instead of lexing and parsing it. The cache is only a shortcut:
+// when anything goes wrong with it (a read-only directory, a damaged entry) the module is
+// parsed as usual. Setting PIDGIN_NO_CACHE turns it off.
+use crate::ast::{Program, FORMAT_VERSION};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+const CACHE_DIR: &str = ".pidgin-cache"; // Made in the directory of each imported module
+const HASH_LENGTH: usize = 16; // Hex digits of the source hash in an entry's file name
+
+// Get the AST cached for a module whose source is `source`, if there is one
+pub fn load(module_file: &str, source: &str) -> Option<Program> {
+    let (path, _) = entry_path(module_file, source)?;
+    let json = fs::read_to_string(path).ok()?;
+    serde_json::from_str(&json).ok()
+}
+
+// Save a module's AST for the next time it is imported with the same source, replacing the
+// entries saved for its earlier sources
+pub fn store(module_file: &str, source: &str, program: &Program) {
+    let Some((path, name)) = entry_path(module_file, source) else {
+        return;
+    };
+    let Some(dir) = path.parent() else {
+        return;
+    };
+    let Ok(json) = serde_json::to_string(program) else {
+        return;
+    };
+    if !dir.is_dir() {
+        if fs::create_dir_all(dir).is_err() {
+            return;
+        }
+        // Keep the cache out of version control, without needing an entry in .gitignore
+        let _ = fs::write(dir.join(".gitignore"), "*\n");
+    }
+    remove_stale(dir, &name, &path);
+
+    // Write under a name of its own first, so a program importing the module at the same
+    // time never reads half an entry
+    static WRITES: AtomicUsize = AtomicUsize::new(0);
+    let temp = path.with_extension(format!(
+        "{}-{}.tmp",
+        process::id(),
+        WRITES.fetch_add(1, Ordering::Relaxed)
+    ));
+    if fs::write(&temp, json).is_err() || fs::rename(&temp, &path).is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+}
+
+// Get the path of a module's cache entry and the module's file name, or None if caching is
+// off (or the path has no file name)
+fn entry_path(module_file: &str, source: &str) -> Option<(PathBuf, String)> {
+    if env::var_os("PIDGIN_NO_CACHE").is_some() {
+        return None;
+    }
+    let path = Path::new(module_file);
+    let name = path.file_name()?.to_str()?.to_string();
+
+    let mut hasher = Sha256::new();
+    hasher.update(env!("CARGO_PKG_VERSION"));
+    hasher.update(FORMAT_VERSION.to_le_bytes());
+    hasher.update([0]); // Keeps the versions apart from the source
+    hasher.update(source);
+    let hash: String = hasher
+        .finalize()
+        .iter()
+        .take(HASH_LENGTH / 2)
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+
+    let entry = path
+        .with_file_name(CACHE_DIR)
+        .join(format!("{name}-{hash}.json"));
+    Some((entry, name))
+}
+
+// Remove the entries cached for a module's other sources, which cannot be used again
+fn remove_stale(dir: &Path, name: &str, current: &Path) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let file_name = entry.file_name();
+        let Some(hash) = file_name
+            .to_str()
+            .and_then(|file_name| file_name.strip_prefix(name))
+            .and_then(|rest| rest.strip_prefix('-'))
+            .and_then(|rest| rest.strip_suffix(".json"))
+        else {
+            continue;
+        };
+        // Only names of the form <name>-<hash>.json, not those of modules whose names start
+        // with this one's
+        let is_entry =
+            hash.len() == HASH_LENGTH && hash.bytes().all(|byte| byte.is_ascii_hexdigit());
+        if is_entry && entry.path() != current {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+}
@@ -0,0 +1,144 @@
+// The top-level definitions of a file, for a quick outline (pidgin <file.pg> --symbols)
+//
+// Only the statements at the top of the file are looked at: the functions, methods, and
+// variables they define, and the modules they import. A name is exported when another file can
+// import it, which is when it starts with an uppercase letter; the methods a file defines come
+// along with anything imported from it, so they are always exported.
+use crate::ast::{Program, Span, Stmt};
+use std::fmt::Write;
+
+// Define the SymbolKind enum, what a top-level statement defines
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Function, // function name(...) { ... }
+    Method,   // function Type.name(...) { ... }
+    Variable, // let name = ...; (or one of the names of let a, b = ...;)
+    Import,   // GET Name from module;
+}
+
+// Implement methods for SymbolKind
+impl SymbolKind {
+    // Get the name of the kind, as shown in the outline and its JSON
+    pub fn name(self) -> &'static str {
+        match self {
+            SymbolKind::Function => "function",
+            SymbolKind::Method => "method",
+            SymbolKind::Variable => "variable",
+            SymbolKind::Import => "import",
+        }
+    }
+}
+
+// Define the Symbol struct, one entry of the outline
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub kind: SymbolKind,
+    pub name: String,            // The name defined (or imported)
+    pub parameters: Vec<String>, // A function's or method's parameters
+    pub module: Option<String>,  // The module an import reads
+    pub exported: bool,          // Whether other files can import it
+    pub span: Span,              // Where its statement starts
+}
+
+// Implement methods for Symbol
+impl Symbol {
+    // Create a symbol, exported if its name starts with an uppercase letter
+    fn new(kind: SymbolKind, name: &str, span: Span) -> Self {
+        Symbol {
+            kind,
+            name: name.to_string(),
+            parameters: Vec::new(),
+            module: None,
+            exported: name.starts_with(char::is_uppercase),
+            span,
+        }
+    }
+}
+
+// List the top-level definitions and imports of a program, in source order
+pub fn symbols(program: &Program) -> Vec<Symbol> {
+    let mut symbols = Vec::new();
+    for statement in &program.statements {
+        match statement {
+            Stmt::FunctionDeclaration {
+                name,
+                parameters,
+                span,
+                ..
+            } => {
+                let method = name.contains('.');
+                let kind = if method {
+                    SymbolKind::Method
+                } else {
+                    SymbolKind::Function
+                };
+                symbols.push(Symbol {
+                    parameters: parameters.clone(),
+                    exported: method || name.starts_with(char::is_uppercase),
+                    ..Symbol::new(kind, name, *span)
+                });
+            }
+            Stmt::VarDeclaration { name, span, .. } => {
+                symbols.push(Symbol::new(SymbolKind::Variable, name, *span))
+            }
+            Stmt::Unpack {
+                names,
+                declares: true,
+                span,
+                ..
+            } => {
+                for name in names {
+                    symbols.push(Symbol::new(SymbolKind::Variable, name, *span));
+                }
+            }
+            Stmt::Import {
+                names,
+                module,
+                span,
+            } => {
+                for name in names {
+                    symbols.push(Symbol {
+                        module: Some(module.clone()),
+                        exported: false, // Imported names are not passed on
+                        ..Symbol::new(SymbolKind::Import, name, *span)
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    symbols
+}
+
+// Render the outline as text, one definition per line:
+//   3:1  function  Add(a, b)  (exported)
+pub fn to_text(symbols: &[Symbol]) -> String {
+    let positions: Vec<String> = symbols
+        .iter()
+        .map(|symbol| format!("{}:{}", symbol.span.line, symbol.span.column))
+        .collect();
+    let width = positions.iter().map(String::len).max().unwrap_or(0);
+
+    let mut text = String::new();
+    for (symbol, position) in symbols.iter().zip(&positions) {
+        let mut shown = symbol.name.clone();
+        match symbol.kind {
+            SymbolKind::Function | SymbolKind::Method => {
+                shown = format!("{shown}({})", symbol.parameters.join(", "))
+            }
+            SymbolKind::Import => {
+                shown = format!("{shown} from {}", symbol.module.as_deref().unwrap_or(""))
+            }
+            SymbolKind::Variable => {}
+        }
+        if symbol.exported {
+            shown.push_str("  (exported)");
+        }
+        let _ = writeln!(
+            text,
+            "{position:>width$}  {:<8}  {shown}",
+            symbol.kind.name()
+        );
+    }
+    text
+}
@@ -0,0 +1,285 @@
+// Package management: pidgin.toml manifests and the pg_modules/ directory (pidgin add, install)
+//
+// A project lists the libraries it uses in pidgin.toml:
+//
+//   [package]
+//   name = "myapp"
+//
+//   [dependencies]
+//   strings = { git = "https://github.com/user/pg-strings", rev = "v1.0" }
+//   utils = { path = "../utils" }
+//
+// `pidgin install` copies each of them into pg_modules/<name>/, along with the dependencies
+// listed in their own pidgin.toml, and imports then find them there (see find_module).
+//
+// An optional [run] section says how `pidgin run` starts the project:
+//
+//   [run]
+//   entry = "src/main.pg"
+//   module_paths = ["lib"]
+//   args = ["data.csv"]
+use crate::interpreter::PACKAGES_DIR;
+use crate::logger::info;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+pub const MANIFEST: &str = "pidgin.toml"; // Name of the manifest file in a project's root
+pub const DEFAULT_ENTRY: &str = "main.pg"; // File `pidgin run` starts when [run] names none
+
+// Define the Manifest struct, the contents of pidgin.toml
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    #[serde(default)]
+    pub package: PackageInfo, // The project itself
+    #[serde(default)]
+    pub dependencies: BTreeMap<String, Dependency>, // Libraries it uses, by package name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run: Option<RunSettings>, // How `pidgin run` starts the project
+}
+
+// Define the PackageInfo struct, the [package] section of pidgin.toml
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PackageInfo {
+    #[serde(default)]
+    pub name: String, // Package name
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub version: String, // Package version (informational)
+}
+
+// Define the RunSettings struct, the [run] section of pidgin.toml
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RunSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entry: Option<String>, // The program to run (DEFAULT_ENTRY if not given)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub module_paths: Vec<String>, // More directories to look for imported modules in
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>, // Arguments passed to the program, before those on the command line
+}
+
+// Define the Dependency struct, where to get one library
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Dependency {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git: Option<String>, // Git repository URL
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rev: Option<String>, // Branch, tag, or commit to check out (git only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>, // Local directory, relative to the manifest that names it
+}
+
+// Define the Source enum, a dependency's location once resolved, used to spot conflicts
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Source {
+    Git { url: String, rev: Option<String> }, // A repository to clone
+    Path(PathBuf),                            // A local directory to copy
+}
+
+// Implement methods for Source
+impl Source {
+    // Describe the source for progress messages and errors
+    fn describe(&self) -> String {
+        match self {
+            Source::Git {
+                url,
+                rev: Some(rev),
+            } => format!("git {url} at {rev}"),
+            Source::Git { url, rev: None } => format!("git {url}"),
+            Source::Path(path) => format!("path {}", path.display()),
+        }
+    }
+}
+
+// Read pidgin.toml from a directory
+pub fn load_manifest(dir: &Path) -> Result<Manifest, String> {
+    let path = dir.join(MANIFEST);
+    let text = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read '{}': {e}", path.display()))?;
+    toml::from_str(&text).map_err(|e| format!("Invalid '{}': {e}", path.display()))
+}
+
+// Write pidgin.toml in the current directory
+fn save_manifest(manifest: &Manifest) -> Result<(), String> {
+    let text = toml::to_string(manifest).map_err(|e| e.to_string())?;
+    fs::write(MANIFEST, text).map_err(|e| format!("Failed to write '{MANIFEST}': {e}"))
+}
+
+// Add a dependency to pidgin.toml (creating it if needed) and install it
+pub fn add(name: &str, dependency: Dependency) -> Result<(), String> {
+    let mut manifest = if Path::new(MANIFEST).exists() {
+        load_manifest(Path::new("."))?
+    } else {
+        // A new project is named after its directory
+        let dir = std::env::current_dir().map_err(|e| e.to_string())?;
+        let name = dir
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        info!("Created {MANIFEST}");
+        Manifest {
+            package: PackageInfo {
+                name,
+                version: "0.1.0".to_string(),
+            },
+            ..Manifest::default()
+        }
+    };
+
+    manifest.dependencies.insert(name.to_string(), dependency);
+    save_manifest(&manifest)?;
+    info!("Added {name} to {MANIFEST}");
+    install(&manifest)
+}
+
+// Check that a dependency is well formed (a valid name and exactly one source)
+pub fn validate(name: &str, dependency: &Dependency) -> Result<(), String> {
+    source_of(name, dependency, Path::new(".")).map(|_| ())
+}
+
+// Install every dependency of a manifest, and theirs, into pg_modules/
+pub fn install(manifest: &Manifest) -> Result<(), String> {
+    let mut installed: BTreeMap<String, Source> = BTreeMap::new();
+    let mut queue: VecDeque<(String, Dependency, PathBuf)> = manifest
+        .dependencies
+        .iter()
+        .map(|(name, dependency)| (name.clone(), dependency.clone(), PathBuf::from(".")))
+        .collect();
+
+    // Breadth first, so a project's own choice wins over what its dependencies ask for
+    while let Some((name, dependency, base)) = queue.pop_front() {
+        let source = source_of(&name, &dependency, &base)?;
+        if let Some(existing) = installed.get(&name) {
+            if *existing != source {
+                return Err(format!(
+                    "Conflicting sources for package '{name}': {} and {}",
+                    existing.describe(),
+                    source.describe()
+                ));
+            }
+            continue; // Already installed
+        }
+
+        info!("Installing {name} ({})", source.describe());
+        let dir = fetch(&name, &source)?;
+
+        // The package's own dependencies; their paths are relative to where it came from
+        if dir.join(MANIFEST).exists() {
+            let base = match &source {
+                Source::Path(path) => path.clone(),
+                Source::Git { .. } => dir.clone(),
+            };
+            for (name, dependency) in load_manifest(&dir)?.dependencies {
+                queue.push_back((name, dependency, base.clone()));
+            }
+        }
+        installed.insert(name, source);
+    }
+
+    info!(
+        "Installed {} package(s) into {PACKAGES_DIR}/",
+        installed.len()
+    );
+    Ok(())
+}
+
+// Check a dependency names exactly one source, and resolve its path
+fn source_of(name: &str, dependency: &Dependency, base: &Path) -> Result<Source, String> {
+    if name.is_empty() || name.contains(['.', '/', '\\']) {
+        return Err(format!(
+            "Invalid package name '{name}' (names cannot contain '.', '/', or '\\')"
+        ));
+    }
+    // git would read a url or rev starting with '-' as one of its options
+    for (field, value) in [("git", &dependency.git), ("rev", &dependency.rev)] {
+        if let Some(value) = value.as_deref().filter(|value| value.starts_with('-')) {
+            return Err(format!(
+                "Package '{name}': invalid '{field}' value '{value}' (it cannot start with '-')"
+            ));
+        }
+    }
+    match (&dependency.git, &dependency.path) {
+        (Some(url), None) => Ok(Source::Git {
+            url: url.clone(),
+            rev: dependency.rev.clone(),
+        }),
+        (None, Some(path)) if dependency.rev.is_none() => {
+            let path = base.join(path);
+            let path = path
+                .canonicalize()
+                .map_err(|e| format!("Package '{name}': cannot use '{}': {e}", path.display()))?;
+            Ok(Source::Path(path))
+        }
+        (None, Some(_)) => Err(format!(
+            "Package '{name}': 'rev' only applies to git packages"
+        )),
+        _ => Err(format!(
+            "Package '{name}' must have either 'git' or 'path' (but not both)"
+        )),
+    }
+}
+
+// Put a package's files in pg_modules/<name>/, replacing any previous copy
+fn fetch(name: &str, source: &Source) -> Result<PathBuf, String> {
+    let dest = Path::new(PACKAGES_DIR).join(name);
+    if dest.exists() {
+        fs::remove_dir_all(&dest)
+            .map_err(|e| format!("Failed to remove '{}': {e}", dest.display()))?;
+    }
+    fs::create_dir_all(PACKAGES_DIR)
+        .map_err(|e| format!("Failed to create '{PACKAGES_DIR}': {e}"))?;
+
+    match source {
+        Source::Git { url, rev } => {
+            let dest_str = dest.to_string_lossy().to_string();
+            let mut args = vec!["clone", "--quiet"];
+            if rev.is_none() {
+                args.extend(["--depth", "1"]); // Only the latest commit is needed
+            }
+            args.extend(["--", url.as_str(), dest_str.as_str()]); // Nothing after -- is an option
+            run_git(&args)?;
+            if let Some(rev) = rev {
+                run_git(&["-C", &dest_str, "checkout", "--quiet", rev])?;
+            }
+        }
+        Source::Path(path) => copy_dir(path, &dest)?,
+    }
+    Ok(dest)
+}
+
+// Run git, failing if it cannot be started or reports an error
+fn run_git(args: &[&str]) -> Result<(), String> {
+    let status = Command::new("git")
+        .args(args)
+        .status()
+        .map_err(|e| format!("Failed to execute git: {e}"))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("git {} failed", args.join(" ")))
+    }
+}
+
+// Copy a directory recursively, skipping version control data and installed packages
+fn copy_dir(from: &Path, to: &Path) -> Result<(), String> {
+    fs::create_dir_all(to).map_err(|e| format!("Failed to create '{}': {e}", to.display()))?;
+    let entries =
+        fs::read_dir(from).map_err(|e| format!("Failed to read '{}': {e}", from.display()))?;
+    for entry in entries {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let file_name = entry.file_name();
+        if file_name == ".git" || file_name == PACKAGES_DIR {
+            continue;
+        }
+        let (source, target) = (entry.path(), to.join(&file_name));
+        if source.is_dir() {
+            copy_dir(&source, &target)?;
+        } else {
+            fs::copy(&source, &target)
+                .map_err(|e| format!("Failed to copy '{}': {e}", source.display()))?;
+        }
+    }
+    Ok(())
+}
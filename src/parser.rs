@@ -1,20 +1,53 @@
 // Import necessary modules and types
-use crate::ast::{BinaryOp, Expr, Program, Stmt, UnaryOp};
+use crate::ast::{BinaryOp, Expr, MatchCase, Pattern, Program, Span, Stmt, UnaryOp};
+use crate::methods::EXTENSION_TYPES;
+use crate::pattern::{self, Compiled};
+use crate::symbols::Slot;
 use crate::token::{Token, TokenInfo}; // Import Token and TokenInfo from token.rs // Import AST types
-
-// Define the Parser struct, which will parse tokens into an AST
-pub struct Parser {
-    tokens: Vec<TokenInfo>, // The list of tokens to parse
-    current: usize,         // The current position in the token list
+use std::collections::HashMap;
+
+// Define the Parser struct, which will parse tokens into an AST. It borrows the tokens, and
+// hands out references to them, so none are copied while parsing.
+pub struct Parser<'a> {
+    tokens: &'a [TokenInfo],        // The list of tokens to parse
+    kept: Vec<usize>,               // Indexes in tokens of the ones the grammar sees, in order
+    current: usize,                 // The current position in kept
+    docs: HashMap<usize, String>,   // Doc comment text, keyed by the position of the token below it
+    line_ends: Vec<(usize, usize)>, // Line and column of each line break that ended a statement
 }
 
 // Implement methods for the Parser struct
-impl Parser {
-    // Create a new Parser from a vector of tokens
-    pub fn new(tokens: Vec<TokenInfo>) -> Self {
+impl<'a> Parser<'a> {
+    // Create a new Parser over a list of tokens (ending with Eof)
+    pub fn new(tokens: &'a [TokenInfo]) -> Self {
+        // Set doc comments aside so the grammar never sees them, remembering which token
+        // (skipping line breaks) each block of them sits above
+        let mut kept = Vec::with_capacity(tokens.len());
+        let mut docs = HashMap::new();
+        let mut pending: Vec<&str> = Vec::new();
+        for (index, info) in tokens.iter().enumerate() {
+            match &info.token {
+                Token::DocComment(text) => {
+                    // One optional space after '///' is not part of the text
+                    pending.push(text.strip_prefix(' ').unwrap_or(text));
+                }
+                Token::Newline => kept.push(index),
+                _ => {
+                    if !pending.is_empty() {
+                        docs.insert(kept.len(), pending.join("\n"));
+                        pending.clear();
+                    }
+                    kept.push(index);
+                }
+            }
+        }
+
         Self {
-            tokens,     // Store the tokens
+            tokens,
+            kept,
             current: 0, // Start at the first token
+            docs,
+            line_ends: Vec::new(),
         }
     }
 
@@ -33,53 +66,81 @@ impl Parser {
         Ok(Program { statements }) // Return the program AST
     }
 
+    // Parse source that is a single expression, such as "x + 1" (a ';' after it is allowed)
+    pub fn parse_expression(&mut self) -> Result<Expr, String> {
+        while self.match_token(&Token::Newline) {}
+        let expr = self.expression()?;
+        self.match_token(&Token::Semicolon);
+        while self.match_token(&Token::Newline) {}
+        if !self.is_at_end() {
+            return Err(format!(
+                "Expect end of expression. Got {:?} at line {} column {}",
+                self.peek().token,
+                self.peek().line,
+                self.peek().column
+            ));
+        }
+        Ok(expr)
+    }
+
+    // Get the line breaks that ended a statement in place of a ';' (line, column of each), so
+    // the formatter can keep those statements on lines of their own
+    pub fn line_ends(&self) -> &[(usize, usize)] {
+        &self.line_ends
+    }
+
     // Parse a statement
     fn statement(&mut self) -> Result<Stmt, String> {
+        let span = Span::new(self.peek().line, self.peek().column); // Where this statement starts
         if self.match_token(&Token::Get) {
             // Check for import statement
-            return self.import_statement(); // Parse import statement
+            return self.import_statement(span); // Parse import statement
         }
         if self.match_token(&Token::Return) {
             // Check for return statement
-            return self.return_statement(); // Parse return statement
+            return self.return_statement(span); // Parse return statement
         }
         if self.match_token(&Token::Print) {
             // Check for print statement
-            return self.print_statement("print"); // Parse print statement
+            return self.print_statement("print", span); // Parse print statement
         }
         if self.match_token(&Token::PrintLn) {
             // Check for printLn statement
-            return self.print_statement("printLn"); // Parse printLn statement
+            return self.print_statement("printLn", span); // Parse printLn statement
         }
         if self.match_token(&Token::PrintErr) {
             // Check for printErr statement
-            return self.print_statement("printErr"); // Parse printErr statement
+            return self.print_statement("printErr", span); // Parse printErr statement
         }
         if self.match_token(&Token::Let) {
             // Check for variable declaration
-            return self.var_declaration(); // Parse variable declaration
+            return self.var_declaration(span); // Parse variable declaration
         }
         if self.match_token(&Token::Function) {
             // Check for function declaration
-            return self.function_declaration(); // Parse function declaration
+            return self.function_declaration(span); // Parse function declaration
         }
         if self.match_token(&Token::If) {
             // Check for if statement
-            return self.if_statement(); // Parse if statement
+            return self.if_statement(span); // Parse if statement
         }
         if self.match_token(&Token::While) {
             // Check for while statement
-            return self.while_statement(); // Parse while statement
+            return self.while_statement(span); // Parse while statement
+        }
+        if self.match_token(&Token::For) {
+            // Check for for-in statement
+            return self.for_in_statement(span); // Parse for-in statement
         }
         if self.match_token(&Token::LeftBrace) {
             // Check for block statement
-            return self.block_statement(); // Parse block statement
+            return self.block_statement(span); // Parse block statement
         }
-        self.expression_statement() // Otherwise, parse as expression statement
+        self.expression_statement(span) // Otherwise, parse as expression statement
     }
 
     // Parse a print statement
-    fn print_statement(&mut self, print_type: &str) -> Result<Stmt, String> {
+    fn print_statement(&mut self, print_type: &str, span: Span) -> Result<Stmt, String> {
         let (format_expr, arguments) = if self.match_token(&Token::LeftParen) {
             // Parenthesized form: print("{}", name3);
             let format_expr = self.expression()?;
@@ -99,33 +160,36 @@ impl Parser {
             (format_expr, arguments)
         };
 
-        self.consume(&Token::Semicolon, "Expect ';' after value.")?;
+        self.end_statement("Expect ';' after value.")?;
         Ok(match print_type {
             "print" => Stmt::Print {
                 format: format_expr,
                 arguments,
+                span,
             },
             "printLn" => Stmt::PrintLn {
                 format: format_expr,
                 arguments,
+                span,
             },
             "printErr" => Stmt::PrintErr {
                 format: format_expr,
                 arguments,
+                span,
             },
             _ => unreachable!(),
         })
     }
 
     // Parse a return statement
-    fn return_statement(&mut self) -> Result<Stmt, String> {
-        let expr = self.expression()?; // Parse the expression to return
-        self.consume(&Token::Semicolon, "Expect ';' after return value.")?; // Expect a semicolon
-        Ok(Stmt::Return(expr)) // Return a Return statement
+    fn return_statement(&mut self, span: Span) -> Result<Stmt, String> {
+        let expr = self.expression_list()?; // Parse the value (or values) to return
+        self.end_statement("Expect ';' after return value.")?; // Expect a semicolon
+        Ok(Stmt::Return(expr, span)) // Return a Return statement
     }
 
     // Parse an import statement
-    fn import_statement(&mut self) -> Result<Stmt, String> {
+    fn import_statement(&mut self, span: Span) -> Result<Stmt, String> {
         let mut names = Vec::new();
 
         // Parse the names to import
@@ -208,13 +272,17 @@ impl Parser {
 
         let module = module_parts.join(".");
 
-        self.consume(&Token::Semicolon, "Expect ';' after import statement")?;
+        self.end_statement("Expect ';' after import statement")?;
 
-        Ok(Stmt::Import { names, module })
+        Ok(Stmt::Import {
+            names,
+            module,
+            span,
+        })
     }
 
     // Parse a variable declaration
-    fn var_declaration(&mut self) -> Result<Stmt, String> {
+    fn var_declaration(&mut self, span: Span) -> Result<Stmt, String> {
         let name_token = self.consume_identifier("Expect variable name.")?; // Expect an identifier
         let name = if let Token::Identifier(n) = &name_token.token {
             n.clone() // Get the variable name
@@ -225,18 +293,62 @@ impl Parser {
                 column = name_token.column
             )); // Error if not an identifier
         };
+        if self.check(&Token::Comma) {
+            return self.unpack(name, true, span); // let x, y = ...;
+        }
         let initializer = if self.match_token(&Token::Assign) {
             // Check for initializer
             Some(self.expression()?) // Parse the initializer expression
         } else {
             None // No initializer
         };
-        self.consume(&Token::Semicolon, "Expect ';' after variable declaration.")?; // Expect a semicolon
-        Ok(Stmt::VarDeclaration { name, initializer }) // Return a VarDeclaration statement
+        self.end_statement("Expect ';' after variable declaration.")?; // Expect a semicolon
+        Ok(Stmt::VarDeclaration {
+            name,
+            initializer,
+            span,
+            slot: Slot::default(),
+        }) // Return a VarDeclaration statement
+    }
+
+    // Parse the rest of a declaration or assignment of several variables, after the first
+    // name: let q, r = divmod(7, 2); or a, b = b, a;
+    fn unpack(&mut self, first: String, declares: bool, span: Span) -> Result<Stmt, String> {
+        let verb = if declares { "declared" } else { "assigned" };
+        let mut names = vec![first];
+        while self.match_token(&Token::Comma) {
+            let name_token = self.consume_identifier("Expect variable name after ','.")?;
+            let Token::Identifier(name) = name_token.token.clone() else {
+                unreachable!("consume_identifier returns an identifier");
+            };
+            if names.contains(&name) {
+                return Err(format!(
+                    "Variable '{name}' is {verb} twice at line {line} column {column}",
+                    line = name_token.line,
+                    column = name_token.column
+                ));
+            }
+            names.push(name);
+        }
+        self.consume(&Token::Assign, "Expect '=' after variable names.")?; // Expect '='
+        let value = self.expression_list()?; // Parse the array (or values) to unpack
+        let message = if declares {
+            "Expect ';' after variable declaration."
+        } else {
+            "Expect ';' after expression."
+        };
+        self.end_statement(message)?; // Expect a semicolon
+        Ok(Stmt::Unpack {
+            names,
+            value,
+            declares,
+            span,
+        })
     }
 
     // Parse a function declaration
-    fn function_declaration(&mut self) -> Result<Stmt, String> {
+    fn function_declaration(&mut self, span: Span) -> Result<Stmt, String> {
+        let doc = self.docs.remove(&(self.current - 1)); // Doc comments above the 'function' keyword
         let name_token = self.consume_identifier("Expect function name.")?; // Expect function name
         let name = if let Token::Identifier(n) = &name_token.token {
             n.clone() // Get the function name
@@ -248,9 +360,29 @@ impl Parser {
             )); // Error if not an identifier
         };
 
+        // A method on a built-in type: function String.shout(self) { ... }
+        let name = if self.match_token(&Token::Dot) {
+            if !EXTENSION_TYPES.contains(&name.as_str()) {
+                return Err(format!(
+                    "Cannot define methods on '{name}' (expected one of: {}) at line {line} column {column}",
+                    EXTENSION_TYPES.join(", "),
+                    line = name_token.line,
+                    column = name_token.column
+                ));
+            }
+            let method_token = self.consume_identifier("Expect method name after '.'.")?;
+            match &method_token.token {
+                Token::Identifier(method) => format!("{name}.{method}"),
+                _ => return Err("Expect method name after '.'.".to_string()),
+            }
+        } else {
+            name
+        };
+
         self.consume(&Token::LeftParen, "Expect '(' after function name.")?; // Expect '('
 
         let mut parameters = Vec::new(); // Store parameter names
+        let mut parameter_spans = Vec::new(); // Store where each parameter is named
 
         // Parse parameters
         if !self.check(&Token::RightParen) {
@@ -258,6 +390,7 @@ impl Parser {
                 let param_token = self.consume_identifier("Expect parameter name.")?; // Expect parameter
                 if let Token::Identifier(param_name) = &param_token.token {
                     parameters.push(param_name.clone()); // Add parameter to list
+                    parameter_spans.push(Span::new(param_token.line, param_token.column));
                 } else {
                     return Err(format!(
                         "Invalid parameter name. at line {line} column {column}",
@@ -275,7 +408,16 @@ impl Parser {
 
         self.consume(&Token::RightParen, "Expect ')' after parameters.")?; // Expect ')'
 
-        self.consume(&Token::LeftBrace, "Expect '{' before function body.")?; // Expect '{'
+        // A method's first parameter is the value it is called on
+        if name.contains('.') && parameters.is_empty() {
+            return Err(format!(
+                "Method '{name}' needs a first parameter for the value it is called on (e.g. self) at line {line} column {column}",
+                line = name_token.line,
+                column = name_token.column
+            ));
+        }
+
+        let brace = self.consume(&Token::LeftBrace, "Expect '{' before function body.")?; // Expect '{'
 
         let mut body_statements = Vec::new(); // Store statements in the function body
         while !self.check(&Token::RightBrace) && !self.is_at_end() {
@@ -289,17 +431,23 @@ impl Parser {
         }
         self.consume(&Token::RightBrace, "Expect '}' after function body.")?; // Expect '}'
 
-        let body = Box::new(Stmt::Block(body_statements)); // Create block statement
+        let body = Box::new(Stmt::Block(
+            body_statements,
+            Span::new(brace.line, brace.column),
+        )); // Create block statement
 
         Ok(Stmt::FunctionDeclaration {
             name,
             parameters,
+            parameter_spans,
             body,
+            doc,
+            span,
         }) // Return function declaration
     }
 
     // Parse an if statement
-    fn if_statement(&mut self) -> Result<Stmt, String> {
+    fn if_statement(&mut self, span: Span) -> Result<Stmt, String> {
         self.consume(&Token::LeftParen, "Expect '(' after 'if'.")?; // Expect '('
         let condition = self.expression()?; // Parse the condition expression
         self.consume(&Token::RightParen, "Expect ')' after if condition.")?; // Expect ')'
@@ -314,20 +462,45 @@ impl Parser {
             condition,
             then_branch,
             else_branch,
+            span,
         }) // Return an If statement
     }
 
     // Parse a while statement
-    fn while_statement(&mut self) -> Result<Stmt, String> {
+    fn while_statement(&mut self, span: Span) -> Result<Stmt, String> {
         self.consume(&Token::LeftParen, "Expect '(' after 'while'.")?; // Expect '('
         let condition = self.expression()?; // Parse the condition expression
         self.consume(&Token::RightParen, "Expect ')' after condition.")?; // Expect ')'
         let body = Box::new(self.statement()?); // Parse the loop body
-        Ok(Stmt::While { condition, body }) // Return a While statement
+        Ok(Stmt::While {
+            condition,
+            body,
+            span,
+        }) // Return a While statement
+    }
+
+    // Parse a for-in statement: for (item in items) body
+    fn for_in_statement(&mut self, span: Span) -> Result<Stmt, String> {
+        self.consume(&Token::LeftParen, "Expect '(' after 'for'.")?; // Expect '('
+        let variable_token = self.consume_identifier("Expect loop variable name.")?;
+        let Token::Identifier(variable) = variable_token.token.clone() else {
+            unreachable!("consume_identifier returns an identifier");
+        };
+        self.consume(&Token::In, "Expect 'in' after loop variable.")?; // Expect 'in'
+        let iterable = self.expression()?; // Parse what to traverse
+        self.consume(&Token::RightParen, "Expect ')' after for-in clause.")?; // Expect ')'
+        let body = Box::new(self.statement()?); // Parse the loop body
+        Ok(Stmt::ForIn {
+            variable,
+            iterable,
+            body,
+            span,
+            slot: Slot::default(),
+        }) // Return a ForIn statement
     }
 
     // Parse a block statement (a sequence of statements in braces)
-    fn block_statement(&mut self) -> Result<Stmt, String> {
+    fn block_statement(&mut self, span: Span) -> Result<Stmt, String> {
         let mut statements = Vec::new(); // Store statements in the block
         while !self.check(&Token::RightBrace) && !self.is_at_end() {
             // Loop until '}' or end
@@ -339,14 +512,50 @@ impl Parser {
             statements.push(self.statement()?); // Parse and add each statement
         }
         self.consume(&Token::RightBrace, "Expect '}' after block.")?; // Expect '}'
-        Ok(Stmt::Block(statements)) // Return a Block statement
+        Ok(Stmt::Block(statements, span)) // Return a Block statement
     }
 
     // Parse an expression statement
-    fn expression_statement(&mut self) -> Result<Stmt, String> {
+    fn expression_statement(&mut self, span: Span) -> Result<Stmt, String> {
         let expr = self.expression()?; // Parse the expression
-        self.consume(&Token::Semicolon, "Expect ';' after expression.")?; // Expect a semicolon
-        Ok(Stmt::Expression(expr)) // Return an Expression statement
+        if let (Expr::Identifier(first, _, _), true) = (&expr, self.check(&Token::Comma)) {
+            // Several targets: a, b = b, a; (every value is computed before any is assigned)
+            return self.unpack(first.clone(), false, span);
+        }
+        self.end_statement("Expect ';' after expression.")?; // Expect a semicolon
+        Ok(Stmt::Expression(expr, span)) // Return an Expression statement
+    }
+
+    // Expect the end of a statement: a ';', or else a line break, a '}' or the end of the file
+    // right after the complete statement. The line break or '}' is left for the statement list
+    // around it, so `let x = 1` on a line of its own needs no semicolon.
+    fn end_statement(&mut self, message: &str) -> Result<(), String> {
+        if self.match_token(&Token::Semicolon) {
+            return Ok(());
+        }
+        if self.check(&Token::Newline) {
+            let info = self.peek();
+            self.line_ends.push((info.line, info.column));
+            return Ok(());
+        }
+        if self.check(&Token::RightBrace) || self.is_at_end() {
+            return Ok(());
+        }
+        self.consume(&Token::Semicolon, message).map(|_| ()) // Report what was expected
+    }
+
+    // Parse one expression, or several separated by commas, which make a fixed array of their
+    // values (return a, b;)
+    fn expression_list(&mut self) -> Result<Expr, String> {
+        let first = self.expression()?;
+        if !self.check(&Token::Comma) {
+            return Ok(first);
+        }
+        let mut elements = vec![first];
+        while self.match_token(&Token::Comma) {
+            elements.push(self.expression()?);
+        }
+        Ok(Expr::FixedArray(elements))
     }
 
     // Parse an expression
@@ -361,10 +570,12 @@ impl Parser {
             // Check for assignment
             let _equals = self.previous(); // Get the '=' token
             let value = self.assignment()?; // Parse the right-hand side
-            if let Expr::Identifier(name) = expr {
+            if let Expr::Identifier(name, span, _) = expr {
                 return Ok(Expr::Assignment {
                     name,
                     value: Box::new(value),
+                    span,
+                    slot: Slot::default(),
                 }); // Return Assignment expression
             }
             return Err(format!(
@@ -392,8 +603,7 @@ impl Parser {
                 left: Box::new(expr),
                 operator,
                 right: Box::new(right),
-                line: previous_token.line,
-                column: previous_token.column,
+                span: Span::new(previous_token.line, previous_token.column),
             };
         }
         Ok(expr) // Return the parsed expression
@@ -420,8 +630,7 @@ impl Parser {
                 left: Box::new(expr),
                 operator,
                 right: Box::new(right),
-                line: previous_token.line,
-                column: previous_token.column,
+                span: Span::new(previous_token.line, previous_token.column),
             };
         }
         Ok(expr) // Return the parsed expression
@@ -442,8 +651,7 @@ impl Parser {
                 left: Box::new(expr),
                 operator,
                 right: Box::new(right),
-                line: previous_token.line,
-                column: previous_token.column,
+                span: Span::new(previous_token.line, previous_token.column),
             };
         }
         Ok(expr) // Return the parsed expression
@@ -464,8 +672,7 @@ impl Parser {
                 left: Box::new(expr),
                 operator,
                 right: Box::new(right),
-                line: previous_token.line,
-                column: previous_token.column,
+                span: Span::new(previous_token.line, previous_token.column),
             };
         }
         Ok(expr) // Return the parsed expression
@@ -474,11 +681,13 @@ impl Parser {
     // Parse a unary expression (-)
     fn unary(&mut self) -> Result<Expr, String> {
         if self.match_token(&Token::Minus) {
+            let minus = self.previous();
             let operator = UnaryOp::Minus; // Only minus is supported
             let operand = self.unary()?; // Parse the operand
             return Ok(Expr::Unary {
                 operator,
                 operand: Box::new(operand),
+                span: Span::new(minus.line, minus.column),
             });
         }
         self.primary() // Otherwise, parse as primary expression
@@ -492,7 +701,11 @@ impl Parser {
             Token::String(s) => Ok(Expr::String(s.clone())), // String literal
             Token::True => Ok(Expr::Boolean(true)),   // true literal
             Token::False => Ok(Expr::Boolean(false)), // false literal
-            Token::Identifier(name) => Ok(Expr::Identifier(name.clone())), // Identifier
+            Token::Identifier(name) => Ok(Expr::Identifier(
+                name.clone(),
+                Span::new(token.line, token.column),
+                Slot::default(),
+            )), // Identifier
             Token::LeftParen => {
                 let expr = self.expression()?; // Parse the inner expression
                 self.consume(&Token::RightParen, "Expect ')' after expression.")?; // Expect ')'
@@ -512,6 +725,7 @@ impl Parser {
                 self.consume(&Token::RightBracket, "Expect ']' after array elements.")?;
                 Ok(Expr::FixedArray(elements))
             }
+            Token::Match => self.match_expression(Span::new(token.line, token.column)),
             Token::LeftBrace => {
                 // Parse dynamic array: {a, b, c}
                 let mut elements = Vec::new();
@@ -542,7 +756,7 @@ impl Parser {
             let mut arguments = Vec::new();
             if !self.check(&Token::RightParen) {
                 // Check if this is an Object() call to support => syntax
-                if let Expr::Identifier(name) = &expr {
+                if let Expr::Identifier(name, _, _) = &expr {
                     if name == "Object" {
                         arguments = self.parse_object_arguments()?;
                     } else {
@@ -556,8 +770,13 @@ impl Parser {
             self.consume(&Token::RightParen, "Expect ')' after arguments.")?;
 
             // Convert identifier to function call
-            if let Expr::Identifier(name) = expr {
-                expr = Expr::FunctionCall { name, arguments };
+            if let Expr::Identifier(name, span, _) = expr {
+                expr = Expr::FunctionCall {
+                    name,
+                    arguments,
+                    span,
+                    slot: Slot::default(),
+                };
             } else {
                 return Err(format!("Only identifiers can be called as functions. Got {expr:?} at line {line} column {column}", expr = expr, line = token.line, column = token.column));
             }
@@ -566,6 +785,7 @@ impl Parser {
         // Check for method calls
         while self.check(&Token::Dot) {
             self.advance(); // consume '.'
+            let method_span = Span::new(self.peek().line, self.peek().column);
             let method_name = match &self.peek().token {
                 Token::Identifier(name) => name.clone(),
                 Token::Get => "get".to_string(), // Handle 'get' as method name
@@ -579,10 +799,9 @@ impl Parser {
             };
             self.advance(); // consume method name
 
-            // Parse method arguments based on method type
-            let argument = if method_name == "replaceChar" {
-                // Special case for replaceChar with backtick syntax
-                self.consume(&Token::Backtick, "Expect '`' after 'replaceChar'")?;
+            // Arguments are a list in parentheses, or a transform in backticks for replaceChar
+            let arguments = if self.check(&Token::Backtick) {
+                let backtick = self.advance();
                 let from = self.parse_transform("from")?;
 
                 self.consume(&Token::Arrow, "Expect '->' in transform")?;
@@ -591,86 +810,176 @@ impl Parser {
 
                 self.consume(&Token::Backtick, "Expect '`' to close transform")?;
 
-                Expr::Transform { from, to }
-            } else if method_name == "push" {
-                // push method requires an argument
-                self.consume(&Token::LeftParen, "Expect '(' after 'push'")?;
-                let arg = self.expression()?;
-                self.consume(&Token::RightParen, "Expect ')' after push argument")?;
-                arg
-            } else if method_name == "pop"
-                || method_name == "length"
-                || method_name == "clear"
-                || method_name == "reverse"
-                || method_name == "toUpper"
-                || method_name == "toLower"
-                || method_name == "trim"
-                || method_name == "getYear"
-                || method_name == "getMonth"
-                || method_name == "getDay"
-                || method_name == "keys"
-            {
-                // These methods don't take arguments
-                self.consume(&Token::LeftParen, "Expect '(' after method name")?;
-                self.consume(&Token::RightParen, "Expect ')' after method name")?;
-                Expr::Nil // Use Nil as placeholder for no argument
-            } else if method_name == "insert" || method_name == "set" {
-                // These methods take two arguments: (arg1, arg2)
+                vec![Expr::Transform {
+                    from,
+                    to,
+                    span: Span::new(backtick.line, backtick.column),
+                }]
+            } else {
                 self.consume(&Token::LeftParen, "Expect '(' after method name")?;
-                let arg1 = self.expression()?;
-                self.consume(&Token::Comma, "Expect ',' between arguments")?;
-                let arg2 = self.expression()?;
+                let arguments = if self.check(&Token::RightParen) {
+                    Vec::new()
+                } else {
+                    self.parse_regular_arguments()?
+                };
                 self.consume(&Token::RightParen, "Expect ')' after arguments")?;
-                Expr::Binary {
-                    left: Box::new(arg1),
-                    operator: crate::ast::BinaryOp::Add, // Use Add as placeholder, will be ignored
-                    right: Box::new(arg2),
-                    line: 0,
-                    column: 0,
-                }
-            } else if method_name == "remove" || method_name == "get" || method_name == "has" {
-                // These methods take one argument
-                self.consume(&Token::LeftParen, "Expect '(' after method name")?;
-                let arg = self.expression()?;
-                self.consume(&Token::RightParen, "Expect ')' after argument")?;
-                arg
-            } else if method_name == "format" {
-                // format method takes one argument
-                self.consume(&Token::LeftParen, "Expect '(' after method name")?;
-                let arg = self.expression()?;
-                self.consume(&Token::RightParen, "Expect ')' after argument")?;
-                arg
-            } else {
-                return Err(format!(
-                    "Unsupported method: {method_name} at line {line} column {column}",
-                    method_name = method_name,
-                    line = token.line,
-                    column = token.column
-                ));
+                arguments
             };
 
             expr = Expr::MethodCall {
                 object: Box::new(expr),
                 method: method_name,
-                argument: Box::new(argument),
+                arguments,
+                span: method_span,
             };
         }
 
         // Check for array indexing
         while self.check(&Token::LeftBracket) {
-            self.advance(); // consume '['
+            let bracket = self.advance(); // consume '['
             let index = self.expression()?; // Parse the index expression
             self.consume(&Token::RightBracket, "Expect ']' after array index.")?;
 
             expr = Expr::Index {
                 array: Box::new(expr),
                 index: Box::new(index),
+                span: Span::new(bracket.line, bracket.column),
             };
         }
 
         Ok(expr)
     }
 
+    // Parse a match expression: match (value) { case pattern if guard: result, ... }
+    fn match_expression(&mut self, span: Span) -> Result<Expr, String> {
+        self.consume(&Token::LeftParen, "Expect '(' after 'match'.")?; // Expect '('
+        let subject = self.expression()?; // Parse the value to match
+        self.consume(&Token::RightParen, "Expect ')' after match value.")?; // Expect ')'
+        self.consume(&Token::LeftBrace, "Expect '{' before match cases.")?; // Expect '{'
+
+        let mut cases = Vec::new();
+        loop {
+            // Cases are separated by commas, line breaks, or both
+            while self.match_token(&Token::Newline) || self.match_token(&Token::Comma) {}
+            if self.check(&Token::RightBrace) || self.is_at_end() {
+                break;
+            }
+            self.consume(&Token::Case, "Expect 'case' in match.")?; // Expect 'case'
+            let pattern = self.pattern()?; // Parse the pattern
+            let names = pattern::bound_names(&pattern);
+            if let Some((_, (name, span))) = names
+                .iter()
+                .enumerate()
+                .find(|(i, (name, _))| names[..*i].iter().any(|(earlier, _)| earlier == name))
+            {
+                return Err(format!(
+                    "Pattern binds '{name}' more than once at line {line} column {column}",
+                    line = span.line,
+                    column = span.column
+                ));
+            }
+            let guard = if self.match_token(&Token::If) {
+                Some(self.expression()?) // Parse the guard
+            } else {
+                None
+            };
+            self.consume(&Token::Colon, "Expect ':' after case pattern.")?; // Expect ':'
+            let body = self.expression()?; // Parse the result
+            cases.push(MatchCase {
+                pattern,
+                guard,
+                body,
+                compiled: Compiled::default(),
+            });
+        }
+        self.consume(&Token::RightBrace, "Expect '}' after match cases.")?; // Expect '}'
+        if cases.is_empty() {
+            return Err(format!(
+                "Match needs at least one case at line {line} column {column}",
+                line = span.line,
+                column = span.column
+            ));
+        }
+        Ok(Expr::Match {
+            subject: Box::new(subject),
+            cases,
+            span,
+        })
+    }
+
+    // Parse a pattern of a match case
+    fn pattern(&mut self) -> Result<Pattern, String> {
+        let token = self.advance(); // Get the next token
+        match &token.token {
+            Token::Number(n) => Ok(Pattern::Number(*n)),
+            Token::Minus => match self.advance().token {
+                Token::Number(n) => Ok(Pattern::Number(-n)), // Negative number
+                _ => Err(format!(
+                    "Expect number after '-' in pattern at line {line} column {column}",
+                    line = token.line,
+                    column = token.column
+                )),
+            },
+            Token::String(s) => Ok(Pattern::String(s.clone())),
+            Token::True => Ok(Pattern::Boolean(true)),
+            Token::False => Ok(Pattern::Boolean(false)),
+            Token::Identifier(name) if name == "_" => Ok(Pattern::Wildcard),
+            Token::Identifier(name) if name == "Object" && self.check(&Token::LeftParen) => {
+                // Object pattern: Object("key" => pattern, ...)
+                self.advance(); // consume '('
+                let mut fields = Vec::new();
+                if !self.check(&Token::RightParen) {
+                    loop {
+                        let key = match &self.advance().token {
+                            Token::String(key) => key.clone(),
+                            _ => {
+                                return Err(format!(
+                                    "Expect string key in object pattern at line {line} column {column}",
+                                    line = self.previous().line,
+                                    column = self.previous().column
+                                ))
+                            }
+                        };
+                        self.consume(
+                            &Token::AssignRight,
+                            "Expect '=>' after key in object pattern",
+                        )?;
+                        fields.push((key, self.pattern()?));
+                        if !self.match_token(&Token::Comma) {
+                            break;
+                        }
+                    }
+                }
+                self.consume(&Token::RightParen, "Expect ')' after object pattern.")?;
+                Ok(Pattern::Object(fields))
+            }
+            Token::Identifier(name) => Ok(Pattern::Binding(
+                name.clone(),
+                Span::new(token.line, token.column),
+            )),
+            Token::LeftBracket => {
+                // Array pattern: [a, b, c]
+                let mut elements = Vec::new();
+                if !self.check(&Token::RightBracket) {
+                    loop {
+                        elements.push(self.pattern()?);
+                        if !self.match_token(&Token::Comma) {
+                            break;
+                        }
+                    }
+                }
+                self.consume(&Token::RightBracket, "Expect ']' after array pattern.")?;
+                Ok(Pattern::Array(elements))
+            }
+            _ => Err(format!(
+                "Expect pattern. Got {token:?} at line {line} column {column}",
+                token = token.token,
+                line = token.line,
+                column = token.column
+            )),
+        }
+    }
+
     fn parse_transform(&mut self, transform_type: &str) -> Result<String, String> {
         if self.match_token(&Token::LeftBrace) {
             // Handle {var} syntax
@@ -723,11 +1032,11 @@ impl Parser {
     }
 
     // Advance and return the current token
-    fn advance(&mut self) -> TokenInfo {
+    fn advance(&mut self) -> &'a TokenInfo {
         if !self.is_at_end() {
             self.current += 1; // Move to next token
         }
-        self.tokens[self.current - 1].clone() // Return the previous token
+        self.previous() // Return the token advanced past
     }
 
     // Check if we've reached the end of the token list
@@ -736,17 +1045,17 @@ impl Parser {
     }
 
     // Peek at the current token without advancing
-    fn peek(&self) -> &TokenInfo {
-        &self.tokens[self.current] // Return current token
+    fn peek(&self) -> &'a TokenInfo {
+        &self.tokens[self.kept[self.current]] // Return current token
     }
 
     // Get the previous token
-    fn previous(&self) -> TokenInfo {
-        self.tokens[self.current - 1].clone() // Return previous token
+    fn previous(&self) -> &'a TokenInfo {
+        &self.tokens[self.kept[self.current - 1]] // Return previous token
     }
 
     // Consume the current token if it matches the expected type, or return an error
-    fn consume(&mut self, token_type: &Token, message: &str) -> Result<TokenInfo, String> {
+    fn consume(&mut self, token_type: &Token, message: &str) -> Result<&'a TokenInfo, String> {
         if self.check(token_type) {
             Ok(self.advance()) // Return the token
         } else {
@@ -759,7 +1068,7 @@ impl Parser {
     }
 
     // Consume and return an identifier token, or return an error
-    fn consume_identifier(&mut self, message: &str) -> Result<TokenInfo, String> {
+    fn consume_identifier(&mut self, message: &str) -> Result<&'a TokenInfo, String> {
         let token = self.advance(); // Get the next token
         match &token.token {
             Token::Identifier(_) => Ok(token), // Return if it's an identifier
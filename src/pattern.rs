@@ -0,0 +1,158 @@
+// Patterns of match expressions, compiled into decision logic
+//
+// A case's pattern becomes a list of tests on parts of the value being matched, tried in order
+// until one fails, and the names to assign once they all pass. A part is reached by a path of
+// steps from the value (element 0, then field "name"), and a part's own test always comes
+// after the test of the array or object holding it, so every step a later test takes is known
+// to exist. The interpreter runs these tests, and the C and JavaScript backends generate code
+// for the same ones, so a value chooses the same case everywhere.
+use crate::ast::{Pattern, Span};
+use crate::interpreter::Value;
+use std::fmt;
+use std::sync::OnceLock;
+
+// Define the Step enum, one step from a value to a part inside it
+#[derive(Debug, Clone, PartialEq)]
+pub enum Step {
+    Index(usize),  // Element of an array
+    Field(String), // Field of an object
+}
+
+// Define the Test enum, one check the part of a value at a path must pass
+#[derive(Debug, Clone, PartialEq)]
+pub enum Test {
+    Array(Vec<Step>, usize), // An array (fixed or dynamic) with exactly this many elements
+    Object(Vec<Step>, Vec<String>), // An object with at least these keys
+    Number(Vec<Step>, f64),  // A number equal to this one
+    String(Vec<Step>, String), // A string equal to this one
+    Boolean(Vec<Step>, bool), // This boolean
+}
+
+// Define the Decision struct, a pattern compiled into what a value must pass and what it binds
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Decision {
+    pub tests: Vec<Test>,                   // Checks, in the order they are made
+    pub bindings: Vec<(String, Vec<Step>)>, // Names assigned the part at a path once all pass
+}
+
+// Define the Compiled struct, where a match case keeps the decision its pattern compiles to. It
+// is filled in the first time the case is tried, and is not part of the case's JSON (or .pgc)
+// form.
+#[derive(Clone, Default)]
+pub struct Compiled(OnceLock<Decision>);
+
+// Implement methods for Compiled
+impl Compiled {
+    // Get the decision of the case's pattern (which must always be the same pattern)
+    pub fn decision(&self, pattern: &Pattern) -> &Decision {
+        self.0.get_or_init(|| compile(pattern))
+    }
+}
+
+// Implement Debug for Compiled (the decision follows from the pattern, so AST dumps leave it out)
+impl fmt::Debug for Compiled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Compiled")
+    }
+}
+
+// Compile a pattern into the tests a value must pass and the names it binds
+pub fn compile(pattern: &Pattern) -> Decision {
+    let mut decision = Decision::default();
+    add(&mut decision, pattern, Vec::new());
+    decision
+}
+
+// Add the tests and bindings of a pattern that applies to the part at a path
+fn add(decision: &mut Decision, pattern: &Pattern, path: Vec<Step>) {
+    match pattern {
+        Pattern::Wildcard => {}
+        Pattern::Binding(name, _) => decision.bindings.push((name.clone(), path)),
+        Pattern::Number(n) => decision.tests.push(Test::Number(path, *n)),
+        Pattern::String(s) => decision.tests.push(Test::String(path, s.clone())),
+        Pattern::Boolean(b) => decision.tests.push(Test::Boolean(path, *b)),
+        Pattern::Array(elements) => {
+            decision
+                .tests
+                .push(Test::Array(path.clone(), elements.len()));
+            for (index, element) in elements.iter().enumerate() {
+                add(decision, element, child(&path, Step::Index(index)));
+            }
+        }
+        Pattern::Object(fields) => {
+            let keys = fields.iter().map(|(key, _)| key.clone()).collect();
+            decision.tests.push(Test::Object(path.clone(), keys));
+            for (key, field) in fields {
+                add(decision, field, child(&path, Step::Field(key.clone())));
+            }
+        }
+    }
+}
+
+// Extend a path by one step
+fn child(path: &[Step], step: Step) -> Vec<Step> {
+    let mut path = path.to_vec();
+    path.push(step);
+    path
+}
+
+// Get the names a pattern binds, in order (a name may appear more than once)
+pub fn bound_names(pattern: &Pattern) -> Vec<(&str, Span)> {
+    match pattern {
+        Pattern::Binding(name, span) => vec![(name, *span)],
+        Pattern::Array(elements) => elements.iter().flat_map(bound_names).collect(),
+        Pattern::Object(fields) => fields
+            .iter()
+            .flat_map(|(_, field)| bound_names(field))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+// Implement methods for Decision
+impl Decision {
+    // Check whether a value passes every test
+    pub fn matches(&self, value: &Value) -> bool {
+        self.tests.iter().all(|test| test.passes(value))
+    }
+}
+
+// Implement methods for Test
+impl Test {
+    // Check the part of a value this test is about; the tests before it have passed
+    fn passes(&self, value: &Value) -> bool {
+        match self {
+            Test::Array(path, length) => matches!(
+                part(value, path).thawed(),
+                Value::FixedArray(items) | Value::DynamicArray(items) if items.len() == *length
+            ),
+            Test::Object(path, keys) => matches!(
+                part(value, path).thawed(),
+                Value::Object(fields) if keys.iter().all(|key| fields.contains_key(key))
+            ),
+            Test::Number(path, n) => {
+                matches!(part(value, path).thawed(), Value::Number(m) if m == n)
+            }
+            Test::String(path, s) => {
+                matches!(part(value, path).thawed(), Value::String(t) if t == s)
+            }
+            Test::Boolean(path, b) => {
+                matches!(part(value, path).thawed(), Value::Boolean(c) if c == b)
+            }
+        }
+    }
+}
+
+// Get the part of a value at a path, looking into frozen arrays and objects like any others;
+// the tests before it have checked every step exists. A frozen part stays frozen, so a name
+// bound to it cannot change it either.
+pub fn part<'a>(value: &'a Value, path: &[Step]) -> &'a Value {
+    path.iter()
+        .fold(value, |value, step| match (value.thawed(), step) {
+            (Value::FixedArray(items) | Value::DynamicArray(items), Step::Index(index)) => {
+                &items[*index]
+            }
+            (Value::Object(fields), Step::Field(key)) => &fields[key],
+            _ => unreachable!("a part is only reached after the tests of its container"),
+        })
+}
@@ -0,0 +1,83 @@
+// Compiled program files (.pgc): a program's parsed AST, saved so it can run without its source
+//
+// A .pgc file starts with a header line naming the format version, followed by the program
+// and every module it imports, serialized as JSON. Running one skips lexing and parsing
+// entirely, and the original .pg files are not needed.
+use crate::ast::{Program, FORMAT_VERSION};
+use crate::interpreter::{self, ModuleSource};
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use crate::watch::program_files;
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+const HEADER: &str = "PIDGIN-PGC"; // First word of every .pgc file
+
+// Define the CompiledProgram struct, the contents of a .pgc file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompiledProgram {
+    pub compiler: String,                // Version of pidgin that compiled it
+    pub source: String,                  // Path of the main program's source file
+    pub program: Program,                // The main program
+    pub modules: Vec<(String, Program)>, // Every imported module (file, AST)
+}
+
+// Parse a program and its modules, returning the contents of the .pgc file
+pub fn compile(path: &str) -> Result<String, String> {
+    let mut files = program_files(path).into_iter();
+    let main = files.next().unwrap_or_else(|| path.to_string());
+
+    let compiled = CompiledProgram {
+        compiler: env!("CARGO_PKG_VERSION").to_string(),
+        source: main.clone(),
+        program: parse_file(&main)?,
+        modules: files
+            .map(|file| parse_file(&file).map(|program| (file, program)))
+            .collect::<Result<_, _>>()?,
+    };
+
+    let json = serde_json::to_string(&compiled).map_err(|e| e.to_string())?;
+    Ok(format!("{HEADER} {FORMAT_VERSION}\n{json}"))
+}
+
+// Read a .pgc file and make its modules available to imports
+pub fn load_file(path: &str) -> Result<Program, String> {
+    let contents =
+        fs::read_to_string(path).map_err(|e| format!("Failed to read file '{path}': {e}"))?;
+    let (header, json) = contents
+        .split_once('\n')
+        .ok_or_else(|| format!("'{path}' is not a compiled Pidgin file"))?;
+
+    match header.strip_prefix(HEADER).map(str::trim) {
+        Some(version) if version == FORMAT_VERSION.to_string() => {}
+        Some(version) => {
+            return Err(format!(
+                "'{path}' was compiled for .pgc format {version}, but this pidgin reads format {FORMAT_VERSION}; recompile it with pidgin compile"
+            ))
+        }
+        None => return Err(format!("'{path}' is not a compiled Pidgin file")),
+    }
+
+    let compiled: CompiledProgram =
+        serde_json::from_str(json).map_err(|e| format!("'{path}' is corrupted: {e}"))?;
+    interpreter::set_embedded_modules(
+        compiled
+            .modules
+            .into_iter()
+            .map(|(file, program)| (file, ModuleSource::Ast(program)))
+            .collect(),
+    );
+    Ok(compiled.program)
+}
+
+// Lex and parse one source file
+pub fn parse_file(path: &str) -> Result<Program, String> {
+    let source =
+        fs::read_to_string(path).map_err(|e| format!("Failed to read file '{path}': {e}"))?;
+    let tokens = Lexer::new(&source)
+        .tokenize()
+        .map_err(|e| format!("{path}: {e}"))?;
+    Parser::new(&tokens)
+        .parse()
+        .map_err(|e| format!("{path}: Parse error: {e}"))
+}
@@ -0,0 +1,503 @@
+// Interactive Read-Eval-Print Loop (`pidgin repl`, or `pidgin` with no arguments)
+use crate::diagnostic::{self, Diagnostic};
+use crate::error::PidginError;
+use crate::highlight::{highlight_with, Category, HighlightFormat};
+use crate::interpreter::{Interpreter, Limits, Snapshot, Value};
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use crate::repl_config::{home_dir, ReplConfig};
+use crate::token::Token;
+use crate::trace::describe;
+use crate::{ast_printer, display_version, print_builtins, print_help, run_with_interpreter};
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Config, Editor, Helper};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+// Define the ReplOptions struct, how the REPL starts up
+#[derive(Debug, Clone)]
+pub struct ReplOptions {
+    pub preload: Option<String>,  // File to run before the first prompt
+    pub banner: bool,             // Print the welcome message
+    pub limits: Limits,           // Resource limits applied to each input
+    pub history: Option<PathBuf>, // History file given by --history (else ~/.pidginrc's, or ~/.pidgin_history)
+    pub save_history: bool,       // Keep input history between sessions at all
+}
+
+// Implement Default for ReplOptions (plain `pidgin` with no arguments)
+impl Default for ReplOptions {
+    fn default() -> Self {
+        Self {
+            preload: None,
+            banner: true,
+            limits: Limits::default(),
+            history: None,
+            save_history: true,
+        }
+    }
+}
+
+// Parse the arguments of `pidgin repl`
+pub fn parse_options(args: &[String], limits: Limits) -> Result<ReplOptions, String> {
+    const USAGE: &str =
+        "Usage: pidgin repl [--preload <file.pg>] [--no-banner] [--history <file> | --no-history]";
+    let mut options = ReplOptions {
+        limits,
+        ..ReplOptions::default()
+    };
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--preload" => {
+                let path = iter.next().ok_or("--preload requires a file")?;
+                options.preload = Some(path.clone());
+            }
+            "--no-banner" => options.banner = false,
+            "--history" => {
+                let path = iter.next().ok_or("--history requires a file")?;
+                options.history = Some(PathBuf::from(path));
+            }
+            "--no-history" => options.save_history = false,
+            other => return Err(format!("Unknown repl option: {other}. {USAGE}")),
+        }
+    }
+    Ok(options)
+}
+
+// Get the default history file, ~/.pidgin_history
+fn default_history_file() -> Option<PathBuf> {
+    home_dir().map(|home| home.join(".pidgin_history"))
+}
+
+// Check whether input stops partway through a statement (inside an open bracket or string),
+// so the REPL should read more lines
+fn needs_more_input(source: &str) -> bool {
+    let tokens = match Lexer::new(source).tokenize() {
+        Ok(tokens) => tokens,
+        Err(e) => return e.starts_with("Unterminated string"),
+    };
+
+    // Statements only span lines inside brackets, so an unclosed one means more is coming
+    let depth = tokens.iter().fold(0i32, |depth, info| match info.token {
+        Token::LeftParen | Token::LeftBrace | Token::LeftBracket => depth + 1,
+        Token::RightParen | Token::RightBrace | Token::RightBracket => depth - 1,
+        _ => depth,
+    });
+    depth > 0
+}
+
+// Create the interpreter for a session (:reset clears it rather than making a new one)
+fn new_interpreter(options: &ReplOptions, interrupt: &Arc<AtomicBool>) -> Interpreter {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_limits(options.limits); // Guard each REPL input against runaway loops
+    interpreter.set_interrupt(Arc::clone(interrupt));
+    interpreter
+}
+
+// Define the ReplHelper struct, which colors input as it is typed
+struct ReplHelper {
+    color: bool,                            // Whether to highlight at all (off for NO_COLOR)
+    theme: HashMap<Category, &'static str>, // Colors changed by ~/.pidginrc
+}
+
+// Implement Highlighter for ReplHelper, using the same colors as `pidgin highlight` unless themed
+impl Highlighter for ReplHelper {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        if !self.color {
+            return Cow::Borrowed(line);
+        }
+        // Input that cannot be lexed yet (e.g. a string still being typed) stays plain
+        let ansi = |category| {
+            self.theme
+                .get(&category)
+                .copied()
+                .unwrap_or(category.ansi())
+        };
+        match highlight_with(line, HighlightFormat::Ansi, ansi) {
+            Ok(colored) => Cow::Owned(colored),
+            Err(_) => Cow::Borrowed(line),
+        }
+    }
+
+    fn highlight_char(&self, _line: &str, _pos: usize, _forced: bool) -> bool {
+        self.color // Recolor the line after every keystroke
+    }
+}
+
+// The helper only highlights, so completion, hints and validation keep their defaults
+impl Completer for ReplHelper {
+    type Candidate = String;
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Validator for ReplHelper {}
+
+impl Helper for ReplHelper {}
+
+// Run a file in the session's interpreter, keeping its definitions (--preload and :load)
+fn load_file(path: &str, interpreter: &mut Interpreter) {
+    match fs::read_to_string(path) {
+        Ok(source) => {
+            if let Err(e) = run_with_interpreter(&source, interpreter) {
+                report_error(&e.in_file(path), &source);
+            }
+        }
+        Err(e) => eprintln!("Failed to read file '{path}': {e}"),
+    }
+}
+
+// Describe the type of an expression's value, with its size for collections (the :type command)
+fn describe_type(value: &Value) -> String {
+    let count = |n: usize, what: &str| format!("{n} {what}{}", if n == 1 { "" } else { "s" });
+    match value {
+        Value::FixedArray(items) | Value::DynamicArray(items) => {
+            format!("{} ({})", value.type_name(), count(items.len(), "element"))
+        }
+        Value::Object(fields) => format!("object ({})", count(fields.len(), "key")),
+        Value::String(s) => format!("string ({})", count(s.chars().count(), "character")),
+        Value::Function(params, _) => format!("function ({})", count(params.len(), "parameter")),
+        Value::Frozen(inner) => format!("frozen {}", describe_type(inner)),
+        other => other.type_name().to_string(),
+    }
+}
+
+// Run one input, remembering it for :save if it succeeds
+fn run_input(source: &str, interpreter: &mut Interpreter, session: &mut Vec<String>) {
+    match run_with_interpreter(source, interpreter) {
+        Ok(()) => session.push(source.trim_end().to_string()),
+        Err(e) => report_error(&e, source),
+    }
+}
+
+// Print an error with the line of the input it points at and a caret under the problem, as
+// the command line does for files
+fn report_error(error: &PidginError, source: &str) {
+    let diagnostic = Diagnostic::from_error(error).in_source("<input>", source);
+    eprint!("{}", diagnostic.render(diagnostic::use_color()));
+}
+
+// Write the inputs that ran successfully to a file, as a script (the :save command)
+fn save_session(path: &str, session: &[String]) {
+    let mut script = session.join("\n");
+    script.push('\n');
+    match fs::write(path, script) {
+        Ok(()) => println!("Saved {} input(s) to {path}", session.len()),
+        Err(e) => eprintln!("Failed to write file '{path}': {e}"),
+    }
+}
+
+// Print the tokens of a snippet, as `--tokens` does for a file (the :tokens command)
+fn print_tokens(source: &str) {
+    match Lexer::new(source).tokenize() {
+        Ok(tokens) => {
+            for token in tokens {
+                println!("{token:?}");
+            }
+        }
+        Err(e) => eprintln!("Error: {e}"),
+    }
+}
+
+// Print the syntax tree of a snippet, as `--ast` does for a file (the :ast command)
+fn print_ast(source: &str) {
+    let parse = |source: &str| {
+        Lexer::new(source)
+            .tokenize()
+            .and_then(|tokens| Parser::new(&tokens).parse())
+    };
+    // Let an expression be given without its semicolon, e.g. `:ast 1 + 2 * 3`
+    let result = parse(source).or_else(|e| {
+        if source.ends_with(';') || source.ends_with('}') {
+            Err(e)
+        } else {
+            parse(&format!("{source};")).map_err(|_| e)
+        }
+    });
+    match result {
+        Ok(program) => print!("{}", ast_printer::print_tree(&program)),
+        Err(e) => report_error(&PidginError::syntax(e), source),
+    }
+}
+
+// Longest value shown by :env before it is cut short
+const ENV_VALUE_WIDTH: usize = 60;
+
+// Print the variables and functions defined so far in the session (the :env command)
+fn print_env(interpreter: &Interpreter) {
+    let mut names: Vec<&str> = interpreter.variables().keys().collect();
+    names.sort();
+    if names.is_empty() {
+        println!("(nothing defined yet)");
+        return;
+    }
+
+    let (functions, variables): (Vec<&str>, Vec<&str>) = names
+        .into_iter()
+        .partition(|name| matches!(interpreter.variables()[*name], Value::Function(..)));
+    if !variables.is_empty() {
+        println!("Variables:");
+        for name in variables {
+            let value = &interpreter.variables()[name];
+            let mut shown = describe(value);
+            if shown.chars().count() > ENV_VALUE_WIDTH {
+                shown = shown.chars().take(ENV_VALUE_WIDTH - 3).collect::<String>() + "...";
+            }
+            println!("  {name}: {} = {shown}", value.type_name());
+        }
+    }
+    if !functions.is_empty() {
+        println!("Functions:");
+        for name in functions {
+            if let Value::Function(params, _) = &interpreter.variables()[name] {
+                println!("  {name}({})", params.join(", "));
+            }
+        }
+    }
+}
+
+// Start a REPL (Read-Eval-Print Loop) prompt
+pub fn run_prompt(options: &ReplOptions) {
+    if options.banner {
+        println!("Welcome to Pidgin REPL! Type 'exit' or 'quit' to exit, 'help' for help.");
+    }
+    // Ctrl-C while a program runs stops it and returns to the prompt (at the prompt itself the
+    // line editor sees Ctrl-C as a key press instead)
+    let interrupt = Arc::new(AtomicBool::new(false));
+    let flag = Arc::clone(&interrupt);
+    if let Err(e) = ctrlc::set_handler(move || flag.store(true, Ordering::SeqCst)) {
+        eprintln!("Failed to install Ctrl-C handler: {e}");
+    }
+    let mut interpreter = new_interpreter(options, &interrupt);
+
+    // Settings from ~/.pidginrc; a broken file is reported and ignored
+    let config = ReplConfig::load().unwrap_or_else(|e| {
+        eprintln!("{e}");
+        ReplConfig::default()
+    });
+
+    // Run the preload files first so their globals are available at the prompt
+    for path in config.preload_files() {
+        load_file(&path.to_string_lossy(), &mut interpreter);
+    }
+    if let Some(path) = &options.preload {
+        load_file(path, &mut interpreter);
+    }
+
+    // Line editor with arrow-key editing, Emacs-style shortcuts and Up/Down history
+    let editor_config = match Config::builder().max_history_size(config.history_size) {
+        Ok(builder) => builder.build(),
+        Err(e) => {
+            eprintln!("Invalid history_size in ~/.pidginrc: {e}");
+            Config::default()
+        }
+    };
+    let mut editor: Editor<ReplHelper, DefaultHistory> = match Editor::with_config(editor_config) {
+        Ok(editor) => editor,
+        Err(e) => {
+            eprintln!("Failed to start line editor: {e}");
+            return;
+        }
+    };
+    // The line editor only highlights when attached to a terminal; NO_COLOR turns it off there too
+    editor.set_helper(Some(ReplHelper {
+        color: env::var_os("NO_COLOR").is_none(),
+        theme: config.colors().unwrap_or_default(),
+    }));
+    // Earlier sessions' inputs, only when typing at a terminal so piped input is not recorded
+    let history = options
+        .history
+        .clone()
+        .or_else(|| config.history_file())
+        .or_else(default_history_file)
+        .filter(|_| options.save_history && io::stdin().is_terminal());
+    if let Some(path) = &history {
+        if path.exists() {
+            if let Err(e) = editor.load_history(path) {
+                eprintln!("Failed to read history from '{}': {e}", path.display());
+            }
+        }
+    }
+    let mut stdout = io::stdout(); // Get standard output
+    let mut buffer = String::new(); // Input so far, which may span several lines
+    let mut session: Vec<String> = Vec::new(); // Inputs that ran successfully (for :save)
+    let mut checkpoint: Option<(Snapshot, usize)> = None; // State and session length (:checkpoint)
+    loop {
+        // Continuation prompt while a statement is unfinished
+        let prompt = if buffer.is_empty() {
+            &config.prompt
+        } else {
+            &config.continuation_prompt
+        };
+        match editor.readline(prompt) {
+            Err(ReadlineError::Eof) => {
+                println!("\nExiting...");
+                break; // Exit on EOF (Ctrl-D)
+            }
+            Err(ReadlineError::Interrupted) => {
+                buffer.clear(); // Ctrl-C abandons the input typed so far
+                continue;
+            }
+            Ok(mut line) => {
+                if !line.trim().is_empty() {
+                    let _ = editor.add_history_entry(line.as_str());
+                }
+                line.push('\n'); // Keep line breaks between lines of a multi-line input
+                if !buffer.is_empty() {
+                    // An empty line gives up on completing the statement and runs it as is
+                    let finished = line.trim().is_empty();
+                    buffer.push_str(&line);
+                    if !finished && needs_more_input(&buffer) {
+                        continue;
+                    }
+                    run_input(&buffer, &mut interpreter, &mut session);
+                    buffer.clear();
+                    continue;
+                }
+
+                let input = line.trim();
+                if input.is_empty() {
+                    continue; // Skip empty lines
+                }
+
+                match input {
+                    ":version" | ":v" => {
+                        display_version();
+                        continue;
+                    }
+                    ":env" => {
+                        print_env(&interpreter);
+                        continue;
+                    }
+                    ":builtins" => {
+                        print_builtins();
+                        continue;
+                    }
+                    ":reset" => {
+                        interpreter.clear(); // Keeping its settings and memory
+                        session.clear();
+                        checkpoint = None;
+                        println!("Session reset: all variables and functions cleared.");
+                        continue;
+                    }
+                    ":type" => {
+                        eprintln!("Usage: :type <expression>");
+                        continue;
+                    }
+                    _ if input.starts_with(":type ") => {
+                        let expression = input[":type ".len()..].trim();
+                        match interpreter.evaluate_source(expression) {
+                            Ok(value) => println!("{}", describe_type(&value)),
+                            Err(e) => report_error(&PidginError::runtime(e), expression),
+                        }
+                        continue;
+                    }
+                    ":checkpoint" => {
+                        checkpoint = Some((interpreter.snapshot(), session.len()));
+                        println!("Checkpoint saved; :rollback returns to it.");
+                        continue;
+                    }
+                    _ if input.starts_with(":checkpoint ") => {
+                        let path = input[":checkpoint ".len()..].trim();
+                        match interpreter.snapshot().save(path) {
+                            Ok(()) => println!("Saved variables and functions to {path}"),
+                            Err(e) => eprintln!("Error: {e}"),
+                        }
+                        continue;
+                    }
+                    ":rollback" => {
+                        match &checkpoint {
+                            Some((snapshot, inputs)) => {
+                                interpreter.restore(snapshot);
+                                session.truncate(*inputs);
+                                println!("Rolled back to the last checkpoint.");
+                            }
+                            None => eprintln!("No checkpoint yet; make one with :checkpoint"),
+                        }
+                        continue;
+                    }
+                    _ if input.starts_with(":rollback ") => {
+                        let path = input[":rollback ".len()..].trim();
+                        match Snapshot::load(path) {
+                            Ok(snapshot) => {
+                                interpreter.restore(&snapshot);
+                                println!("Restored variables and functions from {path}");
+                            }
+                            Err(e) => eprintln!("Error: {e}"),
+                        }
+                        continue;
+                    }
+                    ":save" => {
+                        eprintln!("Usage: :save <file.pg>");
+                        continue;
+                    }
+                    _ if input.starts_with(":save ") => {
+                        save_session(input[":save ".len()..].trim(), &session);
+                        continue;
+                    }
+                    ":tokens" | ":ast" => {
+                        eprintln!("Usage: {input} <code>");
+                        continue;
+                    }
+                    _ if input.starts_with(":tokens ") => {
+                        print_tokens(input[":tokens ".len()..].trim());
+                        continue;
+                    }
+                    _ if input.starts_with(":ast ") => {
+                        print_ast(input[":ast ".len()..].trim());
+                        continue;
+                    }
+                    ":load" => {
+                        eprintln!("Usage: :load <file.pg>");
+                        continue;
+                    }
+                    _ if input.starts_with(":load ") => {
+                        load_file(input[":load ".len()..].trim(), &mut interpreter);
+                        continue;
+                    }
+                    "exit" | "quit" => {
+                        println!("Goodbye!");
+                        break;
+                    }
+                    "help" => {
+                        print_help();
+                        continue;
+                    }
+                    "clear" => {
+                        // Clear screen (works on most terminals)
+                        print!("\x1B[2J\x1B[1;1H");
+                        stdout.flush().unwrap();
+                        continue;
+                    }
+                    _ if needs_more_input(&line) => buffer = line,
+                    _ => run_input(&line, &mut interpreter, &mut session),
+                }
+            }
+            Err(e) => {
+                eprintln!("Error reading input: {e}");
+                break;
+            }
+        }
+    }
+
+    // Add this session's inputs to the history file
+    if let Some(path) = &history {
+        if let Err(e) = editor.append_history(path) {
+            eprintln!("Failed to save history to '{}': {e}", path.display());
+        }
+    }
+}
@@ -0,0 +1,140 @@
+// Pidgin's configuration file, ~/.pidginrc, read by the REPL and pidgin update
+//
+// The file is TOML and every setting is optional:
+//
+//     prompt = "pg> "
+//     continuation_prompt = "... "
+//     history_size = 500
+//     history_file = "~/.config/pidgin/history"
+//     preload = ["~/pidgin/helpers.pg"]
+//
+//     [theme]
+//     keyword = "red"
+//     comment = "none"
+//
+//     [update]
+//     mirror = "https://artifacts.example.com/pidgin"
+//     proxy = "http://proxy.example.com:8080"
+//
+// Command-line options take precedence over the file.
+use crate::highlight::Category;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+pub const CONFIG_FILE: &str = ".pidginrc"; // Name of the file in the home directory
+
+// Define the ReplConfig struct, the settings read from ~/.pidginrc
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReplConfig {
+    pub prompt: String,                  // Prompt for a new input
+    pub continuation_prompt: String,     // Prompt while an input is unfinished
+    pub history_size: usize,             // Most inputs kept in the history
+    pub history_file: Option<String>,    // Where history is saved instead of ~/.pidgin_history
+    pub preload: Vec<String>,            // Files run before the first prompt
+    pub theme: BTreeMap<String, String>, // Color for each kind of token, e.g. keyword = "red"
+    pub update: UpdateConfig,            // Where pidgin update gets releases from
+}
+
+// Define the UpdateConfig struct, the [update] settings
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UpdateConfig {
+    pub mirror: Option<String>, // Server with copies of the releases, used instead of GitHub
+    pub proxy: Option<String>,  // Proxy for update requests (takes precedence over HTTPS_PROXY)
+}
+
+// Implement Default for ReplConfig (no ~/.pidginrc)
+impl Default for ReplConfig {
+    fn default() -> Self {
+        Self {
+            prompt: "pidgin> ".to_string(),
+            continuation_prompt: "....> ".to_string(),
+            history_size: 1000,
+            history_file: None,
+            preload: Vec::new(),
+            theme: BTreeMap::new(),
+            update: UpdateConfig::default(),
+        }
+    }
+}
+
+// Implement methods for ReplConfig
+impl ReplConfig {
+    // Read ~/.pidginrc, using the defaults if there is none
+    pub fn load() -> Result<Self, String> {
+        let Some(path) = home_dir().map(|home| home.join(CONFIG_FILE)) else {
+            return Ok(Self::default());
+        };
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read '{}': {e}", path.display()))?;
+        let config: Self =
+            toml::from_str(&text).map_err(|e| format!("Invalid '{}': {e}", path.display()))?;
+        config.colors()?; // Reject unknown token kinds and colors up front
+        Ok(config)
+    }
+
+    // Get the history file, with a leading ~ expanded
+    pub fn history_file(&self) -> Option<PathBuf> {
+        self.history_file.as_deref().map(expand_home)
+    }
+
+    // Get the preload files, with a leading ~ expanded
+    pub fn preload_files(&self) -> Vec<PathBuf> {
+        self.preload.iter().map(|path| expand_home(path)).collect()
+    }
+
+    // Get the escape code for each token kind the theme changes
+    pub fn colors(&self) -> Result<HashMap<Category, &'static str>, String> {
+        let mut colors = HashMap::new();
+        for (kind, color) in &self.theme {
+            let category = Category::from_name(kind).ok_or_else(|| {
+                format!("Unknown token kind '{kind}' in [theme] (expected keyword, constant, number, string, builtin, operator or comment)")
+            })?;
+            let code = ansi_color(color).ok_or_else(|| {
+                format!("Unknown color '{color}' for {kind} in [theme] (expected black, red, green, yellow, blue, magenta, cyan, white, gray, bold or none)")
+            })?;
+            colors.insert(category, code);
+        }
+        Ok(colors)
+    }
+}
+
+// Get the terminal escape code for a color name
+fn ansi_color(name: &str) -> Option<&'static str> {
+    match name {
+        "black" => Some("\x1b[30m"),
+        "red" => Some("\x1b[31m"),
+        "green" => Some("\x1b[32m"),
+        "yellow" => Some("\x1b[33m"),
+        "blue" => Some("\x1b[34m"),
+        "magenta" => Some("\x1b[35m"),
+        "cyan" => Some("\x1b[36m"),
+        "white" => Some("\x1b[37m"),
+        "gray" | "grey" => Some("\x1b[90m"),
+        "bold" => Some("\x1b[1m"),
+        "none" => Some(""),
+        _ => None,
+    }
+}
+
+// Get the user's home directory
+pub fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+}
+
+// Expand a leading ~ in a path to the home directory
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
@@ -0,0 +1,292 @@
+// Static name resolution for Pidgin programs (`pidgin check`)
+//
+// The resolver walks the AST without executing it and reports names that can never be
+// defined, calls with the wrong number of arguments, broken imports, and top-level returns.
+// Top-level code runs in order, so names must be defined before they are used there;
+// function bodies see whatever globals exist when they are called, so inside a function a
+// name only has to be defined somewhere in the program.
+use crate::ast::{Expr, Program, Span, Stmt};
+use crate::interpreter::{is_builtin, parse_module};
+use crate::pattern;
+use crate::visitor::{self, Visitor};
+use std::collections::{HashMap, HashSet};
+
+// Define the ResolveError struct, a problem found without running the program
+#[derive(Debug, Clone)]
+pub struct ResolveError {
+    pub message: String, // Human-readable explanation
+    pub span: Span,      // Statement where the problem was found
+}
+
+// Resolve every name in a program, returning all problems found
+pub fn resolve_program(program: &Program) -> Vec<ResolveError> {
+    let mut collector = NameCollector::default();
+    visitor::walk_program(&mut collector, program);
+
+    let mut resolver = Resolver {
+        errors: Vec::new(),
+        all_names: collector.names,
+        defined: HashSet::new(),
+        functions: HashMap::new(),
+        function_depth: 0,
+        current_span: Span::default(),
+    };
+    visitor::walk_program(&mut resolver, program);
+    resolver.errors
+}
+
+// Define the NameCollector struct, which gathers every name a program can define
+#[derive(Default)]
+struct NameCollector {
+    names: HashSet<String>, // Variables, functions, parameters, and imports
+}
+
+impl Visitor for NameCollector {
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::VarDeclaration { name, .. } => {
+                self.names.insert(name.clone());
+            }
+            Stmt::Unpack { names, .. } => self.names.extend(names.iter().cloned()),
+            Stmt::FunctionDeclaration {
+                name, parameters, ..
+            } => {
+                self.names.insert(name.clone());
+                self.names.extend(parameters.iter().cloned());
+            }
+            Stmt::Import { names, .. } => self.names.extend(names.iter().cloned()),
+            Stmt::ForIn { variable, .. } => {
+                self.names.insert(variable.clone());
+            }
+            _ => {}
+        }
+        visitor::walk_stmt(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Assignment { name, .. } => {
+                self.names.insert(name.clone());
+            }
+            Expr::Match { cases, .. } => {
+                for case in cases {
+                    let names = pattern::bound_names(&case.pattern);
+                    self.names
+                        .extend(names.into_iter().map(|(name, _)| name.to_string()));
+                }
+            }
+            _ => {}
+        }
+        visitor::walk_expr(self, expr);
+    }
+}
+
+// Define the Resolver struct, which checks names in execution order
+struct Resolver {
+    errors: Vec<ResolveError>,         // Problems found so far
+    all_names: HashSet<String>,        // Every name defined anywhere in the program
+    defined: HashSet<String>,          // Names defined so far by top-level code
+    functions: HashMap<String, usize>, // Arity of known top-level and imported functions
+    function_depth: usize,             // How many function bodies we are inside
+    current_span: Span,                // Span of the statement being resolved
+}
+
+impl Resolver {
+    // Record a resolution error at the current statement
+    fn error(&mut self, message: String) {
+        self.errors.push(ResolveError {
+            message,
+            span: self.current_span,
+        });
+    }
+
+    // Check whether a name can be defined at this point of the program
+    fn is_defined(&self, name: &str) -> bool {
+        if self.function_depth > 0 {
+            self.all_names.contains(name)
+        } else {
+            self.defined.contains(name)
+        }
+    }
+
+    // Mark a name as defined by top-level code
+    fn define(&mut self, name: &str) {
+        self.defined.insert(name.to_string());
+    }
+
+    // Check an import: the module must parse and export every requested name
+    fn resolve_import(&mut self, names: &[String], module: &str) {
+        let (module_file, program) = match parse_module(module) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                self.error(e);
+                for name in names {
+                    self.define(name); // Avoid follow-up "undefined" errors
+                }
+                return;
+            }
+        };
+
+        // Collect the module's top-level definitions
+        let mut exported = HashSet::new();
+        for stmt in &program.statements {
+            match stmt {
+                Stmt::VarDeclaration { name, .. } => {
+                    exported.insert(name.clone());
+                }
+                Stmt::Unpack {
+                    names,
+                    declares: true,
+                    ..
+                } => exported.extend(names.iter().cloned()),
+                Stmt::FunctionDeclaration {
+                    name, parameters, ..
+                } => {
+                    exported.insert(name.clone());
+                    if names.contains(name) {
+                        self.functions.insert(name.clone(), parameters.len());
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        for name in names {
+            if !exported.contains(name) {
+                self.error(format!("Name '{name}' not found in module '{module_file}'"));
+            } else if !name.chars().next().is_some_and(|c| c.is_uppercase()) {
+                self.error(format!(
+                    "Cannot import '{name}' - only names starting with uppercase letters can be imported"
+                ));
+            }
+            self.define(name);
+        }
+    }
+}
+
+impl Visitor for Resolver {
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        self.current_span = stmt.span();
+        match stmt {
+            Stmt::VarDeclaration {
+                name, initializer, ..
+            } => {
+                if let Some(initializer) = initializer {
+                    self.visit_expr(initializer);
+                }
+                self.functions.remove(name); // No longer the function it may have been
+                self.define(name);
+            }
+            Stmt::Unpack { names, value, .. } => {
+                self.visit_expr(value);
+                for name in names {
+                    self.functions.remove(name);
+                    self.define(name);
+                }
+            }
+            Stmt::FunctionDeclaration {
+                name,
+                parameters,
+                body,
+                ..
+            } => {
+                self.define(name); // Defined before the body so recursion resolves
+                self.functions.insert(name.clone(), parameters.len());
+                self.function_depth += 1;
+                self.visit_stmt(body);
+                self.function_depth -= 1;
+            }
+            Stmt::Import { names, module, .. } => self.resolve_import(names, module),
+            Stmt::Return(expr, _) => {
+                if self.function_depth == 0 {
+                    self.error("Return statement not allowed outside function".to_string());
+                }
+                self.visit_expr(expr);
+            }
+            Stmt::While { body, .. } => {
+                // Later iterations can see names defined anywhere in the loop body
+                let mut collector = NameCollector::default();
+                collector.visit_stmt(body);
+                self.defined.extend(collector.names);
+                visitor::walk_stmt(self, stmt);
+            }
+            Stmt::ForIn {
+                variable,
+                iterable,
+                body,
+                ..
+            } => {
+                self.visit_expr(iterable);
+                self.functions.remove(variable); // No longer the function it may have been
+                self.define(variable);
+                let mut collector = NameCollector::default();
+                collector.visit_stmt(body);
+                self.defined.extend(collector.names);
+                self.visit_stmt(body);
+            }
+            _ => visitor::walk_stmt(self, stmt),
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Identifier(name, _, _) => {
+                if !self.is_defined(name) {
+                    self.error(format!("Undefined variable '{name}'"));
+                }
+            }
+            Expr::Assignment { name, value, .. } => {
+                self.visit_expr(value);
+                self.functions.remove(name);
+                self.define(name);
+            }
+            Expr::FunctionCall {
+                name, arguments, ..
+            } => {
+                if !is_builtin(name) {
+                    if !self.is_defined(name) {
+                        self.error(format!("Undefined function '{name}'"));
+                    } else if let Some(&arity) = self.functions.get(name) {
+                        if arity != arguments.len() {
+                            self.error(format!(
+                                "Function '{name}' expects {arity} arguments, got {}",
+                                arguments.len()
+                            ));
+                        }
+                    }
+                }
+                visitor::walk_expr(self, expr);
+            }
+            Expr::Match { subject, cases, .. } => {
+                self.visit_expr(subject);
+                for case in cases {
+                    // A case's names are assigned while its guard and result run, and get
+                    // back what they held before afterwards
+                    let names = pattern::bound_names(&case.pattern);
+                    let mut new = Vec::new();
+                    let mut hidden = Vec::new();
+                    for &(name, _) in &names {
+                        if !self.defined.contains(name) {
+                            new.push(name);
+                        }
+                        if let Some(arity) = self.functions.remove(name) {
+                            hidden.push((name, arity));
+                        }
+                        self.define(name);
+                    }
+                    if let Some(guard) = &case.guard {
+                        self.visit_expr(guard);
+                    }
+                    self.visit_expr(&case.body);
+                    for name in new {
+                        self.defined.remove(name);
+                    }
+                    for (name, arity) in hidden {
+                        self.functions.insert(name.to_string(), arity);
+                    }
+                }
+            }
+            _ => visitor::walk_expr(self, expr),
+        }
+    }
+}
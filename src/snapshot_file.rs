@@ -0,0 +1,172 @@
+// Snapshot files: an interpreter's variables and functions saved to disk (Snapshot::save)
+//
+// A snapshot file starts with a header line naming the format version, followed by the
+// variables as JSON. Unlike the plain JSON conversion of values (src/value_json.rs), nothing is
+// lost on the way: fixed and dynamic arrays stay apart, dates stay dates, and functions are
+// saved with their syntax tree. Limits, arguments and registered builtins are not saved; they
+// belong to the program embedding the interpreter, which sets them up again.
+use crate::ast::Stmt;
+use crate::interpreter::{Snapshot, Value};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::sync::Arc;
+
+const FORMAT_VERSION: u32 = 6; // Bumped whenever the saved form of values changes
+const HEADER: &str = "PIDGIN-STATE"; // First word of every snapshot file
+
+// Define the SavedValue enum, a Value in the form it is saved in
+#[derive(Debug, Serialize, Deserialize)]
+enum SavedValue {
+    Number(f64),
+    String(String),
+    Boolean(bool),
+    FixedArray(Vec<SavedValue>),
+    DynamicArray(Vec<SavedValue>),
+    Object(BTreeMap<String, SavedValue>), // Sorted, so the same state saves the same way
+    Date(DateTime<Local>),
+    Duration(f64),
+    Nil,
+    Function(Vec<String>, Box<Stmt>),
+    Frozen(Box<SavedValue>),
+}
+
+// Implement From<&Value> for SavedValue
+impl From<&Value> for SavedValue {
+    fn from(value: &Value) -> Self {
+        let all = |items: &[Value]| items.iter().map(SavedValue::from).collect();
+        match value {
+            Value::Number(n) => SavedValue::Number(*n),
+            Value::String(s) => SavedValue::String(s.clone()),
+            Value::Boolean(b) => SavedValue::Boolean(*b),
+            Value::FixedArray(items) => SavedValue::FixedArray(all(items)),
+            Value::DynamicArray(items) => SavedValue::DynamicArray(all(items)),
+            Value::Object(fields) => SavedValue::Object(
+                fields
+                    .iter()
+                    .map(|(key, value)| (key.clone(), SavedValue::from(value)))
+                    .collect(),
+            ),
+            Value::Date(date) => SavedValue::Date(*date),
+            Value::Duration(seconds) => SavedValue::Duration(*seconds),
+            Value::Nil => SavedValue::Nil,
+            Value::Function(params, body) => {
+                SavedValue::Function(params.clone(), Box::new(Stmt::clone(body)))
+            }
+            Value::Iterator(_) | Value::Task(_) => SavedValue::Nil, // Never saved: save() refuses them first
+            Value::Frozen(inner) => SavedValue::Frozen(Box::new(SavedValue::from(&**inner))),
+        }
+    }
+}
+
+// Implement From<SavedValue> for Value
+impl From<SavedValue> for Value {
+    fn from(saved: SavedValue) -> Self {
+        let all = |items: Vec<SavedValue>| items.into_iter().map(Value::from).collect();
+        match saved {
+            SavedValue::Number(n) => Value::Number(n),
+            SavedValue::String(s) => Value::String(s),
+            SavedValue::Boolean(b) => Value::Boolean(b),
+            SavedValue::FixedArray(items) => Value::FixedArray(all(items)),
+            SavedValue::DynamicArray(items) => Value::DynamicArray(all(items)),
+            SavedValue::Object(fields) => Value::Object(
+                fields
+                    .into_iter()
+                    .map(|(key, value)| (key, Value::from(value)))
+                    .collect(),
+            ),
+            SavedValue::Date(date) => Value::Date(date),
+            SavedValue::Duration(seconds) => Value::Duration(seconds),
+            SavedValue::Nil => Value::Nil,
+            SavedValue::Function(params, body) => Value::Function(params, Arc::from(body)),
+            SavedValue::Frozen(inner) => Value::Frozen(Box::new(Value::from(*inner))),
+        }
+    }
+}
+
+// Implement saving and loading for Snapshot
+impl Snapshot {
+    // Write the snapshot's variables and functions to a file
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        // An iterator's remaining values come from the host program, and a task's result from
+        // a running thread, neither of which can be saved
+        if let Some((name, kind)) = self
+            .variables()
+            .iter()
+            .find_map(|(name, value)| unsaveable(value).map(|kind| (name, kind)))
+        {
+            return Err(format!("Cannot save '{name}': it holds {kind}"));
+        }
+        let variables: BTreeMap<&str, SavedValue> = self
+            .variables()
+            .iter()
+            .map(|(name, value)| (name, SavedValue::from(value)))
+            .collect();
+        // NaN and infinite numbers have no JSON form, so they would not load again
+        if let Some(name) = variables
+            .iter()
+            .find_map(|(name, value)| has_non_finite(value).then_some(name))
+        {
+            return Err(format!(
+                "Cannot save '{name}': it holds a number that is NaN or infinite"
+            ));
+        }
+        let json = serde_json::to_string(&variables).map_err(|e| e.to_string())?;
+        fs::write(path, format!("{HEADER} {FORMAT_VERSION}\n{json}\n"))
+            .map_err(|e| format!("Failed to write file '{path}': {e}"))
+    }
+
+    // Read a snapshot written by save; it holds only variables and functions
+    pub fn load(path: &str) -> Result<Snapshot, String> {
+        let contents =
+            fs::read_to_string(path).map_err(|e| format!("Failed to read file '{path}': {e}"))?;
+        let (header, json) = contents
+            .split_once('\n')
+            .ok_or_else(|| format!("'{path}' is not a Pidgin snapshot file"))?;
+
+        match header.strip_prefix(HEADER).map(str::trim) {
+            Some(version) if version == FORMAT_VERSION.to_string() => {}
+            Some(version) => {
+                return Err(format!(
+                    "'{path}' is a snapshot in format {version}, but this pidgin reads format {FORMAT_VERSION}"
+                ))
+            }
+            None => return Err(format!("'{path}' is not a Pidgin snapshot file")),
+        }
+
+        let variables: HashMap<String, SavedValue> = serde_json::from_str(json)
+            .map_err(|e| format!("'{path}' is not a valid snapshot: {e}"))?;
+        Ok(Snapshot::from_variables(
+            variables
+                .into_iter()
+                .map(|(name, value)| (name, Value::from(value)))
+                .collect(),
+        ))
+    }
+}
+
+// Find what makes a value impossible to save, if anything: an iterator or a task in it
+fn unsaveable(value: &Value) -> Option<&'static str> {
+    match value {
+        Value::Iterator(_) => Some("an iterator"),
+        Value::Task(_) => Some("a task"),
+        Value::FixedArray(items) | Value::DynamicArray(items) => items.iter().find_map(unsaveable),
+        Value::Object(fields) => fields.values().find_map(unsaveable),
+        Value::Frozen(inner) => unsaveable(inner),
+        _ => None,
+    }
+}
+
+// Check whether a value holds a NaN or infinite number anywhere inside it
+fn has_non_finite(value: &SavedValue) -> bool {
+    match value {
+        SavedValue::Number(n) | SavedValue::Duration(n) => !n.is_finite(),
+        SavedValue::FixedArray(items) | SavedValue::DynamicArray(items) => {
+            items.iter().any(has_non_finite)
+        }
+        SavedValue::Object(fields) => fields.values().any(has_non_finite),
+        SavedValue::Frozen(inner) => has_non_finite(inner),
+        _ => false,
+    }
+}
@@ -0,0 +1,306 @@
+// Variable names as numbers (symbols), and the table of variables they index
+//
+// Each interpreter numbers the variable names it meets, in the order it meets them, so its
+// variables are a vector indexed by those numbers, its symbols. The AST nodes that name a
+// variable keep its symbol in a Slot, found the first time they run, so from then on the
+// variable is found by indexing rather than by hashing its name. A Slot also records which
+// interpreter's numbering its symbol belongs to, since the same AST can run in several (the
+// threads of parallelMap and spawn, or an embedder's interpreters); in another interpreter it is
+// looked up again by name. The names belong to the interpreter, and go when it does.
+use crate::interpreter::Value;
+use crate::methods::EXTENSION_TYPES;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{BuildHasherDefault, Hasher};
+use std::ops::Index;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+// A Slot holds its table's number above the symbol's SYMBOL_BITS bits (0: not found yet), so
+// both are read and written together. Tables are numbered from 1 up, and a symbol too large to
+// fit is simply looked up by name each time.
+const SYMBOL_BITS: u32 = 24;
+const SYMBOL_MASK: u64 = (1 << SYMBOL_BITS) - 1;
+
+// How many of the tables a table was copied from it remembers (see Names::inherited)
+const MAX_INHERITED: usize = 8;
+
+// Get a number for a new table of names
+fn next_table() -> u64 {
+    static NEXT: AtomicU64 = AtomicU64::new(1);
+    NEXT.fetch_add(1, Ordering::Relaxed)
+}
+
+// Define the Names struct, the names an interpreter has given symbols so far
+struct Names {
+    table: u64,                        // Number of this table, recorded by the Slots it fills
+    inherited: Vec<(u64, usize)>,      // Tables it was copied from, with their size then
+    symbols: HashMap<Arc<str>, usize>, // Name -> symbol
+    names: Vec<Arc<str>>,              // Symbol -> name
+    extensions: HashMap<&'static str, HashMap<Arc<str>, usize>>, // Type -> method -> symbol
+}
+
+// Implement methods for Names
+impl Names {
+    // Get the symbol of a name, giving it one if it has none yet
+    fn intern(&mut self, name: &str) -> usize {
+        if let Some(&symbol) = self.symbols.get(name) {
+            return symbol;
+        }
+        let name: Arc<str> = name.into();
+        let symbol = self.names.len();
+        self.names.push(name.clone());
+        self.symbols.insert(name.clone(), symbol);
+        // A method a program defines on a type (function String.shout(self)) is a variable
+        // named after both, which a method call finds through its type and method name
+        if let Some((type_name, method)) = name.split_once('.') {
+            if let Some(&type_name) = EXTENSION_TYPES.iter().find(|&&t| t == type_name) {
+                let methods = self.extensions.entry(type_name).or_default();
+                methods.insert(method.into(), symbol);
+            }
+        }
+        symbol
+    }
+
+    // Get the symbol of a node's name, from its Slot if this table (or one it was copied from,
+    // before the symbol was given) filled it
+    fn symbol(&mut self, slot: &Slot, name: &str) -> usize {
+        let cached = slot.0.load(Ordering::Relaxed);
+        let table = cached >> SYMBOL_BITS;
+        let symbol = (cached & SYMBOL_MASK) as usize;
+        if table == self.table
+            || (table != 0
+                && self
+                    .inherited
+                    .iter()
+                    .any(|&(inherited, count)| inherited == table && symbol < count))
+        {
+            return symbol;
+        }
+        let symbol = self.intern(name);
+        if symbol as u64 <= SYMBOL_MASK {
+            slot.0
+                .store(self.table << SYMBOL_BITS | symbol as u64, Ordering::Relaxed);
+        }
+        symbol
+    }
+}
+
+// Implement Default for Names, an empty table with a number of its own
+impl Default for Names {
+    fn default() -> Self {
+        Names {
+            table: next_table(),
+            inherited: Vec::new(),
+            symbols: HashMap::new(),
+            names: Vec::new(),
+            extensions: HashMap::new(),
+        }
+    }
+}
+
+// Implement Clone for Names: the copy gives new names symbols of its own, so it takes a new
+// number, but the symbols it kept are the same, so Slots this table filled stay good for them
+impl Clone for Names {
+    fn clone(&self) -> Self {
+        let mut inherited = self.inherited.clone();
+        inherited.push((self.table, self.names.len()));
+        if inherited.len() > MAX_INHERITED {
+            inherited.remove(0);
+        }
+        Names {
+            table: next_table(),
+            inherited,
+            symbols: self.symbols.clone(),
+            names: self.names.clone(),
+            extensions: self.extensions.clone(),
+        }
+    }
+}
+
+// Define the SymbolHasher struct, which hashes symbols: they are small numbers handed out in
+// order, so multiplying by a large odd constant spreads them well enough, far faster than the
+// default hasher
+#[derive(Default)]
+pub struct SymbolHasher(u64);
+
+// Implement Hasher for SymbolHasher
+impl Hasher for SymbolHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        // Only symbols (usize) are hashed, through write_usize; anything else still works
+        for &byte in bytes {
+            self.0 = (self.0.rotate_left(8) ^ u64::from(byte)).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        }
+    }
+
+    fn write_usize(&mut self, symbol: usize) {
+        self.0 = (symbol as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+// A map keyed by symbols
+pub type SymbolMap<V> = HashMap<usize, V, BuildHasherDefault<SymbolHasher>>;
+
+// Define the Slot struct, where an AST node keeps the symbol of the variable it names, and
+// the table of names it belongs to. It is filled in the first time the node runs, and is not
+// part of the node's JSON (or .pgc) form.
+#[derive(Default)]
+pub struct Slot(AtomicU64);
+
+// Implement Clone for Slot (a copy of a node names the same variable)
+impl Clone for Slot {
+    fn clone(&self) -> Self {
+        Slot(AtomicU64::new(self.0.load(Ordering::Relaxed)))
+    }
+}
+
+// Implement Debug for Slot (the symbol depends on the interpreter, so it is left out of AST dumps)
+impl fmt::Debug for Slot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Slot")
+    }
+}
+
+// Define the Variables struct, the variables of an interpreter, indexed by their symbols
+#[derive(Clone, Default)]
+pub struct Variables {
+    names: Names,               // The symbols of the names this interpreter has met
+    values: Vec<Option<Value>>, // Each symbol's value (None: not defined)
+    count: usize,               // How many variables are defined
+}
+
+// Implement methods for Variables
+impl Variables {
+    // Get a variable by name
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.names
+            .symbols
+            .get(name)
+            .and_then(|&symbol| self.get_symbol(symbol))
+    }
+
+    // Check whether a variable is defined
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    // Get the names of the variables, in no particular order
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.iter().map(|(name, _)| name)
+    }
+
+    // Get the values of the variables
+    pub fn values(&self) -> impl Iterator<Item = &Value> {
+        self.values.iter().flatten()
+    }
+
+    // Get the variables with their names
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.names
+            .names
+            .iter()
+            .zip(&self.values)
+            .filter_map(|(name, value)| Some((&**name, value.as_ref()?)))
+    }
+
+    // Get the number of variables
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    // Check whether there are no variables
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    // Get the symbol of a name, giving it one if it has none yet
+    pub(crate) fn intern(&mut self, name: &str) -> usize {
+        self.names.intern(name)
+    }
+
+    // Get the symbol of the name an AST node holds, using the symbol its Slot keeps when it
+    // is one of these variables' (the name must always be the same for the same Slot)
+    pub(crate) fn symbol(&mut self, slot: &Slot, name: &str) -> usize {
+        self.names.symbol(slot, name)
+    }
+
+    // Get the method a program defined on a type (EXTENSION_TYPES) under a name, if any. Most
+    // programs define none, and then no name is looked up at all.
+    pub(crate) fn extension(&self, type_name: &str, method: &str) -> Option<&Value> {
+        if self.names.extensions.is_empty() {
+            return None;
+        }
+        let symbol = *self.names.extensions.get(type_name)?.get(method)?;
+        self.get_symbol(symbol)
+    }
+
+    // Get a variable by symbol
+    pub(crate) fn get_symbol(&self, symbol: usize) -> Option<&Value> {
+        self.values.get(symbol).and_then(Option::as_ref)
+    }
+
+    // Set a variable by symbol (None removes it), returning its previous value
+    pub(crate) fn set(&mut self, symbol: usize, value: Option<Value>) -> Option<Value> {
+        if symbol >= self.values.len() {
+            value.as_ref()?; // Nothing to remove
+            self.values.resize(symbol + 1, None);
+        }
+        let old = std::mem::replace(&mut self.values[symbol], value);
+        match (&old, &self.values[symbol]) {
+            (None, Some(_)) => self.count += 1,
+            (Some(_), None) => self.count -= 1,
+            _ => {}
+        }
+        old
+    }
+
+    // Remove every variable, keeping the memory they took and the symbols given so far
+    pub(crate) fn clear(&mut self) {
+        self.values.clear();
+        self.count = 0;
+    }
+}
+
+// Implement Index for Variables, so variables["name"] gets a variable that must exist
+impl<T: AsRef<str> + ?Sized> Index<&T> for Variables {
+    type Output = Value;
+
+    fn index(&self, name: &T) -> &Value {
+        let name = name.as_ref();
+        self.get(name)
+            .unwrap_or_else(|| panic!("no variable named '{name}'"))
+    }
+}
+
+// Implement IntoIterator for &Variables, so a for loop goes through the variables with their names
+impl<'a> IntoIterator for &'a Variables {
+    type Item = (&'a str, &'a Value);
+    type IntoIter = Box<dyn Iterator<Item = Self::Item> + 'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Box::new(self.iter())
+    }
+}
+
+// Implement FromIterator for Variables, to build them from names and values
+impl FromIterator<(String, Value)> for Variables {
+    fn from_iter<I: IntoIterator<Item = (String, Value)>>(items: I) -> Self {
+        let mut variables = Variables::default();
+        for (name, value) in items {
+            let symbol = variables.intern(&name);
+            variables.set(symbol, Some(value));
+        }
+        variables
+    }
+}
+
+// Implement Debug for Variables, shown like a map of names to values
+impl fmt::Debug for Variables {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
@@ -24,6 +24,10 @@ pub enum Token {
     If,       // 'if' keyword token
     Else,     // 'else' keyword token
     While,    // 'while' keyword token
+    For,      // 'for' keyword token
+    In,       // 'in' keyword token (for item in items)
+    Match,    // 'match' keyword token
+    Case,     // 'case' keyword token (a case of a match)
     Break,    // 'break' keyword
     Print,    // 'print' keyword token
     PrintLn,  // 'printLn' keyword token
@@ -54,8 +58,10 @@ pub enum Token {
     Imply,        // '<=>' imply token
 
     // Special
-    Newline, // Newline token (for line breaks)
-    Eof,     // End-of-file token
+    Comment(String), // '// ...' comment text (only produced when the lexer keeps comments)
+    DocComment(String), // '/// ...' documentation comment text (always produced)
+    Newline,         // Newline token (for line breaks)
+    Eof,             // End-of-file token
 }
 
 // Define the TokenInfo struct, which stores a token and its position in the source code
@@ -64,6 +70,7 @@ pub struct TokenInfo {
     pub token: Token,  // The token itself
     pub line: usize,   // The line number where the token appears
     pub column: usize, // The column number where the token appears
+    pub length: usize, // The number of source characters the token covers
 }
 
 // Implement methods for TokenInfo
@@ -74,6 +81,7 @@ impl TokenInfo {
             token,
             line,
             column,
+            length: 0, // Filled in by the lexer once the token has been scanned
         } // Return a new TokenInfo instance
     }
 }
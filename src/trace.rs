@@ -0,0 +1,105 @@
+// Execution tracing (pidgin --trace <file.pg>)
+//
+// A statement hook prints each statement's line as it is about to run and, once it has run,
+// the variables it created or changed. Trace lines go to stderr, indented by call depth, so
+// they can be told apart from (or redirected away from) the program's own output.
+use crate::ast::{Program, Stmt};
+use crate::interpreter::{Interpreter, StatementHook, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+
+// Define the Tracer struct, the statement hook tracing one file
+pub struct Tracer {
+    file: String,                      // Path of the file being traced
+    lines: Vec<String>,                // Its source lines, to show each statement
+    snapshot: HashMap<String, String>, // Variables as of the last report, shown as text
+}
+
+// Implement methods for Tracer
+impl Tracer {
+    // Create a tracer for a file, given its source
+    pub fn new(file: &str, source: &str) -> Self {
+        Self {
+            file: file.to_string(),
+            lines: source.lines().map(str::to_string).collect(),
+            snapshot: HashMap::new(),
+        }
+    }
+}
+
+// Implement StatementHook for Tracer
+impl StatementHook for Tracer {
+    fn before_statement(
+        &mut self,
+        stmt: &Stmt,
+        interpreter: &mut Interpreter,
+    ) -> Result<(), String> {
+        let line = stmt.span().line;
+        let text = self
+            .lines
+            .get(line.wrapping_sub(1))
+            .map_or("", |text| text.trim());
+        finish_line(interpreter);
+        eprintln!("{}[{}:{line}] {text}", indent(interpreter), self.file);
+        Ok(())
+    }
+
+    fn after_statement(
+        &mut self,
+        _stmt: &Stmt,
+        interpreter: &mut Interpreter,
+    ) -> Result<(), String> {
+        let snapshot: HashMap<String, String> = interpreter
+            .variables()
+            .iter()
+            .map(|(name, value)| (name.to_string(), describe(value)))
+            .collect();
+
+        // Only new and changed variables are shown; ones that go away when a call returns are not
+        let mut changes: Vec<(&String, &String)> = snapshot
+            .iter()
+            .filter(|(name, text)| self.snapshot.get(*name) != Some(text))
+            .collect();
+        changes.sort();
+
+        if !changes.is_empty() {
+            finish_line(interpreter);
+        }
+        for (name, text) in changes {
+            match self.snapshot.get(name) {
+                Some(old) => eprintln!("{}    {name} = {text} (was {old})", indent(interpreter)),
+                None => eprintln!("{}    {name} = {text}", indent(interpreter)),
+            }
+        }
+        self.snapshot = snapshot;
+        Ok(())
+    }
+
+    fn module_hook(&mut self, file: &str, _program: &Program) -> Option<Box<dyn StatementHook>> {
+        // A module compiled into a .pgc file has no source to show, only line numbers
+        let source = fs::read_to_string(file).unwrap_or_default();
+        Some(Box::new(Tracer::new(file, &source)))
+    }
+}
+
+// Flush the program's output so the trace stays in step with it, ending any partial line
+fn finish_line(interpreter: &mut Interpreter) {
+    io::stdout().flush().ok();
+    if interpreter.take_mid_line() {
+        eprintln!();
+    }
+}
+
+// Indent trace lines two spaces for each function call in progress
+fn indent(interpreter: &Interpreter) -> String {
+    "  ".repeat(interpreter.call_stack().len())
+}
+
+// Show a value as it would be written in code, so strings stand out from numbers
+pub fn describe(value: &Value) -> String {
+    match value {
+        Value::String(s) => format!("{s:?}"),
+        other => other.to_string(),
+    }
+}
@@ -1,48 +1,255 @@
-// Update the compiler to the latest version
-pub fn update_compiler() -> Result<(), String> {
-    println!("Pidgin Compiler Update");
-    println!("=====================");
+use super::progress::Progress;
+use crate::logger::{debug, info};
+use crate::repl_config::UpdateConfig;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+const RELEASES_API: &str = "https://api.github.com/repos/ojutalayomi/pidgin/releases/latest";
+const RELEASES_URL: &str = "https://github.com/ojutalayomi/pidgin/releases";
+
+// Public key that release archives are signed with (minisign, base64), built in by the release
+// workflow; builds made without it cannot verify updates, so they refuse to install any
+const RELEASE_PUBLIC_KEY: Option<&str> = option_env!("PIDGIN_RELEASE_PUBLIC_KEY");
+
+// Define the UpdateError enum, the ways checking for or installing an update can fail
+#[derive(Debug)]
+pub enum UpdateError {
+    UnsupportedPlatform,     // No release is built for this operating system or CPU
+    Network(String),         // The server could not be reached
+    Http(u16),               // The server answered with an error status
+    InvalidResponse(String), // The release information could not be understood
+    Archive(String),         // The release archive is damaged, incomplete or not an archive
+    InvalidProxy(String, String), // A proxy URL that cannot be used (where it came from, why)
+    MissingChecksum(String), // The release has no checksum file for the archive (its URL)
+    ChecksumMismatch {
+        expected: String, // SHA-256 published with the release
+        actual: String,   // SHA-256 of what was downloaded
+    }, // The download is not the file that was released
+    NoPublicKey,             // This build has no key to verify release signatures with
+    MissingSignature(String), // The release has no signature file for the archive (its URL or path)
+    BadSignature(String),    // The signature is not valid for the download and the built-in key
+    MissingExecutable(String), // The release archive has no pidgin executable for this platform
+    Io(String, io::Error),   // A local file operation failed (what was being done, and why)
+}
 
-    // Detect platform
-    let platform = if cfg!(target_os = "windows") {
-        "windows-x86_64"
-    } else if cfg!(target_os = "macos") {
-        if cfg!(target_arch = "aarch64") {
-            "macos-aarch64"
-        } else {
-            "macos-x86_64"
+// Implement Display for UpdateError
+impl fmt::Display for UpdateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UpdateError::UnsupportedPlatform => write!(f, "Unsupported platform"),
+            UpdateError::Network(e) => write!(f, "Network error: {e}"),
+            UpdateError::Http(404) => write!(
+                f,
+                "Release not found (HTTP 404). Please check if the repository exists and is public."
+            ),
+            UpdateError::Http(status) => write!(f, "Server returned HTTP {status}"),
+            UpdateError::InvalidResponse(e) => write!(f, "Invalid release information: {e}"),
+            UpdateError::Archive(e) => write!(f, "Failed to extract update: {e}"),
+            UpdateError::InvalidProxy(source, e) => write!(
+                f,
+                "Invalid proxy in {source}: {e} (expected e.g. http://proxy.example.com:8080)"
+            ),
+            UpdateError::MissingChecksum(url) => write!(
+                f,
+                "No checksum was published for this release ({url}), so it cannot be verified; refusing to install it"
+            ),
+            UpdateError::ChecksumMismatch { expected, actual } => write!(
+                f,
+                "Checksum mismatch: expected SHA-256 {expected}, but the download has {actual}; refusing to install it"
+            ),
+            UpdateError::NoPublicKey => write!(
+                f,
+                "This build of pidgin has no release signing key, so it cannot verify updates; download the latest version from {RELEASES_URL}"
+            ),
+            UpdateError::MissingSignature(url) => write!(
+                f,
+                "No signature was found for this release ({url}); refusing to install it"
+            ),
+            UpdateError::BadSignature(e) => write!(
+                f,
+                "The release signature does not match ({e}); the download may have been tampered with, so it was not installed"
+            ),
+            UpdateError::MissingExecutable(platform) => write!(
+                f,
+                "Executable not found in the release archive (expected pidgin-{platform}/ inside it; is the archive for this platform?)"
+            ),
+            UpdateError::Io(what, e) => write!(f, "Failed to {what}: {e}"),
         }
-    } else if cfg!(target_os = "linux") {
-        if cfg!(target_arch = "aarch64") {
-            "linux-aarch64"
-        } else {
-            "linux-x86_64"
+    }
+}
+
+// Implement From<ureq::Error> for UpdateError
+impl From<ureq::Error> for UpdateError {
+    fn from(error: ureq::Error) -> Self {
+        match error {
+            ureq::Error::Status(status, _) => UpdateError::Http(status),
+            ureq::Error::Transport(e) => UpdateError::Network(e.to_string()),
         }
-    } else {
-        return Err("Unsupported platform".to_string());
-    };
+    }
+}
+
+// Implement From<zip::result::ZipError> for UpdateError
+impl From<zip::result::ZipError> for UpdateError {
+    fn from(error: zip::result::ZipError) -> Self {
+        UpdateError::Archive(error.to_string())
+    }
+}
+
+// Define the Release struct, the part of GitHub's release information the updater needs
+#[derive(Deserialize)]
+struct Release {
+    tag_name: String, // Version of the release, e.g. "v0.1.20"
+}
+
+// Define the UpdateOptions struct, how `pidgin update` was asked to run
+#[derive(Debug, Clone, Default)]
+pub struct UpdateOptions {
+    pub check: bool,             // Only report whether a newer version exists (--check)
+    pub json: bool,              // Write that report as JSON (--json, with --check)
+    pub proxy: Option<String>, // Proxy for every request (--proxy; else HTTPS_PROXY or HTTP_PROXY)
+    pub proxy_from_config: bool, // Whether the proxy came from ~/.pidginrc rather than --proxy
+    pub mirror: Option<String>, // Server with copies of the releases, instead of GitHub (--mirror)
+    pub from_file: Option<PathBuf>, // Release archive on disk to install, offline (--from-file)
+}
+
+// Implement methods for UpdateOptions
+impl UpdateOptions {
+    // Fill in the settings not given on the command line from ~/.pidginrc's [update] table
+    pub fn with_config(mut self, config: &UpdateConfig) -> Self {
+        if self.proxy.is_none() && config.proxy.is_some() {
+            self.proxy = config.proxy.clone();
+            self.proxy_from_config = true;
+        }
+        if self.mirror.is_none() {
+            self.mirror = config.mirror.clone();
+        }
+        self
+    }
+
+    // Get the URL that names the latest release: GitHub's API, or latest.json on a mirror
+    // (a copy of the API's response; only its tag_name is read)
+    fn latest_url(&self) -> String {
+        match &self.mirror {
+            Some(mirror) => format!("{}/latest.json", mirror.trim_end_matches('/')),
+            None => RELEASES_API.to_string(),
+        }
+    }
 
-    println!("Detected platform: {platform}");
+    // Get the URL the release archives are under, laid out as on GitHub:
+    // <base>/download/<version>/pidgin-<platform>.tar.gz (.zip on Windows), with its .sha256
+    // and .minisig files
+    fn downloads_url(&self) -> String {
+        match &self.mirror {
+            Some(mirror) => mirror.trim_end_matches('/').to_string(),
+            None => RELEASES_URL.to_string(),
+        }
+    }
+}
+
+// Parse the arguments of `pidgin update`
+pub fn parse_options(args: &[String]) -> Result<UpdateOptions, String> {
+    const USAGE: &str = "Usage: pidgin update [--check [--json]] [--proxy <url>] [--mirror <url>] | --from-file <archive>";
+    let mut options = UpdateOptions::default();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--check" => options.check = true,
+            "--json" => options.json = true,
+            "--proxy" => {
+                let url = iter.next().ok_or("--proxy requires a URL")?;
+                options.proxy = Some(url.clone());
+            }
+            "--mirror" => {
+                let url = iter.next().ok_or("--mirror requires a URL")?;
+                options.mirror = Some(url.clone());
+            }
+            "--from-file" => {
+                let path = iter
+                    .next()
+                    .ok_or("--from-file requires the path of a release archive")?;
+                options.from_file = Some(PathBuf::from(path));
+            }
+            other => return Err(format!("Unknown update option: {other}. {USAGE}")),
+        }
+    }
+    if options.json && !options.check {
+        return Err(format!("--json only works with --check. {USAGE}"));
+    }
+    if options.from_file.is_some()
+        && (options.check || options.proxy.is_some() || options.mirror.is_some())
+    {
+        return Err(format!(
+            "--from-file installs an archive without the network, so it cannot be combined with --check, --proxy or --mirror. {USAGE}"
+        ));
+    }
+    Ok(options)
+}
+
+// Define the UpdateCheck struct, what `pidgin update --check` reports (printed as JSON by --json)
+#[derive(Debug, Serialize)]
+struct UpdateCheck {
+    current_version: String, // Version of this executable, e.g. "v0.1.20"
+    latest_version: String,  // Version of the latest release
+    update_available: bool,  // Whether the latest release is newer than this executable
+    changelog_url: String,   // Release notes of the latest release
+}
+
+// Update the compiler to the latest version
+pub fn update_compiler(options: &UpdateOptions) -> Result<(), UpdateError> {
+    // Installing an archive from disk needs no network, so it does not set up HTTP at all
+    if let Some(path) = &options.from_file {
+        info!("Pidgin Compiler Update");
+        info!("=====================");
+        let platform = detect_platform()?;
+        info!("Detected platform: {platform}");
+        info!("Installing from: {}", path.display());
+        install_from_file(path, platform)?;
+        println!("✓ Update completed successfully!");
+        return Ok(());
+    }
+
+    let agent = http_agent(options)?;
+    if options.check {
+        return check_for_update(&agent, options);
+    }
+
+    info!("Pidgin Compiler Update");
+    info!("=====================");
+
+    let platform = detect_platform()?;
+    info!("Detected platform: {platform}");
 
     // Get current version
     let current_version = env!("CARGO_PKG_VERSION");
-    println!("Current version: v{current_version}");
+    info!("Current version: v{current_version}");
 
     // Get latest version from GitHub API
-    println!("Checking for latest version...");
-    match get_latest_version() {
+    info!("Checking for latest version...");
+    if let Some(mirror) = &options.mirror {
+        info!("Using mirror: {mirror}");
+    }
+    match get_latest_version(&agent, &options.latest_url()) {
         Ok(latest_version) => {
-            println!("Latest version: {latest_version}");
+            info!("Latest version: {latest_version}");
 
             // Check if update is needed
-            if latest_version == format!("v{current_version}") {
+            if !is_newer(&latest_version, current_version) {
                 println!("✓ You already have the latest version!");
                 return Ok(());
             }
 
             // Download and install update
-            println!("Downloading update...");
-            download_and_install_update(&latest_version, platform)?;
+            info!("Downloading update...");
+            download_and_install_update(
+                &agent,
+                &options.downloads_url(),
+                &latest_version,
+                platform,
+            )?;
 
             println!("✓ Update completed successfully!");
             println!("New version: {latest_version}");
@@ -51,7 +258,7 @@ pub fn update_compiler() -> Result<(), String> {
             println!("⚠️  Could not check for updates: {e}");
             println!("Current version: v{current_version}");
             println!("To get the latest version, please visit:");
-            println!("https://github.com/ojutalayomi/pidgin/releases");
+            println!("{RELEASES_URL}");
             return Ok(());
         }
     }
@@ -59,113 +266,338 @@ pub fn update_compiler() -> Result<(), String> {
     Ok(())
 }
 
-// Get the latest version from GitHub releases
-fn get_latest_version() -> Result<String, String> {
-    use std::process::Command;
-
-    let output = Command::new("curl")
-        .args([
-            "-s",
-            "https://api.github.com/repos/ojutalayomi/pidgin/releases/latest",
-        ])
-        .output()
-        .map_err(|e| format!("Failed to execute curl: {e}"))?;
-
-    if !output.status.success() {
-        // Check if it's a 404 (repository not found) or other error
-        let status_code = output.status.code().unwrap_or(0);
-        if status_code == 404 {
-            return Err(
-                "Repository not found. Please check if the repository exists and is public."
-                    .to_string(),
-            );
+// Detect the platform, as named in the release archives (pidgin-<platform>.tar.gz)
+fn detect_platform() -> Result<&'static str, UpdateError> {
+    if cfg!(target_os = "windows") {
+        Ok("windows-x86_64")
+    } else if cfg!(target_os = "macos") {
+        if cfg!(target_arch = "aarch64") {
+            Ok("macos-aarch64")
         } else {
-            return Err(format!(
-                "Failed to fetch latest version (HTTP {status_code})"
-            ));
+            Ok("macos-x86_64")
         }
+    } else if cfg!(target_os = "linux") {
+        if cfg!(target_arch = "aarch64") {
+            Ok("linux-aarch64")
+        } else {
+            Ok("linux-x86_64")
+        }
+    } else {
+        Err(UpdateError::UnsupportedPlatform)
     }
+}
 
-    let response =
-        String::from_utf8(output.stdout).map_err(|e| format!("Invalid UTF-8 response: {e}"))?;
+// Report whether a newer version exists, without downloading anything (pidgin update --check)
+fn check_for_update(agent: &ureq::Agent, options: &UpdateOptions) -> Result<(), UpdateError> {
+    let latest_version = get_latest_version(agent, &options.latest_url())?;
+    let current_version = env!("CARGO_PKG_VERSION");
+    let check = UpdateCheck {
+        current_version: format!("v{current_version}"),
+        update_available: is_newer(&latest_version, current_version),
+        changelog_url: format!("{RELEASES_URL}/tag/{latest_version}"),
+        latest_version,
+    };
 
-    // Debug: Print first 200 characters of response for troubleshooting
-    if response.len() > 200 {
-        println!("API Response (first 200 chars): {}", &response[..200]);
+    if options.json {
+        let json = serde_json::to_string(&check)
+            .map_err(|e| UpdateError::InvalidResponse(e.to_string()))?;
+        println!("{json}");
+    } else if check.update_available {
+        println!(
+            "A new version is available: {} (you have {})",
+            check.latest_version, check.current_version
+        );
+        println!("Changelog: {}", check.changelog_url);
+        println!("Run `pidgin update` to install it.");
     } else {
-        println!("API Response: {response}");
-    }
-
-    // More robust JSON parsing for tag_name
-    let tag_patterns = ["\"tag_name\":\"", "\"tag_name\": \"", "\"tag_name\":"];
-
-    for pattern in &tag_patterns {
-        if let Some(tag_start) = response.find(pattern) {
-            let after_pattern = &response[tag_start + pattern.len()..];
-
-            // Find the end of the tag value
-            if let Some(tag_end) = after_pattern.find('"') {
-                let tag = &after_pattern[..tag_end];
-                if !tag.is_empty() {
-                    return Ok(tag.to_string());
-                }
-            } else if let Some(tag_end) = after_pattern.find(',') {
-                let tag = after_pattern[..tag_end].trim_matches('"').trim_matches(' ');
-                if !tag.is_empty() {
-                    return Ok(tag.to_string());
-                }
+        println!(
+            "✓ You already have the latest version ({})",
+            check.current_version
+        );
+    }
+    Ok(())
+}
+
+// Check whether a release version (e.g. "v0.2.0") is newer than this executable's; versions
+// are compared number by number, and ones that are not numbers only count if they differ
+fn is_newer(latest: &str, current: &str) -> bool {
+    let parse = |version: &str| -> Option<Vec<u64>> {
+        version
+            .trim_start_matches('v')
+            .split('.')
+            .map(|part| part.parse().ok())
+            .collect()
+    };
+    match (parse(latest), parse(current)) {
+        (Some(latest), Some(current)) => latest > current,
+        _ => latest.trim_start_matches('v') != current.trim_start_matches('v'),
+    }
+}
+
+// Create the HTTP client for every request of an update, going through the proxy given with
+// --proxy or, failing that, the one in HTTPS_PROXY or HTTP_PROXY (either case); a proxy that
+// cannot be used is an error rather than being skipped, so requests never silently bypass it
+fn http_agent(options: &UpdateOptions) -> Result<ureq::Agent, UpdateError> {
+    const PROXY_VARIABLES: [&str; 4] = ["HTTPS_PROXY", "https_proxy", "HTTP_PROXY", "http_proxy"];
+    let setting = match &options.proxy {
+        Some(url) => {
+            let source = if options.proxy_from_config {
+                "~/.pidginrc"
+            } else {
+                "--proxy"
+            };
+            Some((source.to_string(), url.clone()))
+        }
+        None => PROXY_VARIABLES.iter().find_map(|name| {
+            std::env::var(name)
+                .ok()
+                .filter(|url| !url.trim().is_empty())
+                .map(|url| (name.to_string(), url))
+        }),
+    };
+
+    let mut builder = ureq::AgentBuilder::new();
+    if let Some((source, url)) = setting {
+        let proxy = ureq::Proxy::new(url.trim())
+            .map_err(|e| UpdateError::InvalidProxy(source.clone(), e.to_string()))?;
+        debug!("Using proxy from {source}");
+        builder = builder.proxy(proxy);
+    }
+    Ok(builder.build())
+}
+
+// Make a GET request, identifying pidgin to the server (GitHub's API requires a User-Agent)
+fn get(agent: &ureq::Agent, url: &str) -> Result<ureq::Response, UpdateError> {
+    let user_agent = concat!("pidgin/", env!("CARGO_PKG_VERSION"));
+    Ok(agent.get(url).set("User-Agent", user_agent).call()?)
+}
+
+// Get the latest version from GitHub releases
+fn get_latest_version(agent: &ureq::Agent, url: &str) -> Result<String, UpdateError> {
+    let response = get(agent, url)?
+        .into_string()
+        .map_err(|e| UpdateError::Network(e.to_string()))?;
+
+    // Debug: Print first 200 characters of response for troubleshooting (--verbose)
+    debug!(
+        "API Response: {}",
+        response.chars().take(200).collect::<String>()
+    );
+
+    let release: Release =
+        serde_json::from_str(&response).map_err(|e| UpdateError::InvalidResponse(e.to_string()))?;
+    if release.tag_name.is_empty() {
+        return Err(UpdateError::InvalidResponse(
+            "the release has no version".to_string(),
+        ));
+    }
+    Ok(release.tag_name)
+}
+
+// Download the release
+fn download(agent: &ureq::Agent, url: &str, path: &Path) -> Result<(), UpdateError> {
+    let response = get(agent, url)?;
+    let total = response
+        .header("Content-Length")
+        .and_then(|length| length.parse().ok());
+    let mut body = response.into_reader();
+    let mut file =
+        File::create(path).map_err(|e| UpdateError::Io("create the download file".into(), e))?;
+
+    let mut progress = Progress::new(total);
+    let mut buffer = [0; 64 * 1024];
+    loop {
+        let read = match body.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => {
+                progress.finish();
+                return Err(UpdateError::Network(format!("download interrupted: {e}")));
             }
+        };
+        file.write_all(&buffer[..read])
+            .map_err(|e| UpdateError::Io("write the download file".into(), e))?;
+        progress.advance(read);
+    }
+    progress.finish();
+    Ok(())
+}
+
+// Get a small text file, or None if the server does not have it
+fn get_text(agent: &ureq::Agent, url: &str) -> Result<Option<String>, UpdateError> {
+    match get(agent, url) {
+        Ok(response) => response
+            .into_string()
+            .map(Some)
+            .map_err(|e| UpdateError::Network(e.to_string())),
+        Err(UpdateError::Http(404)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+// Read the SHA-256 from a .sha256 file (sha256sum's format: the hash, then the file name)
+fn parse_checksum(text: &str, source: &str) -> Result<String, UpdateError> {
+    match text.split_whitespace().next() {
+        Some(hash) if hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit()) => {
+            Ok(hash.to_ascii_lowercase())
         }
+        _ => Err(UpdateError::InvalidResponse(format!(
+            "'{source}' does not hold a SHA-256 checksum"
+        ))),
     }
+}
 
-    Err(format!(
-        "Failed to parse version from API response. Response: {}",
-        &response[..response.len().min(500)]
-    ))
+// Read an archive once; it is checked and extracted from this copy, so the file being changed
+// after it was verified makes no difference
+fn read_archive(path: &Path) -> Result<Vec<u8>, UpdateError> {
+    fs::read(path).map_err(|e| UpdateError::Io("read the archive".into(), e))
 }
 
-// Download and install the update
-fn download_and_install_update(version: &str, platform: &str) -> Result<(), String> {
-    use std::process::Command;
+// Check that an archive has the expected SHA-256
+fn verify_checksum(archive: &[u8], expected: &str) -> Result<(), UpdateError> {
+    let actual = format!("{:x}", Sha256::digest(archive));
+    debug!("SHA-256 of the archive: {actual}");
+    if actual != expected {
+        return Err(UpdateError::ChecksumMismatch {
+            expected: expected.to_string(),
+            actual,
+        });
+    }
+    Ok(())
+}
 
-    let download_url = format!(
-        "https://github.com/ojutalayomi/pidgin/releases/download/{version}/pidgin-{platform}.zip"
-    );
+// Check an archive against its minisign signature (the text of its .minisig file), using the
+// public key built into this executable
+fn verify_signature(archive: &[u8], signature: &str) -> Result<(), UpdateError> {
+    let public_key = RELEASE_PUBLIC_KEY.ok_or(UpdateError::NoPublicKey)?;
+    let public_key = minisign_verify::PublicKey::from_base64(public_key.trim())
+        .map_err(|e| UpdateError::BadSignature(format!("invalid built-in key: {e}")))?;
+    let signature = minisign_verify::Signature::decode(signature)
+        .map_err(|e| UpdateError::BadSignature(e.to_string()))?;
+    public_key
+        .verify(archive, &signature, false)
+        .map_err(|e| UpdateError::BadSignature(e.to_string()))
+}
 
-    let temp_dir = std::env::temp_dir().join("pidgin-update");
-    let zip_path = temp_dir.join("pidgin.zip");
+// Get the name of the release archive for a platform: a tar.gz, the usual format on Linux and
+// macOS (it keeps file permissions), and a zip on Windows
+fn archive_name(platform: &str) -> String {
+    if platform.starts_with("windows") {
+        format!("pidgin-{platform}.zip")
+    } else {
+        format!("pidgin-{platform}.tar.gz")
+    }
+}
 
-    // Create temp directory
-    std::fs::create_dir_all(&temp_dir)
-        .map_err(|e| format!("Failed to create temp directory: {e}"))?;
+// Extract a zip or tar.gz archive, telling them apart by their first bytes rather than by the
+// file name, which --from-file leaves up to the user
+fn extract(archive: &[u8], destination: &Path) -> Result<(), UpdateError> {
+    match archive {
+        [0x1f, 0x8b, ..] => tar::Archive::new(flate2::read::GzDecoder::new(archive))
+            .unpack(destination)
+            .map_err(|e| UpdateError::Archive(e.to_string())),
+        [b'P', b'K', ..] => {
+            zip::ZipArchive::new(io::Cursor::new(archive))?.extract(destination)?;
+            Ok(())
+        }
+        [] | [_] => Err(UpdateError::Archive(
+            "the file is too short to be an archive".into(),
+        )),
+        _ => Err(UpdateError::Archive(
+            "the file is neither a zip nor a tar.gz archive".into(),
+        )),
+    }
+}
 
-    // Download the release
-    println!("Downloading from: {download_url}");
-    let status = Command::new("curl")
-        .args(["-L", "-o", zip_path.to_str().unwrap(), &download_url])
-        .status()
-        .map_err(|e| format!("Failed to execute curl: {e}"))?;
+// Create a working directory for an update: a new one with a unique name that only this user
+// can open (a fixed name in a shared temp directory could be made first by someone else, who
+// could then swap the files in it). It is removed when the TempDir is dropped.
+fn work_dir() -> Result<tempfile::TempDir, UpdateError> {
+    tempfile::Builder::new()
+        .prefix("pidgin-update")
+        .tempdir()
+        .map_err(|e| UpdateError::Io("create temp directory".into(), e))
+}
 
-    if !status.success() {
-        return Err("Failed to download update".to_string());
+// Download and install the update
+fn download_and_install_update(
+    agent: &ureq::Agent,
+    downloads_url: &str,
+    version: &str,
+    platform: &str,
+) -> Result<(), UpdateError> {
+    // Without a key nothing could be installed, so do not download anything
+    if RELEASE_PUBLIC_KEY.is_none() {
+        return Err(UpdateError::NoPublicKey);
     }
 
-    // Extract the zip file
-    println!("Extracting update...");
-    let status = Command::new("unzip")
-        .args([
-            "-q",
-            "-o",
-            zip_path.to_str().unwrap(),
-            "-d",
-            temp_dir.to_str().unwrap(),
-        ])
-        .status()
-        .map_err(|e| format!("Failed to execute unzip: {e}"))?;
+    let archive_name = archive_name(platform);
+    let download_url = format!("{downloads_url}/download/{version}/{archive_name}");
+    let checksum_url = format!("{download_url}.sha256");
+    let signature_url = format!("{download_url}.minisig");
+
+    // Dropping the directory on any error removes the download, so a bad one is not left behind
+    let temp_dir = work_dir()?;
+    let archive_path = temp_dir.path().join(&archive_name);
+
+    info!("Downloading from: {download_url}");
+    download(agent, &download_url, &archive_path)?;
+    let archive = read_archive(&archive_path)?;
+
+    info!("Verifying checksum...");
+    let checksum = get_text(agent, &checksum_url)?
+        .ok_or_else(|| UpdateError::MissingChecksum(checksum_url.clone()))?;
+    verify_checksum(&archive, &parse_checksum(&checksum, &checksum_url)?)?;
 
-    if !status.success() {
-        return Err("Failed to extract update".to_string());
+    info!("Verifying signature...");
+    let signature = get_text(agent, &signature_url)?
+        .ok_or_else(|| UpdateError::MissingSignature(signature_url.clone()))?;
+    verify_signature(&archive, &signature)?;
+
+    install_archive(&archive, temp_dir.path(), platform)
+}
+
+// Install a release archive that is already on this machine (pidgin update --from-file); its
+// signature must be in a .minisig file next to it, and a .sha256 file there is checked too
+fn install_from_file(path: &Path, platform: &str) -> Result<(), UpdateError> {
+    if RELEASE_PUBLIC_KEY.is_none() {
+        return Err(UpdateError::NoPublicKey);
+    }
+    if !path.is_file() {
+        return Err(UpdateError::Io(
+            format!("read '{}'", path.display()),
+            io::Error::new(io::ErrorKind::NotFound, "no such file"),
+        ));
     }
+    // The signature and checksum files are named after the archive, e.g. pidgin.tar.gz.minisig
+    let beside = |extension: &str| {
+        let mut name = path.as_os_str().to_owned();
+        name.push(extension);
+        PathBuf::from(name)
+    };
+
+    let archive = read_archive(path)?;
+    let checksum_path = beside(".sha256");
+    if let Ok(checksum) = fs::read_to_string(&checksum_path) {
+        info!("Verifying checksum...");
+        let source = checksum_path.display().to_string();
+        verify_checksum(&archive, &parse_checksum(&checksum, &source)?)?;
+    }
+
+    info!("Verifying signature...");
+    let signature_path = beside(".minisig");
+    let signature = fs::read_to_string(&signature_path)
+        .map_err(|_| UpdateError::MissingSignature(signature_path.display().to_string()))?;
+    verify_signature(&archive, &signature)?;
+
+    install_archive(&archive, work_dir()?.path(), platform)
+}
+
+// Extract a verified release archive and put its executable in place of this one
+fn install_archive(archive: &[u8], temp_dir: &Path, platform: &str) -> Result<(), UpdateError> {
+    info!("Extracting update...");
+    extract(archive, temp_dir)?;
 
     // Find the executable in the extracted directory
     let executable_name = if cfg!(target_os = "windows") {
@@ -178,36 +610,40 @@ fn download_and_install_update(version: &str, platform: &str) -> Result<(), Stri
     let new_executable = extracted_dir.join(executable_name);
 
     if !new_executable.exists() {
-        return Err("Executable not found in downloaded release".to_string());
+        return Err(UpdateError::MissingExecutable(platform.to_string()));
     }
 
     // Get current executable path
     let current_exe = std::env::current_exe()
-        .map_err(|e| format!("Failed to get current executable path: {e}"))?;
+        .map_err(|e| UpdateError::Io("get current executable path".into(), e))?;
 
-    // Create backup
-    let backup_path = current_exe.with_extension("backup");
-    std::fs::copy(&current_exe, &backup_path)
-        .map_err(|e| format!("Failed to create backup: {e}"))?;
-
-    // Replace current executable
-    std::fs::copy(&new_executable, &current_exe)
-        .map_err(|e| format!("Failed to replace executable: {e}"))?;
+    // Copy the new executable next to the current one, so it can be renamed into place (the
+    // temp directory may be on another file system)
+    let staged_path = current_exe.with_extension("new");
+    fs::copy(&new_executable, &staged_path)
+        .map_err(|e| UpdateError::Io("copy the new executable".into(), e))?;
 
     // Make executable (on Unix systems)
     #[cfg(not(target_os = "windows"))]
     {
         use std::os::unix::fs::PermissionsExt;
-        let mut perms = std::fs::metadata(&current_exe)
-            .map_err(|e| format!("Failed to get file metadata: {e}"))?
+        let mut perms = fs::metadata(&staged_path)
+            .map_err(|e| UpdateError::Io("get file metadata".into(), e))?
             .permissions();
         perms.set_mode(0o755);
-        std::fs::set_permissions(&current_exe, perms)
-            .map_err(|e| format!("Failed to set executable permissions: {e}"))?;
+        fs::set_permissions(&staged_path, perms)
+            .map_err(|e| UpdateError::Io("set executable permissions".into(), e))?;
     }
 
-    // Clean up temp directory
-    let _ = std::fs::remove_dir_all(&temp_dir);
+    // Move the running executable aside as the backup, then move the new one into its place;
+    // a running executable cannot be written to, but it can be renamed (on Windows too)
+    let backup_path = current_exe.with_extension("backup");
+    fs::rename(&current_exe, &backup_path)
+        .map_err(|e| UpdateError::Io("create backup".into(), e))?;
+    if let Err(e) = fs::rename(&staged_path, &current_exe) {
+        let _ = fs::rename(&backup_path, &current_exe); // Put the old executable back
+        return Err(UpdateError::Io("replace executable".into(), e));
+    }
 
     Ok(())
 }
@@ -1,2 +1,3 @@
 // Update module for Pidgin compiler
 pub mod compiler;
+pub mod progress;
@@ -0,0 +1,106 @@
+// Download progress for pidgin update: a bar with the bytes received, the total and the rate
+//
+// It is drawn on stderr, redrawn in place, and only when stderr is a terminal and --quiet is
+// not given, so redirected output and scripts see none of it.
+use crate::logger::{self, Verbosity};
+use std::io::{self, IsTerminal, Write};
+use std::time::{Duration, Instant};
+
+const BAR_WIDTH: usize = 30; // Characters between the brackets
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100); // Redraw at most this often
+
+// Define the Progress struct, the state of one download's progress bar
+pub struct Progress {
+    total: Option<u64>, // Size of the download, if the server said (Content-Length)
+    received: u64,      // Bytes received so far
+    started: Instant,   // When the download started (for the rate)
+    drawn: Option<Instant>, // When the bar was last drawn
+    visible: bool,      // Whether the bar is shown at all
+}
+
+// Implement methods for Progress
+impl Progress {
+    // Start the progress bar for a download of the given size
+    pub fn new(total: Option<u64>) -> Self {
+        Self {
+            total,
+            received: 0,
+            started: Instant::now(),
+            drawn: None,
+            visible: io::stderr().is_terminal() && logger::verbosity() >= Verbosity::Normal,
+        }
+    }
+
+    // Count bytes received, redrawing the bar if it has not been drawn for a while
+    pub fn advance(&mut self, bytes: usize) {
+        self.received += bytes as u64;
+        if self
+            .drawn
+            .map_or(true, |at| at.elapsed() >= REDRAW_INTERVAL)
+        {
+            self.draw();
+        }
+    }
+
+    // Draw the bar one last time and move to the next line
+    pub fn finish(&mut self) {
+        if self.visible {
+            self.draw();
+            eprintln!();
+        }
+    }
+
+    // Draw the bar over the previous one
+    fn draw(&mut self) {
+        if !self.visible {
+            return;
+        }
+        self.drawn = Some(Instant::now());
+        let seconds = self.started.elapsed().as_secs_f64();
+        let rate = if seconds > 0.0 {
+            self.received as f64 / seconds
+        } else {
+            0.0
+        };
+        let line = match self.total {
+            Some(total) if total > 0 => {
+                let fraction = (self.received as f64 / total as f64).min(1.0);
+                let filled = (fraction * BAR_WIDTH as f64) as usize;
+                format!(
+                    "[{}{}] {:>3}% {} / {} ({}/s)",
+                    "=".repeat(filled),
+                    " ".repeat(BAR_WIDTH - filled),
+                    (fraction * 100.0) as u32,
+                    format_bytes(self.received as f64),
+                    format_bytes(total as f64),
+                    format_bytes(rate)
+                )
+            }
+            // Without a size there is nothing to fill a bar with
+            _ => format!(
+                "{} ({}/s)",
+                format_bytes(self.received as f64),
+                format_bytes(rate)
+            ),
+        };
+        // \x1b[K clears what is left of a longer previous line
+        eprint!("\r{line}\x1b[K");
+        let _ = io::stderr().flush();
+    }
+}
+
+// Format a number of bytes for people, e.g. "1.4 MB"
+fn format_bytes(bytes: f64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut value = bytes;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{value:.0} {}", UNITS[unit])
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
@@ -0,0 +1,98 @@
+// Runtime values as JSON: serde support for Value and conversions to and from serde_json::Value
+//
+// Values map onto JSON naturally: numbers, strings, booleans and nil become JSON numbers,
+// strings, booleans and null; arrays become JSON arrays and objects become JSON objects.
+// Dates are written as RFC 3339 strings, and NaN or infinite numbers as null (in JSON).
+// Functions have no JSON form, so serializing one fails.
+//
+// Going the other way, JSON arrays become fixed arrays (like a `[...]` literal) and
+// strings stay strings, even when they look like dates.
+use crate::interpreter::Value;
+use serde::de::{Deserialize, Deserializer};
+use serde::ser::{Error, Serialize, SerializeMap, SerializeSeq, Serializer};
+
+// Implement Serialize for Value
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            // Whole numbers are written without a fraction (3, not 3.0)
+            Value::Number(n) if n.fract() == 0.0 && n.abs() < i64::MAX as f64 => {
+                serializer.serialize_i64(*n as i64)
+            }
+            Value::Number(n) => serializer.serialize_f64(*n),
+            Value::String(s) => serializer.serialize_str(s),
+            Value::Boolean(b) => serializer.serialize_bool(*b),
+            Value::Nil => serializer.serialize_unit(),
+            Value::FixedArray(items) | Value::DynamicArray(items) => {
+                let mut seq = serializer.serialize_seq(Some(items.len()))?;
+                for item in items {
+                    seq.serialize_element(item)?;
+                }
+                seq.end()
+            }
+            Value::Object(fields) => {
+                // Sorted by key, so the same object always serializes the same way
+                let mut keys: Vec<&String> = fields.keys().collect();
+                keys.sort();
+                let mut map = serializer.serialize_map(Some(fields.len()))?;
+                for key in keys {
+                    map.serialize_entry(key, &fields[key])?;
+                }
+                map.end()
+            }
+            Value::Date(date) => serializer.serialize_str(&date.to_rfc3339()),
+            Value::Duration(seconds) => serializer.serialize_f64(*seconds),
+            Value::Function(..) => Err(S::Error::custom("functions cannot be serialized")),
+            Value::Iterator(_) => Err(S::Error::custom("iterators cannot be serialized")),
+            Value::Task(_) => Err(S::Error::custom("tasks cannot be serialized")),
+            Value::Frozen(inner) => inner.serialize(serializer),
+        }
+    }
+}
+
+// Implement Deserialize for Value (through serde_json::Value, so any self-describing format works)
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        serde_json::Value::deserialize(deserializer).map(Value::from)
+    }
+}
+
+// Implement From<serde_json::Value> for Value
+impl From<serde_json::Value> for Value {
+    fn from(json: serde_json::Value) -> Self {
+        match json {
+            serde_json::Value::Null => Value::Nil,
+            serde_json::Value::Bool(b) => Value::Boolean(b),
+            serde_json::Value::Number(n) => Value::Number(n.as_f64().unwrap_or(f64::NAN)),
+            serde_json::Value::String(s) => Value::String(s),
+            serde_json::Value::Array(items) => {
+                Value::FixedArray(items.into_iter().map(Value::from).collect())
+            }
+            serde_json::Value::Object(fields) => Value::Object(
+                fields
+                    .into_iter()
+                    .map(|(key, value)| (key, Value::from(value)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+// Implement TryFrom<&Value> for serde_json::Value, failing for functions (NaN and infinite
+// numbers, which JSON cannot hold, become null)
+impl TryFrom<&Value> for serde_json::Value {
+    type Error = String;
+
+    fn try_from(value: &Value) -> Result<Self, String> {
+        serde_json::to_value(value).map_err(|e| e.to_string())
+    }
+}
+
+// Implement TryFrom<Value> for serde_json::Value
+impl TryFrom<Value> for serde_json::Value {
+    type Error = String;
+
+    fn try_from(value: Value) -> Result<Self, String> {
+        serde_json::Value::try_from(&value)
+    }
+}
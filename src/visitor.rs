@@ -0,0 +1,128 @@
+// Generic AST traversal shared by the static analysis passes (lint, check, ...)
+use crate::ast::{Expr, Program, Stmt};
+
+// Define the Visitor trait; override a method to inspect a node, and call the matching
+// walk_* function from it to keep descending into the node's children
+pub trait Visitor {
+    // Visit a statement (default: visit its children)
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        walk_stmt(self, stmt);
+    }
+
+    // Visit an expression (default: visit its children)
+    fn visit_expr(&mut self, expr: &Expr) {
+        walk_expr(self, expr);
+    }
+}
+
+// Visit every top-level statement of a program
+pub fn walk_program<V: Visitor + ?Sized>(visitor: &mut V, program: &Program) {
+    for stmt in &program.statements {
+        visitor.visit_stmt(stmt);
+    }
+}
+
+// Visit the children of a statement
+pub fn walk_stmt<V: Visitor + ?Sized>(visitor: &mut V, stmt: &Stmt) {
+    match stmt {
+        Stmt::Expression(expr, _) | Stmt::Return(expr, _) => visitor.visit_expr(expr),
+        Stmt::Print {
+            format, arguments, ..
+        }
+        | Stmt::PrintLn {
+            format, arguments, ..
+        }
+        | Stmt::PrintErr {
+            format, arguments, ..
+        } => {
+            visitor.visit_expr(format);
+            for argument in arguments {
+                visitor.visit_expr(argument);
+            }
+        }
+        Stmt::Import { .. } => {}
+        Stmt::VarDeclaration { initializer, .. } => {
+            if let Some(initializer) = initializer {
+                visitor.visit_expr(initializer);
+            }
+        }
+        Stmt::Unpack { value, .. } => visitor.visit_expr(value),
+        Stmt::FunctionDeclaration { body, .. } => visitor.visit_stmt(body),
+        Stmt::Block(statements, _) => {
+            for stmt in statements {
+                visitor.visit_stmt(stmt);
+            }
+        }
+        Stmt::If {
+            condition,
+            then_branch,
+            else_branch,
+            ..
+        } => {
+            visitor.visit_expr(condition);
+            visitor.visit_stmt(then_branch);
+            if let Some(else_branch) = else_branch {
+                visitor.visit_stmt(else_branch);
+            }
+        }
+        Stmt::While {
+            condition, body, ..
+        } => {
+            visitor.visit_expr(condition);
+            visitor.visit_stmt(body);
+        }
+        Stmt::ForIn { iterable, body, .. } => {
+            visitor.visit_expr(iterable);
+            visitor.visit_stmt(body);
+        }
+    }
+}
+
+// Visit the children of an expression
+pub fn walk_expr<V: Visitor + ?Sized>(visitor: &mut V, expr: &Expr) {
+    match expr {
+        Expr::Number(_)
+        | Expr::String(_)
+        | Expr::Boolean(_)
+        | Expr::Identifier(_, _, _)
+        | Expr::Nil
+        | Expr::Transform { .. } => {}
+        Expr::FixedArray(elements) | Expr::DynamicArray(elements) => {
+            for element in elements {
+                visitor.visit_expr(element);
+            }
+        }
+        Expr::Index { array, index, .. } => {
+            visitor.visit_expr(array);
+            visitor.visit_expr(index);
+        }
+        Expr::Binary { left, right, .. } => {
+            visitor.visit_expr(left);
+            visitor.visit_expr(right);
+        }
+        Expr::Unary { operand, .. } => visitor.visit_expr(operand),
+        Expr::Assignment { value, .. } => visitor.visit_expr(value),
+        Expr::MethodCall {
+            object, arguments, ..
+        } => {
+            visitor.visit_expr(object);
+            for argument in arguments {
+                visitor.visit_expr(argument);
+            }
+        }
+        Expr::FunctionCall { arguments, .. } => {
+            for argument in arguments {
+                visitor.visit_expr(argument);
+            }
+        }
+        Expr::Match { subject, cases, .. } => {
+            visitor.visit_expr(subject);
+            for case in cases {
+                if let Some(guard) = &case.guard {
+                    visitor.visit_expr(guard);
+                }
+                visitor.visit_expr(&case.body);
+            }
+        }
+    }
+}
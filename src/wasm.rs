@@ -0,0 +1,37 @@
+// JavaScript-facing API of the WebAssembly build, used by the online Pidgin playground
+//
+// The browser has no files, terminal, or processes, so a program run here cannot import
+// modules from disk, readLine() always returns "", and printed text is collected and
+// returned instead of being written to stdout. See docs/WebAssembly.md for how to build it.
+use crate::interpreter::{Interpreter, Limits};
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use wasm_bindgen::prelude::wasm_bindgen;
+
+const MAX_STEPS: usize = 10_000_000; // Stops runaway loops before they freeze the page
+
+// Run a program and return everything it printed; if it fails, the output ends with the
+// error, formatted like the pidgin command line does ("Error: ...")
+#[wasm_bindgen]
+pub fn run(source: &str) -> String {
+    let mut interpreter = Interpreter::new();
+    interpreter.capture_output();
+    interpreter.set_limits(Limits {
+        max_steps: Some(MAX_STEPS),
+        ..Limits::default()
+    });
+
+    let result = Lexer::new(source).tokenize().and_then(|tokens| {
+        let program = Parser::new(&tokens).parse()?;
+        interpreter.interpret(program)
+    });
+
+    let mut output = interpreter.take_output();
+    if let Err(e) = result {
+        if !output.is_empty() && !output.ends_with('\n') {
+            output.push('\n'); // Start the error on its own line
+        }
+        output.push_str(&format!("Error: {e}\n"));
+    }
+    output
+}
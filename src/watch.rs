@@ -0,0 +1,121 @@
+// Watch mode: re-run a program whenever it or one of its imported modules changes
+//
+// Files are polled for modification times rather than using OS notifications, which keeps
+// the implementation dependency-free and works the same on every platform.
+use crate::ast::{Program, Stmt};
+use crate::interpreter::find_module;
+use crate::lexer::Lexer;
+use crate::logger::{debug, info};
+use crate::parser::Parser;
+use crate::visitor::{self, Visitor};
+use crate::GlobalOptions;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(300); // How often files are checked
+
+// Run a file, then re-run it every time a watched file changes (until interrupted); each run
+// takes the global options (--trace, --stdin-from, the limits, ...) like `pidgin <file.pg>`
+pub fn watch_file(
+    path: &str,
+    options: &GlobalOptions,
+    script_args: Vec<String>,
+) -> Result<(), String> {
+    loop {
+        // Clear the screen (works on most terminals)
+        print!("\x1B[2J\x1B[1;1H");
+        io::stdout().flush().map_err(|e| e.to_string())?;
+
+        let files = program_files(path);
+        debug!("Watching: {}", files.join(", "));
+        let before = modification_times(&files); // Taken first so edits made during the run count
+        match crate::run_file(path, options, script_args.clone()) {
+            Ok(()) => info!("\n[watch] Finished"),
+            Err(e) => {
+                eprintln!();
+                crate::report_error(&e.in_file(path));
+            }
+        }
+        info!(
+            "[watch] Watching {} file(s) for changes (Ctrl-C to exit)",
+            files.len()
+        );
+
+        wait_for_change(&files, &before);
+    }
+}
+
+// Find the file itself plus every module it imports, directly or indirectly
+pub fn program_files(path: &str) -> Vec<String> {
+    let mut files = vec![path.to_string()];
+    let mut index = 0;
+
+    while index < files.len() {
+        for module in imported_modules(&files[index]) {
+            // Modules that cannot be found are reported when the program runs
+            if let Ok(module_file) = find_module(&module) {
+                if !files.contains(&module_file) {
+                    files.push(module_file);
+                }
+            }
+        }
+        index += 1;
+    }
+
+    files
+}
+
+// Get the module paths imported by a file (empty if it cannot be read or parsed)
+fn imported_modules(file: &str) -> Vec<String> {
+    let mut collector = ImportCollector::default();
+    if let Some(program) = parse_file(file) {
+        visitor::walk_program(&mut collector, &program);
+    }
+    collector.modules
+}
+
+// Read and parse a file, ignoring errors
+fn parse_file(file: &str) -> Option<Program> {
+    let source = fs::read_to_string(file).ok()?;
+    let tokens = Lexer::new(&source).tokenize().ok()?;
+    Parser::new(&tokens).parse().ok()
+}
+
+// Define the ImportCollector struct, which gathers the modules named by import statements
+#[derive(Default)]
+struct ImportCollector {
+    modules: Vec<String>, // Module paths as written in the source
+}
+
+impl Visitor for ImportCollector {
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        if let Stmt::Import { module, .. } = stmt {
+            self.modules.push(module.clone());
+        }
+        visitor::walk_stmt(self, stmt);
+    }
+}
+
+// Get the last modification time of each file (None if it is missing)
+fn modification_times(files: &[String]) -> HashMap<String, Option<SystemTime>> {
+    files
+        .iter()
+        .map(|file| {
+            let modified = fs::metadata(file).and_then(|meta| meta.modified()).ok();
+            (file.clone(), modified)
+        })
+        .collect()
+}
+
+// Block until any of the files is modified, created, or removed
+fn wait_for_change(files: &[String], before: &HashMap<String, Option<SystemTime>>) {
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        if modification_times(files) != *before {
+            return;
+        }
+    }
+}